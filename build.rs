@@ -0,0 +1,43 @@
+//! Compiles the directory named by `EMBED_ASSETS_DIR` into the binary, for
+//! serving with `--embedded-assets`. Without it no assets are embedded.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+fn main() {
+    println!("cargo:rerun-if-env-changed=EMBED_ASSETS_DIR");
+    let mut files = Vec::new();
+    if let Some(dir) = env::var_os("EMBED_ASSETS_DIR") {
+        let dir = fs::canonicalize(&dir).expect("EMBED_ASSETS_DIR must be a directory");
+        collect(&dir, &dir, &mut files);
+    }
+    files.sort();
+
+    let mut out = String::from("pub static EMBEDDED: &[(&str, &[u8])] = &[\n");
+    for (path, file) in files.iter() {
+        println!("cargo:rerun-if-changed={}", file.display());
+        out.push_str(&format!("    ({:?}, include_bytes!({:?})),\n", path, file));
+    }
+    out.push_str("];\n");
+    let target = PathBuf::from(env::var("OUT_DIR").unwrap()).join("assets.rs");
+    fs::write(target, out).unwrap();
+}
+
+/// Lists regular files below `dir` as `(/relative/path, absolute path)`.
+fn collect(root: &Path, dir: &Path, files: &mut Vec<(String, PathBuf)>) {
+    println!("cargo:rerun-if-changed={}", dir.display());
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            collect(root, &path, files);
+        } else if path.is_file() {
+            let relative = path.strip_prefix(root).unwrap();
+            let segments = relative
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy().into_owned())
+                .collect::<Vec<String>>();
+            files.push((format!("/{}", segments.join("/")), path));
+        }
+    }
+}
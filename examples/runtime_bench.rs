@@ -0,0 +1,81 @@
+//! Compares the default multithreaded runtime against the thread-per-core
+//! layout by hammering `/echo` with many concurrent one-shot connections.
+//!
+//! Run with `cargo run --release --example runtime_bench -- [connections] [seconds]`.
+
+use std::env;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use http_server_starter_rust::{
+    config::{Config, RuntimeMode},
+    handlers, server,
+};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+const REQUEST: &[u8] = b"GET /echo/bench HTTP/1.1\r\nHost: localhost\r\n\r\n";
+
+fn main() {
+    let args = env::args().collect::<Vec<String>>();
+    let connections = args.get(1).and_then(|v| v.parse().ok()).unwrap_or(64);
+    let seconds = args.get(2).and_then(|v| v.parse().ok()).unwrap_or(5);
+
+    for (port, mode) in [
+        (4301, RuntimeMode::MultiThread),
+        (4302, RuntimeMode::PerCore),
+    ] {
+        let address = format!("127.0.0.1:{}", port);
+        let config = Config {
+            address: address.clone(),
+            runtime: mode,
+            log_requests: false,
+            ..Config::default()
+        };
+        thread::spawn(move || server::run(config, handlers::routes));
+        thread::sleep(Duration::from_millis(200));
+
+        let completed = load(&address, connections, Duration::from_secs(seconds));
+        println!(
+            "{:?}: {} requests in {}s ({:.0} req/s)",
+            mode,
+            completed,
+            seconds,
+            completed as f64 / seconds as f64
+        );
+    }
+}
+
+fn load(address: &str, connections: usize, duration: Duration) -> u64 {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let completed = Arc::new(AtomicU64::new(0));
+    let deadline = Instant::now() + duration;
+    runtime.block_on(async {
+        let workers = (0..connections)
+            .map(|_| {
+                let address = address.to_owned();
+                let completed = completed.clone();
+                tokio::spawn(async move {
+                    let mut buf = Vec::with_capacity(256);
+                    while Instant::now() < deadline {
+                        let Ok(mut stream) = TcpStream::connect(&address).await else {
+                            continue;
+                        };
+                        buf.clear();
+                        if stream.write_all(REQUEST).await.is_ok()
+                            && stream.read_to_end(&mut buf).await.is_ok()
+                        {
+                            completed.fetch_add(1, Ordering::Relaxed);
+                        }
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for worker in workers {
+            let _ = worker.await;
+        }
+    });
+    completed.load(Ordering::Relaxed)
+}
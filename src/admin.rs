@@ -0,0 +1,65 @@
+//! Endpoints served on the separate admin listener. Every request must carry
+//! `Authorization: Bearer <admin token>`.
+
+use std::sync::Arc;
+
+use crate::config::Config;
+use crate::control::Control;
+use crate::header_map::HeaderMap;
+use crate::request::Request;
+use crate::response::{HttpCode, Response};
+use crate::routes::{CompareType, Route, Routes};
+
+pub fn routes(config: &Config, control: &Arc<Control>) -> Routes {
+    let mut routes = Routes::new(config);
+    let token = config.admin_token.clone().unwrap_or_default();
+    let control = control.clone();
+    routes.add(Route::new(
+        "POST",
+        "/admin/shutdown",
+        CompareType::Exact,
+        Box::new(move |req, _| {
+            if !authorized(&req, &token) {
+                return unauthorized();
+            }
+            control.shutdown();
+            Response {
+                code: HttpCode::Accepted,
+                content: None,
+                headers: Some(HeaderMap::from([(
+                    String::from("Content-Length"),
+                    String::from("0"),
+                )])),
+            }
+        }),
+    ));
+    routes
+}
+
+fn authorized(req: &Request, token: &str) -> bool {
+    let presented = req
+        .headers
+        .get("Authorization")
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .unwrap_or("");
+    // Compare every byte so the response time doesn't reveal the prefix
+    // that matched.
+    !token.is_empty()
+        && presented.len() == token.len()
+        && presented
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+fn unauthorized() -> Response {
+    Response {
+        code: HttpCode::Unauthorized,
+        content: None,
+        headers: Some(HeaderMap::from([
+            (String::from("WWW-Authenticate"), String::from("Bearer")),
+            (String::from("Content-Length"), String::from("0")),
+        ])),
+    }
+}
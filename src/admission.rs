@@ -0,0 +1,59 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::header_map::HeaderMap;
+use crate::response::{HttpCode, Response};
+
+/// Caps how many requests are handled at once. Requests over the limit
+/// wait in a bounded queue for a limited time; anything beyond that is
+/// refused so latency can't grow without bound. Limits are per route table,
+/// so per shard with the per-core runtime.
+pub struct Admission {
+    permits: Arc<Semaphore>,
+    queued: AtomicUsize,
+    queue_depth: usize,
+    queue_timeout: Duration,
+}
+
+impl Admission {
+    pub fn new(limit: usize, queue_depth: usize, queue_timeout: Duration) -> Self {
+        Admission {
+            permits: Arc::new(Semaphore::new(limit)),
+            queued: AtomicUsize::new(0),
+            queue_depth,
+            queue_timeout,
+        }
+    }
+
+    /// Waits for a slot, or returns `None` if the request should be shed.
+    pub async fn admit(&self) -> Option<OwnedSemaphorePermit> {
+        if let Ok(permit) = self.permits.clone().try_acquire_owned() {
+            return Some(permit);
+        }
+        let queued = self.queued.fetch_add(1, Ordering::Relaxed);
+        let permit = if queued < self.queue_depth {
+            tokio::time::timeout(self.queue_timeout, self.permits.clone().acquire_owned())
+                .await
+                .ok()
+                .and_then(Result::ok)
+        } else {
+            None
+        };
+        self.queued.fetch_sub(1, Ordering::Relaxed);
+        permit
+    }
+}
+
+/// The `503` sent for a request there was no capacity for.
+pub fn overloaded(retry_after: u64) -> Response {
+    Response {
+        code: HttpCode::ServiceUnavailable,
+        content: None,
+        headers: Some(HeaderMap::from([
+            (String::from("Retry-After"), retry_after.to_string()),
+            (String::from("Content-Length"), String::from("0")),
+        ])),
+    }
+}
@@ -0,0 +1,68 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::header_map::HeaderMap;
+use crate::request::{HttpMethod, Request};
+use crate::response::{HttpCode, Response};
+use crate::routes::{Middleware, Next};
+use crate::vhost::content_type;
+
+// Generated by build.rs from `EMBED_ASSETS_DIR`.
+include!(concat!(env!("OUT_DIR"), "/assets.rs"));
+
+/// Serves files compiled into the binary under a path prefix. Bodies are
+/// buffered, so the cache middleware gives them ETags like any other
+/// response. Paths with no embedded file continue to the regular routes.
+pub struct EmbeddedAssets {
+    prefix: String,
+    files: HashMap<&'static str, &'static [u8]>,
+}
+
+impl EmbeddedAssets {
+    /// `files` maps `/relative/path` to contents; the binary's own set is
+    /// [`EMBEDDED`].
+    pub fn new(prefix: &str, files: &[(&'static str, &'static [u8])]) -> Self {
+        EmbeddedAssets {
+            prefix: prefix.trim_end_matches('/').to_owned(),
+            files: files.iter().copied().collect(),
+        }
+    }
+
+    fn find(&self, path: &str) -> Option<(&'static str, &'static [u8])> {
+        let path = path.strip_prefix(self.prefix.as_str())?;
+        if !path.is_empty() && !path.starts_with('/') {
+            return None;
+        }
+        let path = match path {
+            "" | "/" => String::from("/index.html"),
+            path if path.ends_with('/') => format!("{}index.html", path),
+            path => path.to_owned(),
+        };
+        self.files
+            .get_key_value(path.as_str())
+            .map(|(name, contents)| (*name, *contents))
+    }
+}
+
+impl Middleware for EmbeddedAssets {
+    fn handle(&self, req: Request, next: Next<'_>) -> Response {
+        if req.method != HttpMethod::GET {
+            return next(req);
+        }
+        let Some((name, contents)) = self.find(&req.path) else {
+            return next(req);
+        };
+        let headers = HeaderMap::from([
+            (String::from("Content-Length"), contents.len().to_string()),
+            (
+                String::from("Content-Type"),
+                content_type(Path::new(name)).to_owned(),
+            ),
+        ]);
+        Response {
+            code: HttpCode::OK,
+            content: Some(contents.to_vec().into()),
+            headers: Some(headers),
+        }
+    }
+}
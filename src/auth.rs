@@ -0,0 +1,666 @@
+use anyhow::{anyhow, bail, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::clock::{Clock, SystemClock};
+use crate::hash::{
+    base64_decode, base64_encode, constant_time_eq, hex, md5, random_token, sha1, sha256,
+};
+use crate::header_map::HeaderMap;
+use crate::request::Request;
+use crate::response::{HttpCode, Response};
+use crate::routes::{Middleware, Next};
+
+const DEFAULT_NONCE_LIFETIME: Duration = Duration::from_secs(300);
+
+/// An `Authorization` field that doesn't hold well-formed credentials for
+/// its scheme. The credentials themselves are left out of the message.
+#[derive(Debug, thiserror::Error)]
+#[error("invalid Authorization: {0}")]
+pub struct InvalidAuthorization(pub &'static str);
+
+/// The credentials of an `Authorization` field.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Authorization {
+    /// `Basic` with the user-id and password decoded (RFC 7617).
+    Basic { user: String, pass: String },
+    /// A `Bearer` token (RFC 6750).
+    Bearer(String),
+    /// Any other scheme, such as `Digest`, with its unparsed parameters.
+    Other { scheme: String, params: String },
+}
+
+impl Authorization {
+    /// Parses an `Authorization` value. Scheme names are matched
+    /// case-insensitively.
+    pub fn parse(value: &str) -> Result<Self, InvalidAuthorization> {
+        let value = value.trim();
+        let (scheme, params) = value.split_once(' ').unwrap_or((value, ""));
+        let params = params.trim();
+        if scheme.is_empty() {
+            return Err(InvalidAuthorization("missing scheme"));
+        }
+        if scheme.eq_ignore_ascii_case("Basic") {
+            if !is_token68(params) {
+                return Err(InvalidAuthorization("Basic credentials aren't base64"));
+            }
+            let decoded = base64_decode(params)
+                .ok_or(InvalidAuthorization("Basic credentials aren't base64"))?;
+            let decoded = String::from_utf8(decoded)
+                .map_err(|_| InvalidAuthorization("Basic credentials aren't UTF-8"))?;
+            let (user, pass) = decoded
+                .split_once(':')
+                .ok_or(InvalidAuthorization("Basic credentials without a colon"))?;
+            let (user, pass) = (user.to_owned(), pass.to_owned());
+            Ok(Authorization::Basic { user, pass })
+        } else if scheme.eq_ignore_ascii_case("Bearer") {
+            if !is_token68(params) {
+                return Err(InvalidAuthorization("malformed Bearer token"));
+            }
+            Ok(Authorization::Bearer(params.to_owned()))
+        } else {
+            let (scheme, params) = (scheme.to_owned(), params.to_owned());
+            Ok(Authorization::Other { scheme, params })
+        }
+    }
+}
+
+/// Whether `value` is a token68 (RFC 9110 §11.2), the syntax of Basic
+/// credentials and Bearer tokens.
+fn is_token68(value: &str) -> bool {
+    let body = value.trim_end_matches('=');
+    !body.is_empty()
+        && body
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"-._~+/".contains(&b))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DigestAlgorithm {
+    Md5,
+    Sha256,
+}
+
+impl DigestAlgorithm {
+    fn name(&self) -> &'static str {
+        match self {
+            DigestAlgorithm::Md5 => "MD5",
+            DigestAlgorithm::Sha256 => "SHA-256",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_uppercase().as_str() {
+            "MD5" => Some(DigestAlgorithm::Md5),
+            "SHA-256" => Some(DigestAlgorithm::Sha256),
+            _ => None,
+        }
+    }
+
+    fn hash(&self, data: &str) -> String {
+        match self {
+            DigestAlgorithm::Md5 => hex(&md5(data.as_bytes())),
+            DigestAlgorithm::Sha256 => hex(&sha256(data.as_bytes())),
+        }
+    }
+}
+
+enum Verdict {
+    Allowed,
+    Stale,
+    Denied,
+}
+
+/// Whom the credentials are for: the origin, or the proxy opening a
+/// CONNECT tunnel, which has fields and a status of its own.
+#[derive(Clone, Copy)]
+enum Scope {
+    Origin,
+    Proxy,
+}
+
+impl Scope {
+    /// The parsed credentials field for this scope, if it parses.
+    fn credentials(self, req: &Request) -> Option<Authorization> {
+        let field = match self {
+            Scope::Origin => "Authorization",
+            Scope::Proxy => "Proxy-Authorization",
+        };
+        Authorization::parse(req.headers.get(field)?).ok()
+    }
+
+    /// The refusal carrying `challenge`: `401` with `WWW-Authenticate`, or
+    /// `407` with `Proxy-Authenticate`.
+    fn refusal(self, challenge: String) -> Response {
+        let (code, field) = match self {
+            Scope::Origin => (HttpCode::Unauthorized, "WWW-Authenticate"),
+            Scope::Proxy => (HttpCode::ProxyAuthenticationRequired, "Proxy-Authenticate"),
+        };
+        Response {
+            code,
+            content: None,
+            headers: Some(HeaderMap::from([
+                (String::from(field), challenge),
+                (String::from("Content-Length"), String::from("0")),
+            ])),
+        }
+    }
+}
+
+/// RFC 7616 Digest authentication (`qop=auth`, MD5 and SHA-256).
+///
+/// Nonces carry their issue time and a keyed hash, so the server can tell a
+/// forged nonce from an expired one and answer the latter with `stale=true`.
+/// Each nonce's highest `nc` is tracked to reject replayed requests.
+pub struct DigestAuth {
+    realm: String,
+    /// Username to the `H(username:realm:password)` values for each algorithm.
+    users: HashMap<String, Vec<(DigestAlgorithm, String)>>,
+    secret: String,
+    opaque: String,
+    nonce_lifetime: Duration,
+    nonce_counts: Mutex<HashMap<String, u32>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl DigestAuth {
+    pub fn new(realm: &str) -> Self {
+        DigestAuth {
+            realm: realm.to_owned(),
+            users: HashMap::new(),
+            secret: random_token(),
+            opaque: random_token(),
+            nonce_lifetime: DEFAULT_NONCE_LIFETIME,
+            nonce_counts: Mutex::new(HashMap::new()),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Adds a user that may authenticate with either algorithm.
+    pub fn user(mut self, username: &str, password: &str) -> Self {
+        let secret = format!("{}:{}:{}", username, self.realm, password);
+        let hashes = [DigestAlgorithm::Sha256, DigestAlgorithm::Md5]
+            .into_iter()
+            .map(|algorithm| (algorithm, algorithm.hash(&secret)))
+            .collect();
+        self.users.insert(username.to_owned(), hashes);
+        self
+    }
+
+    pub fn nonce_lifetime(mut self, lifetime: Duration) -> Self {
+        self.nonce_lifetime = lifetime;
+        self
+    }
+
+    /// Replaces the clock nonces are issued and expired against.
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Loads users from an `htdigest` file (`user:realm:hash` lines). The
+    /// realm of the first entry is used; 64 hex digit hashes are taken as
+    /// SHA-256 and 32 digit ones as MD5.
+    pub fn from_htdigest(path: &str) -> Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        let mut auth: Option<DigestAuth> = None;
+        for line in contents.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            let mut fields = line.splitn(3, ':');
+            let (Some(username), Some(realm), Some(hash)) =
+                (fields.next(), fields.next(), fields.next())
+            else {
+                bail!("invalid htdigest line in {}: {}", path, line);
+            };
+            let algorithm = match hash.len() {
+                32 => DigestAlgorithm::Md5,
+                64 => DigestAlgorithm::Sha256,
+                _ => bail!("invalid hash for {} in {}", username, path),
+            };
+            let auth = auth.get_or_insert_with(|| DigestAuth::new(realm));
+            if auth.realm != realm {
+                continue;
+            }
+            auth.users
+                .entry(username.to_owned())
+                .or_default()
+                .push((algorithm, hash.to_ascii_lowercase()));
+        }
+        auth.ok_or_else(|| anyhow!("no users in {}", path))
+    }
+
+    fn now(&self) -> u64 {
+        self.clock
+            .system_time()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs()
+    }
+
+    fn nonce_for(&self, issued: u64) -> String {
+        let mac = sha256(format!("{}:{}", issued, self.secret).as_bytes());
+        format!("{:x}-{}", issued, hex(&mac[..16]))
+    }
+
+    /// Returns the issue time of a nonce this server generated.
+    fn nonce_issued(&self, nonce: &str) -> Option<u64> {
+        let (issued, _) = nonce.split_once('-')?;
+        let issued = u64::from_str_radix(issued, 16).ok()?;
+        (self.nonce_for(issued) == nonce).then_some(issued)
+    }
+
+    fn is_expired(&self, issued: u64) -> bool {
+        self.now().saturating_sub(issued) > self.nonce_lifetime.as_secs()
+    }
+
+    fn challenge(&self, stale: bool, scope: Scope) -> Response {
+        let nonce = self.nonce_for(self.now());
+        let mut algorithms = self
+            .users
+            .values()
+            .flatten()
+            .map(|(algorithm, _)| *algorithm)
+            .collect::<Vec<DigestAlgorithm>>();
+        algorithms.sort_by_key(|algorithm| *algorithm == DigestAlgorithm::Md5);
+        algorithms.dedup();
+        let challenges = algorithms
+            .into_iter()
+            .map(|algorithm| {
+                format!(
+                    "Digest realm=\"{}\", qop=\"auth\", algorithm={}, nonce=\"{}\", opaque=\"{}\", stale={}",
+                    self.realm,
+                    algorithm.name(),
+                    nonce,
+                    self.opaque,
+                    stale
+                )
+            })
+            .collect::<Vec<String>>();
+        scope.refusal(challenges.join(", "))
+    }
+
+    /// Checks the credentials of `req`. Its nonce count is only used up
+    /// when `record` is set, so a head can be checked ahead of handling.
+    fn verify(&self, req: &Request, record: bool, scope: Scope) -> Verdict {
+        let Some(params) = (match scope.credentials(req) {
+            Some(Authorization::Other { scheme, params })
+                if scheme.eq_ignore_ascii_case("Digest") =>
+            {
+                Some(parse_params(&params))
+            }
+            _ => None,
+        }) else {
+            return Verdict::Denied;
+        };
+        let field = |name: &str| params.get(name).map(String::as_str).unwrap_or("");
+        let algorithm = match params.get("algorithm") {
+            Some(name) => match DigestAlgorithm::from_name(name) {
+                Some(algorithm) => algorithm,
+                None => return Verdict::Denied,
+            },
+            None => DigestAlgorithm::Md5,
+        };
+        if field("realm") != self.realm
+            || field("uri") != sent_target(req)
+            || field("opaque") != self.opaque
+            || field("qop") != "auth"
+        {
+            return Verdict::Denied;
+        }
+        let Ok(nc) = u32::from_str_radix(field("nc"), 16) else {
+            return Verdict::Denied;
+        };
+        let Some(ha1) = self.users.get(field("username")).and_then(|hashes| {
+            hashes
+                .iter()
+                .find(|(a, _)| *a == algorithm)
+                .map(|(_, hash)| hash)
+        }) else {
+            return Verdict::Denied;
+        };
+
+        let nonce = field("nonce");
+        let ha2 = algorithm.hash(&format!("{}:{}", sent_method(req), sent_target(req)));
+        let expected = algorithm.hash(&format!(
+            "{}:{}:{}:{}:auth:{}",
+            ha1,
+            nonce,
+            field("nc"),
+            field("cnonce"),
+            ha2
+        ));
+        let response = field("response").to_ascii_lowercase();
+        if !constant_time_eq(expected.as_bytes(), response.as_bytes()) {
+            return Verdict::Denied;
+        }
+
+        let Some(issued) = self.nonce_issued(nonce) else {
+            return Verdict::Denied;
+        };
+        if self.is_expired(issued) {
+            return Verdict::Stale;
+        }
+        let mut counts = self.nonce_counts.lock().unwrap();
+        counts.retain(|nonce, _| {
+            self.nonce_issued(nonce)
+                .is_some_and(|issued| !self.is_expired(issued))
+        });
+        if nc <= counts.get(nonce).copied().unwrap_or(0) {
+            return Verdict::Denied;
+        }
+        if record {
+            counts.insert(nonce.to_owned(), nc);
+        }
+        Verdict::Allowed
+    }
+
+    /// The challenge for a request `verify` doesn't allow.
+    fn check(&self, req: &Request, record: bool, scope: Scope) -> Option<Response> {
+        match self.verify(req, record, scope) {
+            Verdict::Allowed => None,
+            Verdict::Stale => Some(self.challenge(true, scope)),
+            Verdict::Denied => Some(self.challenge(false, scope)),
+        }
+    }
+}
+
+impl Middleware for DigestAuth {
+    fn handle(&self, req: Request, next: Next<'_>) -> Response {
+        match self.check(&req, true, Scope::Origin) {
+            Some(challenge) => challenge,
+            None => next(req),
+        }
+    }
+
+    fn expect(&self, req: &Request) -> Option<Response> {
+        self.check(req, false, Scope::Origin)
+    }
+
+    /// Challenges with `407` and checks `Proxy-Authorization`, as the
+    /// server is acting as a proxy for the tunnel.
+    fn connect(&self, req: &Request) -> Option<Response> {
+        self.check(req, true, Scope::Proxy)
+    }
+}
+
+pub type Verifier = Box<dyn Fn(&str, &str) -> bool + Send + Sync>;
+
+/// HTTP Basic authentication, checking credentials with a verifier callback
+/// or an Apache-style htpasswd file.
+pub struct BasicAuth {
+    realm: String,
+    verifier: Verifier,
+}
+
+impl BasicAuth {
+    pub fn new(realm: &str, verifier: Verifier) -> Self {
+        BasicAuth {
+            realm: realm.to_owned(),
+            verifier,
+        }
+    }
+
+    /// Checks credentials against an htpasswd file, reloading it whenever
+    /// its modification time changes. Supports `{SHA}`, `$apr1$` and plain
+    /// entries; a file with any other scheme, such as bcrypt or SHA-crypt,
+    /// is refused rather than leaving those users locked out.
+    pub fn htpasswd(realm: &str, path: &str) -> Result<Self> {
+        let file = Htpasswd::load(path)?;
+        let file = Mutex::new(file);
+        let verifier = move |username: &str, password: &str| {
+            let mut file = file.lock().unwrap();
+            file.reload_if_changed();
+            file.verify(username, password)
+        };
+        Ok(BasicAuth::new(realm, Box::new(verifier)))
+    }
+
+    fn credentials(req: &Request, scope: Scope) -> Option<(String, String)> {
+        match scope.credentials(req)? {
+            Authorization::Basic { user, pass } => Some((user, pass)),
+            _ => None,
+        }
+    }
+
+    fn check(&self, req: &Request, scope: Scope) -> Option<Response> {
+        if let Some((username, password)) = BasicAuth::credentials(req, scope) {
+            if (self.verifier)(&username, &password) {
+                return None;
+            }
+        }
+        let challenge = format!("Basic realm=\"{}\", charset=\"UTF-8\"", self.realm);
+        Some(scope.refusal(challenge))
+    }
+}
+
+impl Middleware for BasicAuth {
+    fn handle(&self, req: Request, next: Next<'_>) -> Response {
+        match self.expect(&req) {
+            Some(challenge) => challenge,
+            None => next(req),
+        }
+    }
+
+    /// Challenges unauthenticated requests before their body is sent or
+    /// any early hints go out.
+    fn expect(&self, req: &Request) -> Option<Response> {
+        self.check(req, Scope::Origin)
+    }
+
+    /// Challenges with `407` and checks `Proxy-Authorization`, as the
+    /// server is acting as a proxy for the tunnel.
+    fn connect(&self, req: &Request) -> Option<Response> {
+        self.check(req, Scope::Proxy)
+    }
+}
+
+struct Htpasswd {
+    path: String,
+    modified: Option<SystemTime>,
+    entries: HashMap<String, StoredPassword>,
+}
+
+/// An htpasswd entry in one of the formats that can be checked.
+enum StoredPassword {
+    /// `{SHA}` and the base64 SHA-1 of the password.
+    Sha(String),
+    /// The whole `$apr1$salt$hash` string.
+    Apr1(String),
+    Plain(String),
+}
+
+impl StoredPassword {
+    /// Reads an entry's hash. Anything that looks like a scheme other than
+    /// `{SHA}` and `$apr1$` is refused rather than compared as plaintext,
+    /// which would let the hash itself through as the password.
+    fn parse(hash: &str) -> Option<Self> {
+        if let Some(digest) = hash.strip_prefix("{SHA}") {
+            return Some(StoredPassword::Sha(digest.to_owned()));
+        }
+        if hash.starts_with("$apr1$") {
+            return Some(StoredPassword::Apr1(hash.to_owned()));
+        }
+        let crypt = hash.len() == 13
+            && hash
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b == b'.' || b == b'/');
+        if hash.starts_with('$') || hash.starts_with('{') || crypt {
+            return None;
+        }
+        Some(StoredPassword::Plain(hash.to_owned()))
+    }
+}
+
+impl Htpasswd {
+    fn load(path: &str) -> Result<Self> {
+        let modified = fs::metadata(path)?.modified().ok();
+        let mut entries = HashMap::new();
+        for line in fs::read_to_string(path)?.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((username, hash)) = line.split_once(':') else {
+                bail!("invalid htpasswd line in {}: {}", path, line);
+            };
+            let Some(stored) = StoredPassword::parse(hash) else {
+                bail!(
+                    "unsupported hash for {} in {}: only {{SHA}}, $apr1$ and plain entries are supported, use -m or -s",
+                    username,
+                    path
+                );
+            };
+            entries.insert(username.to_owned(), stored);
+        }
+        Ok(Htpasswd {
+            path: path.to_owned(),
+            modified,
+            entries,
+        })
+    }
+
+    fn reload_if_changed(&mut self) {
+        let modified = fs::metadata(&self.path).and_then(|m| m.modified()).ok();
+        if modified == self.modified {
+            return;
+        }
+        match Htpasswd::load(&self.path) {
+            Ok(reloaded) => *self = reloaded,
+            Err(err) => println!("error reloading htpasswd {}: {}", self.path, err),
+        }
+    }
+
+    fn verify(&self, username: &str, password: &str) -> bool {
+        let Some(entry) = self.entries.get(username) else {
+            return false;
+        };
+        let (stored, computed) = match entry {
+            StoredPassword::Sha(digest) => (digest, base64_encode(&sha1(password.as_bytes()))),
+            StoredPassword::Apr1(hash) => {
+                let salt = hash["$apr1$".len()..].split('$').next().unwrap_or("");
+                (hash, apr1_crypt(password, salt))
+            }
+            StoredPassword::Plain(plain) => (plain, password.to_owned()),
+        };
+        constant_time_eq(stored.as_bytes(), computed.as_bytes())
+    }
+}
+
+/// The method the client sent, which HEAD requests no longer carry in
+/// `method` once they are handled as GET.
+fn sent_method(req: &Request) -> &str {
+    req.request_line().split(' ').next().unwrap_or("")
+}
+
+/// The request target exactly as the client sent it, which is what the
+/// digest `uri` has to name.
+fn sent_target(req: &Request) -> &str {
+    req.request_line().split(' ').nth(1).unwrap_or("")
+}
+
+/// Apache's MD5-based crypt variant (`$apr1$salt$hash`).
+fn apr1_crypt(password: &str, salt: &str) -> String {
+    const MAGIC: &str = "$apr1$";
+    const ITOA64: &[u8] = b"./0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+    let password = password.as_bytes();
+    let salt = &salt.as_bytes()[..salt.len().min(8)];
+
+    let alternate = md5(&[password, salt, password].concat());
+    let mut context = [password, MAGIC.as_bytes(), salt].concat();
+    let mut remaining = password.len();
+    while remaining > 0 {
+        let take = remaining.min(16);
+        context.extend_from_slice(&alternate[..take]);
+        remaining -= take;
+    }
+    let mut bits = password.len();
+    while bits > 0 {
+        context.push(if bits & 1 == 1 { 0 } else { password[0] });
+        bits >>= 1;
+    }
+    let mut digest = md5(&context);
+
+    for round in 0..1000 {
+        let mut block = Vec::with_capacity(64);
+        if round & 1 == 1 {
+            block.extend_from_slice(password);
+        } else {
+            block.extend_from_slice(&digest);
+        }
+        if round % 3 != 0 {
+            block.extend_from_slice(salt);
+        }
+        if round % 7 != 0 {
+            block.extend_from_slice(password);
+        }
+        if round & 1 == 1 {
+            block.extend_from_slice(&digest);
+        } else {
+            block.extend_from_slice(password);
+        }
+        digest = md5(&block);
+    }
+
+    let mut encoded = String::with_capacity(22);
+    let mut push = |value: u32, chars: usize| {
+        let mut value = value;
+        for _ in 0..chars {
+            encoded.push(ITOA64[(value & 0x3f) as usize] as char);
+            value >>= 6;
+        }
+    };
+    for (a, b, c) in [(0, 6, 12), (1, 7, 13), (2, 8, 14), (3, 9, 15), (4, 10, 5)] {
+        let value = (digest[a] as u32) << 16 | (digest[b] as u32) << 8 | digest[c] as u32;
+        push(value, 4);
+    }
+    push(digest[11] as u32, 2);
+
+    format!("{}{}${}", MAGIC, String::from_utf8_lossy(salt), encoded)
+}
+
+/// Parses comma-separated `key=value` auth parameters, unquoting quoted
+/// values (which may themselves contain commas).
+fn parse_params(input: &str) -> HashMap<String, String> {
+    let mut params = HashMap::new();
+    let mut rest = input.trim();
+    while !rest.is_empty() {
+        let Some((key, after)) = rest.split_once('=') else {
+            break;
+        };
+        let key = key
+            .trim()
+            .trim_start_matches(',')
+            .trim()
+            .to_ascii_lowercase();
+        let after = after.trim_start();
+        let (value, remaining) = match after.strip_prefix('"') {
+            Some(quoted) => {
+                let mut value = String::new();
+                let mut chars = quoted.char_indices();
+                let mut end = quoted.len();
+                while let Some((i, c)) = chars.next() {
+                    match c {
+                        '\\' => {
+                            if let Some((_, escaped)) = chars.next() {
+                                value.push(escaped);
+                            }
+                        }
+                        '"' => {
+                            end = i + 1;
+                            break;
+                        }
+                        c => value.push(c),
+                    }
+                }
+                (value, &quoted[end..])
+            }
+            None => {
+                let end = after.find(',').unwrap_or(after.len());
+                (after[..end].trim().to_owned(), &after[end..])
+            }
+        };
+        params.insert(key, value);
+        rest = remaining.trim_start().trim_start_matches(',').trim_start();
+    }
+    params
+}
@@ -0,0 +1,198 @@
+//! Built-in load generator behind the `bench` subcommand: a fixed number of
+//! connections repeatedly GET one URL for a fixed time, then the request
+//! rate and latency percentiles are reported.
+
+use anyhow::{anyhow, bail, Result};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+const DEFAULT_CONNECTIONS: usize = 64;
+const DEFAULT_DURATION: Duration = Duration::from_secs(10);
+
+pub struct Options {
+    /// `host:port` to connect to.
+    pub address: String,
+    pub request: Vec<u8>,
+    pub connections: usize,
+    pub duration: Duration,
+}
+
+impl Options {
+    /// Parses `URL [--connections N] [--duration SECONDS]`.
+    pub fn from_args(args: &[String]) -> Result<Self> {
+        let mut args = args.iter();
+        let url = args
+            .next()
+            .ok_or_else(|| anyhow!("usage: bench URL [--connections N] [--duration SECONDS]"))?;
+        let (address, path) = parse_url(url)?;
+        let mut options = Options {
+            request: format!(
+                "GET {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n",
+                path, address
+            )
+            .into_bytes(),
+            address,
+            connections: DEFAULT_CONNECTIONS,
+            duration: DEFAULT_DURATION,
+        };
+        while let Some(flag) = args.next() {
+            let value = args
+                .next()
+                .ok_or_else(|| anyhow!("missing value for {}", flag))?;
+            let number = value
+                .parse::<u64>()
+                .map_err(|_| anyhow!("invalid value for {}: {}", flag, value))?;
+            match flag.as_str() {
+                "--connections" => options.connections = number.max(1) as usize,
+                "--duration" => options.duration = Duration::from_secs(number),
+                _ => bail!("unknown argument: {}", flag),
+            }
+        }
+        Ok(options)
+    }
+}
+
+/// Splits `http://host[:port][/path]` into a socket address and a path.
+fn parse_url(url: &str) -> Result<(String, String)> {
+    let rest = url
+        .strip_prefix("http://")
+        .ok_or_else(|| anyhow!("only http:// URLs are supported: {}", url))?;
+    let (authority, path) = match rest.find('/') {
+        Some(pos) => rest.split_at(pos),
+        None => (rest, "/"),
+    };
+    if authority.is_empty() {
+        bail!("missing host in {}", url);
+    }
+    let address = match authority.contains(':') {
+        true => authority.to_owned(),
+        false => format!("{}:80", authority),
+    };
+    Ok((address, path.to_owned()))
+}
+
+pub struct Report {
+    elapsed: Duration,
+    /// Sorted latencies of completed requests.
+    latencies: Vec<Duration>,
+    statuses: BTreeMap<String, u64>,
+    errors: u64,
+}
+
+impl Report {
+    fn percentile(&self, p: f64) -> Duration {
+        if self.latencies.is_empty() {
+            return Duration::ZERO;
+        }
+        let rank = ((self.latencies.len() - 1) as f64 * p).round() as usize;
+        self.latencies[rank]
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let completed = self.latencies.len();
+        writeln!(
+            f,
+            "{} requests in {:.2}s, {} errors",
+            completed,
+            self.elapsed.as_secs_f64(),
+            self.errors
+        )?;
+        writeln!(
+            f,
+            "requests/sec: {:.1}",
+            completed as f64 / self.elapsed.as_secs_f64()
+        )?;
+        writeln!(f, "latency:")?;
+        for (label, p) in [("p50", 0.5), ("p90", 0.9), ("p99", 0.99), ("max", 1.0)] {
+            writeln!(f, "  {:<4} {:?}", label, self.percentile(p))?;
+        }
+        writeln!(f, "status codes:")?;
+        for (status, count) in &self.statuses {
+            writeln!(f, "  {:<4} {}", status, count)?;
+        }
+        Ok(())
+    }
+}
+
+struct WorkerResult {
+    latencies: Vec<Duration>,
+    statuses: Vec<String>,
+    errors: u64,
+}
+
+/// Runs the load test on a fresh multithreaded runtime.
+pub fn run(options: Options) -> Result<Report> {
+    let runtime = tokio::runtime::Runtime::new()?;
+    runtime.block_on(async {
+        let start = Instant::now();
+        let deadline = start + options.duration;
+        let request = Arc::new(options.request);
+        let workers = (0..options.connections)
+            .map(|_| tokio::spawn(worker(options.address.clone(), request.clone(), deadline)))
+            .collect::<Vec<_>>();
+
+        let mut report = Report {
+            elapsed: Duration::ZERO,
+            latencies: Vec::new(),
+            statuses: BTreeMap::new(),
+            errors: 0,
+        };
+        for worker in workers {
+            let result = worker.await?;
+            report.latencies.extend(result.latencies);
+            report.errors += result.errors;
+            for status in result.statuses {
+                *report.statuses.entry(status).or_default() += 1;
+            }
+        }
+        report.elapsed = start.elapsed();
+        report.latencies.sort();
+        Ok(report)
+    })
+}
+
+async fn worker(address: String, request: Arc<Vec<u8>>, deadline: Instant) -> WorkerResult {
+    let mut result = WorkerResult {
+        latencies: Vec::new(),
+        statuses: Vec::new(),
+        errors: 0,
+    };
+    let mut response = Vec::with_capacity(1024);
+    while Instant::now() < deadline {
+        let started = Instant::now();
+        response.clear();
+        match send(&address, &request, &mut response).await {
+            Ok(()) => {
+                result.latencies.push(started.elapsed());
+                let status = response
+                    .split(|b| *b == b' ')
+                    .nth(1)
+                    .map(|code| String::from_utf8_lossy(code).into_owned())
+                    .unwrap_or_else(|| String::from("???"));
+                result.statuses.push(status);
+            }
+            Err(_) => {
+                result.errors += 1;
+                // Back off a little so a refused port doesn't spin.
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        }
+    }
+    result
+}
+
+async fn send(address: &str, request: &[u8], response: &mut Vec<u8>) -> Result<()> {
+    let mut stream = TcpStream::connect(address).await?;
+    stream.write_all(request).await?;
+    stream.read_to_end(response).await?;
+    if response.is_empty() {
+        bail!("connection closed without a response");
+    }
+    Ok(())
+}
@@ -0,0 +1,87 @@
+//! Request bodies handed to handlers as a stream of chunks.
+
+use anyhow::{anyhow, Result};
+use std::string::FromUtf8Error;
+use tokio::sync::{mpsc, oneshot};
+
+use crate::header_map::HeaderMap;
+
+/// How many chunks the connection reads ahead of a handler consuming a
+/// streamed body.
+const READ_AHEAD: usize = 4;
+
+/// Why `Request::body_bytes` or `Request::body_text` couldn't hand over
+/// the body.
+#[derive(Debug, thiserror::Error)]
+pub enum BodyError {
+    #[error("request body was already consumed")]
+    Consumed,
+    #[error("request body is still on the connection; read it with body_stream")]
+    Streamed,
+    #[error("request body is not UTF-8: {0}")]
+    NotUtf8(#[from] FromUtf8Error),
+}
+
+/// The body of a request, delivered in chunks. For routes set up with
+/// `Route::stream_body` the chunks are read off the connection as they are
+/// consumed; for any other request the buffered body is the only chunk.
+#[derive(Debug)]
+pub struct BodyStream {
+    chunks: mpsc::Receiver<Result<Vec<u8>>>,
+    trailers: oneshot::Receiver<HeaderMap>,
+}
+
+impl BodyStream {
+    /// A stream fed through the returned senders: the chunks, then the
+    /// trailer fields once the body has ended.
+    pub(crate) fn channel() -> (
+        mpsc::Sender<Result<Vec<u8>>>,
+        oneshot::Sender<HeaderMap>,
+        Self,
+    ) {
+        let (tx, chunks) = mpsc::channel(READ_AHEAD);
+        let (trailer, trailers) = oneshot::channel();
+        (tx, trailer, BodyStream { chunks, trailers })
+    }
+
+    /// A stream yielding `body` in one chunk.
+    pub fn buffered(body: Vec<u8>) -> Self {
+        BodyStream::buffered_with_trailers(body, HeaderMap::new())
+    }
+
+    /// A stream yielding `body` in one chunk, followed by `trailers`.
+    pub(crate) fn buffered_with_trailers(body: Vec<u8>, trailers: HeaderMap) -> Self {
+        let (tx, chunks) = mpsc::channel(1);
+        if !body.is_empty() {
+            let _ = tx.try_send(Ok(body));
+        }
+        let (trailer, received) = oneshot::channel();
+        let _ = trailer.send(trailers);
+        BodyStream {
+            chunks,
+            trailers: received,
+        }
+    }
+
+    /// The next chunk, or `None` once the body has ended. An error means
+    /// the body was cut short or malformed, and nothing follows it.
+    pub async fn chunk(&mut self) -> Option<Result<Vec<u8>>> {
+        self.chunks.recv().await
+    }
+
+    /// The trailer fields sent after the body, for once `chunk` has
+    /// returned `None`. Empty when there were none, or when the body didn't
+    /// end cleanly.
+    pub async fn trailers(self) -> HeaderMap {
+        self.trailers.await.unwrap_or_default()
+    }
+
+    /// Reads the rest of the body into memory.
+    pub async fn collect(mut self) -> Result<Vec<u8>> {
+        let mut body = Vec::new();
+        while let Some(chunk) = self.chunk().await {
+            body.extend(chunk.map_err(|err| anyhow!("request body: {}", err))?);
+        }
+        Ok(body)
+    }
+}
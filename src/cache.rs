@@ -0,0 +1,81 @@
+use crate::conditional::EntityTag;
+use crate::hash::{hex, sha256};
+use crate::header_map::HeaderMap;
+use crate::pattern;
+use crate::request::{HttpMethod, Request};
+use crate::response::{Body, HttpCode, Response};
+use crate::routes::{Middleware, Next};
+
+/// Fields a `304` carries over from the `200` it stands for (RFC 9110
+/// §15.4.5).
+const NOT_MODIFIED_FIELDS: [&str; 6] = [
+    "Cache-Control",
+    "Content-Location",
+    "Date",
+    "ETag",
+    "Expires",
+    "Vary",
+];
+
+/// Adds conditional caching to buffered responses: a content-hash `ETag`,
+/// `304 Not Modified` answers to a matching `If-None-Match`, and
+/// `Cache-Control` values chosen by path pattern.
+pub struct CacheMiddleware {
+    etag: bool,
+    /// `(glob pattern, Cache-Control value)`; the first match wins.
+    policies: Vec<(String, String)>,
+}
+
+impl CacheMiddleware {
+    pub fn new(etag: bool, policies: Vec<(String, String)>) -> Self {
+        CacheMiddleware { etag, policies }
+    }
+
+    fn policy_for(&self, path: &str) -> Option<&str> {
+        self.policies
+            .iter()
+            .find(|(pattern, _)| pattern::matches(pattern, path))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+impl Middleware for CacheMiddleware {
+    fn handle(&self, req: Request, next: Next<'_>) -> Response {
+        let cacheable = req.method == HttpMethod::GET;
+        let if_none_match = req.if_none_match();
+        let policy = self.policy_for(&req.path).map(str::to_owned);
+
+        let mut res = next(req);
+        if !cacheable || res.code != HttpCode::OK {
+            return res;
+        }
+        let headers = res.headers.get_or_insert_with(HeaderMap::new);
+        if let Some(policy) = policy {
+            headers.get_or_insert_with("Cache-Control", || policy);
+        }
+        if self.etag && !headers.contains_key("ETag") {
+            if let Some(Body::Bytes(body)) = &res.content {
+                let etag = format!("\"{}\"", hex(&sha256(body)[..16]));
+                headers.insert(String::from("ETag"), etag);
+            }
+        }
+
+        let etag = headers.get("ETag").cloned();
+        if let (Some(etag), Some(if_none_match)) = (etag, if_none_match) {
+            if if_none_match.matches_weak(EntityTag::parse(&etag).as_ref()) {
+                let mut kept = HeaderMap::new();
+                for name in NOT_MODIFIED_FIELDS {
+                    for value in headers.get_all(name) {
+                        kept.append(name.to_owned(), value.clone());
+                    }
+                }
+                return Response {
+                    code: HttpCode::NotModified,
+                    content: None,
+                    headers: Some(kept),
+                };
+            }
+        }
+        res
+    }
+}
@@ -0,0 +1,59 @@
+use crate::header_map::HeaderMap;
+use crate::request::Request;
+use crate::response::{HttpCode, Response};
+use crate::routes::{Middleware, Next};
+use crate::vhost::split_port;
+
+/// Permanently redirects requests whose `Host` differs from the canonical
+/// one, keeping the path, query and any port the client used. Requests
+/// without a `Host` header are served as they are.
+pub struct CanonicalHost {
+    host: String,
+}
+
+impl CanonicalHost {
+    pub fn new(host: &str) -> Self {
+        CanonicalHost {
+            host: host.to_owned(),
+        }
+    }
+}
+
+impl CanonicalHost {
+    /// The redirect for `req`, unless it already uses the canonical host.
+    fn redirect(&self, req: &Request) -> Option<Response> {
+        let (name, port) = split_port(req.host()?);
+        let (canonical, canonical_port) = split_port(&self.host);
+        if name == canonical {
+            return None;
+        }
+        let authority = match (port, canonical_port) {
+            (Some(port), None) => format!("{}:{}", self.host, port),
+            _ => self.host.clone(),
+        };
+        Some(Response {
+            code: HttpCode::MovedPermanently,
+            content: None,
+            headers: Some(HeaderMap::from([
+                (
+                    String::from("Location"),
+                    format!("http://{}{}", authority, req.target()),
+                ),
+                (String::from("Content-Length"), String::from("0")),
+            ])),
+        })
+    }
+}
+
+impl Middleware for CanonicalHost {
+    fn handle(&self, req: Request, next: Next<'_>) -> Response {
+        match self.redirect(&req) {
+            Some(redirect) => redirect,
+            None => next(req),
+        }
+    }
+
+    fn expect(&self, req: &Request) -> Option<Response> {
+        self.redirect(req)
+    }
+}
@@ -0,0 +1,267 @@
+use anyhow::{anyhow, bail, Result};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use tokio::runtime::{Handle, RuntimeFlavor};
+use tokio::sync::mpsc;
+use tokio::task;
+
+use crate::header_map::HeaderMap;
+use crate::request::Request;
+use crate::response::{Body, HttpCode, Response};
+use crate::routes::{Middleware, Next};
+
+/// Runs CGI/1.1 scripts from a directory for requests under a path prefix.
+/// The script's output is streamed to the client as it is produced, and
+/// its header section is held to `max_header_size` bytes.
+pub struct Cgi {
+    prefix: String,
+    directory: PathBuf,
+    chunk_size: usize,
+    max_header_size: usize,
+}
+
+impl Cgi {
+    pub fn new(prefix: &str, directory: &str, chunk_size: usize, max_header_size: usize) -> Self {
+        Cgi {
+            prefix: prefix.trim_end_matches('/').to_owned(),
+            directory: PathBuf::from(directory),
+            chunk_size,
+            max_header_size,
+        }
+    }
+
+    /// Splits the path below the prefix into the script and the `PATH_INFO`
+    /// that follows it, refusing any path that would climb out of the
+    /// directory.
+    fn script(&self, path: &str) -> Option<(PathBuf, String, String)> {
+        let rest = path.strip_prefix(self.prefix.as_str())?;
+        let mut script = self.directory.clone();
+        let mut script_name = self.prefix.clone();
+        let mut segments = rest.split('/').filter(|s| !s.is_empty());
+        for segment in segments.by_ref() {
+            if segment == "." || segment == ".." || segment.contains('\\') {
+                return None;
+            }
+            script.push(segment);
+            script_name = format!("{}/{}", script_name, segment);
+            if script.is_file() {
+                let path_info = segments.map(|s| format!("/{}", s)).collect::<String>();
+                return Some((script, script_name, path_info));
+            }
+        }
+        None
+    }
+
+    fn run(
+        &self,
+        req: Request,
+        script: PathBuf,
+        script_name: String,
+        path_info: String,
+    ) -> Result<Response> {
+        let query = req.query_raw().unwrap_or("");
+        let body = req.body.clone();
+        let host = req.host().unwrap_or("");
+        let (server_name, server_port) = host.rsplit_once(':').unwrap_or((host, "80"));
+
+        let mut command = Command::new(&script);
+        command
+            .env_clear()
+            .env("PATH", std::env::var_os("PATH").unwrap_or_default())
+            .env("GATEWAY_INTERFACE", "CGI/1.1")
+            .env("SERVER_PROTOCOL", req.version.as_str())
+            .env("SERVER_SOFTWARE", "http-server-starter-rust")
+            .env("SERVER_NAME", server_name)
+            .env("SERVER_PORT", server_port)
+            .env("REQUEST_METHOD", req.method.as_str())
+            .env("REQUEST_URI", req.target())
+            .env("SCRIPT_NAME", &script_name)
+            .env("SCRIPT_FILENAME", &script)
+            .env("PATH_INFO", &path_info)
+            .env("QUERY_STRING", query)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped());
+        if !body.is_empty() {
+            command.env("CONTENT_LENGTH", body.len().to_string());
+        }
+        if let Some(content_type) = req.headers.get("Content-Type") {
+            command.env("CONTENT_TYPE", content_type);
+        }
+        for (name, value) in req.headers.iter() {
+            // Credentials stay with the server, and `Proxy` would become
+            // HTTP_PROXY, which many CGI libraries treat as a proxy setting.
+            let skip = ["Content-Type", "Content-Length", "Authorization", "Proxy"];
+            if skip.iter().any(|skip| skip.eq_ignore_ascii_case(name)) {
+                continue;
+            }
+            let name = format!("HTTP_{}", name.to_ascii_uppercase().replace('-', "_"));
+            command.env(name, value);
+        }
+        if let Some(dir) = script.parent() {
+            command.current_dir(dir);
+        }
+
+        let mut child = command.spawn()?;
+        let mut stdin = child.stdin.take().ok_or_else(|| anyhow!("no stdin"))?;
+        // Written on the blocking pool so a script that prints before reading
+        // its input can't deadlock against us.
+        task::spawn_blocking(move || {
+            let _ = stdin.write_all(&body);
+        });
+        let stdout = child.stdout.take().ok_or_else(|| anyhow!("no stdout"))?;
+        let mut stdout = BufReader::new(stdout);
+        let head = blocking(|| read_headers(&mut stdout, self.max_header_size));
+        let (code, headers) = match head {
+            Ok(head) => head,
+            Err(err) => {
+                task::spawn_blocking(move || {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                });
+                return Err(err);
+            }
+        };
+        // These can't carry a body, so whatever the script prints after its
+        // headers is read and dropped.
+        if matches!(code, HttpCode::NoContent | HttpCode::NotModified) {
+            task::spawn_blocking(move || {
+                let _ = io::copy(&mut stdout, &mut io::sink());
+                let _ = child.wait();
+            });
+            return Ok(Response {
+                code,
+                content: None,
+                headers: Some(headers),
+            });
+        }
+
+        let (sender, chunks) = mpsc::channel(1);
+        let chunk_size = self.chunk_size;
+        task::spawn_blocking(move || {
+            let mut buf = vec![0; chunk_size];
+            loop {
+                match stdout.read(&mut buf) {
+                    Ok(0) | Err(_) => break,
+                    Ok(len) => {
+                        if sender.blocking_send(buf[..len].to_vec()).is_err() {
+                            let _ = child.kill();
+                            break;
+                        }
+                    }
+                }
+            }
+            let _ = child.wait();
+        });
+        Ok(Response {
+            code,
+            content: Some(Body::Stream(chunks)),
+            headers: Some(headers),
+        })
+    }
+}
+
+impl Middleware for Cgi {
+    fn handle(&self, req: Request, next: Next<'_>) -> Response {
+        let path = req.path.as_str();
+        if path != self.prefix && !path.starts_with(&format!("{}/", self.prefix)) {
+            return next(req);
+        }
+        let Some((script, script_name, path_info)) = self.script(path) else {
+            return Response {
+                code: HttpCode::NotFound,
+                content: None,
+                headers: None,
+            };
+        };
+        match self.run(req, script, script_name, path_info) {
+            Ok(res) => res,
+            Err(err) => {
+                println!("CGI error: {}", err);
+                Response {
+                    code: HttpCode::BadGateway,
+                    content: None,
+                    headers: None,
+                }
+            }
+        }
+    }
+}
+
+/// Runs `work`, which blocks, on a runtime worker. A multi-threaded
+/// runtime hands the worker's other tasks to another thread meanwhile; a
+/// current-thread runtime has no other thread to hand them to.
+fn blocking<T>(work: impl FnOnce() -> T) -> T {
+    match Handle::try_current().map(|handle| handle.runtime_flavor()) {
+        Ok(RuntimeFlavor::MultiThread) => task::block_in_place(work),
+        _ => work(),
+    }
+}
+
+/// Reads the script's header block, at most `limit` bytes of it. `Status`
+/// sets the response code, and a `Location` without one is a redirect.
+/// `Content-Length` and `Transfer-Encoding` are dropped since the body is
+/// always sent chunked. A CR inside a line would let the script split it
+/// into fields of its own choosing, so it is refused.
+fn read_headers(stdout: &mut impl BufRead, limit: usize) -> Result<(HttpCode, HeaderMap)> {
+    let mut headers = HeaderMap::new();
+    let mut code = None;
+    let mut read = 0;
+    loop {
+        let mut line = String::new();
+        // One byte past the limit is enough to tell it was passed.
+        let len = (&mut *stdout)
+            .take((limit - read + 1) as u64)
+            .read_line(&mut line)?;
+        if len == 0 {
+            bail!("script ended before its headers");
+        }
+        read += len;
+        if read > limit {
+            bail!("script headers exceed {} bytes", limit);
+        }
+        let line = line
+            .strip_suffix('\n')
+            .map(|line| line.strip_suffix('\r').unwrap_or(line))
+            .unwrap_or(&line);
+        if line.is_empty() {
+            break;
+        }
+        if line.contains('\r') {
+            bail!("CR in header from script: {:?}", line);
+        }
+        let Some((name, value)) = line.split_once(':') else {
+            bail!("malformed header from script: {}", line);
+        };
+        let value = value.trim().to_owned();
+        if name.eq_ignore_ascii_case("Status") {
+            code =
+                Some(status_code(&value).ok_or_else(|| anyhow!("unsupported status {}", value))?);
+        } else if !name.eq_ignore_ascii_case("Content-Length")
+            && !name.eq_ignore_ascii_case("Transfer-Encoding")
+        {
+            headers.insert(name.to_owned(), value);
+        }
+    }
+    let code = code.unwrap_or(if headers.contains_key("Location") {
+        HttpCode::Found
+    } else {
+        HttpCode::OK
+    });
+    Ok((code, headers))
+}
+
+/// A final status for the script's response, keeping the script's reason
+/// phrase for codes without a variant. Interim codes are refused.
+fn status_code(status: &str) -> Option<HttpCode> {
+    let (code, reason) = match status.split_once(' ') {
+        Some((code, reason)) => (code, Some(reason.trim()).filter(|r| !r.is_empty())),
+        None => (status, None),
+    };
+    let code = code.parse::<u16>().ok()?;
+    let code = match HttpCode::try_from(code) {
+        Ok(known) => known,
+        Err(_) => HttpCode::custom(code, reason).ok()?,
+    };
+    (!code.is_informational()).then_some(code)
+}
@@ -0,0 +1,85 @@
+//! Opt-in fault injection for exercising client retry logic. Each request
+//! independently rolls for a delay and then for at most one fault.
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::{io::AsyncWriteExt, net::TcpStream};
+
+use crate::config::Config;
+use crate::request::Request;
+use crate::response::{HttpCode, Response};
+use crate::routes::Routes;
+
+pub struct Chaos {
+    delay_rate: f64,
+    max_delay: Duration,
+    drop_rate: f64,
+    truncate_rate: f64,
+    error_rate: f64,
+}
+
+impl Chaos {
+    /// Returns `None` unless at least one fault has a non-zero rate.
+    pub fn new(config: &Config) -> Option<Self> {
+        let chaos = Chaos {
+            delay_rate: config.chaos_delay_rate,
+            max_delay: config.chaos_max_delay,
+            drop_rate: config.chaos_drop_rate,
+            truncate_rate: config.chaos_truncate_rate,
+            error_rate: config.chaos_error_rate,
+        };
+        let rates = [
+            chaos.delay_rate,
+            chaos.drop_rate,
+            chaos.truncate_rate,
+            chaos.error_rate,
+        ];
+        rates.iter().any(|rate| *rate > 0.0).then_some(chaos)
+    }
+
+    /// Delays the request and possibly answers it with a fault. Hands the
+    /// request back when it should be served normally.
+    pub async fn inject(
+        &self,
+        stream: &mut TcpStream,
+        routes: &Routes,
+        req: Request,
+    ) -> Option<Request> {
+        if roll() < self.delay_rate {
+            tokio::time::sleep(self.max_delay.mul_f64(roll())).await;
+        }
+        if roll() < self.drop_rate {
+            println!("chaos: dropping connection");
+            return None;
+        }
+        if roll() < self.error_rate {
+            println!("chaos: answering with 500");
+            let (head, _) = Response {
+                code: HttpCode::InternalServerError,
+                content: None,
+                headers: None,
+            }
+            .into_parts();
+            let _ = stream.write_all(&head).await;
+            return None;
+        }
+        if roll() < self.truncate_rate {
+            println!("chaos: truncating response");
+            let mut response = Vec::new();
+            routes.execute(&mut response, req, &[]).await;
+            let _ = stream.write_all(&response[..response.len() / 2]).await;
+            return None;
+        }
+        Some(req)
+    }
+}
+
+/// A uniformly distributed value in `[0, 1)`.
+pub(crate) fn roll() -> f64 {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+    (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64
+}
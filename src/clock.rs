@@ -0,0 +1,112 @@
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::watch;
+
+pub type Sleep = Pin<Box<dyn Future<Output = ()> + Send + 'static>>;
+
+/// Source of time for everything that expires: timeouts, nonces and the
+/// like. Production code uses `SystemClock`; tests drive a `ManualClock`
+/// so time-dependent behavior runs deterministically without real sleeps.
+pub trait Clock: Send + Sync {
+    /// Monotonic time, for measuring durations.
+    fn now(&self) -> Instant;
+    /// Wall-clock time, for values sent to clients.
+    fn system_time(&self) -> SystemTime;
+    fn sleep(&self, duration: Duration) -> Sleep;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn system_time(&self) -> SystemTime {
+        SystemTime::now()
+    }
+
+    fn sleep(&self, duration: Duration) -> Sleep {
+        Box::pin(tokio::time::sleep(duration))
+    }
+}
+
+/// A clock that only moves when `advance` is called.
+pub struct ManualClock {
+    start: Instant,
+    start_system: SystemTime,
+    elapsed: watch::Sender<Duration>,
+    // Keeps the channel open even when no sleeper is subscribed.
+    _elapsed_rx: Mutex<watch::Receiver<Duration>>,
+}
+
+impl ManualClock {
+    pub fn new() -> Self {
+        let (elapsed, elapsed_rx) = watch::channel(Duration::ZERO);
+        ManualClock {
+            start: Instant::now(),
+            start_system: SystemTime::now(),
+            elapsed,
+            _elapsed_rx: Mutex::new(elapsed_rx),
+        }
+    }
+
+    /// Moves time forward, waking every sleep whose deadline has passed.
+    pub fn advance(&self, duration: Duration) {
+        self.elapsed.send_modify(|elapsed| *elapsed += duration);
+    }
+
+    fn elapsed(&self) -> Duration {
+        *self.elapsed.borrow()
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        ManualClock::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        self.start + self.elapsed()
+    }
+
+    fn system_time(&self) -> SystemTime {
+        self.start_system + self.elapsed()
+    }
+
+    fn sleep(&self, duration: Duration) -> Sleep {
+        let deadline = self.elapsed() + duration;
+        let mut elapsed = self.elapsed.subscribe();
+        Box::pin(async move {
+            let _ = elapsed.wait_for(|elapsed| *elapsed >= deadline).await;
+        })
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Elapsed;
+
+impl fmt::Display for Elapsed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "deadline has elapsed")
+    }
+}
+
+impl std::error::Error for Elapsed {}
+
+/// Runs `future` until it completes or `duration` passes on `clock`.
+pub async fn timeout<F: Future>(
+    clock: &dyn Clock,
+    duration: Duration,
+    future: F,
+) -> Result<F::Output, Elapsed> {
+    tokio::select! {
+        output = future => Ok(output),
+        _ = clock.sleep(duration) => Err(Elapsed),
+    }
+}
@@ -0,0 +1,121 @@
+//! Entity tags and the conditional request fields built on them (RFC 9110
+//! §8.8.3, §13.1).
+
+use std::fmt;
+
+/// An entity tag such as `"abc"` or `W/"abc"`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntityTag {
+    pub weak: bool,
+    /// The opaque tag, without quotes.
+    pub tag: String,
+}
+
+impl EntityTag {
+    /// Parses a single entity tag, as in an `ETag` field.
+    pub fn parse(value: &str) -> Option<Self> {
+        match parse_tag(value.trim()) {
+            Some((tag, "")) => Some(tag),
+            _ => None,
+        }
+    }
+
+    /// Strong comparison: both tags strong and equal, as `If-Match` needs.
+    pub fn strong_eq(&self, other: &EntityTag) -> bool {
+        !self.weak && !other.weak && self.tag == other.tag
+    }
+
+    /// Weak comparison: equal tags whether or not weak, as `If-None-Match`
+    /// needs.
+    pub fn weak_eq(&self, other: &EntityTag) -> bool {
+        self.tag == other.tag
+    }
+}
+
+impl fmt::Display for EntityTag {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let prefix = if self.weak { "W/" } else { "" };
+        write!(f, "{}\"{}\"", prefix, self.tag)
+    }
+}
+
+/// The value of `If-Match` or `If-None-Match`: `*` or a list of tags.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ETagMatch {
+    Any,
+    Tags(Vec<EntityTag>),
+}
+
+impl ETagMatch {
+    /// Parses one or more field values. `*` can't be combined with tags;
+    /// `None` if the values aren't a well-formed list.
+    pub fn parse<'a>(values: impl IntoIterator<Item = &'a str>) -> Option<Self> {
+        let mut tags = Vec::new();
+        let mut any = false;
+        for value in values {
+            if value.trim() == "*" {
+                any = true;
+                continue;
+            }
+            let mut rest = value.trim();
+            while !rest.is_empty() {
+                if let Some(next) = rest.strip_prefix(',') {
+                    rest = next.trim_start();
+                    continue;
+                }
+                let (tag, next) = parse_tag(rest)?;
+                tags.push(tag);
+                rest = next.trim_start();
+                if !rest.is_empty() && !rest.starts_with(',') {
+                    return None;
+                }
+            }
+        }
+        match (any, tags.is_empty()) {
+            (true, true) => Some(ETagMatch::Any),
+            (false, false) => Some(ETagMatch::Tags(tags)),
+            _ => None,
+        }
+    }
+
+    /// Whether `If-Match` with this value passes for a representation
+    /// tagged `etag` (`None` when there is no current representation).
+    pub fn matches_strong(&self, etag: Option<&EntityTag>) -> bool {
+        match (self, etag) {
+            (ETagMatch::Any, etag) => etag.is_some(),
+            (ETagMatch::Tags(tags), Some(etag)) => tags.iter().any(|tag| tag.strong_eq(etag)),
+            (ETagMatch::Tags(_), None) => false,
+        }
+    }
+
+    /// Whether `If-None-Match` with this value matches a representation
+    /// tagged `etag`, i.e. the condition fails and a GET is answered with
+    /// 304.
+    pub fn matches_weak(&self, etag: Option<&EntityTag>) -> bool {
+        match (self, etag) {
+            (ETagMatch::Any, etag) => etag.is_some(),
+            (ETagMatch::Tags(tags), Some(etag)) => tags.iter().any(|tag| tag.weak_eq(etag)),
+            (ETagMatch::Tags(_), None) => false,
+        }
+    }
+}
+
+/// The entity tag at the start of `value` and what follows it.
+fn parse_tag(value: &str) -> Option<(EntityTag, &str)> {
+    let (weak, rest) = match value.strip_prefix("W/") {
+        Some(rest) => (true, rest),
+        None => (false, value),
+    };
+    let rest = rest.strip_prefix('"')?;
+    let end = rest.find('"')?;
+    let tag = &rest[..end];
+    // etagc (RFC 9110 §8.8.3): visible characters other than `"`.
+    if tag.bytes().any(|b| b < 0x21 || b == 0x7f) {
+        return None;
+    }
+    let tag = EntityTag {
+        weak,
+        tag: tag.to_string(),
+    };
+    Some((tag, &rest[end + 1..]))
+}
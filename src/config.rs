@@ -0,0 +1,543 @@
+use anyhow::{anyhow, bail, Result};
+use std::net::IpAddr;
+use std::time::Duration;
+
+const DEFAULT_RETRY_AFTER: u64 = 120;
+const DEFAULT_ADDRESS: &str = "127.0.0.1:4221";
+const DEFAULT_INITIAL_BUFFER_SIZE: usize = 1024;
+const DEFAULT_MAX_HEADER_SIZE: usize = 64 * 1024;
+const DEFAULT_MAX_HEADER_COUNT: usize = 100;
+const DEFAULT_MAX_HEADER_LINE: usize = 8 * 1024;
+const DEFAULT_MAX_URI_LENGTH: usize = 8 * 1024;
+const DEFAULT_MAX_BODY_SIZE: usize = 16 * 1024 * 1024;
+const DEFAULT_FILE_CHUNK_SIZE: usize = 16 * 1024;
+const DEFAULT_MAX_FILE_BUFFERS: usize = 256;
+const DEFAULT_MAX_WRITE_BUFFER: usize = 256 * 1024;
+const DEFAULT_QUEUE_DEPTH: usize = 128;
+const DEFAULT_QUEUE_TIMEOUT: Duration = Duration::from_secs(5);
+const DEFAULT_INDEX: &str = "index.html";
+const DEFAULT_KEEP_ALIVE_TIMEOUT: Duration = Duration::from_secs(5);
+const DEFAULT_KEEP_ALIVE_MAX: usize = 100;
+const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
+const DEFAULT_HEADER_TIMEOUT: Duration = Duration::from_secs(10);
+const DEFAULT_BODY_TIMEOUT: Duration = Duration::from_secs(60);
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const DEFAULT_SERVER_HEADER: &str = concat!("codecrafters-http/", env!("CARGO_PKG_VERSION"));
+const DEFAULT_CHAOS_MAX_DELAY: Duration = Duration::from_millis(1000);
+
+/// How the server schedules connections across CPU cores.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RuntimeMode {
+    /// A single work-stealing tokio runtime shared by all cores.
+    MultiThread,
+    /// One single-threaded runtime per core, each with its own
+    /// `SO_REUSEPORT` listener and route table.
+    PerCore,
+}
+
+/// What happens after a rewrite rule matches.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RewriteFlag {
+    /// Rewrite the path and keep evaluating the following rules.
+    Internal,
+    /// Rewrite the path and stop evaluating rules.
+    Last,
+    /// Answer with a `302 Found` redirect to the rewritten target.
+    Redirect,
+}
+
+/// A regex applied to the request path before routing; `$1`..`$9` in the
+/// replacement insert capture groups and `$0` the whole match.
+#[derive(Debug, Clone)]
+pub struct RewriteRule {
+    pub pattern: String,
+    pub replacement: String,
+    pub flag: RewriteFlag,
+}
+
+/// A site served from its own document root when the `Host` header
+/// matches `host` (a glob, e.g. `*.example.com`).
+#[derive(Debug, Clone)]
+pub struct VirtualHost {
+    pub host: String,
+    pub root: String,
+    /// File names tried, in order, when a directory is requested.
+    pub index: Vec<String>,
+    /// `(status, HTML file)` pages sent with 403 and 404 responses.
+    pub error_pages: Vec<(u16, String)>,
+}
+
+/// Server settings, built from the command line.
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub address: String,
+    pub directory: String,
+    pub runtime: RuntimeMode,
+    /// Print every accepted connection and parsed request.
+    pub log_requests: bool,
+    /// Answer TRACE requests by echoing the request head back, without
+    /// credentials. Off by default; disabled TRACE gets a 405.
+    pub trace: bool,
+    /// `host:port` targets CONNECT may tunnel to. `*.` prefixes a domain
+    /// wildcard and `*` matches any port; empty disables CONNECT.
+    pub connect_allow: Vec<String>,
+    /// How long CONNECT waits for the upstream to accept before answering
+    /// 504.
+    pub connect_timeout: Duration,
+    /// Reverse proxies whose `Forwarded` and `X-Forwarded-For` fields are
+    /// believed when working out the client address.
+    pub trusted_proxies: Vec<IpAddr>,
+    /// `(path prefix, Link value)` pairs announced in a `103 Early Hints`
+    /// response before the matching handler runs.
+    pub early_hints: Vec<(String, String)>,
+    /// `(path prefix, htdigest file)` pairs protected by Digest auth.
+    pub digest_auth: Vec<(String, String)>,
+    /// `(path prefix, htpasswd file)` pairs protected by Basic auth.
+    pub basic_auth: Vec<(String, String)>,
+    /// Add content-hash ETags to buffered responses and answer
+    /// `If-None-Match` with 304. Off unless `--etag true` is given.
+    pub etag: bool,
+    /// `(path glob, Cache-Control value)` policies; the first match wins.
+    pub cache_control: Vec<(String, String)>,
+    /// Start in maintenance mode; toggled at runtime with `SIGUSR1`.
+    pub maintenance: bool,
+    /// Path prefixes still served during maintenance.
+    pub maintenance_exempt: Vec<String>,
+    /// Seconds advertised in `Retry-After` during maintenance and when
+    /// requests are shed.
+    pub retry_after: u64,
+    /// HTML page served with maintenance 503 responses.
+    pub maintenance_page: Option<String>,
+    /// Directory every request/response pair is written to, for replaying
+    /// later with the `replay` subcommand.
+    pub record: Option<String>,
+    /// `(path glob, header name, value)` headers added to matching
+    /// responses; `*` applies to everything.
+    pub headers: Vec<(String, String, String)>,
+    /// `Alt-Svc` value advertised on every response, e.g.
+    /// `h2=":8443"; ma=86400`, pointing clients at an alternative endpoint.
+    pub alt_svc: Option<String>,
+    /// Rewrite rules, evaluated in order.
+    pub rewrites: Vec<RewriteRule>,
+    /// Host every request is redirected to when its `Host` header differs,
+    /// e.g. `example.com` to fold `www.example.com` and `EXAMPLE.com` into it.
+    pub canonical_host: Option<String>,
+    /// Serve HTTP/2 to clients that open the connection with its preface
+    /// rather than an HTTP/1.1 request (h2c with prior knowledge).
+    pub h2c: bool,
+    /// `Server` header value sent on every response; `None` leaves it out.
+    pub server_header: Option<String>,
+    /// Sites selected by `Host`; requests for other hosts use the routes.
+    pub vhosts: Vec<VirtualHost>,
+    /// `(path prefix, directory)` pairs whose requests run CGI scripts from
+    /// the directory.
+    pub cgi: Vec<(String, String)>,
+    /// Path prefix serving the files compiled in from `EMBED_ASSETS_DIR`.
+    pub embedded_assets: Option<String>,
+    /// Separate listener for `/admin` endpoints; requires `admin_token`.
+    pub admin_address: Option<String>,
+    /// Bearer token admin requests must present.
+    pub admin_token: Option<String>,
+    /// How long an idle persistent connection waits for its next request.
+    pub keep_alive_timeout: Duration,
+    /// Requests served on one connection before it is closed; 1 disables
+    /// keep-alive.
+    pub keep_alive_max: usize,
+    /// How long shutdown waits for in-flight connections before closing them.
+    pub shutdown_timeout: Duration,
+    /// How long a client has to send a whole request head, counted from
+    /// the connection opening or, on a reused one, from the head's first
+    /// bytes. Slower heads get a 408.
+    pub header_timeout: Duration,
+    /// How long a client has to send a whole request body once its head
+    /// is in. Slower bodies get a 408, or fail a handler streaming them.
+    pub body_timeout: Duration,
+    /// `host:port` that a copy of incoming requests is sent to; its
+    /// responses are discarded.
+    pub mirror: Option<String>,
+    /// Fraction of requests mirrored.
+    pub mirror_rate: f64,
+    /// Fraction of requests delayed by up to `chaos_max_delay`.
+    pub chaos_delay_rate: f64,
+    pub chaos_max_delay: Duration,
+    /// Fraction of connections closed without a response.
+    pub chaos_drop_rate: f64,
+    /// Fraction of responses cut off halfway through.
+    pub chaos_truncate_rate: f64,
+    /// Fraction of requests answered with a 500.
+    pub chaos_error_rate: f64,
+    /// Requests handled at once; 0 disables the limit.
+    pub max_concurrency: usize,
+    /// `(path prefix, limit)` caps on requests handled at once for a group
+    /// of routes. They queue like `max_concurrency`, with its depth and wait.
+    pub route_limits: Vec<(String, usize)>,
+    /// Requests allowed to wait for a slot once a concurrency limit is reached.
+    pub queue_depth: usize,
+    /// How long a queued request waits before it is refused with a 503.
+    pub queue_timeout: Duration,
+    /// Capacity the read buffer starts with for every request.
+    pub initial_buffer_size: usize,
+    /// Upper bound for the request line plus headers; larger heads get a
+    /// 431.
+    pub max_header_size: usize,
+    /// Most header fields a request may have.
+    pub max_header_count: usize,
+    /// Upper bound for a single header line.
+    pub max_header_line: usize,
+    /// Upper bound for the request target; longer ones get a 414.
+    pub max_uri_length: usize,
+    /// Upper bound for a request body, unless its route sets its own.
+    pub max_body_size: usize,
+    /// Size of the chunks files are streamed in.
+    pub file_chunk_size: usize,
+    /// Number of chunk buffers shared by all connections.
+    pub max_file_buffers: usize,
+    /// Bytes read ahead of a slow client before the file reader pauses.
+    pub max_write_buffer: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            address: String::from(DEFAULT_ADDRESS),
+            directory: String::from(""),
+            runtime: RuntimeMode::MultiThread,
+            log_requests: true,
+            trace: false,
+            connect_allow: Vec::new(),
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            trusted_proxies: Vec::new(),
+            early_hints: Vec::new(),
+            digest_auth: Vec::new(),
+            basic_auth: Vec::new(),
+            etag: false,
+            cache_control: Vec::new(),
+            maintenance: false,
+            maintenance_exempt: vec![String::from("/healthz")],
+            retry_after: DEFAULT_RETRY_AFTER,
+            maintenance_page: None,
+            record: None,
+            headers: Vec::new(),
+            alt_svc: None,
+            rewrites: Vec::new(),
+            canonical_host: None,
+            h2c: true,
+            server_header: Some(String::from(DEFAULT_SERVER_HEADER)),
+            vhosts: Vec::new(),
+            cgi: Vec::new(),
+            embedded_assets: None,
+            admin_address: None,
+            admin_token: None,
+            keep_alive_timeout: DEFAULT_KEEP_ALIVE_TIMEOUT,
+            keep_alive_max: DEFAULT_KEEP_ALIVE_MAX,
+            shutdown_timeout: DEFAULT_SHUTDOWN_TIMEOUT,
+            header_timeout: DEFAULT_HEADER_TIMEOUT,
+            body_timeout: DEFAULT_BODY_TIMEOUT,
+            mirror: None,
+            mirror_rate: 1.0,
+            chaos_delay_rate: 0.0,
+            chaos_max_delay: DEFAULT_CHAOS_MAX_DELAY,
+            chaos_drop_rate: 0.0,
+            chaos_truncate_rate: 0.0,
+            chaos_error_rate: 0.0,
+            max_concurrency: 0,
+            route_limits: Vec::new(),
+            queue_depth: DEFAULT_QUEUE_DEPTH,
+            queue_timeout: DEFAULT_QUEUE_TIMEOUT,
+            initial_buffer_size: DEFAULT_INITIAL_BUFFER_SIZE,
+            max_header_size: DEFAULT_MAX_HEADER_SIZE,
+            max_header_count: DEFAULT_MAX_HEADER_COUNT,
+            max_header_line: DEFAULT_MAX_HEADER_LINE,
+            max_uri_length: DEFAULT_MAX_URI_LENGTH,
+            max_body_size: DEFAULT_MAX_BODY_SIZE,
+            file_chunk_size: DEFAULT_FILE_CHUNK_SIZE,
+            max_file_buffers: DEFAULT_MAX_FILE_BUFFERS,
+            max_write_buffer: DEFAULT_MAX_WRITE_BUFFER,
+        }
+    }
+}
+
+impl Config {
+    /// How many file chunks may be queued for a single connection.
+    pub fn write_queue_chunks(&self) -> usize {
+        (self.max_write_buffer / self.file_chunk_size).max(1)
+    }
+
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> Result<Self> {
+        let mut config = Config::default();
+        let mut args = args.into_iter().skip(1);
+        while let Some(flag) = args.next() {
+            let value = args
+                .next()
+                .ok_or_else(|| anyhow!("missing value for {}", flag))?;
+            match flag.as_str() {
+                "--address" => config.address = value,
+                "--directory" => config.directory = value,
+                "--runtime" => config.runtime = parse_runtime(&value)?,
+                "--log-requests" => config.log_requests = parse_bool(&flag, &value)?,
+                "--trace" => config.trace = parse_bool(&flag, &value)?,
+                "--connect-allow" => config.connect_allow.push(value),
+                "--connect-timeout" => {
+                    config.connect_timeout = Duration::from_secs(parse_size(&flag, &value)? as u64)
+                }
+                "--trusted-proxy" => config.trusted_proxies.push(parse_ip(&flag, &value)?),
+                "--early-hint" => config.early_hints.push(parse_pair(&flag, &value)?),
+                "--digest-auth" => config.digest_auth.push(parse_pair(&flag, &value)?),
+                "--basic-auth" => config.basic_auth.push(parse_pair(&flag, &value)?),
+                "--etag" => config.etag = parse_bool(&flag, &value)?,
+                "--cache-control" => config.cache_control.push(parse_pair(&flag, &value)?),
+                "--maintenance" => config.maintenance = parse_bool(&flag, &value)?,
+                "--maintenance-exempt" => config.maintenance_exempt.push(value),
+                "--retry-after" => config.retry_after = parse_size(&flag, &value)? as u64,
+                "--maintenance-page" => config.maintenance_page = Some(value),
+                "--record" => config.record = Some(value),
+                "--add-header" => config.headers.push(parse_header(&flag, &value)?),
+                "--alt-svc" => config.alt_svc = Some(parse_alt_svc(&value)?),
+                "--rewrite" => config.rewrites.push(parse_rewrite(&value)?),
+                "--canonical-host" => config.canonical_host = Some(value),
+                "--h2c" => config.h2c = parse_bool(&flag, &value)?,
+                "--server-header" => {
+                    config.server_header = (value != "off").then_some(value);
+                }
+                "--vhost" => {
+                    let (host, root) = parse_pair(&flag, &value)?;
+                    config.vhosts.push(VirtualHost {
+                        host: host.to_ascii_lowercase(),
+                        root,
+                        index: Vec::new(),
+                        error_pages: Vec::new(),
+                    });
+                }
+                "--vhost-index" => {
+                    let (host, file) = parse_pair(&flag, &value)?;
+                    vhost(&mut config, &flag, &host)?.index.push(file);
+                }
+                "--vhost-error-page" => {
+                    let (host, page) = parse_pair(&flag, &value)?;
+                    let (status, path) = page
+                        .split_once(':')
+                        .and_then(|(status, path)| Some((status.parse::<u16>().ok()?, path)))
+                        .filter(|(status, _)| matches!(status, 403 | 404))
+                        .ok_or_else(|| {
+                            anyhow!(
+                                "invalid value for {}: {} (expected HOST=403|404:FILE)",
+                                flag,
+                                value
+                            )
+                        })?;
+                    vhost(&mut config, &flag, &host)?
+                        .error_pages
+                        .push((status, path.to_owned()));
+                }
+                "--cgi" => config.cgi.push(parse_pair(&flag, &value)?),
+                "--embedded-assets" => config.embedded_assets = Some(value),
+                "--admin-address" => config.admin_address = Some(value),
+                "--admin-token" => config.admin_token = Some(value),
+                "--keep-alive-timeout" => {
+                    config.keep_alive_timeout =
+                        Duration::from_secs(parse_size(&flag, &value)? as u64)
+                }
+                "--keep-alive-max" => config.keep_alive_max = parse_size(&flag, &value)?,
+                "--shutdown-timeout" => {
+                    config.shutdown_timeout = Duration::from_secs(parse_size(&flag, &value)? as u64)
+                }
+                "--header-timeout" => {
+                    config.header_timeout = Duration::from_secs(parse_size(&flag, &value)? as u64)
+                }
+                "--body-timeout" => {
+                    config.body_timeout = Duration::from_secs(parse_size(&flag, &value)? as u64)
+                }
+                "--mirror" => config.mirror = Some(value),
+                "--mirror-rate" => config.mirror_rate = parse_rate(&flag, &value)?,
+                "--chaos-delay-rate" => config.chaos_delay_rate = parse_rate(&flag, &value)?,
+                "--chaos-max-delay" => {
+                    config.chaos_max_delay =
+                        Duration::from_millis(parse_size(&flag, &value)? as u64)
+                }
+                "--chaos-drop-rate" => config.chaos_drop_rate = parse_rate(&flag, &value)?,
+                "--chaos-truncate-rate" => config.chaos_truncate_rate = parse_rate(&flag, &value)?,
+                "--chaos-error-rate" => config.chaos_error_rate = parse_rate(&flag, &value)?,
+                "--max-concurrency" => config.max_concurrency = parse_size(&flag, &value)?,
+                "--route-limit" => {
+                    let (prefix, limit) = parse_pair(&flag, &value)?;
+                    let limit = parse_size(&flag, &limit)?;
+                    if limit == 0 {
+                        bail!("{} limit for {} must be greater than zero", flag, prefix);
+                    }
+                    config.route_limits.push((prefix, limit));
+                }
+                "--queue-depth" => config.queue_depth = parse_size(&flag, &value)?,
+                "--queue-timeout" => {
+                    config.queue_timeout = Duration::from_millis(parse_size(&flag, &value)? as u64)
+                }
+                "--initial-buffer-size" => config.initial_buffer_size = parse_size(&flag, &value)?,
+                "--max-header-size" => config.max_header_size = parse_size(&flag, &value)?,
+                "--max-header-count" => config.max_header_count = parse_size(&flag, &value)?,
+                "--max-header-line" => config.max_header_line = parse_size(&flag, &value)?,
+                "--max-uri-length" => config.max_uri_length = parse_size(&flag, &value)?,
+                "--max-body-size" => config.max_body_size = parse_size(&flag, &value)?,
+                "--file-chunk-size" => config.file_chunk_size = parse_size(&flag, &value)?,
+                "--max-file-buffers" => config.max_file_buffers = parse_size(&flag, &value)?,
+                "--max-write-buffer" => config.max_write_buffer = parse_size(&flag, &value)?,
+                _ => bail!("unknown argument: {}", flag),
+            }
+        }
+        if config.initial_buffer_size == 0 {
+            bail!("--initial-buffer-size must be greater than zero");
+        }
+        if config.keep_alive_max == 0 {
+            bail!("--keep-alive-max must be greater than zero");
+        }
+        if config.file_chunk_size == 0 || config.max_file_buffers == 0 {
+            bail!("--file-chunk-size and --max-file-buffers must be greater than zero");
+        }
+        for vhost in config.vhosts.iter_mut() {
+            if vhost.index.is_empty() {
+                vhost.index.push(String::from(DEFAULT_INDEX));
+            }
+        }
+        if config.admin_address.is_some() && config.admin_token.is_none() {
+            bail!("--admin-address requires --admin-token");
+        }
+        Ok(config)
+    }
+
+    /// Settings for the admin listener: the same limits, without recording,
+    /// mirroring or fault injection.
+    pub fn admin(&self) -> Config {
+        Config {
+            record: None,
+            mirror: None,
+            chaos_delay_rate: 0.0,
+            chaos_drop_rate: 0.0,
+            chaos_truncate_rate: 0.0,
+            chaos_error_rate: 0.0,
+            ..self.clone()
+        }
+    }
+}
+
+fn parse_size(flag: &str, value: &str) -> Result<usize> {
+    value
+        .parse::<usize>()
+        .map_err(|_| anyhow!("invalid value for {}: {}", flag, value))
+}
+
+/// Parses `GLOB=Name: value`.
+fn parse_header(flag: &str, value: &str) -> Result<(String, String, String)> {
+    let (glob, header) = parse_pair(flag, value)?;
+    match header.split_once(':') {
+        Some((name, value)) if !name.trim().is_empty() && !name.contains(' ') => {
+            Ok((glob, name.to_owned(), value.trim().to_owned()))
+        }
+        _ => bail!(
+            "invalid value for {}: {} (expected GLOB=Name: value)",
+            flag,
+            value
+        ),
+    }
+}
+
+/// Checks an `Alt-Svc` value: `clear`, or `protocol="host:port"` entries
+/// with optional `; param=value` parameters.
+fn parse_alt_svc(value: &str) -> Result<String> {
+    let valid = value.trim() == "clear"
+        || value.split(',').all(|entry| {
+            let alternative = entry.split(';').next().unwrap_or("");
+            alternative
+                .split_once('=')
+                .is_some_and(|(protocol, authority)| {
+                    let authority = authority.trim();
+                    !protocol.trim().is_empty()
+                        && authority.len() > 2
+                        && authority.starts_with('"')
+                        && authority.ends_with('"')
+                        && authority.contains(':')
+                })
+        });
+    if !valid {
+        bail!(
+            "invalid value for --alt-svc: {} (expected clear or PROTOCOL=\"HOST:PORT\")",
+            value
+        );
+    }
+    Ok(value.trim().to_owned())
+}
+
+/// Parses `PATTERN REPLACEMENT [internal|last|redirect]`.
+fn parse_rewrite(value: &str) -> Result<RewriteRule> {
+    let mut parts = value.split_whitespace();
+    let (Some(pattern), Some(replacement)) = (parts.next(), parts.next()) else {
+        bail!(
+            "invalid value for --rewrite: {} (expected PATTERN REPLACEMENT [FLAG])",
+            value
+        );
+    };
+    let flag = match parts.next() {
+        None | Some("internal") => RewriteFlag::Internal,
+        Some("last") => RewriteFlag::Last,
+        Some("redirect") => RewriteFlag::Redirect,
+        Some(flag) => bail!(
+            "invalid flag for --rewrite: {} (expected internal, last or redirect)",
+            flag
+        ),
+    };
+    if parts.next().is_some() {
+        bail!("invalid value for --rewrite: {}", value);
+    }
+    Ok(RewriteRule {
+        pattern: pattern.to_owned(),
+        replacement: replacement.to_owned(),
+        flag,
+    })
+}
+
+/// Finds a virtual host declared by an earlier `--vhost`.
+fn vhost<'a>(config: &'a mut Config, flag: &str, host: &str) -> Result<&'a mut VirtualHost> {
+    let host = host.to_ascii_lowercase();
+    config
+        .vhosts
+        .iter_mut()
+        .find(|vhost| vhost.host == host)
+        .ok_or_else(|| anyhow!("{} for {} must follow --vhost {}=ROOT", flag, host, host))
+}
+
+/// Splits a `key=value` argument at the first `=`.
+fn parse_pair(flag: &str, value: &str) -> Result<(String, String)> {
+    value
+        .split_once('=')
+        .map(|(key, value)| (key.to_owned(), value.to_owned()))
+        .ok_or_else(|| anyhow!("invalid value for {}: {} (expected KEY=VALUE)", flag, value))
+}
+
+/// Parses a probability between 0 and 1.
+fn parse_rate(flag: &str, value: &str) -> Result<f64> {
+    value
+        .parse::<f64>()
+        .ok()
+        .filter(|rate| (0.0..=1.0).contains(rate))
+        .ok_or_else(|| anyhow!("invalid value for {}: {} (expected 0 to 1)", flag, value))
+}
+
+fn parse_ip(flag: &str, value: &str) -> Result<IpAddr> {
+    value
+        .parse::<IpAddr>()
+        .map_err(|_| anyhow!("invalid value for {}: {}", flag, value))
+}
+
+fn parse_bool(flag: &str, value: &str) -> Result<bool> {
+    value
+        .parse::<bool>()
+        .map_err(|_| anyhow!("invalid value for {}: {}", flag, value))
+}
+
+fn parse_runtime(value: &str) -> Result<RuntimeMode> {
+    match value {
+        "multi-thread" => Ok(RuntimeMode::MultiThread),
+        "per-core" => Ok(RuntimeMode::PerCore),
+        _ => bail!(
+            "invalid value for --runtime: {} (expected multi-thread or per-core)",
+            value
+        ),
+    }
+}
@@ -0,0 +1,88 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::sync::watch;
+
+use crate::clock::{Clock, SystemClock};
+use crate::config::Config;
+use crate::systemd;
+
+/// Server-wide state that can be changed while the server is running. One
+/// instance is shared by every runtime shard.
+pub struct Control {
+    maintenance: AtomicBool,
+    clock: Arc<dyn Clock>,
+    shutdown: watch::Sender<bool>,
+}
+
+impl Control {
+    pub fn new(config: &Config) -> Self {
+        Control::with_clock(config, Arc::new(SystemClock))
+    }
+
+    /// Like `new`, but with time driven by `clock` instead of the system.
+    pub fn with_clock(config: &Config, clock: Arc<dyn Clock>) -> Self {
+        Control {
+            maintenance: AtomicBool::new(config.maintenance),
+            clock,
+            shutdown: watch::channel(false).0,
+        }
+    }
+
+    pub fn clock(&self) -> &Arc<dyn Clock> {
+        &self.clock
+    }
+
+    pub fn in_maintenance(&self) -> bool {
+        self.maintenance.load(Ordering::Relaxed)
+    }
+
+    pub fn set_maintenance(&self, enabled: bool) {
+        self.maintenance.store(enabled, Ordering::Relaxed);
+        println!(
+            "maintenance mode {}",
+            if enabled { "enabled" } else { "disabled" }
+        );
+    }
+
+    /// Asks every listener to stop accepting and drain its connections.
+    pub fn shutdown(&self) {
+        if !self.shutdown.send_replace(true) {
+            println!("shutting down");
+            systemd::notify("STOPPING=1");
+        }
+    }
+
+    /// Resolves once `shutdown` has been called.
+    pub async fn shutdown_requested(&self) {
+        let mut shutdown = self.shutdown.subscribe();
+        let _ = shutdown.wait_for(|requested| *requested).await;
+    }
+}
+
+/// Toggles maintenance mode every time the process receives `SIGUSR1` and
+/// starts a graceful shutdown on `SIGTERM`.
+#[cfg(unix)]
+pub async fn watch_signals(control: Arc<Control>) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let (mut toggles, mut terminate) = match (
+        signal(SignalKind::user_defined1()),
+        signal(SignalKind::terminate()),
+    ) {
+        (Ok(toggles), Ok(terminate)) => (toggles, terminate),
+        (Err(err), _) | (_, Err(err)) => {
+            println!("error installing signal handlers: {}", err);
+            return;
+        }
+    };
+    loop {
+        tokio::select! {
+            Some(()) = toggles.recv() => control.set_maintenance(!control.in_maintenance()),
+            Some(()) = terminate.recv() => control.shutdown(),
+            else => return,
+        }
+    }
+}
+
+#[cfg(not(unix))]
+pub async fn watch_signals(_control: Arc<Control>) {}
@@ -0,0 +1,173 @@
+//! Basic WebDAV for the `/files` directory: `PROPFIND` to list it and
+//! `MKCOL` to create subdirectories.
+
+use std::fs::{self, Metadata};
+use std::path::{Path, PathBuf};
+
+use crate::date::http_date;
+use crate::header_map::HeaderMap;
+use crate::request::Request;
+use crate::response::{HttpCode, Response};
+
+const PREFIX: &str = "/files";
+
+/// Describes a file or directory and, at `Depth: 1` (the default), the
+/// entries of a directory. `Depth: infinity` is refused rather than walking
+/// the whole tree.
+pub fn propfind(req: Request, directory: &str) -> Response {
+    let depth = match req.headers.get("Depth").map(|depth| depth.trim()) {
+        Some("0") => 0,
+        Some("1") | None => 1,
+        Some(_) => return status(HttpCode::Forbidden),
+    };
+    let Some((path, segments)) = resolve(&req.path, directory) else {
+        return status(HttpCode::Forbidden);
+    };
+    let Ok(metadata) = fs::metadata(&path) else {
+        return status(HttpCode::NotFound);
+    };
+
+    let mut href = format!("{}/{}", PREFIX, segments.join("/"));
+    let name = segments.last().cloned().unwrap_or_default();
+    let mut body = String::from(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<D:multistatus xmlns:D=\"DAV:\">\n",
+    );
+    if metadata.is_dir() && !href.ends_with('/') {
+        href.push('/');
+    }
+    body.push_str(&entry(&href, &name, &metadata));
+    if depth == 1 && metadata.is_dir() {
+        let Ok(entries) = fs::read_dir(&path) else {
+            return status(HttpCode::Forbidden);
+        };
+        let mut children = entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let name = entry.file_name().to_str()?.to_owned();
+                Some((name, entry.metadata().ok()?))
+            })
+            .collect::<Vec<(String, Metadata)>>();
+        children.sort_by(|a, b| a.0.cmp(&b.0));
+        for (name, metadata) in children {
+            let slash = if metadata.is_dir() { "/" } else { "" };
+            let child = format!("{}{}{}", href, encode(&name), slash);
+            body.push_str(&entry(&child, &name, &metadata));
+        }
+    }
+    body.push_str("</D:multistatus>\n");
+
+    let headers = HeaderMap::from([
+        (String::from("Content-Length"), body.len().to_string()),
+        (
+            String::from("Content-Type"),
+            String::from("application/xml; charset=utf-8"),
+        ),
+    ]);
+    Response {
+        code: HttpCode::MultiStatus,
+        content: Some(body.into_bytes().into()),
+        headers: Some(headers),
+    }
+}
+
+/// Creates a directory. Its parent must already exist.
+pub fn mkcol(req: Request, directory: &str) -> Response {
+    let Some((path, segments)) = resolve(&req.path, directory) else {
+        return status(HttpCode::Forbidden);
+    };
+    if segments.is_empty() || path.exists() {
+        return status(HttpCode::MethodNotAllowed);
+    }
+    if !path.parent().is_some_and(Path::is_dir) {
+        return status(HttpCode::Conflict);
+    }
+    match fs::create_dir(&path) {
+        Ok(()) => status(HttpCode::Created),
+        Err(err) => {
+            println!("error creating {}: {}", path.display(), err);
+            status(HttpCode::Forbidden)
+        }
+    }
+}
+
+/// Maps a request path under `/files` onto `directory`, returning the path
+/// and its segments. Paths that would climb out are refused.
+fn resolve(path: &str, directory: &str) -> Option<(PathBuf, Vec<String>)> {
+    let rest = path.strip_prefix(PREFIX)?;
+    if !rest.is_empty() && !rest.starts_with('/') {
+        return None;
+    }
+    let mut resolved = PathBuf::from(directory);
+    let mut segments = Vec::new();
+    for segment in rest.split('/').filter(|s| !s.is_empty() && *s != ".") {
+        if segment == ".." || segment.contains('\\') {
+            return None;
+        }
+        resolved.push(segment);
+        segments.push(segment.to_owned());
+    }
+    Some((resolved, segments))
+}
+
+fn entry(href: &str, name: &str, metadata: &Metadata) -> String {
+    let resource_type = if metadata.is_dir() {
+        "<D:resourcetype><D:collection/></D:resourcetype>"
+    } else {
+        "<D:resourcetype/>"
+    };
+    let mut props = format!(
+        "<D:displayname>{}</D:displayname>{}",
+        escape(name),
+        resource_type
+    );
+    if metadata.is_file() {
+        props.push_str(&format!(
+            "<D:getcontentlength>{}</D:getcontentlength>",
+            metadata.len()
+        ));
+    }
+    if let Ok(modified) = metadata.modified() {
+        props.push_str(&format!(
+            "<D:getlastmodified>{}</D:getlastmodified>",
+            http_date(modified)
+        ));
+    }
+    format!(
+        "<D:response><D:href>{}</D:href><D:propstat><D:prop>{}</D:prop>\
+         <D:status>HTTP/1.1 200 OK</D:status></D:propstat></D:response>\n",
+        escape(href),
+        props
+    )
+}
+
+fn status(code: HttpCode) -> Response {
+    Response {
+        code,
+        content: None,
+        headers: Some(HeaderMap::from([(
+            String::from("Content-Length"),
+            String::from("0"),
+        )])),
+    }
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Percent-encodes a path segment for use in an href.
+fn encode(segment: &str) -> String {
+    segment
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            b => format!("%{:02X}", b),
+        })
+        .collect()
+}
@@ -0,0 +1,75 @@
+//! Integrity checks for request bodies sent with `Content-Digest`
+//! (RFC 9530) or the legacy `Content-MD5` header.
+
+use crate::hash::{base64_decode, Md5, Sha256};
+use crate::header_map::HeaderMap;
+
+/// Checks `body` against the digests the client sent. Algorithms this
+/// server can't compute are skipped, so a body is only rejected for a
+/// digest that was actually checked and didn't match.
+pub fn verify(headers: &HeaderMap, body: &[u8]) -> bool {
+    let mut digester = Digester::default();
+    digester.update(body);
+    digester.finish().verify(headers)
+}
+
+/// Computes the digests `verify` checks as a body arrives in pieces.
+#[derive(Default)]
+pub struct Digester {
+    sha256: Sha256,
+    md5: Md5,
+}
+
+impl Digester {
+    pub fn update(&mut self, data: &[u8]) {
+        self.sha256.update(data);
+        self.md5.update(data);
+    }
+
+    pub fn finish(self) -> Digests {
+        Digests {
+            sha256: self.sha256.finish(),
+            md5: self.md5.finish(),
+        }
+    }
+}
+
+/// The digests of a whole body.
+pub struct Digests {
+    sha256: [u8; 32],
+    md5: [u8; 16],
+}
+
+impl Digests {
+    /// Checks the body against the digests in `headers`, as `verify` does.
+    pub fn verify(&self, headers: &HeaderMap) -> bool {
+        if let Some(value) = headers.get("Content-Digest") {
+            for member in value.split(',') {
+                let Some((algorithm, digest)) = member.trim().split_once('=') else {
+                    return false;
+                };
+                let Some(digest) = digest
+                    .strip_prefix(':')
+                    .and_then(|digest| digest.strip_suffix(':'))
+                    .and_then(base64_decode)
+                else {
+                    return false;
+                };
+                let matches = match algorithm.trim().to_ascii_lowercase().as_str() {
+                    "sha-256" => digest == self.sha256,
+                    "md5" => digest == self.md5,
+                    _ => true,
+                };
+                if !matches {
+                    return false;
+                }
+            }
+        }
+        if let Some(value) = headers.get("Content-MD5") {
+            if base64_decode(value.trim()).is_none_or(|digest| digest != self.md5) {
+                return false;
+            }
+        }
+        true
+    }
+}
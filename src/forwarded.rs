@@ -0,0 +1,62 @@
+//! The client behind reverse proxies, from `Forwarded` (RFC 7239) or
+//! `X-Forwarded-For`.
+
+use std::net::{IpAddr, SocketAddr};
+
+use crate::header_map::HeaderMap;
+
+/// The address of the client that sent a request arriving from `remote`.
+/// Forwarding fields are only believed when `remote` is one of the
+/// `trusted` proxies: the hops they list are then walked back from the
+/// nearest, and the first one that isn't a trusted proxy is the client.
+/// `Forwarded` wins over `X-Forwarded-For` when both are sent. A hop that
+/// isn't an address, such as `unknown`, ends the walk at the proxy that
+/// recorded it.
+pub fn client_ip(remote: IpAddr, headers: &HeaderMap, trusted: &[IpAddr]) -> IpAddr {
+    let hops = if headers.contains_key("Forwarded") {
+        forwarded_for(headers)
+    } else {
+        headers
+            .get_all("X-Forwarded-For")
+            .flat_map(|value| value.split(','))
+            .map(|hop| node_ip(hop.trim()))
+            .collect()
+    };
+    let mut client = remote;
+    for hop in hops.into_iter().rev() {
+        if !trusted.contains(&client) {
+            break;
+        }
+        match hop {
+            Some(ip) => client = ip,
+            None => break,
+        }
+    }
+    client
+}
+
+/// The `for=` node of every `Forwarded` element, in the order given. An
+/// element without one counts as an unknown hop.
+fn forwarded_for(headers: &HeaderMap) -> Vec<Option<IpAddr>> {
+    headers
+        .get_all("Forwarded")
+        .flat_map(|value| value.split(','))
+        .map(|element| {
+            element
+                .split(';')
+                .filter_map(|pair| pair.split_once('='))
+                .find(|(name, _)| name.trim().eq_ignore_ascii_case("for"))
+                .and_then(|(_, node)| node_ip(node.trim().trim_matches('"')))
+        })
+        .collect()
+}
+
+/// The address of a node such as `192.0.2.43`, `192.0.2.43:47011`,
+/// `[2001:db8::1]` or `[2001:db8::1]:4711`. `None` for `unknown` and
+/// obfuscated identifiers.
+fn node_ip(node: &str) -> Option<IpAddr> {
+    node.parse::<IpAddr>()
+        .or_else(|_| node.parse::<SocketAddr>().map(|addr| addr.ip()))
+        .or_else(|_| node.trim_start_matches('[').trim_end_matches(']').parse())
+        .ok()
+}
@@ -0,0 +1,1078 @@
+//! HTTP/2 over cleartext TCP (h2c), for clients that open the connection
+//! with the HTTP/2 preface instead of an HTTP/1.1 request (RFC 9113 §3.3)
+//! or switch to it with `Upgrade: h2c`.
+//!
+//! Each stream's request is answered by the same `Routes` as HTTP/1.x, on
+//! a task of its own. The connection task reads frames, keeps the flow
+//! control windows and writes what the stream tasks hand it.
+
+use anyhow::{bail, Result};
+use bytes::{Buf, BytesMut};
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::TcpStream;
+use tokio::sync::{mpsc, watch};
+use tokio::task::AbortHandle;
+
+use crate::admission::{self, Admission};
+use crate::config::Config;
+use crate::hash;
+use crate::header_map::HeaderMap;
+use crate::hpack::{self, Decoder, HeaderListTooLarge};
+use crate::request::{HttpVersion, Request, UnknownMethod};
+use crate::response::{Body, HttpCode, Response};
+use crate::routes::Routes;
+use crate::upgrade::{Upgrade, Upgraded};
+
+/// What an HTTP/2 client sends before its first frame.
+pub const PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+const DATA: u8 = 0x0;
+const HEADERS: u8 = 0x1;
+const PRIORITY: u8 = 0x2;
+const RST_STREAM: u8 = 0x3;
+const SETTINGS: u8 = 0x4;
+const PUSH_PROMISE: u8 = 0x5;
+const PING: u8 = 0x6;
+const GOAWAY: u8 = 0x7;
+const WINDOW_UPDATE: u8 = 0x8;
+const CONTINUATION: u8 = 0x9;
+
+const END_STREAM: u8 = 0x1;
+const ACK: u8 = 0x1;
+const END_HEADERS: u8 = 0x4;
+const PADDED: u8 = 0x8;
+const PRIORITY_FLAG: u8 = 0x20;
+
+const NO_ERROR: u32 = 0x0;
+const PROTOCOL_ERROR: u32 = 0x1;
+const FLOW_CONTROL_ERROR: u32 = 0x3;
+const STREAM_CLOSED: u32 = 0x5;
+const FRAME_SIZE_ERROR: u32 = 0x6;
+const REFUSED_STREAM: u32 = 0x7;
+const COMPRESSION_ERROR: u32 = 0x9;
+const ENHANCE_YOUR_CALM: u32 = 0xb;
+
+const SETTINGS_ENABLE_PUSH: u16 = 0x2;
+const SETTINGS_MAX_CONCURRENT_STREAMS: u16 = 0x3;
+const SETTINGS_INITIAL_WINDOW_SIZE: u16 = 0x4;
+const SETTINGS_MAX_FRAME_SIZE: u16 = 0x5;
+const SETTINGS_MAX_HEADER_LIST_SIZE: u16 = 0x6;
+
+/// Frame payloads are kept to the protocol default in both directions
+/// until the client allows larger ones.
+const DEFAULT_MAX_FRAME_SIZE: usize = 16_384;
+const DEFAULT_WINDOW: i64 = 65_535;
+const MAX_WINDOW: i64 = (1 << 31) - 1;
+const HEADER_TABLE_SIZE: usize = 4096;
+const MAX_CONCURRENT_STREAMS: usize = 100;
+
+/// A connection error (RFC 9113 §5.4.1): the connection is closed with a
+/// GOAWAY carrying `code`.
+#[derive(Debug, thiserror::Error)]
+#[error("HTTP/2 connection error {code:#x}: {reason}")]
+struct ConnectionError {
+    code: u32,
+    reason: String,
+}
+
+fn fail(code: u32, reason: impl ToString) -> ConnectionError {
+    ConnectionError {
+        code,
+        reason: reason.to_string(),
+    }
+}
+
+/// A request HTTP/2 considers malformed (RFC 9113 §8.1.1); its stream is
+/// reset.
+#[derive(Debug, thiserror::Error)]
+#[error("malformed HTTP/2 request: {0}")]
+struct Malformed(String);
+
+struct Frame {
+    kind: u8,
+    flags: u8,
+    stream_id: u32,
+    payload: Vec<u8>,
+}
+
+/// What a stream's task hands the connection to send.
+enum Out {
+    /// An encoded header block: the response head, or its trailers.
+    Head(Vec<u8>, bool),
+    Data(Vec<u8>, bool),
+}
+
+struct Stream {
+    /// The request while its body arrives; taken when it is answered.
+    req: Option<Request>,
+    body_limit: usize,
+    send_window: i64,
+    pending: VecDeque<Out>,
+    /// The task answering the request, stopped if the stream is reset.
+    task: Option<AbortHandle>,
+}
+
+/// Reads until `buf` holds either the connection preface or something
+/// that can't be one. Returns whether the client speaks HTTP/2.
+pub async fn sniff(stream: &mut TcpStream, buf: &mut BytesMut) -> Result<bool> {
+    while buf.len() < PREFACE.len() && PREFACE.starts_with(buf) {
+        if stream.read_buf(buf).await? == 0 {
+            return Ok(false);
+        }
+    }
+    Ok(buf.starts_with(PREFACE))
+}
+
+/// Serves an HTTP/2 connection whose preface is at the start of `buf`,
+/// until the client goes away, it sits idle for `keep_alive_timeout`, or
+/// `closing` flips and the streams in flight are done.
+/// Each stream waits for a slot from `admission`, like a request on its
+/// own connection would.
+pub async fn serve(
+    stream: TcpStream,
+    buf: BytesMut,
+    config: Arc<Config>,
+    routes: Arc<Routes>,
+    admission: Option<Arc<Admission>>,
+    closing: watch::Receiver<bool>,
+) {
+    serve_streams(stream, buf, config, routes, admission, closing, None).await
+}
+
+/// Switches an HTTP/1.1 request sent with `Upgrade: h2c` to HTTP/2
+/// (RFC 7540 §3.2). The request is answered as stream 1, and its
+/// `HTTP2-Settings` header stands in for the client's first SETTINGS.
+pub struct H2c;
+
+impl Upgrade for H2c {
+    fn accept(&self, req: &Request) -> Option<HeaderMap> {
+        let mut settings = req.headers.get_all("HTTP2-Settings");
+        match (settings.next(), settings.next()) {
+            (Some(value), None) => upgrade_settings(value).map(|_| HeaderMap::new()),
+            _ => None,
+        }
+    }
+
+    fn run(&self, upgraded: Upgraded) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+        let Upgraded {
+            mut req,
+            stream,
+            buf,
+            config,
+            routes,
+            admission,
+            closing,
+        } = upgraded;
+        Box::pin(async move {
+            for name in ["Connection", "Upgrade", "HTTP2-Settings"] {
+                req.headers.remove(name);
+            }
+            req.version = HttpVersion::Http2;
+            serve_streams(stream, buf, config, routes, admission, closing, Some(req)).await
+        })
+    }
+}
+
+/// Decodes `HTTP2-Settings`: a SETTINGS payload in unpadded base64url.
+fn upgrade_settings(value: &str) -> Option<Vec<u8>> {
+    let value = value.trim().replace('-', "+").replace('_', "/");
+    let payload = hash::base64_decode(&value)?;
+    payload.len().is_multiple_of(6).then_some(payload)
+}
+
+async fn serve_streams(
+    stream: TcpStream,
+    buf: BytesMut,
+    config: Arc<Config>,
+    routes: Arc<Routes>,
+    admission: Option<Arc<Admission>>,
+    mut closing: watch::Receiver<bool>,
+    upgraded: Option<Request>,
+) {
+    let peer = stream.peer_addr().ok();
+    let (reader, writer) = stream.into_split();
+    let (frames_tx, mut frames) = mpsc::channel(16);
+    tokio::spawn(read_frames(reader, buf, frames_tx));
+    let (out, mut outgoing) = mpsc::channel(32);
+    let mut conn = Connection {
+        decoder: Decoder::new(HEADER_TABLE_SIZE, config.max_header_size),
+        writer,
+        peer,
+        config,
+        routes,
+        admission,
+        streams: HashMap::new(),
+        continuation: None,
+        last_stream: 0,
+        send_window: DEFAULT_WINDOW,
+        initial_window: DEFAULT_WINDOW,
+        max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+        pending_bytes: 0,
+        out,
+        going_away: false,
+    };
+    let run = conn.run(&mut frames, &mut outgoing, &mut closing, upgraded);
+    if let Err(err) = run.await {
+        println!("HTTP/2 error: {}", err);
+    }
+}
+
+struct Connection {
+    writer: OwnedWriteHalf,
+    /// The client end of the connection, stamped on every request.
+    peer: Option<SocketAddr>,
+    config: Arc<Config>,
+    routes: Arc<Routes>,
+    /// The listener's limit on requests in flight, shared with HTTP/1.x.
+    admission: Option<Arc<Admission>>,
+    decoder: Decoder,
+    streams: HashMap<u32, Stream>,
+    /// A header block continued in CONTINUATION frames: its stream, the
+    /// fragments so far and whether the stream ends with it.
+    continuation: Option<(u32, Vec<u8>, bool)>,
+    /// The highest stream the client has opened.
+    last_stream: u32,
+    send_window: i64,
+    /// The window each new stream starts with, from the client's settings.
+    initial_window: i64,
+    max_frame_size: usize,
+    /// Response data queued behind flow control, over all streams.
+    pending_bytes: usize,
+    out: mpsc::Sender<(u32, Out)>,
+    /// Set once a GOAWAY was sent or received: no new streams are
+    /// accepted and the connection ends with the last one.
+    going_away: bool,
+}
+
+impl Connection {
+    async fn run(
+        &mut self,
+        frames: &mut mpsc::Receiver<Result<Frame>>,
+        outgoing: &mut mpsc::Receiver<(u32, Out)>,
+        closing: &mut watch::Receiver<bool>,
+        upgraded: Option<Request>,
+    ) -> Result<()> {
+        let settings = [
+            (
+                SETTINGS_MAX_CONCURRENT_STREAMS,
+                MAX_CONCURRENT_STREAMS as u32,
+            ),
+            (
+                SETTINGS_MAX_HEADER_LIST_SIZE,
+                self.config.max_header_size as u32,
+            ),
+        ]
+        .iter()
+        .flat_map(|(id, value)| [&id.to_be_bytes()[..], &value.to_be_bytes()[..]].concat())
+        .collect::<Vec<u8>>();
+        self.write_frame(SETTINGS, 0, 0, &settings).await?;
+        if let Some(req) = upgraded {
+            let settings = req
+                .headers
+                .get("HTTP2-Settings")
+                .and_then(|value| upgrade_settings(value))
+                .unwrap_or_default();
+            if let Err(err) = self.apply_settings(&settings) {
+                return self.go_away(err).await;
+            }
+            self.last_stream = 1;
+            self.open(1, req);
+            self.dispatch(1).await?;
+        }
+        let mut reading = true;
+        loop {
+            if (!reading || self.going_away) && self.streams.is_empty() {
+                return Ok(());
+            }
+            let idle = self.streams.is_empty() && !self.going_away;
+            tokio::select! {
+                frame = frames.recv(), if reading => match frame {
+                    Some(Ok(frame)) => {
+                        if let Err(err) = self.handle(frame).await {
+                            return self.go_away(err).await;
+                        }
+                    }
+                    Some(Err(err)) => return self.go_away(err).await,
+                    None => reading = false,
+                },
+                Some((id, out)) = outgoing.recv(),
+                    if self.pending_bytes < self.config.max_write_buffer =>
+                {
+                    self.queue(id, out);
+                    self.flush(id).await?;
+                }
+                _ = tokio::time::sleep(self.config.keep_alive_timeout), if idle => {
+                    self.shut_down().await?;
+                }
+                _ = wait_closing(closing), if !self.going_away => {
+                    self.shut_down().await?;
+                }
+            }
+        }
+    }
+
+    async fn handle(&mut self, frame: Frame) -> Result<()> {
+        if let Some((id, ..)) = &self.continuation {
+            if frame.kind != CONTINUATION || frame.stream_id != *id {
+                bail!(fail(PROTOCOL_ERROR, "header block interrupted"));
+            }
+        }
+        match frame.kind {
+            DATA => self.on_data(frame).await,
+            HEADERS => self.on_headers(frame).await,
+            CONTINUATION => self.on_continuation(frame).await,
+            PRIORITY => {
+                if frame.stream_id == 0 {
+                    bail!(fail(PROTOCOL_ERROR, "PRIORITY on stream 0"));
+                }
+                if frame.payload.len() != 5 {
+                    self.reset(frame.stream_id, FRAME_SIZE_ERROR).await?;
+                }
+                Ok(())
+            }
+            RST_STREAM => {
+                if frame.stream_id == 0 {
+                    bail!(fail(PROTOCOL_ERROR, "RST_STREAM on stream 0"));
+                }
+                if frame.payload.len() != 4 {
+                    bail!(fail(FRAME_SIZE_ERROR, "RST_STREAM of the wrong size"));
+                }
+                if frame.stream_id > self.last_stream {
+                    bail!(fail(PROTOCOL_ERROR, "RST_STREAM on an idle stream"));
+                }
+                self.remove(frame.stream_id);
+                Ok(())
+            }
+            SETTINGS => self.on_settings(frame).await,
+            PUSH_PROMISE => bail!(fail(PROTOCOL_ERROR, "PUSH_PROMISE from a client")),
+            PING => {
+                if frame.stream_id != 0 {
+                    bail!(fail(PROTOCOL_ERROR, "PING on a stream"));
+                }
+                if frame.payload.len() != 8 {
+                    bail!(fail(FRAME_SIZE_ERROR, "PING of the wrong size"));
+                }
+                if frame.flags & ACK == 0 {
+                    self.write_frame(PING, ACK, 0, &frame.payload).await?;
+                }
+                Ok(())
+            }
+            GOAWAY => {
+                self.going_away = true;
+                Ok(())
+            }
+            WINDOW_UPDATE => self.on_window_update(frame).await,
+            // Unknown frame types are ignored (RFC 9113 §4.1).
+            _ => Ok(()),
+        }
+    }
+
+    async fn on_headers(&mut self, frame: Frame) -> Result<()> {
+        let id = frame.stream_id;
+        if id == 0 || id.is_multiple_of(2) {
+            bail!(fail(PROTOCOL_ERROR, format!("HEADERS on stream {}", id)));
+        }
+        let mut payload = unpad(frame.flags, &frame.payload)?;
+        if frame.flags & PRIORITY_FLAG != 0 {
+            if payload.len() < 5 {
+                bail!(fail(FRAME_SIZE_ERROR, "HEADERS too short for its priority"));
+            }
+            payload = &payload[5..];
+        }
+        let end_stream = frame.flags & END_STREAM != 0;
+        if frame.flags & END_HEADERS == 0 {
+            self.continuation = Some((id, payload.to_vec(), end_stream));
+            return Ok(());
+        }
+        self.on_header_block(id, payload, end_stream).await
+    }
+
+    async fn on_continuation(&mut self, frame: Frame) -> Result<()> {
+        let Some((id, mut block, end_stream)) = self.continuation.take() else {
+            bail!(fail(PROTOCOL_ERROR, "CONTINUATION without HEADERS"));
+        };
+        block.extend_from_slice(&frame.payload);
+        if block.len() > self.config.max_header_size {
+            bail!(fail(ENHANCE_YOUR_CALM, "header block too large"));
+        }
+        if frame.flags & END_HEADERS == 0 {
+            self.continuation = Some((id, block, end_stream));
+            return Ok(());
+        }
+        self.on_header_block(id, &block, end_stream).await
+    }
+
+    /// Opens a stream with a request head, or takes the trailers of one
+    /// whose body is arriving.
+    async fn on_header_block(&mut self, id: u32, block: &[u8], end_stream: bool) -> Result<()> {
+        let fields = self.decoder.decode(block).map_err(|err| {
+            if err.is::<HeaderListTooLarge>() {
+                fail(ENHANCE_YOUR_CALM, err)
+            } else {
+                fail(COMPRESSION_ERROR, err)
+            }
+        })?;
+        let too_many = fields
+            .iter()
+            .filter(|(name, _)| !name.starts_with(':'))
+            .count()
+            > self.config.max_header_count;
+        if let Some(stream) = self.streams.get_mut(&id) {
+            let Some(req) = stream.req.as_mut() else {
+                return self.reset(id, STREAM_CLOSED).await;
+            };
+            if !end_stream {
+                bail!(fail(PROTOCOL_ERROR, "trailers without END_STREAM"));
+            }
+            if too_many {
+                self.remove(id);
+                return self
+                    .refuse(id, HttpCode::RequestHeaderFieldsTooLarge, end_stream)
+                    .await;
+            }
+            req.trailers = fields.into_iter().collect();
+            return self.dispatch(id).await;
+        }
+        if id <= self.last_stream {
+            bail!(fail(
+                STREAM_CLOSED,
+                format!("HEADERS on closed stream {}", id)
+            ));
+        }
+        self.last_stream = id;
+        if self.going_away || self.streams.len() >= MAX_CONCURRENT_STREAMS {
+            return self.reset(id, REFUSED_STREAM).await;
+        }
+        if too_many {
+            return self
+                .refuse(id, HttpCode::RequestHeaderFieldsTooLarge, end_stream)
+                .await;
+        }
+        let mut req = match request(fields) {
+            Ok(req) => req,
+            Err(err) => {
+                println!("error read request: {}", err);
+                if err.is::<Malformed>() {
+                    return self.reset(id, PROTOCOL_ERROR).await;
+                }
+                let code = if err.is::<UnknownMethod>() {
+                    HttpCode::NotImplemented
+                } else {
+                    HttpCode::BadRequest
+                };
+                return self.refuse(id, code, end_stream).await;
+            }
+        };
+        if let Some(peer) = self.peer {
+            req.set_remote(peer, &self.config.trusted_proxies);
+        }
+        self.routes.apply_rewrites(&mut req);
+        let declared = req
+            .headers
+            .get("Content-Length")
+            .and_then(|length| length.parse::<usize>().ok());
+        if declared.is_some_and(|length| length > self.routes.body_limit(&req)) {
+            return self.refuse(id, HttpCode::ContentTooLarge, end_stream).await;
+        }
+        self.open(id, req);
+        if end_stream {
+            self.dispatch(id).await?;
+        }
+        Ok(())
+    }
+
+    fn open(&mut self, id: u32, req: Request) {
+        let stream = Stream {
+            body_limit: self.routes.body_limit(&req),
+            req: Some(req),
+            send_window: self.initial_window,
+            pending: VecDeque::new(),
+            task: None,
+        };
+        self.streams.insert(id, stream);
+    }
+
+    async fn on_data(&mut self, frame: Frame) -> Result<()> {
+        let id = frame.stream_id;
+        if id == 0 {
+            bail!(fail(PROTOCOL_ERROR, "DATA on stream 0"));
+        }
+        // Request bodies are buffered in full anyway, so the windows are
+        // handed straight back instead of being throttled.
+        let len = frame.payload.len() as u32;
+        if len > 0 {
+            self.write_frame(WINDOW_UPDATE, 0, 0, &len.to_be_bytes())
+                .await?;
+        }
+        let data = unpad(frame.flags, &frame.payload)?;
+        let end_stream = frame.flags & END_STREAM != 0;
+        let Some(stream) = self.streams.get_mut(&id) else {
+            if id > self.last_stream {
+                bail!(fail(PROTOCOL_ERROR, format!("DATA on idle stream {}", id)));
+            }
+            return self.reset(id, STREAM_CLOSED).await;
+        };
+        let limit = stream.body_limit;
+        let Some(req) = stream.req.as_mut() else {
+            return self.reset(id, STREAM_CLOSED).await;
+        };
+        if req.body.len() + data.len() > limit {
+            self.remove(id);
+            return self.refuse(id, HttpCode::ContentTooLarge, end_stream).await;
+        }
+        req.body.extend_from_slice(data);
+        if end_stream {
+            return self.dispatch(id).await;
+        }
+        if len > 0 {
+            self.write_frame(WINDOW_UPDATE, 0, id, &len.to_be_bytes())
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn on_settings(&mut self, frame: Frame) -> Result<()> {
+        if frame.stream_id != 0 {
+            bail!(fail(PROTOCOL_ERROR, "SETTINGS on a stream"));
+        }
+        if frame.flags & ACK != 0 {
+            if !frame.payload.is_empty() {
+                bail!(fail(FRAME_SIZE_ERROR, "SETTINGS ACK with a payload"));
+            }
+            return Ok(());
+        }
+        if !frame.payload.len().is_multiple_of(6) {
+            bail!(fail(FRAME_SIZE_ERROR, "SETTINGS of the wrong size"));
+        }
+        self.apply_settings(&frame.payload)?;
+        self.write_frame(SETTINGS, ACK, 0, &[]).await?;
+        self.flush_all().await
+    }
+
+    fn apply_settings(&mut self, payload: &[u8]) -> Result<()> {
+        for setting in payload.chunks(6) {
+            let id = u16::from_be_bytes([setting[0], setting[1]]);
+            let value = u32::from_be_bytes([setting[2], setting[3], setting[4], setting[5]]);
+            match id {
+                SETTINGS_ENABLE_PUSH if value > 1 => {
+                    bail!(fail(PROTOCOL_ERROR, "invalid SETTINGS_ENABLE_PUSH"));
+                }
+                SETTINGS_INITIAL_WINDOW_SIZE => {
+                    if value as i64 > MAX_WINDOW {
+                        bail!(fail(FLOW_CONTROL_ERROR, "initial window too large"));
+                    }
+                    let delta = value as i64 - self.initial_window;
+                    for stream in self.streams.values_mut() {
+                        stream.send_window += delta;
+                    }
+                    self.initial_window = value as i64;
+                }
+                SETTINGS_MAX_FRAME_SIZE => {
+                    if !(DEFAULT_MAX_FRAME_SIZE..1 << 24).contains(&(value as usize)) {
+                        bail!(fail(PROTOCOL_ERROR, "invalid SETTINGS_MAX_FRAME_SIZE"));
+                    }
+                    self.max_frame_size = value as usize;
+                }
+                // The header table size only matters to an encoder using
+                // the dynamic table, which this one doesn't.
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    async fn on_window_update(&mut self, frame: Frame) -> Result<()> {
+        if frame.payload.len() != 4 {
+            bail!(fail(FRAME_SIZE_ERROR, "WINDOW_UPDATE of the wrong size"));
+        }
+        let bytes = [
+            frame.payload[0],
+            frame.payload[1],
+            frame.payload[2],
+            frame.payload[3],
+        ];
+        let increment = (u32::from_be_bytes(bytes) & 0x7fff_ffff) as i64;
+        let id = frame.stream_id;
+        if id == 0 {
+            if increment == 0 {
+                bail!(fail(PROTOCOL_ERROR, "WINDOW_UPDATE of 0"));
+            }
+            self.send_window += increment;
+            if self.send_window > MAX_WINDOW {
+                bail!(fail(FLOW_CONTROL_ERROR, "connection window overflow"));
+            }
+            return self.flush_all().await;
+        }
+        let Some(stream) = self.streams.get_mut(&id) else {
+            return Ok(());
+        };
+        if increment == 0 {
+            return self.reset(id, PROTOCOL_ERROR).await;
+        }
+        stream.send_window += increment;
+        if stream.send_window > MAX_WINDOW {
+            return self.reset(id, FLOW_CONTROL_ERROR).await;
+        }
+        self.flush(id).await
+    }
+
+    /// Hands a complete request to a task of its own.
+    async fn dispatch(&mut self, id: u32) -> Result<()> {
+        let Some(req) = self
+            .streams
+            .get_mut(&id)
+            .and_then(|stream| stream.req.take())
+        else {
+            return Ok(());
+        };
+        let declared = req.headers.get("Content-Length");
+        if declared.is_some_and(|length| length.parse() != Ok(req.body.len())) {
+            println!("error read request: body doesn't match its Content-Length");
+            return self.reset(id, PROTOCOL_ERROR).await;
+        }
+        if self.config.log_requests {
+            println!("{:?}", req);
+        }
+        let routes = self.routes.clone();
+        let admission = self.admission.clone();
+        let out = self.out.clone();
+        let chunk_size = self.config.file_chunk_size;
+        let retry_after = self.config.retry_after;
+        let task = tokio::spawn(async move {
+            let _admitted = match &admission {
+                Some(admission) => match admission.admit().await {
+                    Some(permit) => Some(permit),
+                    None => {
+                        let mut res = admission::overloaded(retry_after);
+                        routes.stamp(res.headers.get_or_insert_with(HeaderMap::new));
+                        return send_response(res, id, &out, chunk_size).await;
+                    }
+                },
+                None => None,
+            };
+            let (res, _permit) = routes.answer(req).await;
+            send_response(res, id, &out, chunk_size).await;
+        });
+        if let Some(stream) = self.streams.get_mut(&id) {
+            stream.task = Some(task.abort_handle());
+        }
+        Ok(())
+    }
+
+    /// Answers a stream with just a status, without running any route.
+    async fn refuse(&mut self, id: u32, code: HttpCode, end_stream: bool) -> Result<()> {
+        let mut headers = HeaderMap::new();
+        self.routes.stamp(&mut headers);
+        let block = head_block(&code, headers);
+        self.write_headers(id, &block, true).await?;
+        // The rest of the request isn't wanted.
+        if !end_stream {
+            self.write_frame(RST_STREAM, 0, id, &NO_ERROR.to_be_bytes())
+                .await?;
+        }
+        Ok(())
+    }
+
+    fn queue(&mut self, id: u32, out: Out) {
+        let Some(stream) = self.streams.get_mut(&id) else {
+            return;
+        };
+        if let Out::Data(data, _) = &out {
+            self.pending_bytes += data.len();
+        }
+        stream.pending.push_back(out);
+    }
+
+    /// Writes what `id` has queued, as far as the flow control windows
+    /// allow.
+    async fn flush(&mut self, id: u32) -> Result<()> {
+        loop {
+            let Some(stream) = self.streams.get_mut(&id) else {
+                return Ok(());
+            };
+            let Some(out) = stream.pending.pop_front() else {
+                return Ok(());
+            };
+            let (block, end_stream) = match out {
+                Out::Head(block, end_stream) => {
+                    self.write_headers(id, &block, end_stream).await?;
+                    if end_stream {
+                        self.remove(id);
+                    }
+                    continue;
+                }
+                Out::Data(data, end_stream) => (data, end_stream),
+            };
+            let mut data = block;
+            let room = self
+                .send_window
+                .min(stream.send_window)
+                .max(0)
+                .min(self.max_frame_size as i64) as usize;
+            if data.len() > room {
+                let rest = data.split_off(room);
+                stream.pending.push_front(Out::Data(rest, end_stream));
+                if room == 0 {
+                    return Ok(());
+                }
+            } else if data.is_empty() && !end_stream {
+                continue;
+            }
+            let end = end_stream && stream.pending.is_empty();
+            stream.send_window -= data.len() as i64;
+            self.send_window -= data.len() as i64;
+            self.pending_bytes -= data.len();
+            let flags = if end { END_STREAM } else { 0 };
+            self.write_frame(DATA, flags, id, &data).await?;
+            if end {
+                self.remove(id);
+            }
+        }
+    }
+
+    async fn flush_all(&mut self) -> Result<()> {
+        let ids = self.streams.keys().copied().collect::<Vec<u32>>();
+        for id in ids {
+            self.flush(id).await?;
+        }
+        Ok(())
+    }
+
+    /// Sends a header block, continued in CONTINUATION frames when it
+    /// doesn't fit in one.
+    async fn write_headers(&mut self, id: u32, block: &[u8], end_stream: bool) -> Result<()> {
+        let mut fragments = block.chunks(self.max_frame_size).peekable();
+        let mut kind = HEADERS;
+        let mut flags = if end_stream { END_STREAM } else { 0 };
+        while let Some(fragment) = fragments.next() {
+            if fragments.peek().is_none() {
+                flags |= END_HEADERS;
+            }
+            self.write_frame(kind, flags, id, fragment).await?;
+            kind = CONTINUATION;
+            flags = 0;
+        }
+        Ok(())
+    }
+
+    async fn write_frame(&mut self, kind: u8, flags: u8, id: u32, payload: &[u8]) -> Result<()> {
+        let mut frame = Vec::with_capacity(9 + payload.len());
+        frame.extend_from_slice(&(payload.len() as u32).to_be_bytes()[1..]);
+        frame.push(kind);
+        frame.push(flags);
+        frame.extend_from_slice(&id.to_be_bytes());
+        frame.extend_from_slice(payload);
+        self.writer.write_all(&frame).await?;
+        Ok(())
+    }
+
+    async fn reset(&mut self, id: u32, code: u32) -> Result<()> {
+        self.remove(id);
+        self.write_frame(RST_STREAM, 0, id, &code.to_be_bytes())
+            .await
+    }
+
+    fn remove(&mut self, id: u32) {
+        if let Some(stream) = self.streams.remove(&id) {
+            if let Some(task) = stream.task {
+                task.abort();
+            }
+            for out in stream.pending {
+                if let Out::Data(data, _) = out {
+                    self.pending_bytes -= data.len();
+                }
+            }
+        }
+    }
+
+    /// Stops accepting streams, letting the ones in flight finish.
+    async fn shut_down(&mut self) -> Result<()> {
+        self.going_away = true;
+        self.write_goaway(NO_ERROR, "").await
+    }
+
+    async fn go_away(&mut self, err: anyhow::Error) -> Result<()> {
+        if let Some(err) = err.downcast_ref::<ConnectionError>() {
+            let _ = self.write_goaway(err.code, &err.reason).await;
+        }
+        Err(err)
+    }
+
+    async fn write_goaway(&mut self, code: u32, reason: &str) -> Result<()> {
+        let payload = [
+            &self.last_stream.to_be_bytes()[..],
+            &code.to_be_bytes()[..],
+            reason.as_bytes(),
+        ]
+        .concat();
+        self.write_frame(GOAWAY, 0, 0, &payload).await
+    }
+}
+
+/// Resolves once the listener starts shutting down.
+async fn wait_closing(closing: &mut watch::Receiver<bool>) {
+    let _ = closing.wait_for(|closing| *closing).await;
+}
+
+/// Reads frames off the connection, ending at EOF or the first error.
+async fn read_frames(
+    mut reader: OwnedReadHalf,
+    mut buf: BytesMut,
+    frames: mpsc::Sender<Result<Frame>>,
+) {
+    match read_preface(&mut reader, &mut buf).await {
+        Ok(true) => {}
+        Ok(false) => return,
+        Err(err) => {
+            let _ = frames.send(Err(err)).await;
+            return;
+        }
+    }
+    loop {
+        let frame = match read_frame(&mut reader, &mut buf).await {
+            Ok(Some(frame)) => Ok(frame),
+            Ok(None) => return,
+            Err(err) => Err(err),
+        };
+        let failed = frame.is_err();
+        if frames.send(frame).await.is_err() || failed {
+            return;
+        }
+    }
+}
+
+/// Reads and checks the preface; `false` if the client closed first.
+async fn read_preface(reader: &mut OwnedReadHalf, buf: &mut BytesMut) -> Result<bool> {
+    while buf.len() < PREFACE.len() {
+        if reader.read_buf(buf).await? == 0 {
+            return Ok(false);
+        }
+    }
+    if !buf.starts_with(PREFACE) {
+        bail!(fail(PROTOCOL_ERROR, "invalid connection preface"));
+    }
+    buf.advance(PREFACE.len());
+    Ok(true)
+}
+
+async fn read_frame(reader: &mut OwnedReadHalf, buf: &mut BytesMut) -> Result<Option<Frame>> {
+    while buf.len() < 9 {
+        if reader.read_buf(buf).await? == 0 {
+            return Ok(None);
+        }
+    }
+    let len = u32::from_be_bytes([0, buf[0], buf[1], buf[2]]) as usize;
+    if len > DEFAULT_MAX_FRAME_SIZE {
+        bail!(fail(FRAME_SIZE_ERROR, format!("frame of {} bytes", len)));
+    }
+    while buf.len() < 9 + len {
+        if reader.read_buf(buf).await? == 0 {
+            return Ok(None);
+        }
+    }
+    let head = buf.split_to(9);
+    Ok(Some(Frame {
+        kind: head[3],
+        flags: head[4],
+        stream_id: u32::from_be_bytes([head[5], head[6], head[7], head[8]]) & 0x7fff_ffff,
+        payload: buf.split_to(len).to_vec(),
+    }))
+}
+
+/// The payload of a DATA or HEADERS frame without its padding.
+fn unpad(flags: u8, payload: &[u8]) -> Result<&[u8]> {
+    if flags & PADDED == 0 {
+        return Ok(payload);
+    }
+    let Some((&pad, rest)) = payload.split_first() else {
+        bail!(fail(FRAME_SIZE_ERROR, "padded frame without a pad length"));
+    };
+    if pad as usize > rest.len() {
+        bail!(fail(PROTOCOL_ERROR, "padding longer than the frame"));
+    }
+    Ok(&rest[..rest.len() - pad as usize])
+}
+
+/// Builds a `Request` from a decoded header block by writing it out as the
+/// equivalent HTTP/1.1 head, so it is validated and parsed like one that
+/// arrived as text.
+fn request(fields: Vec<(String, String)>) -> Result<Request> {
+    let malformed = |reason: &str| Malformed(reason.to_owned());
+    let (mut method, mut scheme, mut authority, mut path) = (None, None, None, None);
+    let mut lines = Vec::new();
+    for (name, value) in fields {
+        if name.is_empty()
+            || name.contains([' ', '\r', '\n', '\0'])
+            || value.contains(['\r', '\n', '\0'])
+        {
+            bail!(malformed("invalid field"));
+        }
+        if let Some(pseudo) = name.strip_prefix(':') {
+            if !lines.is_empty() {
+                bail!(malformed("pseudo-header after regular fields"));
+            }
+            let slot = match pseudo {
+                "method" => &mut method,
+                "scheme" => &mut scheme,
+                "authority" => &mut authority,
+                "path" => &mut path,
+                _ => bail!(malformed("unknown pseudo-header")),
+            };
+            if slot.replace(value).is_some() {
+                bail!(malformed("repeated pseudo-header"));
+            }
+            continue;
+        }
+        if name.contains(':') || name.bytes().any(|b| b.is_ascii_uppercase()) {
+            bail!(malformed("invalid field name"));
+        }
+        let connection_specific = matches!(
+            name.as_str(),
+            "connection" | "keep-alive" | "proxy-connection" | "transfer-encoding" | "upgrade"
+        );
+        if connection_specific || (name == "te" && value != "trailers") {
+            bail!(malformed("connection-specific field"));
+        }
+        lines.push(format!("{}: {}", name, value));
+    }
+    let Some(method) = method else {
+        bail!(malformed("missing :method"));
+    };
+    let target = if method == "CONNECT" {
+        authority.clone().unwrap_or_default()
+    } else {
+        match (scheme, path) {
+            (Some(_), Some(path)) if !path.is_empty() => path,
+            _ => bail!(malformed("missing :scheme or :path")),
+        }
+    };
+    // :authority stands in for Host, which may only repeat it
+    // (RFC 9113 §8.3.1).
+    if let Some(authority) = authority {
+        let host = |line: &String| line.strip_prefix("host: ").map(str::to_owned);
+        if lines.iter().filter_map(host).any(|host| host != authority) {
+            bail!(malformed("Host differs from :authority"));
+        }
+        lines.retain(|line| host(line).is_none());
+        lines.insert(0, format!("host: {}", authority));
+    }
+    let mut head = vec![format!("{} {} HTTP/1.1", method, target)];
+    head.extend(lines);
+    let mut req = Request::parse(format!("{}\r\n\r\n", head.join("\r\n")).as_bytes())?;
+    head[0] = format!("{} {} HTTP/2", method, target);
+    req.version = HttpVersion::Http2;
+    req.head = head.join("\r\n");
+    Ok(req)
+}
+
+/// Encodes the response head: `:status`, then the headers, lowercased and
+/// without the fields HTTP/2 has no use for.
+fn head_block(code: &HttpCode, headers: HeaderMap) -> Vec<u8> {
+    let mut fields = vec![(String::from(":status"), code.as_u16().to_string())];
+    fields.extend(
+        headers
+            .into_iter()
+            .map(|(name, value)| (name.to_ascii_lowercase(), value))
+            .filter(|(name, _)| {
+                !matches!(
+                    name.as_str(),
+                    "connection"
+                        | "keep-alive"
+                        | "proxy-connection"
+                        | "transfer-encoding"
+                        | "upgrade"
+                )
+            }),
+    );
+    hpack::encode(
+        fields
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str())),
+    )
+}
+
+/// Hands the connection a response's head and then its body, as it is
+/// produced.
+async fn send_response(res: Response, id: u32, out: &mpsc::Sender<(u32, Out)>, chunk_size: usize) {
+    let body = match res.content {
+        // Deferred responses are resolved before they get here.
+        Some(Body::Bytes(bytes)) if bytes.is_empty() => None,
+        Some(Body::Deferred(_)) => None,
+        body => body,
+    };
+    let head = head_block(&res.code, res.headers.unwrap_or_default());
+    let send = |item: Out| out.send((id, item));
+    if send(Out::Head(head, body.is_none())).await.is_err() {
+        return;
+    }
+    let (mut chunks, trailers) = match body {
+        None | Some(Body::Deferred(_)) => return,
+        Some(Body::Bytes(bytes)) => {
+            let _ = send(Out::Data(bytes, true)).await;
+            return;
+        }
+        Some(Body::File(file)) => {
+            let file = tokio::fs::File::from_std(file);
+            return send_reader(Box::new(file), id, out, chunk_size).await;
+        }
+        Some(Body::Reader(reader)) => return send_reader(reader, id, out, chunk_size).await,
+        Some(Body::Stream(chunks)) => (chunks, None),
+        Some(Body::Trailed(chunks, trailers)) => (chunks, Some(trailers)),
+    };
+    while let Some(chunk) = chunks.recv().await {
+        if send(Out::Data(chunk, false)).await.is_err() {
+            return;
+        }
+    }
+    let trailers = match trailers {
+        Some(trailers) => trailers.await.unwrap_or_default(),
+        None => HeaderMap::new(),
+    };
+    let last = if trailers.is_empty() {
+        Out::Data(Vec::new(), true)
+    } else {
+        let fields = trailers
+            .into_iter()
+            .map(|(name, value)| (name.to_ascii_lowercase(), value))
+            .collect::<Vec<(String, String)>>();
+        Out::Head(
+            hpack::encode(
+                fields
+                    .iter()
+                    .map(|(name, value)| (name.as_str(), value.as_str())),
+            ),
+            true,
+        )
+    };
+    let _ = send(last).await;
+}
+
+/// Sends a body read from `reader` in `chunk_size` chunks. A read error
+/// ends the stream early.
+async fn send_reader(
+    mut reader: Box<dyn AsyncRead + Send + Unpin>,
+    id: u32,
+    out: &mpsc::Sender<(u32, Out)>,
+    chunk_size: usize,
+) {
+    loop {
+        let mut chunk = vec![0; chunk_size];
+        let len = match reader.read(&mut chunk).await {
+            Ok(len) => len,
+            Err(err) => {
+                println!("Error sending response: {}", err);
+                0
+            }
+        };
+        chunk.truncate(len);
+        if out.send((id, Out::Data(chunk, len == 0))).await.is_err() || len == 0 {
+            return;
+        }
+    }
+}
@@ -0,0 +1,331 @@
+use anyhow::{bail, Result};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::{mpsc, oneshot};
+
+use crate::admission::Admission;
+use crate::assets::{self, EmbeddedAssets};
+use crate::auth::{BasicAuth, DigestAuth};
+use crate::body::BodyStream;
+use crate::cache::CacheMiddleware;
+use crate::canonical::CanonicalHost;
+use crate::cgi::Cgi;
+use crate::config::Config;
+use crate::control::Control;
+use crate::dav;
+use crate::digest::Digester;
+use crate::hash::{base64_encode, random_token, Sha256};
+use crate::header_map::HeaderMap;
+use crate::headers::StaticHeaders;
+use crate::json::Value;
+use crate::maintenance::Maintenance;
+use crate::request::Request;
+use crate::response::{Body, HttpCode, Response};
+use crate::rewrite::Rewriter;
+use crate::routes;
+use crate::routes::Routes;
+use crate::server;
+use crate::vhost::VirtualHosts;
+
+/// Builds the route table served by the binary.
+pub fn routes(config: &Config, control: &Arc<Control>) -> Result<Routes> {
+    let mut routes = Routes::new(config);
+    if !config.rewrites.is_empty() {
+        routes.rewrite(Rewriter::new(&config.rewrites)?);
+    }
+    for (prefix, limit) in config.route_limits.iter() {
+        let admission = Admission::new(*limit, config.queue_depth, config.queue_timeout);
+        routes.limit(prefix, admission);
+    }
+    let page = config
+        .maintenance_page
+        .as_ref()
+        .map(std::fs::read)
+        .transpose()?;
+    let maintenance = Maintenance::new(
+        control.clone(),
+        config.maintenance_exempt.clone(),
+        config.retry_after,
+        page,
+    );
+    let mut headers = config.headers.clone();
+    if let Some(alt_svc) = &config.alt_svc {
+        headers.push((String::from("*"), String::from("Alt-Svc"), alt_svc.clone()));
+    }
+    if !headers.is_empty() {
+        routes.middleware("/", Arc::new(StaticHeaders::new(headers)));
+    }
+    if let Some(host) = &config.canonical_host {
+        routes.middleware("/", Arc::new(CanonicalHost::new(host)));
+    }
+    routes.middleware("/", Arc::new(maintenance));
+    if config.etag || !config.cache_control.is_empty() {
+        let cache = CacheMiddleware::new(config.etag, config.cache_control.clone());
+        routes.middleware("/", Arc::new(cache));
+    }
+    for (prefix, path) in config.digest_auth.iter() {
+        routes.middleware(
+            prefix,
+            Arc::new(DigestAuth::from_htdigest(path)?.clock(control.clock().clone())),
+        );
+    }
+    for (prefix, path) in config.basic_auth.iter() {
+        routes.middleware(prefix, Arc::new(BasicAuth::htpasswd(prefix, path)?));
+    }
+    for (prefix, directory) in config.cgi.iter() {
+        let cgi = Cgi::new(
+            prefix,
+            directory,
+            config.file_chunk_size,
+            config.max_header_size,
+        );
+        routes.middleware(prefix, Arc::new(cgi));
+    }
+    if let Some(prefix) = &config.embedded_assets {
+        if assets::EMBEDDED.is_empty() {
+            bail!("--embedded-assets needs a binary built with EMBED_ASSETS_DIR set");
+        }
+        routes.middleware(
+            prefix,
+            Arc::new(EmbeddedAssets::new(prefix, assets::EMBEDDED)),
+        );
+    }
+    if !config.vhosts.is_empty() {
+        routes.middleware("/", Arc::new(VirtualHosts::new(&config.vhosts)?));
+    }
+    let health_control = control.clone();
+    routes! { routes;
+        GET "/" => |_, _| Response {
+            code: HttpCode::OK,
+            headers: None,
+            content: None,
+        };
+        GET "/healthz" => move |_, _| health(&health_control);
+        GET "/echo*" => echo;
+        GET "/user-agent" => user_agent;
+        GET "/files*" => get_file;
+        PROPFIND "/files*" => |req, directory| dav::propfind(req, directory);
+        MKCOL "/files*" => |req, directory| dav::mkcol(req, directory);
+        // These bodies are piped back or to disk as they arrive, so they
+        // aren't buffered first.
+        POST "/echo" => echo_body, stream_body;
+        POST "/files*" => post_file, stream_body;
+    }
+
+    Ok(routes)
+}
+
+/// Reports whether the server is serving normally or in maintenance mode.
+pub fn health(control: &Control) -> Response {
+    let status = if control.in_maintenance() {
+        "maintenance"
+    } else {
+        "ok"
+    };
+    Response::text(HttpCode::OK, status)
+}
+
+pub fn echo(req: Request, _directory: &String) -> Response {
+    let value = req.path.replace("/echo/", "");
+    // Either answer depends on `Accept`, so caches must key on it.
+    let vary = (String::from("Vary"), String::from("Accept"));
+    let Some(content_type) = req.negotiate(&["text/plain", "application/json"]) else {
+        return Response {
+            code: HttpCode::NotAcceptable,
+            content: None,
+            headers: Some(HeaderMap::from([vary])),
+        };
+    };
+    let mut res = if content_type == "application/json" {
+        let message = Value::Object(vec![(String::from("message"), Value::String(value))]);
+        Response::json(&message)
+    } else {
+        Response::text(HttpCode::OK, value)
+    };
+    res.headers
+        .get_or_insert_with(HeaderMap::new)
+        .insert(vary.0, vary.1);
+    res
+}
+
+/// Pipes the request body back as a chunked response, a chunk at a time as
+/// it is read off the connection. Clients that accept trailers
+/// (`TE: trailers`) get the body's `Content-Digest` as one.
+pub fn echo_body(mut req: Request, _directory: &String) -> Response {
+    let mut body = req.body_stream();
+    let (sender, chunks) = mpsc::channel(1);
+    let content_type = req
+        .headers
+        .get("Content-Type")
+        .cloned()
+        .unwrap_or_else(|| String::from("application/octet-stream"));
+    let mut headers = HeaderMap::from([(String::from("Content-Type"), content_type)]);
+    let wants_trailers = req.headers.get("TE").is_some_and(|te| {
+        te.split(',')
+            .any(|coding| coding.trim().eq_ignore_ascii_case("trailers"))
+    });
+    let (trailer, trailers) = oneshot::channel();
+    let mut digest = wants_trailers.then(Sha256::new);
+    tokio::spawn(async move {
+        while let Some(chunk) = body.chunk().await {
+            // A body cut short ends the response without its digest.
+            let Ok(chunk) = chunk else {
+                return;
+            };
+            if let Some(digest) = digest.as_mut() {
+                digest.update(&chunk);
+            }
+            if sender.send(chunk).await.is_err() {
+                return;
+            }
+        }
+        if let Some(digest) = digest {
+            let _ = trailer.send(HeaderMap::from([(
+                String::from("Content-Digest"),
+                format!("sha-256=:{}:", base64_encode(&digest.finish())),
+            )]));
+        }
+    });
+    if !wants_trailers {
+        return Response {
+            code: HttpCode::OK,
+            content: Some(Body::Stream(chunks)),
+            headers: Some(headers),
+        };
+    }
+    headers.insert(String::from("Trailer"), String::from("Content-Digest"));
+    Response {
+        code: HttpCode::OK,
+        content: Some(Body::Trailed(chunks, trailers)),
+        headers: Some(headers),
+    }
+}
+
+pub fn user_agent(req: Request, _directory: &String) -> Response {
+    Response::text(
+        HttpCode::OK,
+        req.headers.get("User-Agent").map_or("", String::as_str),
+    )
+}
+
+pub fn get_file(req: Request, directory: &String) -> Response {
+    let Some(filename) = req.path.strip_prefix("/files/") else {
+        return Response {
+            code: HttpCode::NotFound,
+            content: None,
+            headers: None,
+        };
+    };
+    let filename = format!("/{}/{}", &directory, filename);
+    let path_filename = Path::new(&filename);
+    if !path_filename.exists() {
+        return Response {
+            code: HttpCode::NotFound,
+            content: None,
+            headers: None,
+        };
+    }
+    let file = match File::open(path_filename) {
+        Ok(f) => f,
+        Err(_) => {
+            return Response {
+                code: HttpCode::NotFound,
+                content: None,
+                headers: None,
+            }
+        }
+    };
+    let len = match file.metadata() {
+        Ok(metadata) => metadata.len(),
+        Err(_) => {
+            return Response {
+                code: HttpCode::NotFound,
+                content: None,
+                headers: None,
+            }
+        }
+    };
+    let headers = HeaderMap::from([
+        (String::from("Content-Length"), len.to_string()),
+        (
+            String::from("Content-Type"),
+            String::from("application/octet-stream"),
+        ),
+    ]);
+    // Read a chunk at a time as the client takes it, never all at once.
+    Response {
+        code: HttpCode::OK,
+        content: Some(Body::reader(tokio::fs::File::from_std(file))),
+        headers: Some(headers),
+    }
+}
+
+pub fn post_file(mut req: Request, directory: &String) -> Response {
+    let Some(filename) = req.path.strip_prefix("/files/") else {
+        return Response {
+            code: HttpCode::NotFound,
+            content: None,
+            headers: None,
+        };
+    };
+    let path = PathBuf::from(format!("/{}/{}", &directory, filename));
+    let body = req.body_stream();
+    let headers = std::mem::take(&mut req.headers);
+    let (sender, response) = oneshot::channel();
+    tokio::spawn(async move {
+        let _ = sender.send(store_file(body, &headers, &path).await);
+    });
+    Response::deferred(response)
+}
+
+/// Writes an upload to a temporary file beside `path` and only moves it
+/// into place once the whole body has arrived and matches the digests sent
+/// with it, so a cut-short or corrupted upload never replaces what is on
+/// disk.
+async fn store_file(body: BodyStream, headers: &HeaderMap, path: &Path) -> Response {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temp = path.with_file_name(format!(".{}.{}.part", name, random_token()));
+    let code = match write_upload(body, headers, &temp).await {
+        Ok(()) => match tokio::fs::rename(&temp, path).await {
+            Ok(()) => HttpCode::Created,
+            Err(_) => HttpCode::NotFound,
+        },
+        Err(code) => code,
+    };
+    if code != HttpCode::Created {
+        let _ = tokio::fs::remove_file(&temp).await;
+    }
+    Response {
+        code,
+        content: None,
+        headers: None,
+    }
+}
+
+/// Writes `body` to `temp`, hashing it on the way to check it against the
+/// digests in `headers` and the trailers.
+async fn write_upload(
+    mut body: BodyStream,
+    headers: &HeaderMap,
+    temp: &Path,
+) -> Result<(), HttpCode> {
+    let mut file = tokio::fs::File::create(temp)
+        .await
+        .map_err(|_| HttpCode::NotFound)?;
+    let mut digester = Digester::default();
+    while let Some(chunk) = body.chunk().await {
+        let chunk = chunk.map_err(|err| server::rejection(&err).unwrap_or(HttpCode::BadRequest))?;
+        digester.update(&chunk);
+        file.write_all(&chunk)
+            .await
+            .map_err(|_| HttpCode::NotFound)?;
+    }
+    file.flush().await.map_err(|_| HttpCode::NotFound)?;
+    let digests = digester.finish();
+    if !digests.verify(headers) || !digests.verify(&body.trailers().await) {
+        return Err(HttpCode::UnprocessableContent);
+    }
+    Ok(())
+}
@@ -0,0 +1,145 @@
+use std::ops::Index;
+
+use crate::typed_header::{InvalidHeaderValue, TypedHeader};
+
+/// Header fields of a request or response. Names are matched
+/// case-insensitively but sent with the case they were set with, and a
+/// field received more than once keeps each of its values, in order.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HeaderMap {
+    fields: Vec<(String, String)>,
+}
+
+impl HeaderMap {
+    pub fn new() -> Self {
+        HeaderMap { fields: Vec::new() }
+    }
+
+    /// The first value of `name`. Use `get_all` for fields that may repeat.
+    pub fn get(&self, name: &str) -> Option<&String> {
+        self.fields
+            .iter()
+            .find(|(field, _)| field.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value)
+    }
+
+    /// The field `T`, parsed from all of its values. `None` if it wasn't
+    /// sent.
+    pub fn typed<T: TypedHeader>(&self) -> Option<Result<T, InvalidHeaderValue>> {
+        let values = self
+            .get_all(T::NAME)
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        (!values.is_empty()).then(|| T::decode(&values))
+    }
+
+    /// Sets the field `T` to `value`, replacing any values it had.
+    pub fn insert_typed<T: TypedHeader>(&mut self, value: &T) {
+        self.insert(String::from(T::NAME), value.encode());
+    }
+
+    /// Every value of `name`, in the order they were received or added.
+    pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a String> + 'a {
+        self.fields
+            .iter()
+            .filter(move |(field, _)| field.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value)
+    }
+
+    pub fn contains_key(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    /// Sets `name` to `value`, replacing every value it had. Returns the
+    /// first value replaced.
+    pub fn insert(&mut self, name: String, value: String) -> Option<String> {
+        let old = self.remove(&name);
+        self.fields.push((name, value));
+        old
+    }
+
+    /// Adds another value for `name`, keeping those it already has.
+    pub fn append(&mut self, name: String, value: String) {
+        self.fields.push((name, value));
+    }
+
+    /// Removes every value of `name`, returning the first.
+    pub fn remove(&mut self, name: &str) -> Option<String> {
+        let mut removed = None;
+        self.fields.retain_mut(|(field, value)| {
+            if !field.eq_ignore_ascii_case(name) {
+                return true;
+            }
+            if removed.is_none() {
+                removed = Some(std::mem::take(value));
+            }
+            false
+        });
+        removed
+    }
+
+    /// Returns the first value of `name`, setting it to `default()` first
+    /// if the field is missing.
+    pub fn get_or_insert_with(&mut self, name: &str, default: impl FnOnce() -> String) -> &String {
+        let index = match self
+            .fields
+            .iter()
+            .position(|(field, _)| field.eq_ignore_ascii_case(name))
+        {
+            Some(index) => index,
+            None => {
+                self.fields.push((name.to_owned(), default()));
+                self.fields.len() - 1
+            }
+        };
+        &self.fields[index].1
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &String)> {
+        self.fields.iter().map(|(name, value)| (name, value))
+    }
+
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+}
+
+impl<const N: usize> From<[(String, String); N]> for HeaderMap {
+    fn from(fields: [(String, String); N]) -> Self {
+        fields.into_iter().collect()
+    }
+}
+
+/// Collects fields with `append`, so repeated names keep every value.
+impl FromIterator<(String, String)> for HeaderMap {
+    fn from_iter<I: IntoIterator<Item = (String, String)>>(iter: I) -> Self {
+        let mut headers = HeaderMap::new();
+        for (name, value) in iter {
+            headers.append(name, value);
+        }
+        headers
+    }
+}
+
+impl IntoIterator for HeaderMap {
+    type Item = (String, String);
+    type IntoIter = std::vec::IntoIter<(String, String)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.fields.into_iter()
+    }
+}
+
+/// Panics if `name` is missing, like indexing a `HashMap`.
+impl Index<&str> for HeaderMap {
+    type Output = String;
+
+    fn index(&self, name: &str) -> &String {
+        self.get(name)
+            .unwrap_or_else(|| panic!("no {} header", name))
+    }
+}
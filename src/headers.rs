@@ -0,0 +1,32 @@
+use crate::header_map::HeaderMap;
+use crate::pattern;
+use crate::request::Request;
+use crate::response::Response;
+use crate::routes::{Middleware, Next};
+
+/// Adds configured headers to every response whose request path matches a
+/// glob pattern. Headers a handler already set are left alone.
+pub struct StaticHeaders {
+    /// `(glob pattern, header name, value)`, applied in order.
+    rules: Vec<(String, String, String)>,
+}
+
+impl StaticHeaders {
+    pub fn new(rules: Vec<(String, String, String)>) -> Self {
+        StaticHeaders { rules }
+    }
+}
+
+impl Middleware for StaticHeaders {
+    fn handle(&self, req: Request, next: Next<'_>) -> Response {
+        let path = req.path.clone();
+        let mut res = next(req);
+        let headers = res.headers.get_or_insert_with(HeaderMap::new);
+        for (glob, name, value) in self.rules.iter() {
+            if pattern::matches(glob, &path) {
+                headers.get_or_insert_with(name, || value.clone());
+            }
+        }
+        res
+    }
+}
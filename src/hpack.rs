@@ -0,0 +1,362 @@
+//! HPACK, the header compression of HTTP/2 (RFC 7541).
+
+use anyhow::{bail, Result};
+use std::collections::{HashMap, VecDeque};
+use std::sync::OnceLock;
+
+/// Fields every peer knows, addressed by index 1 to 61.
+const STATIC_TABLE: [(&str, &str); 61] = [
+    (":authority", ""),
+    (":method", "GET"),
+    (":method", "POST"),
+    (":path", "/"),
+    (":path", "/index.html"),
+    (":scheme", "http"),
+    (":scheme", "https"),
+    (":status", "200"),
+    (":status", "204"),
+    (":status", "206"),
+    (":status", "304"),
+    (":status", "400"),
+    (":status", "404"),
+    (":status", "500"),
+    ("accept-charset", ""),
+    ("accept-encoding", "gzip, deflate"),
+    ("accept-language", ""),
+    ("accept-ranges", ""),
+    ("accept", ""),
+    ("access-control-allow-origin", ""),
+    ("age", ""),
+    ("allow", ""),
+    ("authorization", ""),
+    ("cache-control", ""),
+    ("content-disposition", ""),
+    ("content-encoding", ""),
+    ("content-language", ""),
+    ("content-length", ""),
+    ("content-location", ""),
+    ("content-range", ""),
+    ("content-type", ""),
+    ("cookie", ""),
+    ("date", ""),
+    ("etag", ""),
+    ("expect", ""),
+    ("expires", ""),
+    ("from", ""),
+    ("host", ""),
+    ("if-match", ""),
+    ("if-modified-since", ""),
+    ("if-none-match", ""),
+    ("if-range", ""),
+    ("if-unmodified-since", ""),
+    ("last-modified", ""),
+    ("link", ""),
+    ("location", ""),
+    ("max-forwards", ""),
+    ("proxy-authenticate", ""),
+    ("proxy-authorization", ""),
+    ("range", ""),
+    ("referer", ""),
+    ("refresh", ""),
+    ("retry-after", ""),
+    ("server", ""),
+    ("set-cookie", ""),
+    ("strict-transport-security", ""),
+    ("transfer-encoding", ""),
+    ("user-agent", ""),
+    ("vary", ""),
+    ("via", ""),
+    ("www-authenticate", ""),
+];
+
+/// `(code, bit length)` of every byte value, then of EOS, from the
+/// canonical Huffman code in RFC 7541 Appendix B.
+#[rustfmt::skip]
+const HUFFMAN: [(u32, u8); 257] = [
+    (0x1ff8, 13), (0x7fffd8, 23), (0xfffffe2, 28), (0xfffffe3, 28), (0xfffffe4, 28), (0xfffffe5, 28),
+    (0xfffffe6, 28), (0xfffffe7, 28), (0xfffffe8, 28), (0xffffea, 24), (0x3ffffffc, 30), (0xfffffe9, 28),
+    (0xfffffea, 28), (0x3ffffffd, 30), (0xfffffeb, 28), (0xfffffec, 28), (0xfffffed, 28), (0xfffffee, 28),
+    (0xfffffef, 28), (0xffffff0, 28), (0xffffff1, 28), (0xffffff2, 28), (0x3ffffffe, 30), (0xffffff3, 28),
+    (0xffffff4, 28), (0xffffff5, 28), (0xffffff6, 28), (0xffffff7, 28), (0xffffff8, 28), (0xffffff9, 28),
+    (0xffffffa, 28), (0xffffffb, 28), (0x14, 6), (0x3f8, 10), (0x3f9, 10), (0xffa, 12),
+    (0x1ff9, 13), (0x15, 6), (0xf8, 8), (0x7fa, 11), (0x3fa, 10), (0x3fb, 10),
+    (0xf9, 8), (0x7fb, 11), (0xfa, 8), (0x16, 6), (0x17, 6), (0x18, 6),
+    (0x0, 5), (0x1, 5), (0x2, 5), (0x19, 6), (0x1a, 6), (0x1b, 6),
+    (0x1c, 6), (0x1d, 6), (0x1e, 6), (0x1f, 6), (0x5c, 7), (0xfb, 8),
+    (0x7ffc, 15), (0x20, 6), (0xffb, 12), (0x3fc, 10), (0x1ffa, 13), (0x21, 6),
+    (0x5d, 7), (0x5e, 7), (0x5f, 7), (0x60, 7), (0x61, 7), (0x62, 7),
+    (0x63, 7), (0x64, 7), (0x65, 7), (0x66, 7), (0x67, 7), (0x68, 7),
+    (0x69, 7), (0x6a, 7), (0x6b, 7), (0x6c, 7), (0x6d, 7), (0x6e, 7),
+    (0x6f, 7), (0x70, 7), (0x71, 7), (0x72, 7), (0xfc, 8), (0x73, 7),
+    (0xfd, 8), (0x1ffb, 13), (0x7fff0, 19), (0x1ffc, 13), (0x3ffc, 14), (0x22, 6),
+    (0x7ffd, 15), (0x3, 5), (0x23, 6), (0x4, 5), (0x24, 6), (0x5, 5),
+    (0x25, 6), (0x26, 6), (0x27, 6), (0x6, 5), (0x74, 7), (0x75, 7),
+    (0x28, 6), (0x29, 6), (0x2a, 6), (0x7, 5), (0x2b, 6), (0x76, 7),
+    (0x2c, 6), (0x8, 5), (0x9, 5), (0x2d, 6), (0x77, 7), (0x78, 7),
+    (0x79, 7), (0x7a, 7), (0x7b, 7), (0x7ffe, 15), (0x7fc, 11), (0x3ffd, 14),
+    (0x1ffd, 13), (0xffffffc, 28), (0xfffe6, 20), (0x3fffd2, 22), (0xfffe7, 20), (0xfffe8, 20),
+    (0x3fffd3, 22), (0x3fffd4, 22), (0x3fffd5, 22), (0x7fffd9, 23), (0x3fffd6, 22), (0x7fffda, 23),
+    (0x7fffdb, 23), (0x7fffdc, 23), (0x7fffdd, 23), (0x7fffde, 23), (0xffffeb, 24), (0x7fffdf, 23),
+    (0xffffec, 24), (0xffffed, 24), (0x3fffd7, 22), (0x7fffe0, 23), (0xffffee, 24), (0x7fffe1, 23),
+    (0x7fffe2, 23), (0x7fffe3, 23), (0x7fffe4, 23), (0x1fffdc, 21), (0x3fffd8, 22), (0x7fffe5, 23),
+    (0x3fffd9, 22), (0x7fffe6, 23), (0x7fffe7, 23), (0xffffef, 24), (0x3fffda, 22), (0x1fffdd, 21),
+    (0xfffe9, 20), (0x3fffdb, 22), (0x3fffdc, 22), (0x7fffe8, 23), (0x7fffe9, 23), (0x1fffde, 21),
+    (0x7fffea, 23), (0x3fffdd, 22), (0x3fffde, 22), (0xfffff0, 24), (0x1fffdf, 21), (0x3fffdf, 22),
+    (0x7fffeb, 23), (0x7fffec, 23), (0x1fffe0, 21), (0x1fffe1, 21), (0x3fffe0, 22), (0x1fffe2, 21),
+    (0x7fffed, 23), (0x3fffe1, 22), (0x7fffee, 23), (0x7fffef, 23), (0xfffea, 20), (0x3fffe2, 22),
+    (0x3fffe3, 22), (0x3fffe4, 22), (0x7ffff0, 23), (0x3fffe5, 22), (0x3fffe6, 22), (0x7ffff1, 23),
+    (0x3ffffe0, 26), (0x3ffffe1, 26), (0xfffeb, 20), (0x7fff1, 19), (0x3fffe7, 22), (0x7ffff2, 23),
+    (0x3fffe8, 22), (0x1ffffec, 25), (0x3ffffe2, 26), (0x3ffffe3, 26), (0x3ffffe4, 26), (0x7ffffde, 27),
+    (0x7ffffdf, 27), (0x3ffffe5, 26), (0xfffff1, 24), (0x1ffffed, 25), (0x7fff2, 19), (0x1fffe3, 21),
+    (0x3ffffe6, 26), (0x7ffffe0, 27), (0x7ffffe1, 27), (0x3ffffe7, 26), (0x7ffffe2, 27), (0xfffff2, 24),
+    (0x1fffe4, 21), (0x1fffe5, 21), (0x3ffffe8, 26), (0x3ffffe9, 26), (0xffffffd, 28), (0x7ffffe3, 27),
+    (0x7ffffe4, 27), (0x7ffffe5, 27), (0xfffec, 20), (0xfffff3, 24), (0xfffed, 20), (0x1fffe6, 21),
+    (0x3fffe9, 22), (0x1fffe7, 21), (0x1fffe8, 21), (0x7ffff3, 23), (0x3fffea, 22), (0x3fffeb, 22),
+    (0x1ffffee, 25), (0x1ffffef, 25), (0xfffff4, 24), (0xfffff5, 24), (0x3ffffea, 26), (0x7ffff4, 23),
+    (0x3ffffeb, 26), (0x7ffffe6, 27), (0x3ffffec, 26), (0x3ffffed, 26), (0x7ffffe7, 27), (0x7ffffe8, 27),
+    (0x7ffffe9, 27), (0x7ffffea, 27), (0x7ffffeb, 27), (0xffffffe, 28), (0x7ffffec, 27), (0x7ffffed, 27),
+    (0x7ffffee, 27), (0x7ffffef, 27), (0x7fffff0, 27), (0x3ffffee, 26), (0x3fffffff, 30),
+];
+
+/// A decoded header block added up to more than the advertised
+/// `SETTINGS_MAX_HEADER_LIST_SIZE`.
+#[derive(Debug, thiserror::Error)]
+#[error("header list exceeds {0} bytes")]
+pub struct HeaderListTooLarge(pub usize);
+
+/// Bytes of overhead counted for every dynamic table entry.
+const ENTRY_OVERHEAD: usize = 32;
+
+/// Decodes the header blocks of one connection, which share a dynamic
+/// table.
+pub struct Decoder {
+    table: VecDeque<(String, String)>,
+    size: usize,
+    max_size: usize,
+    /// The table size advertised in `SETTINGS`; the encoder may pick any
+    /// size up to it.
+    limit: usize,
+    /// The `SETTINGS_MAX_HEADER_LIST_SIZE` advertised: the most a decoded
+    /// block may add up to, counting each field as in the table.
+    max_list_size: usize,
+}
+
+impl Decoder {
+    pub fn new(limit: usize, max_list_size: usize) -> Self {
+        Decoder {
+            table: VecDeque::new(),
+            size: 0,
+            max_size: limit,
+            limit,
+            max_list_size,
+        }
+    }
+
+    /// Decodes a complete header block into its fields, in order.
+    /// Any error leaves the table out of step with the peer's, so it ends
+    /// the connection.
+    /// Stops with `HeaderListTooLarge` as soon as the fields decoded so
+    /// far pass `max_list_size`, so a small block of indexed fields can't
+    /// expand without bound.
+    pub fn decode(&mut self, block: &[u8]) -> Result<Vec<(String, String)>> {
+        let mut fields = Vec::new();
+        let mut list_size = 0;
+        let mut pos = 0;
+        while pos < block.len() {
+            let first = block[pos];
+            let field = if first & 0x80 != 0 {
+                let index = integer(block, &mut pos, 7)?;
+                self.entry(index)?
+            } else if first & 0xc0 == 0x40 {
+                let field = self.literal(block, &mut pos, 6)?;
+                self.add(field.clone());
+                field
+            } else if first & 0xe0 == 0x20 {
+                let size = integer(block, &mut pos, 5)?;
+                if size > self.limit {
+                    bail!("table size update to {} exceeds {}", size, self.limit);
+                }
+                self.max_size = size;
+                self.evict(0);
+                continue;
+            } else {
+                // Without indexing (0000) or never indexed (0001).
+                self.literal(block, &mut pos, 4)?
+            };
+            list_size += field.0.len() + field.1.len() + ENTRY_OVERHEAD;
+            if list_size > self.max_list_size {
+                bail!(HeaderListTooLarge(self.max_list_size));
+            }
+            fields.push(field);
+        }
+        Ok(fields)
+    }
+
+    /// A field with a literal value, and a name either literal or taken
+    /// from the entry its `prefix`-bit index points at.
+    fn literal(&self, block: &[u8], pos: &mut usize, prefix: u8) -> Result<(String, String)> {
+        let name = match integer(block, pos, prefix)? {
+            0 => string(block, pos)?,
+            index => self.entry(index)?.0,
+        };
+        Ok((name, string(block, pos)?))
+    }
+
+    fn entry(&self, index: usize) -> Result<(String, String)> {
+        let entry = match index {
+            0 => None,
+            1..=61 => STATIC_TABLE
+                .get(index - 1)
+                .map(|&(name, value)| (name.to_owned(), value.to_owned())),
+            _ => self.table.get(index - 62).cloned(),
+        };
+        match entry {
+            Some(entry) => Ok(entry),
+            None => bail!("header table index {} out of range", index),
+        }
+    }
+
+    fn add(&mut self, field: (String, String)) {
+        let size = field.0.len() + field.1.len() + ENTRY_OVERHEAD;
+        self.evict(size);
+        // An entry larger than the whole table just empties it.
+        if size <= self.max_size {
+            self.size += size;
+            self.table.push_front(field);
+        }
+    }
+
+    /// Drops the oldest entries until `room` more bytes fit.
+    fn evict(&mut self, room: usize) {
+        while self.size + room > self.max_size {
+            let Some((name, value)) = self.table.pop_back() else {
+                break;
+            };
+            self.size -= name.len() + value.len() + ENTRY_OVERHEAD;
+        }
+    }
+}
+
+/// Encodes `fields` without touching the dynamic table, so the peer's
+/// decoder never has to keep state for this side. Names must already be
+/// lowercase.
+pub fn encode<'a>(fields: impl IntoIterator<Item = (&'a str, &'a str)>) -> Vec<u8> {
+    let mut block = Vec::new();
+    for (name, value) in fields {
+        let exact = STATIC_TABLE
+            .iter()
+            .position(|&field| field == (name, value));
+        if let Some(index) = exact {
+            put_integer(&mut block, 0x80, 7, index + 1);
+            continue;
+        }
+        // Literal without indexing, reusing a static name where there is one.
+        match STATIC_TABLE.iter().position(|&(known, _)| known == name) {
+            Some(index) => put_integer(&mut block, 0x00, 4, index + 1),
+            None => {
+                block.push(0x00);
+                put_string(&mut block, name);
+            }
+        }
+        put_string(&mut block, value);
+    }
+    block
+}
+
+/// Reads an integer whose first byte holds it in the low `prefix` bits.
+fn integer(block: &[u8], pos: &mut usize, prefix: u8) -> Result<usize> {
+    let max = (1usize << prefix) - 1;
+    let Some(&first) = block.get(*pos) else {
+        bail!("header block ends inside an integer");
+    };
+    *pos += 1;
+    let mut value = first as usize & max;
+    if value < max {
+        return Ok(value);
+    }
+    let mut shift = 0;
+    loop {
+        let Some(&byte) = block.get(*pos) else {
+            bail!("header block ends inside an integer");
+        };
+        *pos += 1;
+        if shift > 28 {
+            bail!("integer in header block overflows");
+        }
+        value += (byte as usize & 0x7f) << shift;
+        shift += 7;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+}
+
+fn put_integer(block: &mut Vec<u8>, flags: u8, prefix: u8, value: usize) {
+    let max = (1usize << prefix) - 1;
+    if value < max {
+        block.push(flags | value as u8);
+        return;
+    }
+    block.push(flags | max as u8);
+    let mut rest = value - max;
+    while rest >= 0x80 {
+        block.push((rest & 0x7f) as u8 | 0x80);
+        rest >>= 7;
+    }
+    block.push(rest as u8);
+}
+
+/// Reads a string literal, Huffman coded or not.
+fn string(block: &[u8], pos: &mut usize) -> Result<String> {
+    let huffman = block.get(*pos).is_some_and(|byte| byte & 0x80 != 0);
+    let len = integer(block, pos, 7)?;
+    let Some(raw) = block.get(*pos..*pos + len) else {
+        bail!("header block ends inside a string");
+    };
+    *pos += len;
+    let bytes = if huffman {
+        decode_huffman(raw)?
+    } else {
+        raw.to_vec()
+    };
+    Ok(String::from_utf8(bytes)?)
+}
+
+/// Strings are sent as is; Huffman coding them would only save bytes.
+fn put_string(block: &mut Vec<u8>, value: &str) {
+    put_integer(block, 0x00, 7, value.len());
+    block.extend_from_slice(value.as_bytes());
+}
+
+fn decode_huffman(raw: &[u8]) -> Result<Vec<u8>> {
+    static CODES: OnceLock<HashMap<(u8, u32), u16>> = OnceLock::new();
+    let codes = CODES.get_or_init(|| {
+        HUFFMAN
+            .iter()
+            .enumerate()
+            .map(|(symbol, &(code, len))| ((len, code), symbol as u16))
+            .collect()
+    });
+    let mut decoded = Vec::with_capacity(raw.len() * 8 / 5);
+    let (mut code, mut len) = (0u32, 0u8);
+    for byte in raw {
+        for shift in (0..8).rev() {
+            code = code << 1 | (byte >> shift & 1) as u32;
+            len += 1;
+            match codes.get(&(len, code)) {
+                Some(256) => bail!("EOS in Huffman coded string"),
+                Some(&symbol) => {
+                    decoded.push(symbol as u8);
+                    (code, len) = (0, 0);
+                }
+                None if len >= 30 => bail!("invalid Huffman code"),
+                None => {}
+            }
+        }
+    }
+    // Only up to 7 bits of EOS (all ones) may pad the last byte.
+    if len > 7 || code != (1 << len) - 1 {
+        bail!("invalid Huffman padding");
+    }
+    Ok(decoded)
+}
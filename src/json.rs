@@ -0,0 +1,583 @@
+//! JSON (RFC 8259) values, read from request bodies with `Request::json`
+//! and written to responses with `Response::json`.
+//!
+//! Types read from a body implement `FromJson`, usually by pulling their
+//! fields out of an object with `Value::field`; types written out implement
+//! `ToJson`, usually by building a `Value::Object`.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::header_map::HeaderMap;
+use crate::response::{HttpCode, Response};
+
+/// How deeply arrays and objects may nest before a document is refused.
+const MAX_DEPTH: usize = 128;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    /// Members in document order. A repeated name keeps its first value.
+    Object(Vec<(String, Value)>),
+}
+
+/// Why a request body couldn't be read as JSON.
+#[derive(Debug, thiserror::Error)]
+pub enum JsonError {
+    #[error("expected application/json, got {0:?}")]
+    UnsupportedMediaType(String),
+    #[error("invalid JSON at byte {offset}: {message}")]
+    Syntax {
+        offset: usize,
+        message: &'static str,
+    },
+    #[error("{0}")]
+    Shape(String),
+}
+
+/// A value JSON has no way to write, such as a NaN or infinite number.
+#[derive(Debug, thiserror::Error)]
+#[error("can't write {0} as JSON")]
+pub struct NotSerializable(pub String);
+
+impl JsonError {
+    /// The status to answer with: 415 for a body that isn't JSON, 400 for
+    /// one that doesn't parse or doesn't fit.
+    pub fn code(&self) -> HttpCode {
+        match self {
+            JsonError::UnsupportedMediaType(_) => HttpCode::UnsupportedMediaType,
+            JsonError::Syntax { .. } | JsonError::Shape(_) => HttpCode::BadRequest,
+        }
+    }
+}
+
+impl From<JsonError> for Response {
+    fn from(err: JsonError) -> Self {
+        let message = err.to_string();
+        let headers = HeaderMap::from([
+            (String::from("Content-Length"), message.len().to_string()),
+            (String::from("Content-Type"), String::from("text/plain")),
+        ]);
+        Response {
+            code: err.code(),
+            content: Some(message.into_bytes().into()),
+            headers: Some(headers),
+        }
+    }
+}
+
+impl Value {
+    /// Parses a whole document; anything but whitespace after the value is
+    /// an error.
+    pub fn parse(text: &str) -> Result<Value, JsonError> {
+        let mut parser = Parser {
+            bytes: text.as_bytes(),
+            pos: 0,
+        };
+        let value = parser.value(0)?;
+        parser.skip_whitespace();
+        if parser.pos < parser.bytes.len() {
+            return Err(parser.error("trailing characters"));
+        }
+        Ok(value)
+    }
+
+    /// The member `name` of an object.
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        match self {
+            Value::Object(members) => members
+                .iter()
+                .find(|(member, _)| member == name)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    /// Takes the member `name` out of an object as a `T`. A missing member
+    /// reads as `null`, so it only fits an `Option`.
+    pub fn field<T: FromJson>(&mut self, name: &str) -> Result<T, JsonError> {
+        let Value::Object(members) = self else {
+            return Err(JsonError::Shape(format!(
+                "expected an object with {:?}",
+                name
+            )));
+        };
+        let value = match members.iter().position(|(member, _)| member == name) {
+            Some(index) => members.remove(index).1,
+            None => Value::Null,
+        };
+        T::from_json(value).map_err(|err| match err {
+            JsonError::Shape(message) => JsonError::Shape(format!("{}: {}", name, message)),
+            err => err,
+        })
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            Value::Null => "null",
+            Value::Bool(_) => "a boolean",
+            Value::Number(_) => "a number",
+            Value::String(_) => "a string",
+            Value::Array(_) => "an array",
+            Value::Object(_) => "an object",
+        }
+    }
+}
+
+impl Value {
+    /// Writes the value as compact JSON, refusing numbers JSON can't
+    /// represent rather than writing them as `null` like `Display` does.
+    pub fn serialize(&self) -> Result<String, NotSerializable> {
+        self.check_finite()?;
+        Ok(self.to_string())
+    }
+
+    fn check_finite(&self) -> Result<(), NotSerializable> {
+        match self {
+            Value::Number(n) if !n.is_finite() => Err(NotSerializable(n.to_string())),
+            Value::Array(items) => items.iter().try_for_each(Value::check_finite),
+            Value::Object(members) => members.iter().try_for_each(|(_, v)| v.check_finite()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Writes the value as compact JSON.
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Null => write!(f, "null"),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Number(n) if !n.is_finite() => write!(f, "null"),
+            Value::Number(n) => write!(f, "{}", n),
+            Value::String(s) => write!(f, "\"{}\"", escape(s)),
+            Value::Array(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+            Value::Object(members) => {
+                write!(f, "{{")?;
+                for (i, (name, value)) in members.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "\"{}\":{}", escape(name), value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+/// Escapes `value` for use inside a JSON string.
+pub fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Types that can be read from a JSON value.
+pub trait FromJson: Sized {
+    fn from_json(value: Value) -> Result<Self, JsonError>;
+}
+
+fn mismatch<T>(expected: &str, value: &Value) -> Result<T, JsonError> {
+    Err(JsonError::Shape(format!(
+        "expected {}, got {}",
+        expected,
+        value.kind()
+    )))
+}
+
+impl FromJson for Value {
+    fn from_json(value: Value) -> Result<Self, JsonError> {
+        Ok(value)
+    }
+}
+
+impl FromJson for bool {
+    fn from_json(value: Value) -> Result<Self, JsonError> {
+        match value {
+            Value::Bool(b) => Ok(b),
+            value => mismatch("a boolean", &value),
+        }
+    }
+}
+
+impl FromJson for String {
+    fn from_json(value: Value) -> Result<Self, JsonError> {
+        match value {
+            Value::String(s) => Ok(s),
+            value => mismatch("a string", &value),
+        }
+    }
+}
+
+impl FromJson for f64 {
+    fn from_json(value: Value) -> Result<Self, JsonError> {
+        match value {
+            Value::Number(n) => Ok(n),
+            value => mismatch("a number", &value),
+        }
+    }
+}
+
+impl FromJson for i64 {
+    fn from_json(value: Value) -> Result<Self, JsonError> {
+        match value {
+            // Beyond 2^53 an f64 no longer holds every integer exactly.
+            Value::Number(n) if n.fract() == 0.0 && n.abs() <= 9007199254740992.0 => Ok(n as i64),
+            value => mismatch("an integer", &value),
+        }
+    }
+}
+
+impl FromJson for u64 {
+    fn from_json(value: Value) -> Result<Self, JsonError> {
+        match i64::from_json(value)? {
+            n if n >= 0 => Ok(n as u64),
+            _ => Err(JsonError::Shape(String::from(
+                "expected a non-negative integer",
+            ))),
+        }
+    }
+}
+
+impl<T: FromJson> FromJson for Option<T> {
+    fn from_json(value: Value) -> Result<Self, JsonError> {
+        match value {
+            Value::Null => Ok(None),
+            value => T::from_json(value).map(Some),
+        }
+    }
+}
+
+impl<T: FromJson> FromJson for Vec<T> {
+    fn from_json(value: Value) -> Result<Self, JsonError> {
+        match value {
+            Value::Array(items) => items.into_iter().map(T::from_json).collect(),
+            value => mismatch("an array", &value),
+        }
+    }
+}
+
+impl<T: FromJson> FromJson for HashMap<String, T> {
+    fn from_json(value: Value) -> Result<Self, JsonError> {
+        match value {
+            Value::Object(members) => members
+                .into_iter()
+                .map(|(name, value)| Ok((name, T::from_json(value)?)))
+                .collect(),
+            value => mismatch("an object", &value),
+        }
+    }
+}
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &'static str) -> JsonError {
+        JsonError::Syntax {
+            offset: self.pos,
+            message,
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.bytes.get(self.pos), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+            self.pos += 1;
+        }
+    }
+
+    fn eat(&mut self, expected: u8) -> bool {
+        self.skip_whitespace();
+        let found = self.bytes.get(self.pos) == Some(&expected);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Value, JsonError> {
+        if depth > MAX_DEPTH {
+            return Err(self.error("nested too deeply"));
+        }
+        self.skip_whitespace();
+        match self.bytes.get(self.pos) {
+            Some(b'{') => self.object(depth),
+            Some(b'[') => self.array(depth),
+            Some(b'"') => self.string().map(Value::String),
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            Some(_) => {
+                for (word, value) in [
+                    ("null", Value::Null),
+                    ("true", Value::Bool(true)),
+                    ("false", Value::Bool(false)),
+                ] {
+                    if self.bytes[self.pos..].starts_with(word.as_bytes()) {
+                        self.pos += word.len();
+                        return Ok(value);
+                    }
+                }
+                Err(self.error("expected a value"))
+            }
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    fn object(&mut self, depth: usize) -> Result<Value, JsonError> {
+        self.pos += 1;
+        let mut members: Vec<(String, Value)> = Vec::new();
+        if self.eat(b'}') {
+            return Ok(Value::Object(members));
+        }
+        loop {
+            self.skip_whitespace();
+            if self.bytes.get(self.pos) != Some(&b'"') {
+                return Err(self.error("expected a member name"));
+            }
+            let name = self.string()?;
+            if !self.eat(b':') {
+                return Err(self.error("expected ':'"));
+            }
+            let value = self.value(depth + 1)?;
+            if !members.iter().any(|(member, _)| *member == name) {
+                members.push((name, value));
+            }
+            if self.eat(b'}') {
+                return Ok(Value::Object(members));
+            }
+            if !self.eat(b',') {
+                return Err(self.error("expected ',' or '}'"));
+            }
+        }
+    }
+
+    fn array(&mut self, depth: usize) -> Result<Value, JsonError> {
+        self.pos += 1;
+        let mut items = Vec::new();
+        if self.eat(b']') {
+            return Ok(Value::Array(items));
+        }
+        loop {
+            items.push(self.value(depth + 1)?);
+            if self.eat(b']') {
+                return Ok(Value::Array(items));
+            }
+            if !self.eat(b',') {
+                return Err(self.error("expected ',' or ']'"));
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Value, JsonError> {
+        let start = self.pos;
+        let digits = |parser: &mut Self| {
+            let from = parser.pos;
+            while parser.bytes.get(parser.pos).is_some_and(u8::is_ascii_digit) {
+                parser.pos += 1;
+            }
+            parser.pos > from
+        };
+        if self.bytes[self.pos] == b'-' {
+            self.pos += 1;
+        }
+        if self.bytes.get(self.pos) == Some(&b'0') {
+            self.pos += 1;
+            if self.bytes.get(self.pos).is_some_and(u8::is_ascii_digit) {
+                return Err(self.error("leading zero"));
+            }
+        } else if !digits(self) {
+            return Err(self.error("invalid number"));
+        }
+        if self.bytes.get(self.pos) == Some(&b'.') {
+            self.pos += 1;
+            if !digits(self) {
+                return Err(self.error("invalid number"));
+            }
+        }
+        if matches!(self.bytes.get(self.pos), Some(b'e' | b'E')) {
+            self.pos += 1;
+            if matches!(self.bytes.get(self.pos), Some(b'+' | b'-')) {
+                self.pos += 1;
+            }
+            if !digits(self) {
+                return Err(self.error("invalid number"));
+            }
+        }
+        // Only ASCII digits, signs, '.' and 'e' were consumed.
+        let text = std::str::from_utf8(&self.bytes[start..self.pos]).unwrap_or_default();
+        text.parse()
+            .map(Value::Number)
+            .map_err(|_| self.error("invalid number"))
+    }
+
+    fn string(&mut self) -> Result<String, JsonError> {
+        self.pos += 1;
+        let mut out = Vec::new();
+        loop {
+            let Some(&b) = self.bytes.get(self.pos) else {
+                return Err(self.error("unterminated string"));
+            };
+            self.pos += 1;
+            match b {
+                b'"' => break,
+                b'\\' => {
+                    let Some(&escape) = self.bytes.get(self.pos) else {
+                        return Err(self.error("unterminated string"));
+                    };
+                    self.pos += 1;
+                    let c = match escape {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => self.unicode_escape()?,
+                        _ => return Err(self.error("invalid escape")),
+                    };
+                    let mut encoded = [0; 4];
+                    out.extend_from_slice(c.encode_utf8(&mut encoded).as_bytes());
+                }
+                b if b < 0x20 => return Err(self.error("control character in string")),
+                b => out.push(b),
+            }
+        }
+        // The input was a &str and escapes were re-encoded, so this holds.
+        String::from_utf8(out).map_err(|_| self.error("invalid UTF-8"))
+    }
+
+    /// Reads the hex digits of a `\u` escape, pairing surrogates.
+    fn unicode_escape(&mut self) -> Result<char, JsonError> {
+        let first = self.hex4()?;
+        let code = if (0xD800..0xDC00).contains(&first) {
+            if !self.bytes[self.pos..].starts_with(b"\\u") {
+                return Err(self.error("unpaired surrogate"));
+            }
+            self.pos += 2;
+            let second = self.hex4()?;
+            if !(0xDC00..0xE000).contains(&second) {
+                return Err(self.error("unpaired surrogate"));
+            }
+            0x10000 + ((first - 0xD800) << 10) + (second - 0xDC00)
+        } else {
+            first
+        };
+        char::from_u32(code).ok_or_else(|| self.error("unpaired surrogate"))
+    }
+
+    fn hex4(&mut self) -> Result<u32, JsonError> {
+        let digits = self
+            .bytes
+            .get(self.pos..self.pos + 4)
+            .filter(|digits| digits.iter().all(u8::is_ascii_hexdigit))
+            .ok_or_else(|| self.error("invalid \\u escape"))?;
+        self.pos += 4;
+        // Four ASCII hex digits always parse.
+        Ok(u32::from_str_radix(std::str::from_utf8(digits).unwrap_or("0"), 16).unwrap_or(0))
+    }
+}
+
+/// Types that can be written as JSON.
+pub trait ToJson {
+    fn to_json(&self) -> Value;
+}
+
+impl ToJson for Value {
+    fn to_json(&self) -> Value {
+        self.clone()
+    }
+}
+
+impl ToJson for bool {
+    fn to_json(&self) -> Value {
+        Value::Bool(*self)
+    }
+}
+
+impl ToJson for str {
+    fn to_json(&self) -> Value {
+        Value::String(self.to_owned())
+    }
+}
+
+impl ToJson for String {
+    fn to_json(&self) -> Value {
+        Value::String(self.clone())
+    }
+}
+
+macro_rules! number_to_json {
+    ($($t:ty),*) => {
+        $(impl ToJson for $t {
+            fn to_json(&self) -> Value {
+                Value::Number(*self as f64)
+            }
+        })*
+    };
+}
+
+number_to_json!(f64, f32, i64, i32, u64, u32, usize);
+
+impl<T: ToJson + ?Sized> ToJson for &T {
+    fn to_json(&self) -> Value {
+        (**self).to_json()
+    }
+}
+
+impl<T: ToJson> ToJson for Option<T> {
+    fn to_json(&self) -> Value {
+        self.as_ref().map_or(Value::Null, ToJson::to_json)
+    }
+}
+
+impl<T: ToJson> ToJson for [T] {
+    fn to_json(&self) -> Value {
+        Value::Array(self.iter().map(ToJson::to_json).collect())
+    }
+}
+
+impl<T: ToJson> ToJson for Vec<T> {
+    fn to_json(&self) -> Value {
+        self.as_slice().to_json()
+    }
+}
+
+/// Members are written sorted by name, so the output doesn't depend on the
+/// map's iteration order.
+impl<T: ToJson> ToJson for HashMap<String, T> {
+    fn to_json(&self) -> Value {
+        let mut members = self
+            .iter()
+            .map(|(name, value)| (name.clone(), value.to_json()))
+            .collect::<Vec<_>>();
+        members.sort_by(|(a, _), (b, _)| a.cmp(b));
+        Value::Object(members)
+    }
+}
@@ -0,0 +1,46 @@
+pub mod admin;
+pub mod admission;
+pub mod assets;
+pub mod auth;
+pub mod bench;
+pub mod body;
+pub mod cache;
+pub mod canonical;
+pub mod cgi;
+pub mod chaos;
+pub mod clock;
+pub mod conditional;
+pub mod config;
+pub mod control;
+pub mod cookie;
+pub mod date;
+pub mod dav;
+pub mod digest;
+pub mod forwarded;
+pub mod h2;
+pub mod handlers;
+pub mod hash;
+pub mod header_map;
+pub mod headers;
+pub mod hpack;
+pub mod json;
+pub mod maintenance;
+pub mod media_type;
+pub mod mirror;
+pub mod negotiate;
+pub mod parser;
+pub mod pattern;
+pub mod pool;
+pub mod range;
+pub mod record;
+pub mod regex;
+pub mod request;
+pub mod response;
+pub mod rewrite;
+pub mod routes;
+pub mod server;
+pub mod systemd;
+pub mod tunnel;
+pub mod typed_header;
+pub mod upgrade;
+pub mod vhost;
@@ -0,0 +1,73 @@
+use std::sync::Arc;
+
+use crate::control::Control;
+use crate::header_map::HeaderMap;
+use crate::request::Request;
+use crate::response::{HttpCode, Response};
+use crate::routes::{Middleware, Next};
+
+/// Answers `503 Service Unavailable` with `Retry-After` while maintenance
+/// mode is on, except for exempt path prefixes such as health checks.
+pub struct Maintenance {
+    control: Arc<Control>,
+    exempt: Vec<String>,
+    retry_after: u64,
+    page: Option<Vec<u8>>,
+}
+
+impl Maintenance {
+    pub fn new(
+        control: Arc<Control>,
+        exempt: Vec<String>,
+        retry_after: u64,
+        page: Option<Vec<u8>>,
+    ) -> Self {
+        Maintenance {
+            control,
+            exempt,
+            retry_after,
+            page,
+        }
+    }
+
+    /// The 503 for `req`, unless maintenance is off or its path exempt.
+    fn refusal(&self, req: &Request) -> Option<Response> {
+        let exempt = self
+            .exempt
+            .iter()
+            .any(|prefix| req.path.starts_with(prefix.as_str()));
+        if exempt || !self.control.in_maintenance() {
+            return None;
+        }
+
+        let mut headers =
+            HeaderMap::from([(String::from("Retry-After"), self.retry_after.to_string())]);
+        let content = self.page.clone().map(|page| {
+            headers.insert(String::from("Content-Type"), String::from("text/html"));
+            headers.insert(String::from("Content-Length"), page.len().to_string());
+            page.into()
+        });
+        Some(Response {
+            code: HttpCode::ServiceUnavailable,
+            content,
+            headers: Some(headers),
+        })
+    }
+}
+
+impl Middleware for Maintenance {
+    fn handle(&self, req: Request, next: Next<'_>) -> Response {
+        match self.refusal(&req) {
+            Some(refusal) => refusal,
+            None => next(req),
+        }
+    }
+
+    fn expect(&self, req: &Request) -> Option<Response> {
+        self.refusal(req)
+    }
+
+    fn connect(&self, req: &Request) -> Option<Response> {
+        self.refusal(req)
+    }
+}
@@ -0,0 +1,146 @@
+//! Media types with their parameters, as in `Content-Type` (RFC 9110 §8.3.1).
+
+use std::fmt;
+use std::str::FromStr;
+
+/// A `Content-Type` value that isn't `type/subtype` followed by
+/// `;name=value` parameters.
+#[derive(Debug, thiserror::Error)]
+#[error("invalid media type {0:?}")]
+pub struct InvalidMediaType(pub String);
+
+/// A media type such as `text/html; charset=utf-8`. The type, subtype and
+/// parameter names are lowercased; parameter values keep their case, with
+/// quoting removed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaType {
+    pub kind: String,
+    pub subtype: String,
+    pub params: Vec<(String, String)>,
+}
+
+impl MediaType {
+    /// Whether this is `kind/subtype`, ignoring case and parameters.
+    pub fn is(&self, kind: &str, subtype: &str) -> bool {
+        self.kind.eq_ignore_ascii_case(kind) && self.subtype.eq_ignore_ascii_case(subtype)
+    }
+
+    /// The structured syntax suffix, e.g. `json` for `application/ld+json`.
+    pub fn suffix(&self) -> Option<&str> {
+        self.subtype.rsplit_once('+').map(|(_, suffix)| suffix)
+    }
+
+    /// The type and subtype without parameters, e.g. `text/html`.
+    pub fn essence(&self) -> String {
+        format!("{}/{}", self.kind, self.subtype)
+    }
+
+    /// The value of the parameter `name`, matched case-insensitively.
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(param, _)| param.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn charset(&self) -> Option<&str> {
+        self.param("charset")
+    }
+
+    pub fn boundary(&self) -> Option<&str> {
+        self.param("boundary")
+    }
+}
+
+fn is_token(value: &str) -> bool {
+    !value.is_empty()
+        && value
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b))
+}
+
+impl FromStr for MediaType {
+    type Err = InvalidMediaType;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || InvalidMediaType(value.to_owned());
+        let (essence, mut rest) = match value.find(';') {
+            Some(pos) => (&value[..pos], &value[pos..]),
+            None => (value, ""),
+        };
+        let (kind, subtype) = essence.trim().split_once('/').ok_or_else(invalid)?;
+        if !is_token(kind) || !is_token(subtype) {
+            return Err(invalid());
+        }
+        let mut params = Vec::new();
+        loop {
+            rest = rest.trim_start();
+            let Some(next) = rest.strip_prefix(';') else {
+                break;
+            };
+            let next = next.trim_start();
+            // Empty parameters (`text/plain;;charset=utf-8`) are tolerated.
+            if next.is_empty() || next.starts_with(';') {
+                rest = next;
+                continue;
+            }
+            let (name, after) = next.split_once('=').ok_or_else(invalid)?;
+            if !is_token(name) {
+                return Err(invalid());
+            }
+            let (param, after) = match after.strip_prefix('"') {
+                Some(quoted) => unquote(quoted).ok_or_else(invalid)?,
+                None => {
+                    let end = after.find(';').unwrap_or(after.len());
+                    let token = after[..end].trim_end();
+                    if !is_token(token) {
+                        return Err(invalid());
+                    }
+                    (token.to_owned(), &after[end..])
+                }
+            };
+            params.push((name.to_ascii_lowercase(), param));
+            rest = after;
+        }
+        if !rest.is_empty() {
+            return Err(invalid());
+        }
+        Ok(MediaType {
+            kind: kind.to_ascii_lowercase(),
+            subtype: subtype.to_ascii_lowercase(),
+            params,
+        })
+    }
+}
+
+/// Reads a quoted-string whose opening quote was already taken, returning
+/// its contents and what follows the closing quote.
+fn unquote(value: &str) -> Option<(String, &str)> {
+    let mut out = String::new();
+    let mut chars = value.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Some((out, &value[i + 1..])),
+            '\\' => out.push(chars.next()?.1),
+            c => out.push(c),
+        }
+    }
+    None
+}
+
+/// Writes the media type back out, quoting parameter values that aren't
+/// tokens.
+impl fmt::Display for MediaType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.kind, self.subtype)?;
+        for (name, value) in &self.params {
+            if is_token(value) {
+                write!(f, "; {}={}", name, value)?;
+            } else {
+                let escaped = value.replace('\\', "\\\\").replace('"', "\\\"");
+                write!(f, "; {}=\"{}\"", name, escaped)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,70 @@
+//! Shadow traffic: a sampled copy of incoming requests is replayed against
+//! a secondary upstream. Mirrored requests never delay or affect the
+//! response the client gets.
+
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+    sync::Semaphore,
+};
+
+use crate::chaos::roll;
+use crate::config::Config;
+use crate::record;
+use crate::request::Request;
+
+/// Mirrored requests allowed in flight at once; beyond that they are
+/// skipped rather than queued.
+const MAX_IN_FLIGHT: usize = 64;
+/// How long a mirrored request may take before it is abandoned.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+pub struct Mirror {
+    address: String,
+    rate: f64,
+    in_flight: Arc<Semaphore>,
+}
+
+impl Mirror {
+    /// Returns `None` unless a mirror upstream is configured.
+    pub fn new(config: &Config) -> Option<Self> {
+        let address = config.mirror.clone()?;
+        Some(Mirror {
+            address,
+            rate: config.mirror_rate,
+            in_flight: Arc::new(Semaphore::new(MAX_IN_FLIGHT)),
+        })
+    }
+
+    /// Sends a copy of `req` in the background if it is sampled, reading and
+    /// discarding whatever the upstream answers.
+    pub fn send(&self, req: &Request) {
+        if roll() >= self.rate {
+            return;
+        }
+        let Ok(permit) = self.in_flight.clone().try_acquire_owned() else {
+            println!("mirror: too many requests in flight, skipping");
+            return;
+        };
+        let address = self.address.clone();
+        let request = record::request_bytes(req);
+        tokio::spawn(async move {
+            let exchange = async {
+                let mut upstream = TcpStream::connect(&address).await?;
+                upstream.write_all(&request).await?;
+                upstream.shutdown().await?;
+                let mut sink = [0; 4096];
+                while upstream.read(&mut sink).await? > 0 {}
+                Ok::<_, std::io::Error>(())
+            };
+            match tokio::time::timeout(TIMEOUT, exchange).await {
+                Ok(Ok(())) => {}
+                Ok(Err(err)) => println!("mirror: error sending to {}: {}", address, err),
+                Err(_) => println!("mirror: {} timed out", address),
+            }
+            drop(permit);
+        });
+    }
+}
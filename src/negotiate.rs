@@ -0,0 +1,186 @@
+/// Picks the offer a client prefers according to its `Accept` header.
+///
+/// Each offer is rated with the quality of the most specific media range that
+/// matches it; the highest rated offer wins and ties go to the earlier offer.
+/// Without an `Accept` header the first offer is returned, and `None` means
+/// the client refused every offer.
+pub fn negotiate<'a>(accept: Option<&str>, offers: &[&'a str]) -> Option<&'a str> {
+    match accept {
+        Some(accept) => best_offer(&parse_accept([accept]), offers),
+        None => offers.first().copied(),
+    }
+}
+
+/// The offer rated highest by `ranges`, as in `negotiate`.
+pub(crate) fn best_offer<'a>(ranges: &[MediaRange], offers: &[&'a str]) -> Option<&'a str> {
+    pick(offers, |offer| {
+        ranges
+            .iter()
+            .filter_map(|range| range.specificity(offer).map(|s| (s, range.quality)))
+            .max_by_key(|(specificity, _)| *specificity)
+            .map_or(0.0, |(_, quality)| quality)
+    })
+}
+
+/// The offer `rate` gives the highest quality, the earlier one on a tie.
+/// Offers rated 0 are never picked.
+fn pick<'a>(offers: &[&'a str], rate: impl Fn(&str) -> f32) -> Option<&'a str> {
+    let mut best: Option<(&str, f32)> = None;
+    for offer in offers {
+        let quality = rate(offer);
+        if quality > 0.0 && !matches!(best, Some((_, q)) if q >= quality) {
+            best = Some((offer, quality));
+        }
+    }
+    best.map(|(offer, _)| offer)
+}
+
+/// One media range of an `Accept` header, such as `text/*;q=0.5`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaRange<'a> {
+    pub kind: &'a str,
+    pub subtype: &'a str,
+    /// From the `q` parameter, 1 when absent.
+    pub quality: f32,
+}
+
+impl MediaRange<'_> {
+    /// How specifically this range matches `offer`, or `None` if it doesn't.
+    pub fn specificity(&self, offer: &str) -> Option<u8> {
+        let (kind, subtype) = offer.split_once('/')?;
+        match (self.kind, self.subtype) {
+            ("*", "*") => Some(0),
+            (k, "*") if k.eq_ignore_ascii_case(kind) => Some(1),
+            (k, s) if k.eq_ignore_ascii_case(kind) && s.eq_ignore_ascii_case(subtype) => Some(2),
+            _ => None,
+        }
+    }
+}
+
+/// The media ranges of one or more `Accept` values, most preferred first:
+/// by quality, then by how specific they are, then in the order given.
+/// Malformed ranges are skipped.
+pub fn parse_accept<'a>(values: impl IntoIterator<Item = &'a str>) -> Vec<MediaRange<'a>> {
+    let mut ranges = values
+        .into_iter()
+        .flat_map(|value| value.split(','))
+        .filter_map(parse_range)
+        .collect::<Vec<_>>();
+    let specificity = |range: &MediaRange| match (range.kind, range.subtype) {
+        ("*", _) => 0,
+        (_, "*") => 1,
+        _ => 2,
+    };
+    ranges.sort_by(|a, b| {
+        b.quality
+            .total_cmp(&a.quality)
+            .then(specificity(b).cmp(&specificity(a)))
+    });
+    ranges
+}
+
+fn parse_range(value: &str) -> Option<MediaRange<'_>> {
+    let mut params = value.split(';');
+    let (kind, subtype) = params.next()?.trim().split_once('/')?;
+    let quality = quality(params)?;
+    Some(MediaRange {
+        kind: kind.trim(),
+        subtype: subtype.trim(),
+        quality,
+    })
+}
+
+/// The quality set by a `q` among `params`: 1 without one, `None` if it
+/// doesn't parse.
+fn quality<'a>(params: impl Iterator<Item = &'a str>) -> Option<f32> {
+    let mut quality = 1.0;
+    for param in params {
+        if let Some((name, value)) = param.split_once('=') {
+            if name.trim().eq_ignore_ascii_case("q") {
+                quality = value.trim().parse::<f32>().ok()?.clamp(0.0, 1.0);
+            }
+        }
+    }
+    Some(quality)
+}
+
+/// An entry of a list like `Accept-Encoding` or `Accept-Language`: a
+/// token with its quality.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Weighted<'a> {
+    pub value: &'a str,
+    pub quality: f32,
+}
+
+/// Parses one or more `token;q=x` lists, most preferred first, keeping the
+/// given order among equals. Malformed entries are skipped.
+pub fn parse_weighted<'a>(values: impl IntoIterator<Item = &'a str>) -> Vec<Weighted<'a>> {
+    let mut entries = values
+        .into_iter()
+        .flat_map(|value| value.split(','))
+        .filter_map(|entry| {
+            let mut params = entry.split(';');
+            let value = params.next()?.trim();
+            let quality = quality(params)?;
+            (!value.is_empty()).then_some(Weighted { value, quality })
+        })
+        .collect::<Vec<_>>();
+    entries.sort_by(|a, b| b.quality.total_cmp(&a.quality));
+    entries
+}
+
+/// How much a client listing `accepted` content codings wants `coding`
+/// (RFC 9110 §12.5.3). `identity` is acceptable unless excluded, by name
+/// or through `*`; anything else has to be listed.
+pub fn encoding_quality(accepted: &[Weighted], coding: &str) -> f32 {
+    let listed = |name: &str| {
+        accepted
+            .iter()
+            .find(|entry| entry.value.eq_ignore_ascii_case(name))
+            .map(|entry| entry.quality)
+    };
+    listed(coding)
+        .or_else(|| listed("*"))
+        .unwrap_or(if coding.eq_ignore_ascii_case("identity") {
+            1.0
+        } else {
+            0.0
+        })
+}
+
+/// The content coding among `offers` a client listing `accepted` prefers;
+/// `None` if it refuses all of them.
+pub fn negotiate_encoding<'a>(accepted: &[Weighted], offers: &[&'a str]) -> Option<&'a str> {
+    pick(offers, |offer| encoding_quality(accepted, offer))
+}
+
+/// How much a client listing `accepted` language ranges wants content in
+/// `tag`. A range matches the tag itself and any tag it is a prefix of up
+/// to a `-` (RFC 4647 §3.3.1), so `en` covers `en-GB`; the longest match
+/// counts and `*` matches anything.
+pub fn language_quality(accepted: &[Weighted], tag: &str) -> f32 {
+    accepted
+        .iter()
+        .filter(|entry| {
+            let range = entry.value;
+            range == "*"
+                || tag.len() >= range.len()
+                    && tag.is_char_boundary(range.len())
+                    && tag[..range.len()].eq_ignore_ascii_case(range)
+                    && matches!(tag.as_bytes().get(range.len()), None | Some(b'-'))
+        })
+        .max_by_key(|entry| {
+            if entry.value == "*" {
+                0
+            } else {
+                entry.value.len()
+            }
+        })
+        .map_or(0.0, |entry| entry.quality)
+}
+
+/// The language tag among `offers` a client listing `accepted` prefers;
+/// `None` if it wants none of them.
+pub fn negotiate_language<'a>(accepted: &[Weighted], offers: &[&'a str]) -> Option<&'a str> {
+    pick(offers, |offer| language_quality(accepted, offer))
+}
@@ -0,0 +1,158 @@
+//! Finding a request head in the bytes read so far.
+//!
+//! `parse_head` works on the read buffer as it fills: it either finds a
+//! whole head and returns its parts borrowed from the buffer, or reports
+//! that more has to be read. Nothing is copied until `Request` takes the
+//! parts over.
+
+use anyhow::{bail, Result};
+
+use crate::request::{InvalidHeader, InvalidRequestLine, TlsHandshake};
+
+/// The longest method looked for before a request line that hasn't fully
+/// arrived is taken for garbage.
+const MAX_METHOD_LEN: usize = 32;
+
+/// The result of parsing a buffer that may hold only part of a head.
+#[derive(Debug)]
+pub enum Parsed<T> {
+    /// The head, and how many bytes of the buffer it took up, including the
+    /// empty line ending it.
+    Complete(T, usize),
+    /// The head isn't all there yet; read more and parse again.
+    Incomplete,
+}
+
+/// A request head as laid out in the buffer it was parsed from. Nothing
+/// is validated beyond the framing of its lines, the request line's three
+/// tokens and the field names.
+#[derive(Debug)]
+pub struct RawHead<'a> {
+    pub method: &'a str,
+    pub target: &'a str,
+    pub version: &'a str,
+    /// Field lines split at their first `:`, with the value trimmed.
+    pub fields: Vec<(&'a str, &'a str)>,
+    /// The request line and field lines, without the empty line ending
+    /// them.
+    pub text: &'a str,
+}
+
+/// Parses the head at the start of `buf`. Lines end with CRLF or a bare
+/// LF, and empty lines before the request line are skipped (RFC 9112
+/// §2.2).
+pub fn parse_head(buf: &[u8]) -> Result<Parsed<RawHead<'_>>> {
+    let mut start = 0;
+    while let Some(rest) = buf.get(start..) {
+        if rest.starts_with(b"\r\n") {
+            start += 2;
+        } else if rest.starts_with(b"\n") {
+            start += 1;
+        } else {
+            break;
+        }
+    }
+    // A TLS record starts with the handshake type and a 3.x version.
+    match &buf[start..] {
+        [0x16, 0x03, ..] => bail!(TlsHandshake),
+        [0x16] => return Ok(Parsed::Incomplete),
+        _ => {}
+    }
+    let Some((line, mut pos)) = next_line(buf, start) else {
+        partial_request_line(&buf[start..])?;
+        return Ok(Parsed::Incomplete);
+    };
+    let mut text_end = start + line.len();
+    let (method, target, version) = request_line(line)?;
+
+    let mut fields = Vec::new();
+    loop {
+        let Some((line, next)) = next_line(buf, pos) else {
+            return Ok(Parsed::Incomplete);
+        };
+        if line.is_empty() {
+            // Every line so far was checked to be UTF-8.
+            let text = std::str::from_utf8(&buf[start..text_end])?;
+            let head = RawHead {
+                method,
+                target,
+                version,
+                fields,
+                text,
+            };
+            return Ok(Parsed::Complete(head, next));
+        }
+        let invalid = || InvalidHeader(String::from_utf8_lossy(line).into_owned());
+        // obs-fold continuation lines (RFC 9112 §5.2) and stray CRs are
+        // refused rather than interpreted.
+        if line.starts_with(b" ") || line.starts_with(b"\t") || line.contains(&b'\r') {
+            bail!(invalid());
+        }
+        let line = std::str::from_utf8(line).map_err(|_| invalid())?;
+        // A name that isn't a token, such as one with whitespace before
+        // the colon, could be read differently by another parser.
+        match line.split_once(':') {
+            Some((name, value)) if !name.is_empty() && name.bytes().all(is_tchar) => {
+                fields.push((name, value.trim()))
+            }
+            _ => bail!(invalid()),
+        }
+        text_end = pos + line.len();
+        pos = next;
+    }
+}
+
+/// Splits a request line into method, target and version, which must be
+/// separated by exactly one space each (RFC 9112 §3).
+fn request_line(line: &[u8]) -> Result<(&str, &str, &str)> {
+    let invalid = |reason| InvalidRequestLine {
+        line: String::from_utf8_lossy(line).into_owned(),
+        reason,
+    };
+    let Ok(text) = std::str::from_utf8(line) else {
+        bail!(invalid("not UTF-8"));
+    };
+    if text.bytes().any(|b| b.is_ascii_control()) {
+        bail!(invalid("control character or tab"));
+    }
+    let parts = text.split(' ').collect::<Vec<&str>>();
+    if parts.iter().any(|part| part.is_empty()) {
+        bail!(invalid("extra whitespace"));
+    }
+    match parts[..] {
+        [method, target, version] => Ok((method, target, version)),
+        [_] => bail!(invalid("missing request target")),
+        [_, _] => bail!(invalid("missing HTTP version")),
+        _ => bail!(invalid("space in the request target")),
+    }
+}
+
+/// Refuses the start of a request line that can't become a valid one,
+/// rather than buffering garbage until it fills `max_header_size`.
+fn partial_request_line(partial: &[u8]) -> Result<()> {
+    let partial = partial.strip_suffix(b"\r").unwrap_or(partial);
+    let method = partial.split(|&b| b == b' ').next().unwrap_or_default();
+    let reason = if !method.iter().all(|&b| is_tchar(b)) {
+        "method isn't a token"
+    } else if method.len() > MAX_METHOD_LEN {
+        "method too long"
+    } else {
+        return Ok(());
+    };
+    let line = String::from_utf8_lossy(&partial[..partial.len().min(64)]).into_owned();
+    bail!(InvalidRequestLine { line, reason })
+}
+
+/// Whether `b` may appear in a token (RFC 9110 §5.6.2), such as a method
+/// or field name.
+pub(crate) fn is_tchar(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b)
+}
+
+/// The line starting at `pos`, without its line ending, and where the next
+/// one starts. `None` if the line hasn't fully arrived.
+fn next_line(buf: &[u8], pos: usize) -> Option<(&[u8], usize)> {
+    let len = buf.get(pos..)?.iter().position(|&b| b == b'\n')?;
+    let line = &buf[pos..pos + len];
+    Some((line.strip_suffix(b"\r").unwrap_or(line), pos + len + 1))
+}
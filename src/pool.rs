@@ -0,0 +1,72 @@
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Shared pool of fixed-size buffers used when streaming files to clients.
+///
+/// At most `max_buffers` buffers are checked out at once; callers wait for
+/// one to be returned instead of allocating, which bounds the memory used by
+/// concurrent downloads.
+pub struct BufferPool {
+    chunk_size: usize,
+    free: Mutex<Vec<Vec<u8>>>,
+    permits: Arc<Semaphore>,
+}
+
+impl BufferPool {
+    pub fn new(chunk_size: usize, max_buffers: usize) -> Self {
+        BufferPool {
+            chunk_size,
+            free: Mutex::new(Vec::with_capacity(max_buffers)),
+            permits: Arc::new(Semaphore::new(max_buffers)),
+        }
+    }
+
+    pub async fn get(self: &Arc<Self>) -> PooledBuf {
+        let permit = self
+            .permits
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("buffer pool semaphore is never closed");
+        let buf = self
+            .free
+            .lock()
+            .unwrap()
+            .pop()
+            .unwrap_or_else(|| vec![0; self.chunk_size]);
+        PooledBuf {
+            buf,
+            pool: self.clone(),
+            _permit: permit,
+        }
+    }
+}
+
+/// A buffer checked out of a `BufferPool`, returned to it on drop.
+pub struct PooledBuf {
+    buf: Vec<u8>,
+    pool: Arc<BufferPool>,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl Deref for PooledBuf {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.buf
+    }
+}
+
+impl DerefMut for PooledBuf {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        &mut self.buf
+    }
+}
+
+impl Drop for PooledBuf {
+    fn drop(&mut self) {
+        let buf = std::mem::take(&mut self.buf);
+        self.pool.free.lock().unwrap().push(buf);
+    }
+}
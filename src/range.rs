@@ -0,0 +1,105 @@
+//! Byte ranges requested with `Range` (RFC 9110 §14.2).
+
+/// A `Range` value that isn't a well-formed, sensible `bytes=` range set.
+#[derive(Debug, thiserror::Error)]
+#[error("invalid range {value:?}: {reason}")]
+pub struct InvalidRange {
+    pub value: String,
+    pub reason: &'static str,
+}
+
+/// One range of a `bytes=` range set. Offsets are inclusive, as on the
+/// wire.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteRange {
+    /// `first-last`.
+    Bounded { first: u64, last: u64 },
+    /// `first-`, up to the end of the representation.
+    From(u64),
+    /// `-len`, the last `len` bytes of the representation.
+    Suffix(u64),
+}
+
+impl ByteRange {
+    /// The first and last offset this range covers in a representation of
+    /// `len` bytes, clipped to its end. `None` if it covers none of it, in
+    /// which case the range isn't satisfiable.
+    pub fn resolve(self, len: u64) -> Option<(u64, u64)> {
+        let (first, last) = match self {
+            ByteRange::Bounded { first, last } => (first, last.min(len.checked_sub(1)?)),
+            ByteRange::From(first) => (first, len.checked_sub(1)?),
+            ByteRange::Suffix(0) => return None,
+            ByteRange::Suffix(n) => (len.saturating_sub(n), len.checked_sub(1)?),
+        };
+        (first <= last).then_some((first, last))
+    }
+
+    /// Where the range starts and ends, as far as that's known without the
+    /// length of the representation.
+    fn span(self) -> Option<(u64, u64)> {
+        match self {
+            ByteRange::Bounded { first, last } => Some((first, last)),
+            ByteRange::From(first) => Some((first, u64::MAX)),
+            ByteRange::Suffix(_) => None,
+        }
+    }
+}
+
+/// Parses a `Range` value such as `bytes=0-99,200-,-50`. Besides malformed
+/// syntax, units other than `bytes`, descending ranges like `5-2` and
+/// ranges that overlap are refused; a server answers those with the full
+/// representation. Suffix ranges aren't checked for overlap, as where they
+/// start depends on the length.
+pub fn parse_range(value: &str) -> Result<Vec<ByteRange>, InvalidRange> {
+    let invalid = |reason| InvalidRange {
+        value: value.to_string(),
+        reason,
+    };
+    let (unit, set) = value
+        .split_once('=')
+        .ok_or_else(|| invalid("missing unit"))?;
+    if !unit.trim().eq_ignore_ascii_case("bytes") {
+        return Err(invalid("unit other than bytes"));
+    }
+    let mut ranges = Vec::new();
+    for spec in set
+        .split(',')
+        .map(str::trim)
+        .filter(|spec| !spec.is_empty())
+    {
+        let (first, last) = spec.split_once('-').ok_or_else(|| invalid("missing -"))?;
+        let offset = |digits: &str| {
+            if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(invalid("offset isn't a number"));
+            }
+            digits
+                .parse::<u64>()
+                .map_err(|_| invalid("offset too large"))
+        };
+        let range = match (first, last) {
+            ("", last) => ByteRange::Suffix(offset(last)?),
+            (first, "") => ByteRange::From(offset(first)?),
+            (first, last) => {
+                let (first, last) = (offset(first)?, offset(last)?);
+                if first > last {
+                    return Err(invalid("descending range"));
+                }
+                ByteRange::Bounded { first, last }
+            }
+        };
+        ranges.push(range);
+    }
+    if ranges.is_empty() {
+        return Err(invalid("no ranges"));
+    }
+
+    let mut spans = ranges
+        .iter()
+        .filter_map(|range| range.span())
+        .collect::<Vec<_>>();
+    spans.sort_unstable();
+    if spans.windows(2).any(|pair| pair[0].1 >= pair[1].0) {
+        return Err(invalid("overlapping ranges"));
+    }
+    Ok(ranges)
+}
@@ -0,0 +1,226 @@
+//! Traffic recording and replay. With `--record DIR` every request and the
+//! bytes written back for it are saved side by side; `replay DIR` sends the
+//! recorded requests to a running server and diffs what comes back, which
+//! catches behavior changes when reworking the parser or router.
+
+use anyhow::{anyhow, Result};
+use std::fs;
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::task::{ready, Context, Poll};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::io::AsyncWrite;
+
+use crate::request::Request;
+
+const REPLAY_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub struct Recorder {
+    dir: PathBuf,
+}
+
+impl Recorder {
+    pub fn new(dir: &str) -> Result<Self> {
+        fs::create_dir_all(dir)?;
+        Ok(Recorder { dir: dir.into() })
+    }
+
+    /// Saves one exchange as `<name>.request` and `<name>.response`. Names
+    /// sort in the order the requests were received.
+    pub async fn save(&self, request: &[u8], response: &[u8]) {
+        static SEQUENCE: AtomicU64 = AtomicU64::new(0);
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let name = format!(
+            "{:013}-{:06}",
+            millis,
+            SEQUENCE.fetch_add(1, Ordering::Relaxed)
+        );
+        for (extension, data) in [("request", request), ("response", response)] {
+            let path = self.dir.join(format!("{}.{}", name, extension));
+            if let Err(err) = tokio::fs::write(&path, data).await {
+                println!("error recording {}: {}", path.display(), err);
+            }
+        }
+    }
+}
+
+/// The request as it came off the wire.
+pub fn request_bytes(req: &Request) -> Vec<u8> {
+    let mut data = format!("{}\r\n\r\n", req.head).into_bytes();
+    data.extend_from_slice(&req.body);
+    data
+}
+
+/// Passes writes through to `inner`, keeping a copy of everything written.
+pub struct Tee<'a, W> {
+    inner: &'a mut W,
+    pub written: Vec<u8>,
+}
+
+impl<'a, W> Tee<'a, W> {
+    pub fn new(inner: &'a mut W) -> Self {
+        Tee {
+            inner,
+            written: Vec::new(),
+        }
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for Tee<'_, W> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let len = ready!(Pin::new(&mut *self.inner).poll_write(cx, buf))?;
+        self.written.extend_from_slice(&buf[..len]);
+        Poll::Ready(Ok(len))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut *self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut *self.inner).poll_shutdown(cx)
+    }
+}
+
+/// Replays every recorded request in `dir` against `address`, printing a
+/// diff for each response that changed. Returns how many differed.
+pub fn replay(dir: &str, address: &str) -> Result<usize> {
+    let mut requests = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<Vec<PathBuf>>>()?;
+    requests.retain(|path| path.extension().is_some_and(|ext| ext == "request"));
+    requests.sort();
+
+    let mut changed = 0;
+    for path in &requests {
+        let recorded = fs::read(path.with_extension("response"))
+            .map_err(|err| anyhow!("{}: {}", path.with_extension("response").display(), err))?;
+        let replayed = exchange(address, &fs::read(path)?)?;
+        let recorded = normalize(&recorded);
+        let replayed = normalize(&replayed);
+        if recorded != replayed {
+            changed += 1;
+            println!("--- {}", name(path));
+            for line in diff(&recorded, &replayed) {
+                println!("{}", line);
+            }
+        }
+    }
+    println!(
+        "replayed {} requests, {} responses changed",
+        requests.len(),
+        changed
+    );
+    Ok(changed)
+}
+
+fn name(path: &Path) -> String {
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+fn exchange(address: &str, request: &[u8]) -> Result<Vec<u8>> {
+    let mut stream = TcpStream::connect(address)?;
+    stream.set_read_timeout(Some(REPLAY_TIMEOUT))?;
+    stream.write_all(request)?;
+    stream.shutdown(std::net::Shutdown::Write)?;
+    let mut response = Vec::new();
+    match stream.read_to_end(&mut response) {
+        Ok(_) => Ok(response),
+        Err(err) if err.kind() == io::ErrorKind::WouldBlock => Ok(response),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Splits a response stream into lines that compare equal whenever the
+/// responses do. Header order is not significant, so headers are sorted,
+/// and `Date` only says when the response was sent, so its value is
+/// dropped.
+fn normalize(mut data: &[u8]) -> Vec<String> {
+    let mut lines = Vec::new();
+    while !data.is_empty() {
+        let Some(head_len) = data.windows(4).position(|window| window == b"\r\n\r\n") else {
+            break;
+        };
+        let head = String::from_utf8_lossy(&data[..head_len]);
+        let mut head_lines = head.split("\r\n");
+        let status = head_lines.next().unwrap_or("").to_owned();
+        let mut headers = head_lines
+            .map(|header| match header.split_once(':') {
+                Some((name, _)) if name.eq_ignore_ascii_case("Date") => format!("{}:", name),
+                _ => header.to_owned(),
+            })
+            .collect::<Vec<String>>();
+        headers.sort();
+        data = &data[head_len + 4..];
+
+        let informational = status
+            .split(' ')
+            .nth(1)
+            .is_some_and(|code| code.starts_with('1'));
+        let content_length = headers.iter().find_map(|header| {
+            let (name, value) = header.split_once(':')?;
+            name.eq_ignore_ascii_case("Content-Length")
+                .then(|| value.trim().parse::<usize>().ok())?
+        });
+        lines.push(status);
+        lines.extend(headers);
+        lines.push(String::new());
+        if informational {
+            continue;
+        }
+        let body_len = content_length.unwrap_or(data.len()).min(data.len());
+        lines.extend(
+            String::from_utf8_lossy(&data[..body_len])
+                .split('\n')
+                .map(str::to_owned),
+        );
+        data = &data[body_len..];
+    }
+    if !data.is_empty() {
+        lines.extend(String::from_utf8_lossy(data).split('\n').map(str::to_owned));
+    }
+    lines
+}
+
+/// Line diff of two responses, `-` for recorded and `+` for replayed lines.
+fn diff(old: &[String], new: &[String]) -> Vec<String> {
+    // Longest common subsequence table, filled from the end.
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut out = Vec::new();
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            out.push(format!("  {}", old[i]));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            out.push(format!("- {}", old[i]));
+            i += 1;
+        } else {
+            out.push(format!("+ {}", new[j]));
+            j += 1;
+        }
+    }
+    out
+}
@@ -0,0 +1,387 @@
+//! A small backtracking regular expression engine for configuration-level
+//! matching (rewrite rules and the like). It supports literals, `.`, `^`,
+//! `$`, classes (`[a-z]`, `[^/]`, `\d`, `\w`, `\s`), groups (`(...)`,
+//! `(?:...)`), alternation and the quantifiers `*`, `+`, `?` and `{m,n}`,
+//! each optionally lazy. Matching is exponential in the worst case, so
+//! patterns must come from trusted configuration, never from requests.
+
+use anyhow::{anyhow, bail, Result};
+
+#[derive(Debug)]
+enum Node {
+    Char(char),
+    Any,
+    Class(Vec<ClassItem>, bool),
+    Start,
+    End,
+    Group(Box<Node>, Option<usize>),
+    Alt(Vec<Node>),
+    Concat(Vec<Node>),
+    Repeat {
+        node: Box<Node>,
+        min: usize,
+        max: usize,
+        greedy: bool,
+    },
+}
+
+#[derive(Debug)]
+enum ClassItem {
+    Range(char, char),
+    Digit,
+    Word,
+    Space,
+}
+
+impl ClassItem {
+    fn matches(&self, c: char) -> bool {
+        match self {
+            ClassItem::Range(from, to) => (*from..=*to).contains(&c),
+            ClassItem::Digit => c.is_ascii_digit(),
+            ClassItem::Word => c.is_alphanumeric() || c == '_',
+            ClassItem::Space => c.is_whitespace(),
+        }
+    }
+}
+
+type Captures = Vec<Option<(usize, usize)>>;
+
+#[derive(Debug)]
+pub struct Regex {
+    node: Node,
+    groups: usize,
+}
+
+impl Regex {
+    pub fn new(pattern: &str) -> Result<Self> {
+        let mut parser = Parser {
+            chars: pattern.chars().collect(),
+            pos: 0,
+            groups: 0,
+        };
+        let node = parser.alternation()?;
+        if parser.pos < parser.chars.len() {
+            bail!("unmatched ) in pattern {}", pattern);
+        }
+        Ok(Regex {
+            node,
+            groups: parser.groups,
+        })
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        self.captures(text).is_some()
+    }
+
+    /// Finds the leftmost match, returning the whole match at index 0
+    /// followed by each capture group (`None` for groups that didn't take
+    /// part in the match).
+    pub fn captures(&self, text: &str) -> Option<Vec<Option<String>>> {
+        let chars = text.chars().collect::<Vec<char>>();
+        for start in 0..=chars.len() {
+            let mut caps = vec![None; self.groups + 1];
+            let mut end = None;
+            let matched = self.node_matches(&self.node, &chars, start, &mut caps, &mut |pos, _| {
+                end = Some(pos);
+                true
+            });
+            if let (true, Some(end)) = (matched, end) {
+                caps[0] = Some((start, end));
+                return Some(
+                    caps.into_iter()
+                        .map(|span| span.map(|(from, to)| chars[from..to].iter().collect()))
+                        .collect(),
+                );
+            }
+        }
+        None
+    }
+
+    /// Matches `node` at `pos`, calling `k` with the end position of each
+    /// way it can match until `k` accepts one.
+    fn node_matches(
+        &self,
+        node: &Node,
+        text: &[char],
+        pos: usize,
+        caps: &mut Captures,
+        k: &mut dyn FnMut(usize, &mut Captures) -> bool,
+    ) -> bool {
+        let next = text.get(pos).copied();
+        match node {
+            Node::Char(c) => next == Some(*c) && k(pos + 1, caps),
+            Node::Any => next.is_some() && k(pos + 1, caps),
+            Node::Class(items, negated) => match next {
+                Some(c) => items.iter().any(|item| item.matches(c)) != *negated && k(pos + 1, caps),
+                None => false,
+            },
+            Node::Start => pos == 0 && k(pos, caps),
+            Node::End => pos == text.len() && k(pos, caps),
+            Node::Group(inner, index) => {
+                self.node_matches(inner, text, pos, caps, &mut |end, caps: &mut Captures| {
+                    let Some(index) = *index else {
+                        return k(end, caps);
+                    };
+                    let saved = caps[index];
+                    caps[index] = Some((pos, end));
+                    if k(end, caps) {
+                        return true;
+                    }
+                    caps[index] = saved;
+                    false
+                })
+            }
+            Node::Alt(alternatives) => {
+                for alternative in alternatives {
+                    if self.node_matches(alternative, text, pos, caps, k) {
+                        return true;
+                    }
+                }
+                false
+            }
+            Node::Concat(nodes) => self.sequence_matches(nodes, text, pos, caps, k),
+            Node::Repeat {
+                node,
+                min,
+                max,
+                greedy,
+            } => self.repeat_matches(node, (*min, *max, *greedy), 0, text, pos, caps, k),
+        }
+    }
+
+    fn sequence_matches(
+        &self,
+        nodes: &[Node],
+        text: &[char],
+        pos: usize,
+        caps: &mut Captures,
+        k: &mut dyn FnMut(usize, &mut Captures) -> bool,
+    ) -> bool {
+        match nodes.split_first() {
+            None => k(pos, caps),
+            Some((first, rest)) => self.node_matches(first, text, pos, caps, &mut |end, caps| {
+                self.sequence_matches(rest, text, end, caps, k)
+            }),
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn repeat_matches(
+        &self,
+        node: &Node,
+        (min, max, greedy): (usize, usize, bool),
+        count: usize,
+        text: &[char],
+        pos: usize,
+        caps: &mut Captures,
+        k: &mut dyn FnMut(usize, &mut Captures) -> bool,
+    ) -> bool {
+        let more = |caps: &mut Captures, k: &mut dyn FnMut(usize, &mut Captures) -> bool| {
+            count < max
+                && self.node_matches(node, text, pos, caps, &mut |end, caps| {
+                    // An empty iteration past the minimum can't make progress.
+                    (end != pos || count < min)
+                        && self.repeat_matches(
+                            node,
+                            (min, max, greedy),
+                            count + 1,
+                            text,
+                            end,
+                            caps,
+                            k,
+                        )
+                })
+        };
+        if count < min {
+            return more(caps, k);
+        }
+        // Greedy repeats try another iteration before stopping here; lazy
+        // ones stop first.
+        if !greedy && k(pos, caps) {
+            return true;
+        }
+        more(caps, k) || (greedy && k(pos, caps))
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    groups: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            return true;
+        }
+        false
+    }
+
+    fn alternation(&mut self) -> Result<Node> {
+        let mut alternatives = vec![self.concatenation()?];
+        while self.eat('|') {
+            alternatives.push(self.concatenation()?);
+        }
+        Ok(match alternatives.len() {
+            1 => alternatives.pop().unwrap(),
+            _ => Node::Alt(alternatives),
+        })
+    }
+
+    fn concatenation(&mut self) -> Result<Node> {
+        let mut nodes = Vec::new();
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            let atom = self.atom()?;
+            nodes.push(self.quantified(atom)?);
+        }
+        Ok(Node::Concat(nodes))
+    }
+
+    fn atom(&mut self) -> Result<Node> {
+        let c = self
+            .peek()
+            .ok_or_else(|| anyhow!("unexpected end of pattern"))?;
+        self.pos += 1;
+        Ok(match c {
+            '(' => {
+                let index = if self.eat('?') {
+                    if !self.eat(':') {
+                        bail!("unsupported group syntax at {}", self.pos);
+                    }
+                    None
+                } else {
+                    self.groups += 1;
+                    Some(self.groups)
+                };
+                let inner = self.alternation()?;
+                if !self.eat(')') {
+                    bail!("missing ) in pattern");
+                }
+                Node::Group(Box::new(inner), index)
+            }
+            '[' => self.class()?,
+            '.' => Node::Any,
+            '^' => Node::Start,
+            '$' => Node::End,
+            '\\' => self.escape()?,
+            '*' | '+' | '?' | '{' => bail!("nothing to repeat before {}", c),
+            c => Node::Char(c),
+        })
+    }
+
+    fn escape(&mut self) -> Result<Node> {
+        let c = self.peek().ok_or_else(|| anyhow!("pattern ends with \\"))?;
+        self.pos += 1;
+        Ok(match c {
+            'd' => Node::Class(vec![ClassItem::Digit], false),
+            'D' => Node::Class(vec![ClassItem::Digit], true),
+            'w' => Node::Class(vec![ClassItem::Word], false),
+            'W' => Node::Class(vec![ClassItem::Word], true),
+            's' => Node::Class(vec![ClassItem::Space], false),
+            'S' => Node::Class(vec![ClassItem::Space], true),
+            c => Node::Char(c),
+        })
+    }
+
+    fn class(&mut self) -> Result<Node> {
+        let negated = self.eat('^');
+        let mut items = Vec::new();
+        let mut first = true;
+        loop {
+            let c = self.peek().ok_or_else(|| anyhow!("missing ] in pattern"))?;
+            self.pos += 1;
+            if c == ']' && !first {
+                break;
+            }
+            first = false;
+            let from = match c {
+                '\\' => {
+                    let escaped = self.peek().ok_or_else(|| anyhow!("missing ] in pattern"))?;
+                    self.pos += 1;
+                    match escaped {
+                        'd' => {
+                            items.push(ClassItem::Digit);
+                            continue;
+                        }
+                        'w' => {
+                            items.push(ClassItem::Word);
+                            continue;
+                        }
+                        's' => {
+                            items.push(ClassItem::Space);
+                            continue;
+                        }
+                        c => c,
+                    }
+                }
+                c => c,
+            };
+            if self.peek() == Some('-') && self.chars.get(self.pos + 1).is_some_and(|c| *c != ']') {
+                let to = self.chars[self.pos + 1];
+                self.pos += 2;
+                if to < from {
+                    bail!("invalid class range {}-{}", from, to);
+                }
+                items.push(ClassItem::Range(from, to));
+            } else {
+                items.push(ClassItem::Range(from, from));
+            }
+        }
+        Ok(Node::Class(items, negated))
+    }
+
+    fn quantified(&mut self, atom: Node) -> Result<Node> {
+        let (min, max) = match self.peek() {
+            Some('*') => (0, usize::MAX),
+            Some('+') => (1, usize::MAX),
+            Some('?') => (0, 1),
+            Some('{') => return self.counted(atom),
+            _ => return Ok(atom),
+        };
+        self.pos += 1;
+        Ok(self.repeat(atom, min, max))
+    }
+
+    fn counted(&mut self, atom: Node) -> Result<Node> {
+        let close = self.chars[self.pos..]
+            .iter()
+            .position(|c| *c == '}')
+            .ok_or_else(|| anyhow!("missing }} in pattern"))?;
+        let body = self.chars[self.pos + 1..self.pos + close]
+            .iter()
+            .collect::<String>();
+        let number = |s: &str| {
+            s.parse::<usize>()
+                .map_err(|_| anyhow!("invalid repetition {{{}}}", body))
+        };
+        let (min, max) = match body.split_once(',') {
+            None => (number(&body)?, number(&body)?),
+            Some((min, "")) => (number(min)?, usize::MAX),
+            Some((min, max)) => (number(min)?, number(max)?),
+        };
+        if max < min {
+            bail!("invalid repetition {{{}}}", body);
+        }
+        self.pos += close + 1;
+        Ok(self.repeat(atom, min, max))
+    }
+
+    fn repeat(&mut self, atom: Node, min: usize, max: usize) -> Node {
+        let greedy = !self.eat('?');
+        Node::Repeat {
+            node: Box::new(atom),
+            min,
+            max,
+            greedy,
+        }
+    }
+}
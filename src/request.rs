@@ -0,0 +1,646 @@
+use crate::auth::{Authorization, InvalidAuthorization};
+use crate::body::{BodyError, BodyStream};
+use crate::conditional::ETagMatch;
+use crate::cookie::CookieJar;
+use crate::date;
+use crate::forwarded;
+use crate::header_map::HeaderMap;
+use crate::json::{FromJson, JsonError, Value};
+use crate::media_type::MediaType;
+use crate::negotiate::{self, MediaRange, Weighted};
+use crate::parser::{self, Parsed, RawHead};
+use crate::range::{self, ByteRange, InvalidRange};
+use crate::typed_header::{InvalidHeaderValue, TypedHeader};
+use anyhow::{bail, Result};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+use std::time::SystemTime;
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, PartialEq)]
+pub enum HttpMethod {
+    GET,
+    HEAD,
+    POST,
+    TRACE,
+    CONNECT,
+    OPTIONS,
+    PROPFIND,
+    MKCOL,
+    PUT,
+    DELETE,
+    PATCH,
+}
+
+/// A request method this server doesn't know; answered with
+/// `501 Not Implemented`.
+#[derive(Debug, thiserror::Error)]
+#[error("unknown method {0}")]
+pub struct UnknownMethod(pub String);
+
+impl FromStr for HttpMethod {
+    type Err = UnknownMethod;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "GET" => Ok(HttpMethod::GET),
+            "HEAD" => Ok(HttpMethod::HEAD),
+            "POST" => Ok(HttpMethod::POST),
+            "TRACE" => Ok(HttpMethod::TRACE),
+            "CONNECT" => Ok(HttpMethod::CONNECT),
+            "OPTIONS" => Ok(HttpMethod::OPTIONS),
+            "PROPFIND" => Ok(HttpMethod::PROPFIND),
+            "MKCOL" => Ok(HttpMethod::MKCOL),
+            "PUT" => Ok(HttpMethod::PUT),
+            "DELETE" => Ok(HttpMethod::DELETE),
+            "PATCH" => Ok(HttpMethod::PATCH),
+            _ => Err(UnknownMethod(value.to_owned())),
+        }
+    }
+}
+
+impl HttpMethod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HttpMethod::GET => "GET",
+            HttpMethod::HEAD => "HEAD",
+            HttpMethod::POST => "POST",
+            HttpMethod::TRACE => "TRACE",
+            HttpMethod::CONNECT => "CONNECT",
+            HttpMethod::OPTIONS => "OPTIONS",
+            HttpMethod::PROPFIND => "PROPFIND",
+            HttpMethod::MKCOL => "MKCOL",
+            HttpMethod::PUT => "PUT",
+            HttpMethod::DELETE => "DELETE",
+            HttpMethod::PATCH => "PATCH",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HttpVersion {
+    Http10,
+    Http11,
+    /// A request that arrived as a stream of an HTTP/2 connection.
+    Http2,
+}
+
+impl HttpVersion {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HttpVersion::Http10 => "HTTP/1.0",
+            HttpVersion::Http11 => "HTTP/1.1",
+            HttpVersion::Http2 => "HTTP/2",
+        }
+    }
+}
+
+/// A request line carrying an HTTP version other than 1.0 or 1.1, or none
+/// at all; answered with `505 HTTP Version Not Supported`.
+#[derive(Debug, thiserror::Error)]
+#[error("unsupported HTTP version {0:?}")]
+pub struct UnsupportedVersion(pub String);
+
+/// A request line that isn't `method SP request-target SP HTTP-version`.
+#[derive(Debug, thiserror::Error)]
+#[error("invalid request line {line:?}: {reason}")]
+pub struct InvalidRequestLine {
+    pub line: String,
+    pub reason: &'static str,
+}
+
+/// The start of a TLS handshake, from a client speaking HTTPS to this
+/// plaintext port.
+#[derive(Debug, thiserror::Error)]
+#[error("TLS handshake on a plaintext connection")]
+pub struct TlsHandshake;
+
+/// A request target in a form this server doesn't route: neither a path,
+/// `*`, an absolute URI, nor an authority on `CONNECT`.
+#[derive(Debug, thiserror::Error)]
+#[error("invalid request target {0:?}")]
+pub struct InvalidTarget(pub String);
+
+/// An HTTP/1.1 request without a `Host` header, with more than one, or
+/// with one that isn't a plain `host[:port]`.
+#[derive(Debug, thiserror::Error)]
+#[error("missing or invalid Host {0:?}")]
+pub struct InvalidHost(pub String);
+
+/// A header line this server refuses to interpret, such as an obs-fold
+/// continuation line.
+#[derive(Debug, thiserror::Error)]
+#[error("invalid header line {0:?}")]
+pub struct InvalidHeader(pub String);
+
+#[derive(Debug)]
+pub struct Request {
+    /// The path of the request target, without its query.
+    pub path: String,
+    /// The query of the request target, without its `?`.
+    pub(crate) query: Option<String>,
+    /// The fragment of the request target, without its `#`. Clients
+    /// shouldn't send one, but some do.
+    pub(crate) fragment: Option<String>,
+    pub method: HttpMethod,
+    pub version: HttpVersion,
+    pub headers: HeaderMap,
+    /// Request body as received, empty when there is none.
+    pub body: Vec<u8>,
+    /// Trailer fields sent after a chunked body.
+    pub trailers: HeaderMap,
+    /// Request line and header lines exactly as received.
+    pub head: String,
+    /// When the head of the request had been read in full.
+    pub received_at: SystemTime,
+    /// The body still on the connection, for routes that stream it.
+    pub(crate) body_stream: Option<BodyStream>,
+    /// Whether one of the body accessors took the body.
+    body_consumed: bool,
+    /// Address of the peer the request was read from; `None` for requests
+    /// that weren't read off a connection.
+    pub remote_addr: Option<SocketAddr>,
+    /// Address of the client: the peer's, unless the peer is a trusted
+    /// proxy that said whom it forwarded the request for.
+    pub client_ip: Option<IpAddr>,
+    /// Where a rewrite rule redirects the request; it is answered with a
+    /// `302 Found` instead of being routed.
+    pub redirect: Option<String>,
+}
+
+impl Request {
+    fn parse_top(head: &RawHead) -> Result<(HttpMethod, Target, HttpVersion)> {
+        let http_method = head.method.parse::<HttpMethod>()?;
+        let target = Request::parse_target(&http_method, head.target)?;
+        let version = match head.version {
+            "HTTP/1.0" => HttpVersion::Http10,
+            "HTTP/1.1" => HttpVersion::Http11,
+            other if valid_version(other) => bail!(UnsupportedVersion(other.to_string())),
+            other => bail!(InvalidRequestLine {
+                line: format!("{} {} {}", head.method, head.target, other),
+                reason: "malformed HTTP version",
+            }),
+        };
+        Ok((http_method, target, version))
+    }
+
+    /// Splits the request target into the path to route on, the query and,
+    /// for an absolute-form target (`http://host:port/path`, as sent to
+    /// proxies), its authority. `CONNECT` keeps its authority-form target
+    /// as the path.
+    fn parse_target(method: &HttpMethod, target: &str) -> Result<Target> {
+        if *method == HttpMethod::CONNECT || target == "*" {
+            return Ok(Target {
+                path: target.to_owned(),
+                query: None,
+                fragment: None,
+                authority: None,
+            });
+        }
+        if target.starts_with('/') {
+            return Target::split(target, None);
+        }
+        let rest = target.split_once("://").and_then(|(scheme, rest)| {
+            let http = scheme.eq_ignore_ascii_case("http") || scheme.eq_ignore_ascii_case("https");
+            http.then_some(rest)
+        });
+        let Some(rest) = rest else {
+            return Err(InvalidTarget(target.to_owned()).into());
+        };
+        let end = rest.find(['/', '?', '#']).unwrap_or(rest.len());
+        let (authority, path) = rest.split_at(end);
+        if authority.is_empty() {
+            return Err(InvalidTarget(target.to_owned()).into());
+        }
+        let path = if path.starts_with('/') {
+            path.to_owned()
+        } else {
+            format!("/{}", path)
+        };
+        Target::split(&path, Some(authority.to_owned()))
+    }
+
+    /// Collects header fields, keeping every value of a field sent more
+    /// than once.
+    fn parse_header(fields: &[(&str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in fields {
+            headers.append(name.to_string(), value.to_string());
+        }
+        headers
+    }
+
+    /// The `Host` the request was sent to, including any port. Always
+    /// present on HTTP/1.1 requests.
+    pub fn host(&self) -> Option<&str> {
+        self.headers.get("Host").map(|host| host.trim())
+    }
+
+    /// The cookies sent in `Cookie`, from every such field.
+    pub fn cookies(&self) -> CookieJar {
+        CookieJar::parse(self.headers.get_all("Cookie").map(String::as_str))
+    }
+
+    /// The media ranges the client accepts, most preferred first. Empty
+    /// when it sent no `Accept`, which means anything goes.
+    pub fn accept(&self) -> Vec<MediaRange<'_>> {
+        negotiate::parse_accept(self.headers.get_all("Accept").map(String::as_str))
+    }
+
+    /// The offer the client prefers according to `Accept`, the first one if
+    /// it sent none. `None` means it refused every offer, which calls for a
+    /// 406.
+    pub fn negotiate<'a>(&self, offers: &[&'a str]) -> Option<&'a str> {
+        if !self.headers.contains_key("Accept") {
+            return offers.first().copied();
+        }
+        negotiate::best_offer(&self.accept(), offers)
+    }
+
+    /// The content codings listed in `Accept-Encoding`, most preferred
+    /// first.
+    pub fn accept_encoding(&self) -> Vec<Weighted<'_>> {
+        negotiate::parse_weighted(self.headers.get_all("Accept-Encoding").map(String::as_str))
+    }
+
+    /// Whether the client takes a response in `coding`. Without an
+    /// `Accept-Encoding` only `identity` is assumed: compressing for clients
+    /// that didn't ask for it trips up too many of them.
+    pub fn accepts_encoding(&self, coding: &str) -> bool {
+        negotiate::encoding_quality(&self.accept_encoding(), coding) > 0.0
+    }
+
+    /// The content coding among `offers` the client prefers; `None` if it
+    /// refuses all of them, `identity` included.
+    pub fn negotiate_encoding<'a>(&self, offers: &[&'a str]) -> Option<&'a str> {
+        negotiate::negotiate_encoding(&self.accept_encoding(), offers)
+    }
+
+    /// The language ranges listed in `Accept-Language`, most preferred
+    /// first.
+    pub fn accept_language(&self) -> Vec<Weighted<'_>> {
+        negotiate::parse_weighted(self.headers.get_all("Accept-Language").map(String::as_str))
+    }
+
+    /// The language among `offers` the client prefers, for handlers with
+    /// localized content. Without an `Accept-Language` the first offer is
+    /// returned; `None` means the client wants none of them, and it's up to
+    /// the handler whether to fall back anyway.
+    pub fn pick_language<'a>(&self, offers: &[&'a str]) -> Option<&'a str> {
+        let accepted = self.accept_language();
+        if accepted.is_empty() {
+            return offers.first().copied();
+        }
+        negotiate::negotiate_language(&accepted, offers)
+    }
+
+    /// The header field `T`, e.g. `req.typed_header::<ContentLength>()`.
+    /// `None` if it wasn't sent.
+    pub fn typed_header<T: TypedHeader>(&self) -> Option<Result<T, InvalidHeaderValue>> {
+        self.headers.typed()
+    }
+
+    /// The request line exactly as received, e.g. `GET /a%20b HTTP/1.1`.
+    pub fn request_line(&self) -> &str {
+        self.head.lines().next().unwrap_or("")
+    }
+
+    /// Records that the request came from `peer`, and works out the client
+    /// from forwarding fields if `peer` is one of the `trusted` proxies.
+    pub fn set_remote(&mut self, peer: SocketAddr, trusted: &[IpAddr]) {
+        self.remote_addr = Some(peer);
+        self.client_ip = Some(forwarded::client_ip(peer.ip(), &self.headers, trusted));
+    }
+
+    /// The credentials in `Authorization`, or `None` without the field.
+    pub fn authorization(&self) -> Option<Result<Authorization, InvalidAuthorization>> {
+        self.headers
+            .get("Authorization")
+            .map(|value| Authorization::parse(value))
+    }
+
+    /// `If-Match`, or `None` without it. A malformed value is treated as
+    /// absent.
+    pub fn if_match(&self) -> Option<ETagMatch> {
+        self.etag_match("If-Match")
+    }
+
+    /// `If-None-Match`, or `None` without it. A malformed value is treated
+    /// as absent.
+    pub fn if_none_match(&self) -> Option<ETagMatch> {
+        self.etag_match("If-None-Match")
+    }
+
+    fn etag_match(&self, name: &str) -> Option<ETagMatch> {
+        if !self.headers.contains_key(name) {
+            return None;
+        }
+        ETagMatch::parse(self.headers.get_all(name).map(String::as_str))
+    }
+
+    /// `If-Modified-Since`. Dates that don't parse are ignored, as RFC 9110
+    /// §13.1.3 requires, and so is the field when `If-None-Match` is sent.
+    pub fn if_modified_since(&self) -> Option<SystemTime> {
+        if self.headers.contains_key("If-None-Match") {
+            return None;
+        }
+        date::parse_http_date(self.headers.get("If-Modified-Since")?)
+    }
+
+    /// `If-Unmodified-Since`. Dates that don't parse are ignored, and so is
+    /// the field when `If-Match` is sent (RFC 9110 §13.1.4).
+    pub fn if_unmodified_since(&self) -> Option<SystemTime> {
+        if self.headers.contains_key("If-Match") {
+            return None;
+        }
+        date::parse_http_date(self.headers.get("If-Unmodified-Since")?)
+    }
+
+    /// The byte ranges asked for with `Range`, parsed when called. `None`
+    /// without the field; an error means it should be ignored and the full
+    /// representation served.
+    pub fn range(&self) -> Option<Result<Vec<ByteRange>, InvalidRange>> {
+        self.headers
+            .get("Range")
+            .map(|value| range::parse_range(value))
+    }
+
+    /// The media type of the body, from `Content-Type`. `None` when the
+    /// field is missing or doesn't parse.
+    pub fn content_type(&self) -> Option<MediaType> {
+        self.headers.get("Content-Type")?.parse().ok()
+    }
+
+    /// Reads the body as JSON into a `T`. The body has to be declared as
+    /// `application/json` (or a `+json` type); turning the error into a
+    /// `Response` answers with 415 or 400 accordingly.
+    pub fn json<T: FromJson>(&self) -> Result<T, JsonError> {
+        let json = self
+            .content_type()
+            .is_some_and(|media| media.is("application", "json") || media.suffix() == Some("json"));
+        if !json {
+            let content_type = self.headers.get("Content-Type").map_or("", String::as_str);
+            return Err(JsonError::UnsupportedMediaType(content_type.to_owned()));
+        }
+        let text = std::str::from_utf8(&self.body).map_err(|err| JsonError::Syntax {
+            offset: err.valid_up_to(),
+            message: "invalid UTF-8",
+        })?;
+        T::from_json(Value::parse(text)?)
+    }
+
+    /// The query string as sent, without its `?`; `None` when the target
+    /// has none.
+    pub fn query_raw(&self) -> Option<&str> {
+        self.query.as_deref()
+    }
+
+    /// The query parameters, with `+` and percent-escapes decoded. The
+    /// first value of a parameter given more than once wins.
+    pub fn query(&self) -> HashMap<String, String> {
+        let mut params = HashMap::new();
+        let pairs = self.query_raw().unwrap_or("").split('&');
+        for pair in pairs.filter(|pair| !pair.is_empty()) {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            params
+                .entry(decode_query(name))
+                .or_insert_with(|| decode_query(value));
+        }
+        params
+    }
+
+    /// The fragment the client sent, without its `#`. It is never used for
+    /// routing.
+    pub fn fragment(&self) -> Option<&str> {
+        self.fragment.as_deref()
+    }
+
+    /// The path, escaped again, followed by the query: a target naming
+    /// this request that can be sent on, as in a `Location`.
+    pub fn target(&self) -> String {
+        let path = percent_encode_path(&self.path);
+        match &self.query {
+            Some(query) => format!("{}?{}", path, query),
+            None => path,
+        }
+    }
+
+    /// The body as a stream of chunks. On routes set up with
+    /// `Route::stream_body` it is read off the connection as it is consumed
+    /// and `body` stays empty; otherwise `body` moves into the stream.
+    pub fn body_stream(&mut self) -> BodyStream {
+        self.body_consumed = true;
+        self.body_stream.take().unwrap_or_else(|| {
+            let body = std::mem::take(&mut self.body);
+            BodyStream::buffered_with_trailers(body, self.trailers.clone())
+        })
+    }
+
+    /// Takes the buffered body. Like the other body accessors it hands the
+    /// body over once; later calls fail with `BodyError::Consumed`.
+    pub fn body_bytes(&mut self) -> Result<Vec<u8>, BodyError> {
+        if self.body_consumed {
+            return Err(BodyError::Consumed);
+        }
+        if self.body_stream.is_some() {
+            return Err(BodyError::Streamed);
+        }
+        self.body_consumed = true;
+        Ok(std::mem::take(&mut self.body))
+    }
+
+    /// Takes the buffered body as text. A body that isn't UTF-8 still
+    /// counts as consumed.
+    pub fn body_text(&mut self) -> Result<String, BodyError> {
+        Ok(String::from_utf8(self.body_bytes()?)?)
+    }
+
+    /// The body size the client declared in `Content-Length`, if it did.
+    /// Chunked bodies have none; their size is only known once read.
+    pub fn content_length(&self) -> Option<u64> {
+        if self.headers.contains_key("Transfer-Encoding") {
+            return None;
+        }
+        self.headers.get("Content-Length")?.trim().parse().ok()
+    }
+
+    /// Parses a request head, followed by as much of the body as `data`
+    /// holds. Only the head has to be UTF-8; the body is kept as raw bytes.
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        match Request::parse_prefix(data)? {
+            Parsed::Complete(mut req, len) => {
+                req.body = data[len..].to_vec();
+                Ok(req)
+            }
+            Parsed::Incomplete => bail!("incomplete request head"),
+        }
+    }
+
+    /// Parses the request head at the start of `buf`, which may not have
+    /// all arrived yet. The body, if any, is left for the caller.
+    pub fn parse_prefix(buf: &[u8]) -> Result<Parsed<Self>> {
+        match parser::parse_head(buf)? {
+            Parsed::Complete(head, len) => Ok(Parsed::Complete(Request::from_head(head)?, len)),
+            Parsed::Incomplete => Ok(Parsed::Incomplete),
+        }
+    }
+
+    fn from_head(head: RawHead) -> Result<Self> {
+        let (method, target, version) = Request::parse_top(&head)?;
+        let Target {
+            path,
+            query,
+            fragment,
+            authority,
+        } = target;
+        let mut headers = Request::parse_header(&head.fields);
+        // RFC 9112 §3.2: more than one Host is a 400, whatever the target.
+        let hosts = headers.get_all("Host").cloned().collect::<Vec<String>>();
+        if hosts.len() > 1 {
+            bail!(InvalidHost(hosts.join(", ")));
+        }
+        // The authority of an absolute-form target overrides Host.
+        if let Some(authority) = authority {
+            headers.insert(String::from("Host"), authority);
+        }
+        match headers.get("Host") {
+            Some(host) if !valid_host(host.trim()) => bail!(InvalidHost(host.clone())),
+            None if version == HttpVersion::Http11 => bail!(InvalidHost(String::new())),
+            _ => {}
+        }
+
+        Ok(Request {
+            method,
+            path,
+            query,
+            fragment,
+            version,
+            headers,
+            body: Vec::new(),
+            trailers: HeaderMap::new(),
+            head: head.text.to_owned(),
+            received_at: SystemTime::now(),
+            body_stream: None,
+            body_consumed: false,
+            remote_addr: None,
+            client_ip: None,
+            redirect: None,
+        })
+    }
+}
+
+/// Whether `version` is `HTTP/` followed by a digit, a dot and a digit.
+fn valid_version(version: &str) -> bool {
+    matches!(
+        version.strip_prefix("HTTP/").map(str::as_bytes),
+        Some([major, b'.', minor]) if major.is_ascii_digit() && minor.is_ascii_digit()
+    )
+}
+
+/// Whether `host` is a `host[:port]` authority with no userinfo, path or
+/// whitespace. Empty is allowed for targets without an authority.
+fn valid_host(host: &str) -> bool {
+    !host
+        .bytes()
+        .any(|b| b.is_ascii_whitespace() || b.is_ascii_control() || b"/?#@\\".contains(&b))
+}
+
+/// A request target taken apart.
+struct Target {
+    path: String,
+    query: Option<String>,
+    fragment: Option<String>,
+    authority: Option<String>,
+}
+
+impl Target {
+    /// Splits the fragment, then the query off an origin-form `target`,
+    /// then decodes and normalizes the path. Decoding comes first so an
+    /// escaped `..` can't slip past normalization.
+    fn split(target: &str, authority: Option<String>) -> Result<Self> {
+        let (rest, fragment) = match target.split_once('#') {
+            Some((rest, fragment)) => (rest, Some(fragment.to_owned())),
+            None => (target, None),
+        };
+        let (path, query) = match rest.split_once('?') {
+            Some((path, query)) => (path, Some(query.to_owned())),
+            None => (rest, None),
+        };
+        let path = percent_decode(path)
+            .filter(|path| !path.chars().any(|c| c.is_control()))
+            .ok_or_else(|| InvalidTarget(target.to_owned()))?;
+        Ok(Target {
+            path: normalize(&path),
+            query,
+            fragment,
+            authority,
+        })
+    }
+}
+
+/// Collapses repeated slashes and resolves `.` and `..` segments in a
+/// path, so routes and prefixes match on the path the request really
+/// names. `..` never climbs above the root.
+pub(crate) fn normalize(path: &str) -> String {
+    let mut segments: Vec<&str> = Vec::new();
+    for segment in path.split('/').filter(|s| !s.is_empty() && *s != ".") {
+        if segment == ".." {
+            segments.pop();
+        } else {
+            segments.push(segment);
+        }
+    }
+    // A trailing slash (or dot segment) still names a directory.
+    let trailing = path.len() > 1
+        && (path.ends_with('/') || path.ends_with("/.") || path.ends_with("/.."))
+        && !segments.is_empty();
+    let mut normalized = format!("/{}", segments.join("/"));
+    if trailing {
+        normalized.push('/');
+    }
+    normalized
+}
+
+/// Decodes `%XX` escapes. `None` if an escape is malformed or the result
+/// isn't UTF-8.
+pub fn percent_decode(value: &str) -> Option<String> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = bytes.get(i + 1..i + 3)?;
+            if !hex.iter().all(u8::is_ascii_hexdigit) {
+                return None;
+            }
+            decoded.push(u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+/// Escapes what can't appear as is in the path of a target, leaving `/`
+/// and the other characters RFC 3986 allows in a segment alone.
+pub(crate) fn percent_encode_path(path: &str) -> String {
+    let mut encoded = String::with_capacity(path.len());
+    for b in path.bytes() {
+        if b.is_ascii_alphanumeric() || b"-._~!$&'()*+,;=:@/".contains(&b) {
+            encoded.push(b as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", b));
+        }
+    }
+    encoded
+}
+
+/// Decodes a query parameter name or value, keeping it as sent if it
+/// doesn't decode.
+fn decode_query(value: &str) -> String {
+    let value = value.replace('+', " ");
+    percent_decode(&value).unwrap_or(value)
+}
@@ -0,0 +1,388 @@
+use crate::header_map::HeaderMap;
+use crate::json::ToJson;
+use bytes::BufMut;
+use std::fmt;
+use std::fs::File;
+use tokio::io::AsyncRead;
+use tokio::sync::{mpsc, oneshot};
+
+macro_rules! http_codes {
+    ($($name:ident = $code:literal $reason:literal,)*) => {
+        #[derive(Debug, Clone, PartialEq)]
+        pub enum HttpCode {
+            $($name,)*
+            /// Any other code, sent with the given reason phrase or an
+            /// empty one. Build it with `HttpCode::custom`.
+            Custom(u16, Option<String>),
+        }
+
+        impl HttpCode {
+            pub fn as_u16(&self) -> u16 {
+                match self {
+                    $(Self::$name => $code,)*
+                    Self::Custom(code, _) => *code,
+                }
+            }
+
+            /// The reason phrase sent after the code: the canonical one, or
+            /// whatever a custom code was given.
+            pub fn reason(&self) -> &str {
+                match self {
+                    $(Self::$name => $reason,)*
+                    Self::Custom(_, reason) => reason.as_deref().unwrap_or(""),
+                }
+            }
+        }
+
+        impl TryFrom<u16> for HttpCode {
+            type Error = UnknownStatus;
+
+            fn try_from(code: u16) -> Result<Self, Self::Error> {
+                match code {
+                    $($code => Ok(Self::$name),)*
+                    _ => Err(UnknownStatus(code)),
+                }
+            }
+        }
+    };
+}
+
+http_codes! {
+    Continue = 100 "Continue",
+    SwitchingProtocols = 101 "Switching Protocols",
+    EarlyHints = 103 "Early Hints",
+    OK = 200 "OK",
+    Created = 201 "Created",
+    Accepted = 202 "Accepted",
+    NonAuthoritativeInformation = 203 "Non-Authoritative Information",
+    NoContent = 204 "No Content",
+    ResetContent = 205 "Reset Content",
+    PartialContent = 206 "Partial Content",
+    MultiStatus = 207 "Multi-Status",
+    MultipleChoices = 300 "Multiple Choices",
+    MovedPermanently = 301 "Moved Permanently",
+    Found = 302 "Found",
+    SeeOther = 303 "See Other",
+    NotModified = 304 "Not Modified",
+    TemporaryRedirect = 307 "Temporary Redirect",
+    PermanentRedirect = 308 "Permanent Redirect",
+    BadRequest = 400 "Bad Request",
+    Unauthorized = 401 "Unauthorized",
+    PaymentRequired = 402 "Payment Required",
+    Forbidden = 403 "Forbidden",
+    NotFound = 404 "Not Found",
+    MethodNotAllowed = 405 "Method Not Allowed",
+    NotAcceptable = 406 "Not Acceptable",
+    ProxyAuthenticationRequired = 407 "Proxy Authentication Required",
+    RequestTimeout = 408 "Request Timeout",
+    Conflict = 409 "Conflict",
+    Gone = 410 "Gone",
+    LengthRequired = 411 "Length Required",
+    PreconditionFailed = 412 "Precondition Failed",
+    ContentTooLarge = 413 "Content Too Large",
+    UriTooLong = 414 "URI Too Long",
+    UnsupportedMediaType = 415 "Unsupported Media Type",
+    RangeNotSatisfiable = 416 "Range Not Satisfiable",
+    ExpectationFailed = 417 "Expectation Failed",
+    MisdirectedRequest = 421 "Misdirected Request",
+    UnprocessableContent = 422 "Unprocessable Content",
+    UpgradeRequired = 426 "Upgrade Required",
+    PreconditionRequired = 428 "Precondition Required",
+    TooManyRequests = 429 "Too Many Requests",
+    RequestHeaderFieldsTooLarge = 431 "Request Header Fields Too Large",
+    UnavailableForLegalReasons = 451 "Unavailable For Legal Reasons",
+    InternalServerError = 500 "Internal Server Error",
+    NotImplemented = 501 "Not Implemented",
+    BadGateway = 502 "Bad Gateway",
+    ServiceUnavailable = 503 "Service Unavailable",
+    GatewayTimeout = 504 "Gateway Timeout",
+    HttpVersionNotSupported = 505 "HTTP Version Not Supported",
+}
+
+/// A status code with no `HttpCode` variant.
+#[derive(Debug, thiserror::Error)]
+#[error("unknown status code {0}")]
+pub struct UnknownStatus(pub u16);
+
+/// A status code outside 100-999, or a reason phrase that can't go on a
+/// status line.
+#[derive(Debug, thiserror::Error)]
+#[error("invalid status {0}")]
+pub struct InvalidStatus(pub String);
+
+impl HttpCode {
+    /// A code with a reason phrase of its own, such as `299 Custom`. Known
+    /// codes keep their variant when no reason is given.
+    pub fn custom(code: u16, reason: Option<&str>) -> Result<Self, InvalidStatus> {
+        if !(100..1000).contains(&code) {
+            return Err(InvalidStatus(code.to_string()));
+        }
+        if let Some(reason) = reason {
+            // reason-phrase = 1*( HTAB / SP / VCHAR / obs-text )
+            if reason.chars().any(|c| c.is_control() && c != '\t') {
+                return Err(InvalidStatus(format!("{} {:?}", code, reason)));
+            }
+        }
+        match (HttpCode::try_from(code), reason) {
+            (Ok(known), None) => Ok(known),
+            _ => Ok(HttpCode::Custom(code, reason.map(str::to_owned))),
+        }
+    }
+
+    /// 1xx: an interim response, sent before the final one.
+    pub fn is_informational(&self) -> bool {
+        (100..200).contains(&self.as_u16())
+    }
+
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.as_u16())
+    }
+
+    pub fn is_redirection(&self) -> bool {
+        (300..400).contains(&self.as_u16())
+    }
+
+    pub fn is_client_error(&self) -> bool {
+        (400..500).contains(&self.as_u16())
+    }
+
+    pub fn is_server_error(&self) -> bool {
+        (500..600).contains(&self.as_u16())
+    }
+}
+
+impl fmt::Display for HttpCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.as_u16(), self.reason())
+    }
+}
+
+pub enum Body {
+    Bytes(Vec<u8>),
+    File(File),
+    /// Chunks sent with `Transfer-Encoding: chunked` as they arrive; the
+    /// body ends when every sender is dropped.
+    Stream(mpsc::Receiver<Vec<u8>>),
+    /// Like `Stream`, followed by the trailer fields sent on the oneshot
+    /// once the chunks end. Their names belong in a `Trailer` header.
+    Trailed(mpsc::Receiver<Vec<u8>>, oneshot::Receiver<HeaderMap>),
+    /// Read from any source, such as a `tokio::fs::File`, and sent in
+    /// `file_chunk_size` chunks as the connection takes them. Sent as is
+    /// when the response sets `Content-Length`, chunked otherwise.
+    Reader(Box<dyn AsyncRead + Send + Unpin>),
+    /// A response still being worked out, such as by a handler reading a
+    /// streamed request body first; see `Response::deferred`.
+    Deferred(oneshot::Receiver<Response>),
+}
+
+impl Body {
+    /// A body read from `reader`; see `Body::Reader`.
+    pub fn reader(reader: impl AsyncRead + Send + Unpin + 'static) -> Self {
+        Body::Reader(Box::new(reader))
+    }
+}
+
+impl From<Vec<u8>> for Body {
+    fn from(value: Vec<u8>) -> Self {
+        Body::Bytes(value)
+    }
+}
+
+impl From<String> for Body {
+    fn from(value: String) -> Self {
+        Body::Bytes(value.into_bytes())
+    }
+}
+
+impl From<&str> for Body {
+    fn from(value: &str) -> Self {
+        Body::Bytes(value.as_bytes().to_vec())
+    }
+}
+
+/// A response, built as a struct literal or with `Response::builder`.
+/// The default is an empty `200 OK`.
+pub struct Response {
+    pub code: HttpCode,
+    pub content: Option<Body>,
+    pub headers: Option<HeaderMap>,
+}
+
+impl Default for Response {
+    fn default() -> Self {
+        Response {
+            code: HttpCode::OK,
+            content: None,
+            headers: None,
+        }
+    }
+}
+
+/// Builds a `Response` step by step, e.g.
+/// `Response::builder().status(HttpCode::Created).header("Location", "/a").body("done")`.
+#[derive(Default)]
+pub struct ResponseBuilder {
+    response: Response,
+}
+
+impl ResponseBuilder {
+    pub fn status(mut self, code: HttpCode) -> Self {
+        self.response.code = code;
+        self
+    }
+
+    /// Adds a header. Setting a name again adds another value rather than
+    /// replacing the first, as `Set-Cookie` needs.
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        let headers = self.response.headers.get_or_insert_with(HeaderMap::new);
+        headers.append(name.into(), value.into());
+        self
+    }
+
+    /// Finishes the response with `body`.
+    pub fn body(mut self, body: impl Into<Body>) -> Response {
+        self.response.content = Some(body.into());
+        self.response
+    }
+
+    /// Finishes the response without a body.
+    pub fn build(self) -> Response {
+        self.response
+    }
+}
+
+impl Response {
+    pub fn builder() -> ResponseBuilder {
+        ResponseBuilder::default()
+    }
+
+    /// A stand-in for the response sent on `response`, for handlers that
+    /// can only answer once they have read their streamed request body.
+    /// Headers middleware sets on the stand-in are carried over to the
+    /// real response where it doesn't set them itself.
+    pub fn deferred(response: oneshot::Receiver<Response>) -> Response {
+        Response {
+            code: HttpCode::OK,
+            content: Some(Body::Deferred(response)),
+            headers: None,
+        }
+    }
+
+    /// Waits for the response behind a `Body::Deferred` stand-in; any
+    /// other response is returned as is. A handler that gave up without
+    /// answering gets a 500.
+    pub(crate) async fn resolve(self) -> Response {
+        let Some(Body::Deferred(response)) = self.content else {
+            return self;
+        };
+        let mut res = response.await.unwrap_or(Response {
+            code: HttpCode::InternalServerError,
+            content: None,
+            headers: None,
+        });
+        if let Some(stand_in) = self.headers {
+            let headers = res.headers.get_or_insert_with(HeaderMap::new);
+            for (name, value) in stand_in.into_iter() {
+                if !headers.contains_key(&name) {
+                    headers.insert(name, value);
+                }
+            }
+        }
+        res
+    }
+
+    /// A response with `body` as plain text.
+    pub fn text(code: HttpCode, body: impl Into<String>) -> Response {
+        Response::builder()
+            .status(code)
+            .header("Content-Type", "text/plain; charset=utf-8")
+            .body(body.into())
+    }
+
+    /// A response with `body` as an HTML page.
+    pub fn html(code: HttpCode, body: impl Into<String>) -> Response {
+        Response::builder()
+            .status(code)
+            .header("Content-Type", "text/html; charset=utf-8")
+            .body(body.into())
+    }
+
+    /// A `200 OK` with `value` as its JSON body. A value JSON can't
+    /// represent, such as a NaN, gets a 500 instead.
+    pub fn json<T: ToJson + ?Sized>(value: &T) -> Response {
+        match value.to_json().serialize() {
+            Ok(body) => Response::builder()
+                .header("Content-Type", "application/json")
+                .body(body),
+            Err(err) => {
+                println!("error serializing JSON response: {}", err);
+                Response::builder()
+                    .status(HttpCode::InternalServerError)
+                    .build()
+            }
+        }
+    }
+
+    /// Serializes the status line and headers, handing back the body
+    /// separately so file bodies can be streamed.
+    pub fn into_parts(self) -> (Vec<u8>, Option<Body>) {
+        self.into_framed_parts(true)
+    }
+
+    /// Like `into_parts`, but a streamed body is only announced as chunked
+    /// when `chunked` is set.
+    pub fn into_framed_parts(self, chunked: bool) -> (Vec<u8>, Option<Body>) {
+        let mut buff = vec![];
+        buff.put(format!("HTTP/1.1 {}\r\n", self.code).as_bytes());
+        let mut headers = self.headers.unwrap_or_default();
+        // Every response must be framed for the connection to be reused.
+        // The length of a buffered body is known here, so it overrides
+        // whatever a handler worked out; without a body, a length set for
+        // HEAD stands.
+        let bodyless = self.code.is_informational()
+            || matches!(self.code, HttpCode::NoContent | HttpCode::NotModified);
+        if !bodyless && !headers.contains_key("Transfer-Encoding") {
+            match &self.content {
+                None => {
+                    headers.get_or_insert_with("Content-Length", || String::from("0"));
+                }
+                Some(Body::Bytes(bytes)) => {
+                    headers.insert(String::from("Content-Length"), bytes.len().to_string());
+                }
+                Some(Body::File(file)) => {
+                    if let Some(len) = file_len(file) {
+                        headers.get_or_insert_with("Content-Length", || len.to_string());
+                    }
+                }
+                Some(Body::Stream(_))
+                | Some(Body::Trailed(..))
+                | Some(Body::Reader(_))
+                | Some(Body::Deferred(_)) => {}
+            }
+        }
+        let streamed = is_streamed(&self.content, &headers);
+        for (key, value) in headers.into_iter() {
+            buff.put(format!("{}: {}\r\n", key, value).as_bytes());
+        }
+        if chunked && streamed {
+            buff.put(&b"Transfer-Encoding: chunked\r\n"[..]);
+        }
+        buff.put(&b"\r\n"[..]);
+        (buff, self.content)
+    }
+}
+
+/// Whether `content` has no length up front, so it goes out with the
+/// chunked coding, or over HTTP/1.0 until the connection closes.
+pub(crate) fn is_streamed(content: &Option<Body>, headers: &HeaderMap) -> bool {
+    match content {
+        Some(Body::Stream(_) | Body::Trailed(..)) => true,
+        Some(Body::Reader(_)) => !headers.contains_key("Content-Length"),
+        _ => false,
+    }
+}
+
+/// The size of a file body, if its metadata can be read.
+pub(crate) fn file_len(file: &File) -> Option<u64> {
+    file.metadata().ok().map(|metadata| metadata.len())
+}
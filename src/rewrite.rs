@@ -0,0 +1,95 @@
+use anyhow::{anyhow, Result};
+
+use crate::config::{RewriteFlag, RewriteRule};
+use crate::header_map::HeaderMap;
+use crate::regex::Regex;
+use crate::request::{self, Request};
+use crate::response::{HttpCode, Response};
+
+/// Applies the configured rewrite rules to request paths before routing.
+pub struct Rewriter {
+    rules: Vec<(Regex, String, RewriteFlag)>,
+}
+
+impl Rewriter {
+    pub fn new(rules: &[RewriteRule]) -> Result<Self> {
+        let rules = rules
+            .iter()
+            .map(|rule| {
+                let regex = Regex::new(&rule.pattern)
+                    .map_err(|err| anyhow!("invalid rewrite pattern {}: {}", rule.pattern, err))?;
+                Ok((regex, rule.replacement.clone(), rule.flag))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Rewriter { rules })
+    }
+
+    /// Rewrites the path of `req`, normalized like a parsed one, or
+    /// records the target a redirect rule sends it to in `req.redirect`,
+    /// leaving the path as sent. Rules match the path without its query
+    /// string, which is carried over unless the replacement sets its own.
+    pub fn apply(&self, req: &mut Request) {
+        for (regex, replacement, flag) in self.rules.iter() {
+            let Some(captures) = regex.captures(&req.path) else {
+                continue;
+            };
+            let target = expand(replacement, &captures);
+            let (path, query) = match target.split_once('?') {
+                Some((path, query)) => (path.to_owned(), Some(query.to_owned())),
+                None => (target, req.query.clone()),
+            };
+            if *flag == RewriteFlag::Redirect {
+                let path = request::percent_encode_path(&path);
+                req.redirect = Some(match query {
+                    Some(query) => format!("{}?{}", path, query),
+                    None => path,
+                });
+                return;
+            }
+            req.path = request::normalize(&path);
+            req.query = query;
+            if *flag == RewriteFlag::Last {
+                break;
+            }
+        }
+    }
+}
+
+/// Substitutes `$0`..`$9` in `replacement`; `$$` is a literal `$`.
+fn expand(replacement: &str, captures: &[Option<String>]) -> String {
+    let mut out = String::with_capacity(replacement.len());
+    let mut chars = replacement.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '$' {
+            out.push(c);
+            continue;
+        }
+        match chars.peek().copied() {
+            Some('$') => {
+                chars.next();
+                out.push('$');
+            }
+            Some(d @ '0'..='9') => {
+                chars.next();
+                let index = d as usize - '0' as usize;
+                if let Some(Some(value)) = captures.get(index) {
+                    out.push_str(value);
+                }
+            }
+            _ => out.push('$'),
+        }
+    }
+    out
+}
+
+/// The `302 Found` answering a request a redirect rule matched.
+pub(crate) fn redirect(location: String) -> Response {
+    Response {
+        code: HttpCode::Found,
+        content: None,
+        headers: Some(HeaderMap::from([
+            (String::from("Location"), location),
+            (String::from("Content-Length"), String::from("0")),
+        ])),
+    }
+}
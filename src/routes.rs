@@ -0,0 +1,750 @@
+use anyhow::Result;
+use std::io;
+use std::sync::Arc;
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, Sink},
+    sync::{mpsc, oneshot, OwnedSemaphorePermit},
+};
+
+use crate::admission::{self, Admission};
+use crate::config::Config;
+use crate::date;
+use crate::h2;
+use crate::header_map::HeaderMap;
+use crate::pool::{BufferPool, PooledBuf};
+use crate::request::{HttpMethod, HttpVersion, Request};
+use crate::response::{self, Body, HttpCode, Response};
+use crate::rewrite::{self, Rewriter};
+use crate::upgrade::{self, Upgrade};
+
+pub enum CompareType {
+    Prefix,
+    Exact,
+}
+
+pub type FnRoute = Box<dyn Fn(Request, &String) -> Response + Send + Sync>;
+
+/// Continues a request down the middleware chain towards the handler.
+pub type Next<'a> = &'a dyn Fn(Request) -> Response;
+
+/// Wraps request handling for a group of routes. Implementations may answer
+/// a request themselves, or call `next` and inspect or modify its response.
+pub trait Middleware: Send + Sync {
+    fn handle(&self, req: Request, next: Next<'_>) -> Response;
+
+    /// Looks at the head of a request before its body is read or any
+    /// `103 Early Hints` are sent for it. Returning a response refuses the
+    /// request up front: a client sending `Expect: 100-continue` is spared
+    /// sending the body, and no hints go out ahead of the refusal.
+    /// Middleware that answers requests itself should refuse them here too.
+    fn expect(&self, _req: &Request) -> Option<Response> {
+        None
+    }
+
+    /// Looks at a CONNECT request before its tunnel opens. Returning a
+    /// response refuses the tunnel.
+    fn connect(&self, _req: &Request) -> Option<Response> {
+        None
+    }
+}
+
+pub struct Route {
+    pub path: String,
+    method: HttpMethod,
+    compare_type: CompareType,
+    handler: FnRoute,
+    max_body_size: Option<usize>,
+    stream_body: bool,
+}
+
+impl Route {
+    /// # Panics
+    ///
+    /// If `method` is not one of the `HttpMethod` verbs.
+    pub fn new(method: &str, path: &str, compare_type: CompareType, handler: FnRoute) -> Self {
+        Route {
+            method: method
+                .parse()
+                .unwrap_or_else(|err| panic!("route {}: {}", path, err)),
+            path: path.to_owned(),
+            compare_type,
+            handler,
+            max_body_size: None,
+            stream_body: false,
+        }
+    }
+
+    /// Overrides the server's `max_body_size` for requests to this route.
+    pub fn max_body_size(mut self, limit: usize) -> Self {
+        self.max_body_size = Some(limit);
+        self
+    }
+
+    /// Leaves the body on the connection for the handler to read through
+    /// `Request::body_stream` while the response is written, instead of
+    /// buffering it first. The body limit still applies as it arrives.
+    pub fn stream_body(mut self) -> Self {
+        self.stream_body = true;
+        self
+    }
+
+    /// Builds a route from a `routes!` pattern: a trailing `*` matches any
+    /// path with that prefix, anything else matches exactly.
+    pub fn from_pattern(method: &str, pattern: &str, handler: FnRoute) -> Self {
+        match pattern.strip_suffix('*') {
+            Some(prefix) => Route::new(method, prefix, CompareType::Prefix, handler),
+            None => Route::new(method, pattern, CompareType::Exact, handler),
+        }
+    }
+
+    pub fn matches(&self, req: &Request) -> Option<&FnRoute> {
+        if self.method == req.method && self.covers(&req.path) {
+            Some(&self.handler)
+        } else {
+            None
+        }
+    }
+
+    /// Whether this route's path matches `path`, whatever the method.
+    pub fn covers(&self, path: &str) -> bool {
+        match self.compare_type {
+            CompareType::Exact => self.path == path,
+            CompareType::Prefix => path.starts_with(&self.path),
+        }
+    }
+}
+
+/// Whether `pattern` is usable in `routes!`: it starts with `/`, has no
+/// whitespace and only uses `*` as its last character. Evaluated at
+/// compile time by the macro.
+pub const fn valid_pattern(pattern: &str) -> bool {
+    let bytes = pattern.as_bytes();
+    if bytes.is_empty() || bytes[0] != b'/' {
+        return false;
+    }
+    let mut i = 0;
+    while i < bytes.len() {
+        let b = bytes[i];
+        if b.is_ascii_whitespace() || b.is_ascii_control() || (b == b'*' && i + 1 != bytes.len()) {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// Declares routes and middleware on a route table in one block, expanding
+/// to `Routes::add` and `Routes::middleware` calls. Methods are checked
+/// against `HttpMethod` and patterns with [`valid_pattern`] at compile
+/// time. A pattern ending in `*` is a prefix match. `Route` builder calls
+/// such as `stream_body` or `max_body_size(n)` may follow the handler,
+/// separated by commas.
+///
+/// ```
+/// use std::sync::Arc;
+/// use http_server_starter_rust::{config::Config, handlers, headers::StaticHeaders};
+/// use http_server_starter_rust::{routes, routes::Routes};
+///
+/// let headers = vec![(String::from("*"), String::from("X-Frame-Options"), String::from("DENY"))];
+/// let mut table = Routes::new(&Config::default());
+/// routes! { table;
+///     use "/" => Arc::new(StaticHeaders::new(headers));
+///     GET "/echo*" => handlers::echo;
+///     GET "/user-agent" => handlers::user_agent;
+///     POST "/files*" => handlers::post_file, stream_body, max_body_size(1 << 20);
+/// }
+/// ```
+#[macro_export]
+macro_rules! routes {
+    ($routes:expr; $($items:tt)*) => {{
+        let routes: &mut $crate::routes::Routes = &mut $routes;
+        $crate::routes!(@items routes; $($items)*);
+    }};
+    (@items $routes:ident;) => {};
+    (@items $routes:ident; use $prefix:literal => $middleware:expr; $($rest:tt)*) => {
+        const _: () = assert!(
+            $crate::routes::valid_pattern($prefix),
+            concat!("invalid middleware prefix ", $prefix)
+        );
+        $routes.middleware($prefix, $middleware);
+        $crate::routes!(@items $routes; $($rest)*);
+    };
+    (@items $routes:ident;
+        $method:ident $pattern:literal => $handler:expr $(, $modifier:ident $(($($arg:expr),*))?)*;
+        $($rest:tt)*
+    ) => {
+        const _: () = assert!(
+            $crate::routes::valid_pattern($pattern),
+            concat!("invalid route pattern ", $pattern)
+        );
+        let _ = $crate::request::HttpMethod::$method;
+        $routes.add(
+            $crate::routes::Route::from_pattern(stringify!($method), $pattern, Box::new($handler))
+                $(.$modifier($($($arg),*)?))*,
+        );
+        $crate::routes!(@items $routes; $($rest)*);
+    };
+}
+
+/// Request fields TRACE leaves out of its echo, as they carry credentials.
+const TRACE_REDACTED: [&str; 3] = ["Authorization", "Proxy-Authorization", "Cookie"];
+
+pub struct Routes {
+    routes: Vec<Route>,
+    directory: String,
+    pool: Arc<BufferPool>,
+    write_queue_chunks: usize,
+    trace: bool,
+    connect: bool,
+    early_hints: Vec<(String, String)>,
+    middleware: Vec<(String, Arc<dyn Middleware>)>,
+    rewriter: Option<Rewriter>,
+    limits: Vec<(String, Admission)>,
+    retry_after: u64,
+    max_body_size: usize,
+    server_header: Option<String>,
+    upgrades: Vec<(String, Arc<dyn Upgrade>)>,
+}
+
+impl Routes {
+    pub fn new(config: &Config) -> Self {
+        let mut routes = Self {
+            routes: Vec::new(),
+            directory: config.directory.clone(),
+            pool: Arc::new(BufferPool::new(
+                config.file_chunk_size,
+                config.max_file_buffers,
+            )),
+            write_queue_chunks: config.write_queue_chunks(),
+            trace: config.trace,
+            connect: !config.connect_allow.is_empty(),
+            early_hints: config.early_hints.clone(),
+            middleware: Vec::new(),
+            rewriter: None,
+            limits: Vec::new(),
+            retry_after: config.retry_after,
+            max_body_size: config.max_body_size,
+            server_header: config.server_header.clone(),
+            upgrades: Vec::new(),
+        };
+        if config.h2c {
+            routes.upgrade("h2c", Arc::new(h2::H2c));
+        }
+        routes
+    }
+
+    pub fn add(&mut self, route: Route) {
+        self.routes.push(route);
+    }
+
+    /// Announces `link` (e.g. `</app.css>; rel=preload; as=style`) in a
+    /// `103 Early Hints` response to requests under `prefix`, so browsers can
+    /// start fetching it while the handler is still running.
+    pub fn early_hint(&mut self, prefix: &str, link: &str) {
+        self.early_hints.push((prefix.to_owned(), link.to_owned()));
+    }
+
+    /// Runs `middleware` for every request whose path starts with `prefix`.
+    /// Middleware registered first sees the request first.
+    pub fn middleware(&mut self, prefix: &str, middleware: Arc<dyn Middleware>) {
+        self.middleware.push((prefix.to_owned(), middleware));
+    }
+
+    /// Installs rewrite rules, applied by `apply_rewrites` before anything
+    /// else looks up routes or middleware for a request.
+    pub fn rewrite(&mut self, rewriter: Rewriter) {
+        self.rewriter = Some(rewriter);
+    }
+
+    /// Caps concurrent requests under `prefix`. The first matching limit
+    /// applies; requests it can't admit get a 503.
+    pub fn limit(&mut self, prefix: &str, admission: Admission) {
+        self.limits.push((prefix.to_owned(), admission));
+    }
+
+    /// Lets clients switch to `protocol` (an `Upgrade` token such as
+    /// `websocket`), handing their connection to `handler`.
+    pub fn upgrade(&mut self, protocol: &str, handler: Arc<dyn Upgrade>) {
+        self.upgrades.push((protocol.to_owned(), handler));
+    }
+
+    /// The first protocol `req` asks to switch to that has a handler.
+    pub fn upgrade_for(&self, req: &Request) -> Option<(String, Arc<dyn Upgrade>)> {
+        upgrade::requested(req).into_iter().find_map(|protocol| {
+            self.upgrades
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(&protocol))
+                .map(|(name, handler)| (name.clone(), handler.clone()))
+        })
+    }
+
+    /// Rewrites the path of a request fresh off the connection, so its body
+    /// limit, middleware and route are all picked by the rewritten path.
+    pub fn apply_rewrites(&self, req: &mut Request) {
+        if let Some(rewriter) = &self.rewriter {
+            rewriter.apply(req);
+        }
+    }
+
+    /// The largest body accepted for `req`: the limit of the route it goes
+    /// to, if that route sets one, or the server-wide one.
+    pub fn body_limit(&self, req: &Request) -> usize {
+        self.routes
+            .iter()
+            .find(|route| route.matches(req).is_some())
+            .and_then(|route| route.max_body_size)
+            .unwrap_or(self.max_body_size)
+    }
+
+    /// Whether the route `req` goes to reads its body as a stream.
+    pub fn streams_body(&self, req: &Request) -> bool {
+        self.routes
+            .iter()
+            .find(|route| route.matches(req).is_some())
+            .is_some_and(|route| route.stream_body)
+    }
+
+    /// Asks the middleware covering `req` whether it would refuse the
+    /// request from its head alone. The first refusal wins.
+    pub fn expect(&self, req: &Request) -> Option<Response> {
+        // The redirect is answered before any middleware runs.
+        if req.redirect.is_some() {
+            return None;
+        }
+        self.middleware
+            .iter()
+            .filter(|(prefix, _)| req.path.starts_with(prefix.as_str()))
+            .find_map(|(_, middleware)| middleware.expect(req))
+    }
+
+    /// Asks the middleware covering every path whether a CONNECT request
+    /// may open its tunnel; its target is an authority, not a path, so
+    /// narrower middleware doesn't see it. The first refusal wins.
+    pub(crate) fn connect(&self, req: &Request) -> Option<Response> {
+        self.middleware
+            .iter()
+            .filter(|(prefix, _)| prefix == "/")
+            .find_map(|(_, middleware)| middleware.connect(req))
+    }
+
+    /// Answers `req` on `stream`. `connection` holds hop-by-hop headers
+    /// (`Connection`, `Keep-Alive`) the caller wants on the final response.
+    /// Returns `false` when the response could only be delimited by closing
+    /// the connection.
+    pub async fn execute<W: AsyncWrite + Unpin>(
+        &self,
+        stream: &mut W,
+        req: Request,
+        connection: &[(String, String)],
+    ) -> bool {
+        // HEAD runs the GET handler; only the body is left out.
+        let head = req.method == HttpMethod::HEAD;
+        let mut req = req;
+        if head {
+            req.method = HttpMethod::GET;
+        }
+        // HTTP/1.0 clients understand neither interim responses nor chunked
+        // bodies.
+        let http11 = req.version == HttpVersion::Http11;
+        let hints = if http11 { Some(&mut *stream) } else { None };
+        // Holds any route limit slot until the response is fully written.
+        let (mut res, _permit) = self.respond(hints, req).await;
+        if head {
+            strip_body(&mut res, http11);
+        }
+        let headers = res.headers.get_or_insert_with(HeaderMap::new);
+        let reusable = http11 || !response::is_streamed(&res.content, headers);
+        for (name, value) in connection.iter() {
+            headers.insert(name.clone(), value.clone());
+        }
+        if !reusable {
+            headers.remove("Keep-Alive");
+            headers.insert(String::from("Connection"), String::from("close"));
+        }
+        self.send_response(stream, res, http11).await;
+        reusable
+    }
+
+    /// Answers `req` without writing anything, for connections that frame
+    /// the response themselves. HEAD is handled as in `execute`, and the
+    /// response is stamped with `Date` and `Server`. The permit, if any,
+    /// is to be held until the response is sent.
+    pub(crate) async fn answer(&self, req: Request) -> (Response, Option<OwnedSemaphorePermit>) {
+        let head = req.method == HttpMethod::HEAD;
+        let mut req = req;
+        if head {
+            req.method = HttpMethod::GET;
+        }
+        let (mut res, permit) = self.respond::<Sink>(None, req).await;
+        // The connection drops `Transfer-Encoding` and ends the stream with
+        // the headers, as there is no body to wait for.
+        if head {
+            strip_body(&mut res, true);
+        }
+        self.stamp(res.headers.get_or_insert_with(HeaderMap::new));
+        (res, permit)
+    }
+
+    /// Produces the final response, sending any early hints on `hints`
+    /// on the way.
+    async fn respond<W: AsyncWrite + Unpin>(
+        &self,
+        hints: Option<&mut W>,
+        req: Request,
+    ) -> (Response, Option<OwnedSemaphorePermit>) {
+        if let Some(location) = req.redirect {
+            return (rewrite::redirect(location), None);
+        }
+        let limit = self
+            .limits
+            .iter()
+            .find(|(prefix, _)| req.path.starts_with(prefix.as_str()));
+        let permit = match limit {
+            Some((_, admission)) => match admission.admit().await {
+                Some(permit) => Some(permit),
+                None => return (admission::overloaded(self.retry_after), None),
+            },
+            None => None,
+        };
+        // Hints only go out once middleware has let the request through, so
+        // a 103 is never followed by a refusal.
+        if let Some(stream) = hints {
+            if self
+                .routes
+                .iter()
+                .any(|route| route.matches(&req).is_some())
+                && self.expect(&req).is_none()
+            {
+                self.send_early_hints(stream, &req).await;
+            }
+        }
+        let chain = self
+            .middleware
+            .iter()
+            .filter(|(prefix, _)| req.path.starts_with(prefix.as_str()))
+            .map(|(_, middleware)| middleware.as_ref())
+            .collect::<Vec<&dyn Middleware>>();
+        (self.run_chain(&chain, req).resolve().await, permit)
+    }
+
+    fn run_chain(&self, chain: &[&dyn Middleware], req: Request) -> Response {
+        match chain.split_first() {
+            Some((middleware, rest)) => middleware.handle(req, &|req| self.run_chain(rest, req)),
+            None => self.dispatch(req),
+        }
+    }
+
+    fn dispatch(&self, req: Request) -> Response {
+        // With TRACE off, the request is answered like any other method no
+        // route takes: 405 with the path's methods, or 404.
+        if req.method == HttpMethod::TRACE && self.trace {
+            return self.trace(&req);
+        }
+        if req.method == HttpMethod::OPTIONS {
+            return self.options(&req.path);
+        }
+        for route in self.routes.iter() {
+            if let Some(handler) = route.matches(&req) {
+                return handler(req, &self.directory);
+            }
+        }
+        let mut allow = self.allowed(|route| route.covers(&req.path));
+        if !allow.is_empty() {
+            allow.push(HttpMethod::OPTIONS.as_str());
+            return Response {
+                code: HttpCode::MethodNotAllowed,
+                content: None,
+                headers: Some(HeaderMap::from([(String::from("Allow"), allow.join(", "))])),
+            };
+        }
+        Response {
+            code: HttpCode::NotFound,
+            content: None,
+            headers: None,
+        }
+    }
+
+    /// Answers `OPTIONS *` with the methods the server supports anywhere,
+    /// and `OPTIONS` on a path with the methods registered for it.
+    fn options(&self, path: &str) -> Response {
+        let mut allow = self.allowed(|route| path == "*" || route.covers(path));
+        if allow.is_empty() {
+            return Response {
+                code: HttpCode::NotFound,
+                content: None,
+                headers: None,
+            };
+        }
+        allow.push(HttpMethod::OPTIONS.as_str());
+        let code = if path == "*" {
+            if self.trace {
+                allow.push(HttpMethod::TRACE.as_str());
+            }
+            if self.connect {
+                allow.push(HttpMethod::CONNECT.as_str());
+            }
+            HttpCode::OK
+        } else {
+            HttpCode::NoContent
+        };
+        let mut headers = HeaderMap::from([(String::from("Allow"), allow.join(", "))]);
+        if code == HttpCode::OK {
+            headers.insert(String::from("Content-Length"), String::from("0"));
+        }
+        Response {
+            code,
+            content: None,
+            headers: Some(headers),
+        }
+    }
+
+    /// Lists, in a fixed order, the route methods registered on a route
+    /// accepted by `filter`. HEAD is included wherever GET is.
+    fn allowed(&self, filter: impl Fn(&Route) -> bool) -> Vec<&'static str> {
+        let used = |method: &HttpMethod| {
+            let method = match method {
+                HttpMethod::HEAD => &HttpMethod::GET,
+                method => method,
+            };
+            self.routes
+                .iter()
+                .any(|route| route.method == *method && filter(route))
+        };
+        [
+            HttpMethod::GET,
+            HttpMethod::HEAD,
+            HttpMethod::POST,
+            HttpMethod::PUT,
+            HttpMethod::PATCH,
+            HttpMethod::DELETE,
+            HttpMethod::PROPFIND,
+            HttpMethod::MKCOL,
+        ]
+        .into_iter()
+        .filter(used)
+        .map(|method| method.as_str())
+        .collect()
+    }
+
+    /// Echoes the request head back as `message/http`, leaving out fields
+    /// that carry credentials. This server never forwards requests, so it
+    /// always answers as the final recipient and `Max-Forwards` only has to
+    /// be a valid count.
+    fn trace(&self, req: &Request) -> Response {
+        if let Some(value) = req.headers.get("Max-Forwards") {
+            if value.trim().parse::<u32>().is_err() {
+                return Response {
+                    code: HttpCode::BadRequest,
+                    content: None,
+                    headers: None,
+                };
+            }
+        }
+        let mut body = String::new();
+        for line in req.head.lines() {
+            let name = line.split_once(':').map_or("", |(name, _)| name);
+            if !TRACE_REDACTED
+                .iter()
+                .any(|redacted| name.eq_ignore_ascii_case(redacted))
+            {
+                body.push_str(line);
+                body.push_str("\r\n");
+            }
+        }
+        body.push_str("\r\n");
+        let headers =
+            HeaderMap::from([(String::from("Content-Type"), String::from("message/http"))]);
+        Response {
+            code: HttpCode::OK,
+            content: Some(body.into()),
+            headers: Some(headers),
+        }
+    }
+
+    async fn send_early_hints<W: AsyncWrite + Unpin>(&self, stream: &mut W, req: &Request) {
+        let links = self
+            .early_hints
+            .iter()
+            .filter(|(prefix, _)| req.path.starts_with(prefix.as_str()))
+            .map(|(_, link)| link.as_str())
+            .collect::<Vec<&str>>();
+        if links.is_empty() {
+            return;
+        }
+        let hints = Response {
+            code: HttpCode::EarlyHints,
+            content: None,
+            headers: Some(HeaderMap::from([(String::from("Link"), links.join(", "))])),
+        };
+        self.send_response(stream, hints, true).await;
+    }
+
+    /// Adds the `Date` and, unless disabled, `Server` headers every
+    /// response carries, keeping any the response already set.
+    pub(crate) fn stamp(&self, headers: &mut HeaderMap) {
+        headers.get_or_insert_with("Date", date::now);
+        if let Some(server) = &self.server_header {
+            headers.get_or_insert_with("Server", || server.clone());
+        }
+    }
+
+    /// Writes `data`, stamped with `Date` and `Server`. Without
+    /// `chunked`, a streamed body is sent as is and delimited by the
+    /// connection closing.
+    async fn send_response<W: AsyncWrite + Unpin>(
+        &self,
+        stream: &mut W,
+        mut data: Response,
+        chunked: bool,
+    ) {
+        self.stamp(data.headers.get_or_insert_with(HeaderMap::new));
+        let res = self.write_response(stream, data, chunked).await;
+        if let Err(err) = res {
+            println!("Error sending response: {}", err);
+        }
+    }
+
+    async fn write_response<W: AsyncWrite + Unpin>(
+        &self,
+        stream: &mut W,
+        data: Response,
+        chunked: bool,
+    ) -> Result<()> {
+        let streamed = data
+            .headers
+            .as_ref()
+            .is_some_and(|headers| response::is_streamed(&data.content, headers));
+        let (head, body) = data.into_framed_parts(chunked);
+        stream.write_all(&head).await?;
+        match body {
+            Some(Body::Bytes(content)) => stream.write_all(&content).await?,
+            Some(Body::File(file)) => {
+                let file = tokio::fs::File::from_std(file);
+                self.stream_reader(stream, Box::new(file), false).await?
+            }
+            Some(Body::Reader(reader)) => {
+                self.stream_reader(stream, reader, chunked && streamed)
+                    .await?
+            }
+            Some(Body::Stream(chunks)) => write_chunks(stream, chunks, None, chunked).await?,
+            Some(Body::Trailed(chunks, trailers)) => {
+                write_chunks(stream, chunks, Some(trailers), chunked).await?
+            }
+            Some(Body::Deferred(_)) | None => {}
+        }
+        Ok(())
+    }
+
+    /// Pumps a file or other reader to the client, in chunked framing when
+    /// `chunked` is set. Chunks are read ahead by a separate task through a
+    /// bounded queue, so a slow client pauses the reads once
+    /// `write_queue_chunks` chunks are waiting on the socket.
+    async fn stream_reader<W: AsyncWrite + Unpin>(
+        &self,
+        stream: &mut W,
+        mut reader: Box<dyn AsyncRead + Send + Unpin>,
+        chunked: bool,
+    ) -> Result<()> {
+        let (tx, mut rx) = mpsc::channel::<io::Result<(PooledBuf, usize)>>(self.write_queue_chunks);
+        let pool = self.pool.clone();
+        tokio::spawn(async move {
+            loop {
+                let mut buf = pool.get().await;
+                let chunk = reader.read(&mut buf).await.map(|len| (buf, len));
+                let done = matches!(chunk, Ok((_, 0)) | Err(_));
+                if tx.send(chunk).await.is_err() || done {
+                    break;
+                }
+            }
+        });
+        while let Some(chunk) = rx.recv().await {
+            let (buf, len) = chunk?;
+            if len == 0 {
+                break;
+            }
+            if chunked {
+                stream
+                    .write_all(format!("{:x}\r\n", len).as_bytes())
+                    .await?;
+                stream.write_all(&buf[..len]).await?;
+                stream.write_all(b"\r\n").await?;
+            } else {
+                stream.write_all(&buf[..len]).await?;
+            }
+        }
+        if chunked {
+            stream.write_all(b"0\r\n\r\n").await?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes a streamed body as it arrives, followed by any trailer fields.
+/// Without `chunked` the chunks are written as is and the trailers are
+/// dropped, as there is no framing to carry them.
+async fn write_chunks<W: AsyncWrite + Unpin>(
+    stream: &mut W,
+    mut chunks: mpsc::Receiver<Vec<u8>>,
+    trailers: Option<oneshot::Receiver<HeaderMap>>,
+    chunked: bool,
+) -> Result<()> {
+    while let Some(chunk) = chunks.recv().await {
+        if !chunked {
+            stream.write_all(&chunk).await?;
+            continue;
+        }
+        if chunk.is_empty() {
+            continue;
+        }
+        stream
+            .write_all(format!("{:x}\r\n", chunk.len()).as_bytes())
+            .await?;
+        stream.write_all(&chunk).await?;
+        stream.write_all(b"\r\n").await?;
+    }
+    if !chunked {
+        return Ok(());
+    }
+    stream.write_all(b"0\r\n").await?;
+    if let Some(trailers) = trailers {
+        // A producer that dropped the sender just ends without trailers.
+        for (name, value) in trailers.await.unwrap_or_default() {
+            stream
+                .write_all(format!("{}: {}\r\n", name, value).as_bytes())
+                .await?;
+        }
+    }
+    stream.write_all(b"\r\n").await?;
+    Ok(())
+}
+
+/// Drops the body of a response to HEAD, keeping the headers that describe
+/// it: the length of a buffered body, or the chunked coding of a stream
+/// when `chunked` says the client would have been sent one.
+fn strip_body(res: &mut Response, chunked: bool) {
+    let headers = res.headers.get_or_insert_with(HeaderMap::new);
+    if response::is_streamed(&res.content, headers) {
+        if chunked {
+            res.content = None;
+            headers.insert(String::from("Transfer-Encoding"), String::from("chunked"));
+        } else {
+            // The GET would be delimited by closing the connection, so no
+            // length is announced either: the body is swapped for a stream
+            // that ends at once.
+            let (_, empty) = mpsc::channel(1);
+            res.content = Some(Body::Stream(empty));
+        }
+        return;
+    }
+    match res.content.take() {
+        Some(Body::Bytes(bytes)) => {
+            headers.get_or_insert_with("Content-Length", || bytes.len().to_string());
+        }
+        Some(Body::File(file)) => {
+            if let Some(len) = response::file_len(&file) {
+                headers.get_or_insert_with("Content-Length", || len.to_string());
+            }
+        }
+        Some(Body::Stream(_) | Body::Trailed(..) | Body::Reader(_) | Body::Deferred(_)) | None => {}
+    }
+}
@@ -0,0 +1,955 @@
+use anyhow::{anyhow, bail, Result};
+use bytes::{Buf, BytesMut};
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpSocket, TcpStream},
+    runtime::Builder,
+    sync::{mpsc, oneshot, watch},
+    task::{JoinHandle, JoinSet},
+    time::Instant,
+};
+
+use crate::admin;
+use crate::admission::{self, Admission};
+use crate::body::BodyStream;
+use crate::chaos::Chaos;
+use crate::config::{Config, RuntimeMode};
+use crate::control::{self, Control};
+use crate::h2;
+use crate::header_map::HeaderMap;
+use crate::mirror::Mirror;
+use crate::parser::{self, Parsed};
+use crate::record::{self, Recorder, Tee};
+use crate::request::{
+    HttpMethod, HttpVersion, InvalidHeader, InvalidHost, InvalidRequestLine, InvalidTarget,
+    Request, TlsHandshake, UnknownMethod, UnsupportedVersion,
+};
+use crate::response::{Body, HttpCode, Response};
+use crate::routes::Routes;
+use crate::systemd;
+use crate::tunnel;
+use crate::upgrade::{self, Upgraded};
+
+/// Builds a route table; called once per runtime so shards never share one.
+pub type RoutesFactory = fn(&Config, &Arc<Control>) -> Result<Routes>;
+
+/// Runs the server with the runtime layout selected in `config`, blocking
+/// the calling thread.
+pub fn run(config: Config, make_routes: RoutesFactory) -> Result<()> {
+    let config = Arc::new(config);
+    let control = Arc::new(Control::new(&config));
+    match config.runtime {
+        RuntimeMode::MultiThread => {
+            let runtime = Builder::new_multi_thread().enable_all().build()?;
+            runtime.block_on(async {
+                tokio::spawn(control::watch_signals(control.clone()));
+                tokio::spawn(systemd::watchdog(control.clone()));
+                let admin = spawn_admin(&config, &control).await?;
+                let listener = TcpListener::bind(&config.address).await?;
+                let routes = Arc::new(make_routes(&config, &control)?);
+                systemd::notify("READY=1");
+                serve_until(listener, config, routes, control.shutdown_requested()).await;
+                if let Some(admin) = admin {
+                    let _ = admin.await;
+                }
+                Ok(())
+            })
+        }
+        RuntimeMode::PerCore => run_per_core(config, control, make_routes),
+    }
+}
+
+fn run_per_core(
+    config: Arc<Config>,
+    control: Arc<Control>,
+    make_routes: RoutesFactory,
+) -> Result<()> {
+    let cores = thread::available_parallelism().map_or(1, |n| n.get());
+    let mut shards = Vec::with_capacity(cores);
+    // The last shard to finish binding reports the server as ready.
+    let ready = Arc::new(AtomicUsize::new(0));
+    for shard in 0..cores {
+        let config = config.clone();
+        let control = control.clone();
+        let ready = ready.clone();
+        let handle = thread::Builder::new()
+            .name(format!("shard-{}", shard))
+            .spawn(move || -> Result<()> {
+                let runtime = Builder::new_current_thread().enable_all().build()?;
+                runtime.block_on(async {
+                    let mut admin = None;
+                    if shard == 0 {
+                        tokio::spawn(control::watch_signals(control.clone()));
+                        tokio::spawn(systemd::watchdog(control.clone()));
+                        admin = spawn_admin(&config, &control).await?;
+                    }
+                    let listener = reuse_port_listener(&config.address)?;
+                    let routes = Arc::new(make_routes(&config, &control)?);
+                    if ready.fetch_add(1, Ordering::AcqRel) + 1 == cores {
+                        systemd::notify("READY=1");
+                    }
+                    serve_until(listener, config, routes, control.shutdown_requested()).await;
+                    if let Some(admin) = admin {
+                        let _ = admin.await;
+                    }
+                    Ok(())
+                })
+            })?;
+        shards.push(handle);
+    }
+    for handle in shards {
+        handle
+            .join()
+            .map_err(|_| anyhow!("server shard panicked"))??;
+    }
+    Ok(())
+}
+
+/// Starts the admin listener if one is configured. It shuts down with the
+/// main listeners and never records or injects faults.
+async fn spawn_admin(
+    config: &Arc<Config>,
+    control: &Arc<Control>,
+) -> Result<Option<JoinHandle<()>>> {
+    let Some(address) = &config.admin_address else {
+        return Ok(None);
+    };
+    let listener = TcpListener::bind(address).await?;
+    let routes = Arc::new(admin::routes(config, control));
+    let admin_config = Arc::new(config.admin());
+    let control = control.clone();
+    Ok(Some(tokio::spawn(async move {
+        serve_until(listener, admin_config, routes, control.shutdown_requested()).await
+    })))
+}
+
+/// Binds a listener with `SO_REUSEPORT` so every shard can accept on the
+/// same address and let the kernel balance connections between them.
+fn reuse_port_listener(address: &str) -> Result<TcpListener> {
+    let addr = address.parse::<SocketAddr>()?;
+    let socket = match addr {
+        SocketAddr::V4(_) => TcpSocket::new_v4()?,
+        SocketAddr::V6(_) => TcpSocket::new_v6()?,
+    };
+    socket.set_reuseaddr(true)?;
+    #[cfg(unix)]
+    socket.set_reuseport(true)?;
+    socket.bind(addr)?;
+    Ok(socket.listen(1024)?)
+}
+
+/// Accepts connections forever, handling each on its own task.
+pub async fn serve(listener: TcpListener, config: Arc<Config>, routes: Arc<Routes>) {
+    serve_until(listener, config, routes, std::future::pending()).await
+}
+
+/// What every connection accepted by one listener shares.
+struct Listener {
+    config: Arc<Config>,
+    routes: Arc<Routes>,
+    recorder: Option<Recorder>,
+    chaos: Option<Chaos>,
+    mirror: Option<Mirror>,
+    admission: Option<Arc<Admission>>,
+    /// Flips to true when the listener shuts down, so idle persistent
+    /// connections close instead of waiting for another request.
+    closing: watch::Receiver<bool>,
+}
+
+/// Accepts connections until `shutdown` resolves, then stops listening and
+/// waits up to `shutdown_timeout` for in-flight connections to finish.
+pub async fn serve_until(
+    listener: TcpListener,
+    config: Arc<Config>,
+    routes: Arc<Routes>,
+    shutdown: impl Future<Output = ()>,
+) {
+    let recorder = match config.record.as_deref().map(Recorder::new) {
+        Some(Ok(recorder)) => Some(recorder),
+        Some(Err(err)) => {
+            println!("error opening record directory: {}", err);
+            None
+        }
+        None => None,
+    };
+    let (closing, closing_rx) = watch::channel(false);
+    let shared = Arc::new(Listener {
+        chaos: Chaos::new(&config),
+        mirror: Mirror::new(&config),
+        admission: (config.max_concurrency > 0).then(|| {
+            Arc::new(Admission::new(
+                config.max_concurrency,
+                config.queue_depth,
+                config.queue_timeout,
+            ))
+        }),
+        config,
+        routes,
+        recorder,
+        closing: closing_rx,
+    });
+    let config = &shared.config;
+    let mut connections = JoinSet::new();
+    tokio::pin!(shutdown);
+    loop {
+        tokio::select! {
+            _ = &mut shutdown => break,
+            Some(_) = connections.join_next(), if !connections.is_empty() => {}
+            accepted = listener.accept() => match accepted {
+                Ok((stream, peer)) => {
+                    if config.log_requests {
+                        println!("accepted new connection from {}", peer);
+                    }
+                    connections.spawn(handle_connection(stream, peer, shared.clone()));
+                }
+                Err(e) => println!("Error: {}", e),
+            },
+        }
+    }
+
+    drop(listener);
+    closing.send_replace(true);
+    if !connections.is_empty() {
+        println!("draining {} connections", connections.len());
+    }
+    let drain = async { while connections.join_next().await.is_some() {} };
+    if tokio::time::timeout(config.shutdown_timeout, drain)
+        .await
+        .is_err()
+    {
+        println!(
+            "closing {} connections still open after {:?}",
+            connections.len(),
+            config.shutdown_timeout
+        );
+        connections.shutdown().await;
+    }
+}
+
+/// Serves requests off one connection until the client or the server ends
+/// it, or it sits idle for longer than `keep_alive_timeout`.
+async fn handle_connection(mut stream: TcpStream, peer: SocketAddr, shared: Arc<Listener>) {
+    let config = &shared.config;
+    let routes = &shared.routes;
+    let mut closing = shared.closing.clone();
+    let mut buf = BytesMut::with_capacity(config.initial_buffer_size);
+    if config.h2c {
+        let sniff = h2::sniff(&mut stream, &mut buf);
+        match tokio::time::timeout(config.header_timeout, sniff).await {
+            Ok(Ok(true)) => {
+                let (config, routes) = (config.clone(), routes.clone());
+                let admission = shared.admission.clone();
+                return h2::serve(stream, buf, config, routes, admission, closing).await;
+            }
+            Ok(Ok(false)) => {}
+            Ok(Err(_)) => return,
+            Err(_) if buf.is_empty() => return,
+            Err(_) => return reject(&mut stream, routes, HttpCode::RequestTimeout).await,
+        }
+    }
+    let mut served = 0;
+    loop {
+        if served > 0 && buf.is_empty() {
+            // Between requests the connection is idle and can be dropped.
+            let next = fill_buf(&mut stream, &mut buf, config.max_header_size);
+            tokio::select! {
+                filled = tokio::time::timeout(config.keep_alive_timeout, next) => match filled {
+                    Ok(Ok(filled)) if filled > 0 => {}
+                    _ => return,
+                },
+                _ = closing.wait_for(|closing| *closing) => return,
+            }
+        }
+        let read = read_request(&mut stream, &mut buf, config, routes).await;
+        let (mut req, pending) = match read {
+            Ok(Some(read)) => read,
+            Ok(None) => return,
+            Err(err) => {
+                println!("error read request: {}", err);
+                if let Some(code) = rejection(&err) {
+                    reject(&mut stream, routes, code).await;
+                } else if err.is::<TlsHandshake>() {
+                    let message =
+                        "This is a plain HTTP port; connect with http:// instead of https://.\n";
+                    reject_with(&mut stream, routes, HttpCode::BadRequest, Some(message)).await;
+                }
+                return;
+            }
+        };
+        served += 1;
+        req.set_remote(peer, &config.trusted_proxies);
+        if config.log_requests {
+            println!("{:?}", req);
+        }
+        if let Some((protocol, handler)) = routes.upgrade_for(&req) {
+            if let Some(headers) = handler.accept(&req) {
+                if upgrade::switch(&mut stream, routes, &protocol, headers).await {
+                    let upgraded = Upgraded {
+                        req,
+                        stream,
+                        buf,
+                        config: config.clone(),
+                        routes: routes.clone(),
+                        admission: shared.admission.clone(),
+                        closing,
+                    };
+                    handler.run(upgraded).await;
+                }
+                return;
+            }
+        }
+        let _permit = match &shared.admission {
+            Some(admission) => match admission.admit().await {
+                Some(permit) => Some(permit),
+                None => return shed(&mut stream, config, routes).await,
+            },
+            None => None,
+        };
+        if req.method == HttpMethod::CONNECT {
+            tunnel::connect(&mut stream, &buf, &req, config, routes).await;
+            return;
+        }
+        if let Some(mirror) = &shared.mirror {
+            mirror.send(&req);
+        }
+        let keep_alive =
+            served < config.keep_alive_max && !*closing.borrow() && wants_keep_alive(&req);
+        let connection = connection_headers(config, keep_alive, served);
+        let req = match &shared.chaos {
+            Some(chaos) => match chaos.inject(&mut stream, routes, req).await {
+                Some(req) => req,
+                None => return,
+            },
+            None => req,
+        };
+
+        // A streamed body is read while the handler runs and the response
+        // goes out.
+        let (mut reader, mut writer) = stream.split();
+        let feed = async {
+            match pending {
+                Some(pending) => pending.feed(&mut reader, &mut buf, config).await,
+                None => true,
+            }
+        };
+        let respond = async {
+            match &shared.recorder {
+                Some(recorder) => {
+                    let request = record::request_bytes(&req);
+                    let mut tee = Tee::new(&mut writer);
+                    let reusable = routes.execute(&mut tee, req, &connection).await;
+                    recorder.save(&request, &tee.written).await;
+                    reusable
+                }
+                None => routes.execute(&mut writer, req, &connection).await,
+            }
+        };
+        let (reusable, complete) = tokio::join!(respond, feed);
+        if !keep_alive || !reusable || !complete {
+            return;
+        }
+    }
+}
+
+/// HTTP/1.1 connections persist unless the client sends `Connection:
+/// close`; HTTP/1.0 ones only when it asks for `keep-alive`.
+fn wants_keep_alive(req: &Request) -> bool {
+    let options = req
+        .headers
+        .get("Connection")
+        .map(|value| value.to_ascii_lowercase())
+        .unwrap_or_default();
+    let has = |option: &str| options.split(',').any(|o| o.trim() == option);
+    if req.version == HttpVersion::Http10 {
+        has("keep-alive")
+    } else {
+        !has("close")
+    }
+}
+
+/// The `Connection` and `Keep-Alive` headers for the response to the
+/// `served`-th request on a connection.
+fn connection_headers(config: &Config, keep_alive: bool, served: usize) -> Vec<(String, String)> {
+    if !keep_alive {
+        return vec![(String::from("Connection"), String::from("close"))];
+    }
+    vec![
+        (String::from("Connection"), String::from("keep-alive")),
+        (
+            String::from("Keep-Alive"),
+            format!(
+                "timeout={}, max={}",
+                config.keep_alive_timeout.as_secs(),
+                config.keep_alive_max - served
+            ),
+        ),
+    ]
+}
+
+/// Refuses a request the server has no capacity for.
+async fn shed(stream: &mut TcpStream, config: &Config, routes: &Routes) {
+    if config.log_requests {
+        println!("shedding request: over capacity");
+    }
+    let mut overloaded = admission::overloaded(config.retry_after);
+    if let Some(headers) = overloaded.headers.as_mut() {
+        headers.insert(String::from("Connection"), String::from("close"));
+        routes.stamp(headers);
+    }
+    let (head, _) = overloaded.into_parts();
+    let _ = stream.write_all(&head).await;
+}
+
+/// Answers a request the server can't handle at all. The rest of it is
+/// left unread, so the connection is closed.
+async fn reject(stream: &mut TcpStream, routes: &Routes, code: HttpCode) {
+    reject_with(stream, routes, code, None).await
+}
+
+/// Like `reject`, with a plain-text `message` saying what went wrong.
+async fn reject_with(
+    stream: &mut TcpStream,
+    routes: &Routes,
+    code: HttpCode,
+    message: Option<&str>,
+) {
+    let mut headers = HeaderMap::from([(String::from("Connection"), String::from("close"))]);
+    if message.is_some() {
+        headers.insert(
+            String::from("Content-Type"),
+            String::from("text/plain; charset=utf-8"),
+        );
+    }
+    routes.stamp(&mut headers);
+    let response = Response {
+        code,
+        content: message.map(|message| Body::Bytes(message.as_bytes().to_vec())),
+        headers: Some(headers),
+    };
+    let (head, body) = response.into_parts();
+    let _ = stream.write_all(&head).await;
+    if let Some(Body::Bytes(body)) = body {
+        let _ = stream.write_all(&body).await;
+    }
+}
+
+/// The status answering a request that couldn't be read for `err`, or
+/// `None` when nothing should be sent. Handlers reading a streamed body get
+/// the same errors from it.
+pub(crate) fn rejection(err: &anyhow::Error) -> Option<HttpCode> {
+    if err.is::<UnknownMethod>() || err.is::<UnsupportedTransferCoding>() {
+        Some(HttpCode::NotImplemented)
+    } else if err.is::<UnsupportedVersion>() {
+        Some(HttpCode::HttpVersionNotSupported)
+    } else if err.is::<ContentTooLarge>() {
+        Some(HttpCode::ContentTooLarge)
+    } else if err.is::<InvalidRequestLine>()
+        || err.is::<InvalidTarget>()
+        || err.is::<InvalidHost>()
+        || err.is::<InvalidHeader>()
+        || err.is::<InvalidContentLength>()
+        || err.is::<InvalidTransferEncoding>()
+        || err.is::<InvalidChunk>()
+    {
+        Some(HttpCode::BadRequest)
+    } else if err.is::<HeaderFieldsTooLarge>() {
+        Some(HttpCode::RequestHeaderFieldsTooLarge)
+    } else if err.is::<UriTooLong>() {
+        Some(HttpCode::UriTooLong)
+    } else if err.is::<ExpectationFailed>() {
+        Some(HttpCode::ExpectationFailed)
+    } else if err.is::<RequestTimeout>() {
+        Some(HttpCode::RequestTimeout)
+    } else {
+        None
+    }
+}
+
+/// A request body, declared or as received, over the limit for its route.
+#[derive(Debug, thiserror::Error)]
+#[error("request body exceeds {0} bytes")]
+struct ContentTooLarge(usize);
+
+/// A `Content-Length` that isn't a single non-negative number.
+#[derive(Debug, thiserror::Error)]
+#[error("invalid Content-Length {0:?}")]
+struct InvalidContentLength(String);
+
+/// A `Transfer-Encoding` sent more than once, or that doesn't end in a
+/// single `chunked`.
+#[derive(Debug, thiserror::Error)]
+#[error("invalid Transfer-Encoding {0:?}")]
+struct InvalidTransferEncoding(String);
+
+/// A chunk whose size isn't 1*HEXDIG, or whose data isn't followed by
+/// CRLF.
+#[derive(Debug, thiserror::Error)]
+#[error("invalid chunk: {0}")]
+struct InvalidChunk(String);
+
+/// A transfer coding the server can't decode.
+#[derive(Debug, thiserror::Error)]
+#[error("unsupported transfer coding {0:?}")]
+struct UnsupportedTransferCoding(String);
+
+/// A request head over `max_header_size`, `max_header_count` or
+/// `max_header_line`.
+#[derive(Debug, thiserror::Error)]
+#[error("request header fields too large: {0}")]
+struct HeaderFieldsTooLarge(String);
+
+/// A request target longer than `max_uri_length`.
+#[derive(Debug, thiserror::Error)]
+#[error("request target exceeds {0} bytes")]
+struct UriTooLong(usize);
+
+/// An `Expect` header asking for anything but `100-continue`.
+#[derive(Debug, thiserror::Error)]
+#[error("unsupported expectation {0:?}")]
+struct ExpectationFailed(String);
+
+/// A request head or body that didn't arrive within `header_timeout` or
+/// `body_timeout`.
+#[derive(Debug, thiserror::Error)]
+#[error("request {0} not received within {1:?}")]
+struct RequestTimeout(&'static str, Duration);
+
+/// Reads the next request from `stream`, keeping any bytes that arrive
+/// after it in `buf` for the following call. Returns `None` when the
+/// client closes the connection between requests, or sends nothing at all
+/// within `header_timeout`.
+///
+/// Requests to routes that stream their body come back with the body still
+/// to be read through the returned `PendingBody`.
+///
+/// A client sending `Expect: 100-continue` waits for `100 Continue` before
+/// the body; `routes` may refuse the request instead, and the refusal is
+/// written here.
+pub async fn read_request(
+    stream: &mut TcpStream,
+    buf: &mut BytesMut,
+    config: &Config,
+    routes: &Routes,
+) -> Result<Option<(Request, Option<PendingBody>)>> {
+    let head_deadline = Instant::now() + config.header_timeout;
+    let (mut req, head_len) = loop {
+        // Checked as the request line arrives, so an oversized target is
+        // refused without buffering all of it.
+        if target_len(buf) > config.max_uri_length {
+            bail!(UriTooLong(config.max_uri_length));
+        }
+        match Request::parse_prefix(buf)? {
+            Parsed::Complete(req, len) => {
+                check_header_fields(&buf[..len], config)?;
+                break (req, len);
+            }
+            Parsed::Incomplete => check_header_fields(buf, config)?,
+        }
+        if buf.len() >= config.max_header_size {
+            bail!(HeaderFieldsTooLarge(format!(
+                "head exceeds {} bytes",
+                config.max_header_size
+            )));
+        }
+        let fill = fill_buf(stream, buf, config.max_header_size);
+        let filled = match tokio::time::timeout_at(head_deadline, fill).await {
+            Ok(filled) => filled?,
+            Err(_) if buf.is_empty() => return Ok(None),
+            Err(_) => bail!(RequestTimeout("head", config.header_timeout)),
+        };
+        if filled == 0 {
+            if buf.is_empty() {
+                return Ok(None);
+            }
+            bail!("connection closed before the request was complete");
+        }
+    };
+
+    routes.apply_rewrites(&mut req);
+    let max_body_size = routes.body_limit(&req);
+    let lengths = req
+        .headers
+        .get_all("Content-Length")
+        .map(String::as_str)
+        .collect::<Vec<&str>>();
+    let content_length = if lengths.is_empty() {
+        0
+    } else {
+        content_length(&lengths.join(", "))?
+    };
+    if content_length > max_body_size {
+        bail!(ContentTooLarge(max_body_size));
+    }
+    let framing = match req.headers.get("Transfer-Encoding") {
+        Some(codings) => {
+            if req.headers.get_all("Transfer-Encoding").count() > 1 {
+                bail!(InvalidTransferEncoding(codings.clone()));
+            }
+            transfer_codings(codings)?;
+            // The chunked framing wins over any Content-Length sent
+            // alongside, but such a request may be framed differently by an
+            // intermediary, so the connection isn't reused after it.
+            if req.headers.remove("Content-Length").is_some() {
+                req.headers
+                    .insert(String::from("Connection"), String::from("close"));
+            }
+            Framing::Chunked
+        }
+        None => Framing::Length(content_length),
+    };
+    if let Some(expect) = req.headers.get("Expect") {
+        if !expect.trim().eq_ignore_ascii_case("100-continue") {
+            bail!(ExpectationFailed(expect.clone()));
+        }
+        let has_body = matches!(framing, Framing::Chunked) || content_length > 0;
+        // Nothing to wait for if the body is already on its way.
+        if has_body && buf.len() == head_len && req.version == HttpVersion::Http11 {
+            if let Some(mut refusal) = routes.expect(&req) {
+                let headers = refusal.headers.get_or_insert_with(HeaderMap::new);
+                headers.insert(String::from("Connection"), String::from("close"));
+                routes.stamp(headers);
+                let (head, body) = refusal.into_parts();
+                stream.write_all(&head).await?;
+                if let Some(Body::Bytes(body)) = body {
+                    stream.write_all(&body).await?;
+                }
+                bail!(
+                    "{} {} refused before its body was sent",
+                    req.method.as_str(),
+                    req.path
+                );
+            }
+            stream.write_all(b"HTTP/1.1 100 Continue\r\n\r\n").await?;
+        }
+    }
+    let mut data = buf.split_to(head_len);
+    if routes.streams_body(&req) {
+        if config.log_requests {
+            println!("{:?}", String::from_utf8_lossy(&data));
+        }
+        let (sender, trailers, body) = BodyStream::channel();
+        req.body_stream = Some(body);
+        let pending = PendingBody {
+            framing,
+            max_body_size,
+            sender,
+            trailers,
+        };
+        return Ok(Some((req, Some(pending))));
+    }
+    let mut sink = BodySink::Buffer(Vec::new());
+    let read = read_body(stream, buf, &framing, max_body_size, config, &mut sink);
+    req.trailers = match tokio::time::timeout(config.body_timeout, read).await {
+        Ok(trailers) => trailers?,
+        Err(_) => bail!(RequestTimeout("body", config.body_timeout)),
+    };
+    if let BodySink::Buffer(body) = sink {
+        req.body = body;
+    }
+    if config.log_requests {
+        data.extend_from_slice(&req.body);
+        println!("{:?}", String::from_utf8_lossy(&data));
+    }
+    Ok(Some((req, None)))
+}
+
+/// How the body of a request is delimited on the connection.
+enum Framing {
+    Length(usize),
+    Chunked,
+}
+
+/// Where the body goes as it is read off the connection.
+enum BodySink {
+    Buffer(Vec<u8>),
+    Stream(mpsc::Sender<Result<Vec<u8>>>),
+}
+
+impl BodySink {
+    async fn push(&mut self, data: &[u8]) -> Result<()> {
+        match self {
+            BodySink::Buffer(body) => body.extend_from_slice(data),
+            BodySink::Stream(sender) => {
+                if sender.send(Ok(data.to_vec())).await.is_err() {
+                    bail!("handler stopped reading the request body");
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The body of a request to a route that streams it, still to be read off
+/// the connection.
+pub struct PendingBody {
+    framing: Framing,
+    max_body_size: usize,
+    sender: mpsc::Sender<Result<Vec<u8>>>,
+    trailers: oneshot::Sender<HeaderMap>,
+}
+
+impl PendingBody {
+    /// Reads the body off `stream` into the request's `BodyStream`, passing
+    /// on any error. Returns whether the whole body was read, leaving the
+    /// connection at the next request.
+    pub async fn feed<S: AsyncRead + Unpin>(
+        self,
+        stream: &mut S,
+        buf: &mut BytesMut,
+        config: &Config,
+    ) -> bool {
+        let mut sink = BodySink::Stream(self.sender.clone());
+        let read = read_body(
+            stream,
+            buf,
+            &self.framing,
+            self.max_body_size,
+            config,
+            &mut sink,
+        );
+        let read = async {
+            match tokio::time::timeout(config.body_timeout, read).await {
+                Ok(read) => read,
+                Err(_) => bail!(RequestTimeout("body", config.body_timeout)),
+            }
+        };
+        match read.await {
+            Ok(trailers) => {
+                let _ = self.trailers.send(trailers);
+                true
+            }
+            Err(err) => {
+                let _ = self.sender.send(Err(err)).await;
+                false
+            }
+        }
+    }
+}
+
+/// Reads a body framed as `framing` from the start of `buf` into `sink`,
+/// consuming it from `buf` as it goes. Returns the trailer fields of a
+/// chunked body.
+async fn read_body<S: AsyncRead + Unpin>(
+    stream: &mut S,
+    buf: &mut BytesMut,
+    framing: &Framing,
+    max_body_size: usize,
+    config: &Config,
+    sink: &mut BodySink,
+) -> Result<HeaderMap> {
+    match framing {
+        Framing::Length(length) => {
+            read_data(stream, buf, *length, config, sink).await?;
+            Ok(HeaderMap::new())
+        }
+        Framing::Chunked => read_chunked(stream, buf, max_body_size, config, sink).await,
+    }
+}
+
+/// Parses `Content-Length`, given as a list when the header was sent more
+/// than once or with several values. The list is only accepted when every
+/// value is the same.
+fn content_length(value: &str) -> Result<usize> {
+    let invalid = || InvalidContentLength(value.to_owned());
+    let mut lengths = value.split(',').map(str::trim);
+    let first = lengths.next().unwrap_or("");
+    if first.is_empty() || !first.bytes().all(|b| b.is_ascii_digit()) {
+        bail!(invalid());
+    }
+    if lengths.any(|length| length != first) {
+        bail!(invalid());
+    }
+    Ok(first.parse().map_err(|_| invalid())?)
+}
+
+/// Checks that `Transfer-Encoding` lists only `chunked`, the one coding the
+/// server decodes, and that it comes last so the body's end can be found.
+fn transfer_codings(value: &str) -> Result<()> {
+    let codings = value
+        .split(',')
+        .map(|coding| coding.trim().to_ascii_lowercase())
+        .collect::<Vec<_>>();
+    if let Some(unknown) = codings.iter().find(|coding| *coding != "chunked") {
+        if unknown.is_empty() || !unknown.bytes().all(parser::is_tchar) {
+            bail!(InvalidTransferEncoding(value.to_owned()));
+        }
+        bail!(UnsupportedTransferCoding(unknown.clone()));
+    }
+    if codings.len() > 1 {
+        bail!(InvalidTransferEncoding(value.to_owned()));
+    }
+    Ok(())
+}
+
+/// Checks the header lines in `head` against `max_header_count` and
+/// `max_header_line`, including a last line that is still arriving.
+fn check_header_fields(head: &[u8], config: &Config) -> Result<()> {
+    let lines = head
+        .split(|&b| b == b'\n')
+        .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+        .skip_while(|line| line.is_empty())
+        .skip(1);
+    for (count, line) in lines.enumerate() {
+        if line.is_empty() {
+            break;
+        }
+        if count == config.max_header_count {
+            bail!(HeaderFieldsTooLarge(format!(
+                "more than {} fields",
+                config.max_header_count
+            )));
+        }
+        if line.len() > config.max_header_line {
+            bail!(HeaderFieldsTooLarge(format!(
+                "field exceeds {} bytes",
+                config.max_header_line
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Length of the request target received so far: the bytes between the
+/// first space of the request line and the next space or line end.
+fn target_len(buf: &[u8]) -> usize {
+    let Some(start) = buf.iter().position(|&b| b == b' ') else {
+        return 0;
+    };
+    buf[start + 1..]
+        .iter()
+        .position(|&b| b == b' ' || b == b'\r' || b == b'\n')
+        .unwrap_or(buf.len() - start - 1)
+}
+
+/// Decodes a chunked body at the start of `buf`, reading more as needed.
+/// Chunk extensions are skipped. Returns the trailer fields.
+async fn read_chunked<S: AsyncRead + Unpin>(
+    stream: &mut S,
+    buf: &mut BytesMut,
+    max_body_size: usize,
+    config: &Config,
+    sink: &mut BodySink,
+) -> Result<HeaderMap> {
+    let mut received: usize = 0;
+    loop {
+        let line = read_line(stream, buf, config.max_header_size).await?;
+        // `from_str_radix` would also take a sign.
+        let size = line.split(';').next().unwrap_or("").trim_end();
+        if size.is_empty() || !size.bytes().all(|b| b.is_ascii_hexdigit()) {
+            bail!(InvalidChunk(format!("size {:?}", line)));
+        }
+        let size = usize::from_str_radix(size, 16)
+            .map_err(|_| InvalidChunk(format!("size {:?}", line)))?;
+        if size == 0 {
+            break;
+        }
+        received = match received.checked_add(size) {
+            Some(received) if received <= max_body_size => received,
+            _ => bail!(ContentTooLarge(max_body_size)),
+        };
+        read_data(stream, buf, size, config, sink).await?;
+        while buf.len() < 2 {
+            if fill_buf(stream, buf, config.max_header_size).await? == 0 {
+                bail!("connection closed before the request was complete");
+            }
+        }
+        if &buf[..2] != b"\r\n" {
+            bail!(InvalidChunk(format!("{} bytes not followed by CRLF", size)));
+        }
+        buf.advance(2);
+    }
+    // Trailer fields are held to the same limits as the head's.
+    let mut trailers = HeaderMap::new();
+    let (mut count, mut size) = (0, 0);
+    loop {
+        let trailer = read_line(stream, buf, config.max_header_size).await?;
+        if trailer.is_empty() {
+            return Ok(trailers);
+        }
+        if count == config.max_header_count {
+            bail!(HeaderFieldsTooLarge(format!(
+                "more than {} trailer fields",
+                config.max_header_count
+            )));
+        }
+        count += 1;
+        if trailer.len() > config.max_header_line {
+            bail!(HeaderFieldsTooLarge(format!(
+                "trailer field exceeds {} bytes",
+                config.max_header_line
+            )));
+        }
+        size += trailer.len() + 2;
+        if size > config.max_header_size {
+            bail!(HeaderFieldsTooLarge(format!(
+                "trailer exceeds {} bytes",
+                config.max_header_size
+            )));
+        }
+        let Some((name, value)) = trailer.split_once(':') else {
+            bail!("malformed trailer field: {}", trailer);
+        };
+        trailers.insert(name.trim().to_owned(), value.trim().to_owned());
+    }
+}
+
+/// Passes the next `len` bytes to `sink` as they arrive.
+async fn read_data<S: AsyncRead + Unpin>(
+    stream: &mut S,
+    buf: &mut BytesMut,
+    mut len: usize,
+    config: &Config,
+    sink: &mut BodySink,
+) -> Result<()> {
+    while len > 0 {
+        if buf.is_empty() && fill_buf(stream, buf, len.min(config.max_body_size)).await? == 0 {
+            bail!("connection closed before the request was complete");
+        }
+        let data = buf.split_to(len.min(buf.len()));
+        len -= data.len();
+        sink.push(&data).await?;
+    }
+    Ok(())
+}
+
+/// Takes the line at the start of `buf` and its CRLF off it, returning the
+/// line without the CRLF.
+async fn read_line<S: AsyncRead + Unpin>(
+    stream: &mut S,
+    buf: &mut BytesMut,
+    limit: usize,
+) -> Result<String> {
+    loop {
+        if let Some(end) = buf.windows(2).position(|window| window == b"\r\n") {
+            let line = buf.split_to(end + 2);
+            return Ok(String::from_utf8(line[..end].to_vec())?);
+        }
+        if buf.len() >= limit {
+            bail!("chunked request body exceeds its limit");
+        }
+        if fill_buf(stream, buf, limit).await? == 0 {
+            bail!("connection closed before the request was complete");
+        }
+    }
+}
+
+/// Reads more data into `buf`, doubling its capacity when full but never
+/// growing it past `limit` bytes. Returns how much was read; 0 means the
+/// client closed the connection.
+async fn fill_buf<S: AsyncRead + Unpin>(
+    stream: &mut S,
+    buf: &mut BytesMut,
+    limit: usize,
+) -> Result<usize> {
+    if buf.len() == buf.capacity() {
+        let additional = buf.capacity().min(limit.saturating_sub(buf.len())).max(1);
+        buf.reserve(additional);
+    }
+    Ok(stream.read_buf(buf).await?)
+}
@@ -0,0 +1,70 @@
+//! `sd_notify` support for running as a systemd `Type=notify` service.
+//! Everything here is a no-op unless systemd set `NOTIFY_SOCKET`.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::control::Control;
+
+/// Sends `state` (e.g. `READY=1`) to the service manager, if there is one.
+/// Failures are logged and otherwise ignored: notification must never take
+/// the server down.
+pub fn notify(state: &str) {
+    let Some(socket) = std::env::var_os("NOTIFY_SOCKET") else {
+        return;
+    };
+    if let Err(err) = send(&socket, state) {
+        println!("error notifying systemd: {}", err);
+    }
+}
+
+#[cfg(unix)]
+fn send(socket: &std::ffi::OsStr, state: &str) -> std::io::Result<()> {
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::net::UnixDatagram;
+
+    let datagram = UnixDatagram::unbound()?;
+    match socket.as_bytes().strip_prefix(b"@") {
+        #[cfg(target_os = "linux")]
+        Some(name) => {
+            use std::os::linux::net::SocketAddrExt;
+            use std::os::unix::net::SocketAddr;
+
+            let address = SocketAddr::from_abstract_name(name)?;
+            datagram.send_to_addr(state.as_bytes(), &address)?;
+        }
+        _ => {
+            datagram.send_to(state.as_bytes(), socket)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn send(_socket: &std::ffi::OsStr, _state: &str) -> std::io::Result<()> {
+    Ok(())
+}
+
+/// The watchdog timeout systemd expects this process to honor, from
+/// `WATCHDOG_USEC` (and `WATCHDOG_PID`, when it is set).
+fn watchdog_timeout() -> Option<Duration> {
+    if let Ok(pid) = std::env::var("WATCHDOG_PID") {
+        if pid.parse::<u32>().ok()? != std::process::id() {
+            return None;
+        }
+    }
+    let usec = std::env::var("WATCHDOG_USEC").ok()?.parse::<u64>().ok()?;
+    (usec > 0).then(|| Duration::from_micros(usec))
+}
+
+/// Pings the systemd watchdog at half its timeout for as long as the
+/// runtime is up. Returns immediately when no watchdog is configured.
+pub async fn watchdog(control: Arc<Control>) {
+    let Some(timeout) = watchdog_timeout() else {
+        return;
+    };
+    loop {
+        control.clock().sleep(timeout / 2).await;
+        notify("WATCHDOG=1");
+    }
+}
@@ -0,0 +1,125 @@
+use tokio::io::{self, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use crate::config::Config;
+use crate::header_map::HeaderMap;
+use crate::request::Request;
+use crate::response::{Body, HttpCode, Response};
+use crate::routes::Routes;
+
+/// Handles a CONNECT request: dials the requested `host:port` when it is on
+/// the allow-list and no middleware refuses it, then relays bytes both ways
+/// until either side closes. `early` holds whatever the client sent after
+/// the request head, which belongs to the tunnel.
+pub async fn connect(
+    stream: &mut TcpStream,
+    early: &[u8],
+    req: &Request,
+    config: &Config,
+    routes: &Routes,
+) {
+    let target = req.path.as_str();
+    if !config
+        .connect_allow
+        .iter()
+        .any(|rule| allowed(rule, target))
+    {
+        refuse(stream, routes, HttpCode::Forbidden).await;
+        return;
+    }
+    if let Some(refusal) = routes.connect(req) {
+        refuse_with(stream, routes, refusal).await;
+        return;
+    }
+    let dial = TcpStream::connect(target);
+    let mut upstream = match tokio::time::timeout(config.connect_timeout, dial).await {
+        Ok(Ok(upstream)) => upstream,
+        Ok(Err(err)) => {
+            println!("error connecting tunnel to {}: {}", target, err);
+            refuse(stream, routes, HttpCode::BadGateway).await;
+            return;
+        }
+        Err(_) => {
+            println!(
+                "error connecting tunnel to {}: no answer within {:?}",
+                target, config.connect_timeout
+            );
+            refuse(stream, routes, HttpCode::GatewayTimeout).await;
+            return;
+        }
+    };
+    if let Err(err) = established(stream, routes).await {
+        println!("Error sending response: {}", err);
+        return;
+    }
+    if let Err(err) = upstream.write_all(early).await {
+        println!("error relaying tunnel to {}: {}", target, err);
+        return;
+    }
+    match io::copy_bidirectional(stream, &mut upstream).await {
+        Ok((sent, received)) => {
+            if config.log_requests {
+                println!(
+                    "tunnel to {} closed ({} bytes sent, {} bytes received)",
+                    target,
+                    sent + early.len() as u64,
+                    received
+                );
+            }
+        }
+        Err(err) => println!("error relaying tunnel to {}: {}", target, err),
+    }
+}
+
+/// Writes the 2xx that turns the connection into a tunnel. It is written by
+/// hand because a 2xx to CONNECT must not carry `Content-Length` or
+/// `Transfer-Encoding` (RFC 9110 §9.3.6), which `Response` would add.
+async fn established(stream: &mut TcpStream, routes: &Routes) -> io::Result<()> {
+    let mut headers = HeaderMap::new();
+    routes.stamp(&mut headers);
+    let mut head = format!("HTTP/1.1 {}\r\n", HttpCode::OK);
+    for (name, value) in headers.iter() {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    head.push_str("\r\n");
+    stream.write_all(head.as_bytes()).await
+}
+
+/// Answers a CONNECT that won't be tunneled with an empty `code`.
+async fn refuse(stream: &mut TcpStream, routes: &Routes, code: HttpCode) {
+    refuse_with(stream, routes, Response::builder().status(code).build()).await
+}
+
+/// Answers a CONNECT that won't be tunneled with `res`, leaving the
+/// connection to be closed.
+async fn refuse_with(stream: &mut TcpStream, routes: &Routes, mut res: Response) {
+    let headers = res.headers.get_or_insert_with(HeaderMap::new);
+    headers.insert(String::from("Connection"), String::from("close"));
+    routes.stamp(headers);
+    let (head, body) = res.into_parts();
+    let mut sent = stream.write_all(&head).await;
+    if let (Ok(_), Some(Body::Bytes(body))) = (&sent, body) {
+        sent = stream.write_all(&body).await;
+    }
+    if let Err(err) = sent {
+        println!("Error sending response: {}", err);
+    }
+}
+
+fn allowed(rule: &str, target: &str) -> bool {
+    let (Some((rule_host, rule_port)), Some((host, port))) =
+        (rule.rsplit_once(':'), target.rsplit_once(':'))
+    else {
+        return false;
+    };
+    let host = host.to_ascii_lowercase();
+    let rule_host = rule_host.to_ascii_lowercase();
+    let port_matches = rule_port == "*" || rule_port == port;
+    let host_matches = match rule_host.strip_prefix("*.") {
+        Some(domain) => host
+            .strip_suffix(domain)
+            .is_some_and(|sub| sub.ends_with('.')),
+        None => rule_host == host,
+    };
+    port_matches && host_matches
+}
@@ -0,0 +1,155 @@
+//! Header fields read and written as typed values instead of strings.
+
+use std::fmt;
+
+use crate::media_type::MediaType;
+
+/// A field whose values don't parse as its type.
+#[derive(Debug, thiserror::Error)]
+#[error("invalid {name} {value:?}")]
+pub struct InvalidHeaderValue {
+    pub name: &'static str,
+    pub value: String,
+}
+
+/// A header field with a typed value, read with `HeaderMap::typed` or
+/// `Request::typed_header` and written with `HeaderMap::insert_typed`.
+pub trait TypedHeader: Sized {
+    /// The field name, as sent.
+    const NAME: &'static str;
+
+    /// Parses the field from every value it was sent with, in order; there
+    /// is always at least one.
+    fn decode(values: &[&str]) -> Result<Self, InvalidHeaderValue>;
+
+    /// The field value to send.
+    fn encode(&self) -> String;
+}
+
+/// The error for `values` of `T` that don't parse.
+fn invalid<T: TypedHeader>(values: &[&str]) -> InvalidHeaderValue {
+    InvalidHeaderValue {
+        name: T::NAME,
+        value: values.join(", "),
+    }
+}
+
+/// The value of a field that may only be sent once.
+fn single<'a, T: TypedHeader>(values: &[&'a str]) -> Result<&'a str, InvalidHeaderValue> {
+    match values {
+        [value] => Ok(value.trim()),
+        _ => Err(invalid::<T>(values)),
+    }
+}
+
+/// `Content-Length`. Repeated values are accepted when they all agree
+/// (RFC 9110 §8.6).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContentLength(pub u64);
+
+impl TypedHeader for ContentLength {
+    const NAME: &'static str = "Content-Length";
+
+    fn decode(values: &[&str]) -> Result<Self, InvalidHeaderValue> {
+        let mut lengths = values
+            .iter()
+            .flat_map(|value| value.split(','))
+            .map(str::trim);
+        let first = lengths.next().unwrap_or("");
+        if first.is_empty()
+            || !first.bytes().all(|b| b.is_ascii_digit())
+            || lengths.any(|length| length != first)
+        {
+            return Err(invalid::<Self>(values));
+        }
+        first
+            .parse()
+            .map(ContentLength)
+            .map_err(|_| invalid::<Self>(values))
+    }
+
+    fn encode(&self) -> String {
+        self.0.to_string()
+    }
+}
+
+/// `Content-Type`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContentType(pub MediaType);
+
+impl TypedHeader for ContentType {
+    const NAME: &'static str = "Content-Type";
+
+    fn decode(values: &[&str]) -> Result<Self, InvalidHeaderValue> {
+        let value = single::<Self>(values)?;
+        value
+            .parse()
+            .map(ContentType)
+            .map_err(|_| invalid::<Self>(values))
+    }
+
+    fn encode(&self) -> String {
+        self.0.to_string()
+    }
+}
+
+/// `User-Agent`, kept as sent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserAgent(pub String);
+
+impl TypedHeader for UserAgent {
+    const NAME: &'static str = "User-Agent";
+
+    fn decode(values: &[&str]) -> Result<Self, InvalidHeaderValue> {
+        Ok(UserAgent(single::<Self>(values)?.to_owned()))
+    }
+
+    fn encode(&self) -> String {
+        self.0.clone()
+    }
+}
+
+/// `Host`: a name or IP literal, with the port if one was given. IPv6
+/// literals keep their brackets.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Host {
+    pub host: String,
+    pub port: Option<u16>,
+}
+
+impl TypedHeader for Host {
+    const NAME: &'static str = "Host";
+
+    fn decode(values: &[&str]) -> Result<Self, InvalidHeaderValue> {
+        let value = single::<Self>(values)?;
+        // The colon before the port is the last one, outside any brackets.
+        let (host, port) = match value.rfind(':') {
+            Some(colon) if !value[colon..].contains(']') => {
+                let port = value[colon + 1..]
+                    .parse()
+                    .map_err(|_| invalid::<Self>(values))?;
+                (&value[..colon], Some(port))
+            }
+            _ => (value, None),
+        };
+        let bracketed = host.starts_with('[') && host.ends_with(']');
+        if host.is_empty() || !bracketed && host.contains(['[', ']', ':']) {
+            return Err(invalid::<Self>(values));
+        }
+        let host = host.to_owned();
+        Ok(Host { host, port })
+    }
+
+    fn encode(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl fmt::Display for Host {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.port {
+            Some(port) => write!(f, "{}:{}", self.host, port),
+            None => f.write_str(&self.host),
+        }
+    }
+}
@@ -0,0 +1,84 @@
+//! Switching an HTTP/1.1 connection to another protocol with `Upgrade`
+//! (RFC 9110 §7.8).
+
+use bytes::BytesMut;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+use tokio::sync::watch;
+
+use crate::admission::Admission;
+use crate::config::Config;
+use crate::header_map::HeaderMap;
+use crate::request::{HttpVersion, Request};
+use crate::response::{HttpCode, Response};
+use crate::routes::Routes;
+
+/// A connection handed over after `101 Switching Protocols`.
+pub struct Upgraded {
+    /// The request that asked for the switch, body included.
+    pub req: Request,
+    pub stream: TcpStream,
+    /// Bytes the client sent after the request, already read off `stream`.
+    pub buf: BytesMut,
+    pub config: Arc<Config>,
+    pub routes: Arc<Routes>,
+    /// The listener's limit on requests in flight, if it has one.
+    pub admission: Option<Arc<Admission>>,
+    /// Flips to true when the server shuts down.
+    pub closing: watch::Receiver<bool>,
+}
+
+/// A protocol clients can switch to, registered with `Routes::upgrade`.
+pub trait Upgrade: Send + Sync {
+    /// Looks at a request asking to switch. Returning headers accepts it
+    /// and adds them to the `101` response; `None` leaves the request to
+    /// the routes.
+    fn accept(&self, req: &Request) -> Option<HeaderMap>;
+
+    /// Speaks the protocol on the connection until it ends.
+    fn run(&self, upgraded: Upgraded) -> Pin<Box<dyn Future<Output = ()> + Send>>;
+}
+
+/// The protocols a request asks to switch to, in its order of preference.
+/// Only HTTP/1.1 requests listing `upgrade` in `Connection` count.
+pub fn requested(req: &Request) -> Vec<String> {
+    if req.version != HttpVersion::Http11 {
+        return Vec::new();
+    }
+    let connection = req
+        .headers
+        .get_all("Connection")
+        .flat_map(|value| value.split(','))
+        .any(|option| option.trim().eq_ignore_ascii_case("upgrade"));
+    if !connection {
+        return Vec::new();
+    }
+    req.headers
+        .get_all("Upgrade")
+        .flat_map(|value| value.split(','))
+        .map(|protocol| protocol.trim().to_owned())
+        .filter(|protocol| !protocol.is_empty())
+        .collect()
+}
+
+/// Writes the `101 Switching Protocols` response accepting `protocol`.
+pub async fn switch(
+    stream: &mut TcpStream,
+    routes: &Routes,
+    protocol: &str,
+    mut headers: HeaderMap,
+) -> bool {
+    headers.insert(String::from("Connection"), String::from("Upgrade"));
+    headers.insert(String::from("Upgrade"), protocol.to_owned());
+    routes.stamp(&mut headers);
+    let (head, _) = Response {
+        code: HttpCode::SwitchingProtocols,
+        content: None,
+        headers: Some(headers),
+    }
+    .into_parts();
+    stream.write_all(&head).await.is_ok()
+}
@@ -0,0 +1,168 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+
+use crate::config::VirtualHost;
+use crate::header_map::HeaderMap;
+use crate::pattern;
+use crate::request::{HttpMethod, Request};
+use crate::response::{Body, HttpCode, Response};
+use crate::routes::{Middleware, Next};
+
+struct Site {
+    host: String,
+    root: PathBuf,
+    index: Vec<String>,
+    error_pages: HashMap<u16, Vec<u8>>,
+}
+
+/// Serves static files from a per-host document root. Requests whose `Host`
+/// matches no site continue to the regular routes.
+pub struct VirtualHosts {
+    sites: Vec<Site>,
+}
+
+impl VirtualHosts {
+    /// Error pages are read once, up front, so a missing file fails startup.
+    pub fn new(vhosts: &[VirtualHost]) -> Result<Self> {
+        let sites = vhosts
+            .iter()
+            .map(|vhost| {
+                let error_pages = vhost
+                    .error_pages
+                    .iter()
+                    .map(|(status, path)| Ok((*status, fs::read(path)?)))
+                    .collect::<Result<HashMap<u16, Vec<u8>>>>()?;
+                Ok(Site {
+                    host: vhost.host.clone(),
+                    root: PathBuf::from(&vhost.root),
+                    index: vhost.index.clone(),
+                    error_pages,
+                })
+            })
+            .collect::<Result<Vec<Site>>>()?;
+        Ok(VirtualHosts { sites })
+    }
+
+    fn site(&self, req: &Request) -> Option<&Site> {
+        let host = req.host()?;
+        let host = split_port(host).0.to_ascii_lowercase();
+        self.sites
+            .iter()
+            .find(|site| pattern::matches(&site.host, &host))
+    }
+}
+
+impl Middleware for VirtualHosts {
+    fn handle(&self, req: Request, next: Next<'_>) -> Response {
+        match self.site(&req) {
+            Some(site) => site.serve(&req),
+            None => next(req),
+        }
+    }
+}
+
+impl Site {
+    fn serve(&self, req: &Request) -> Response {
+        if req.method != HttpMethod::GET {
+            return self.error(HttpCode::MethodNotAllowed);
+        }
+        let Some(mut path) = self.resolve(&req.path) else {
+            return self.error(HttpCode::Forbidden);
+        };
+        if path.is_dir() {
+            match self
+                .index
+                .iter()
+                .map(|index| path.join(index))
+                .find(|candidate| candidate.is_file())
+            {
+                Some(index) => path = index,
+                None => return self.error(HttpCode::Forbidden),
+            }
+        }
+        let Ok(file) = File::open(&path) else {
+            return self.error(HttpCode::NotFound);
+        };
+        let Ok(metadata) = file.metadata() else {
+            return self.error(HttpCode::NotFound);
+        };
+        let headers = HeaderMap::from([
+            (String::from("Content-Length"), metadata.len().to_string()),
+            (String::from("Content-Type"), content_type(&path).to_owned()),
+        ]);
+        Response {
+            code: HttpCode::OK,
+            content: Some(Body::File(file)),
+            headers: Some(headers),
+        }
+    }
+
+    /// Maps a request path onto the document root, refusing any path that
+    /// would climb out of it.
+    fn resolve(&self, path: &str) -> Option<PathBuf> {
+        let mut resolved = self.root.clone();
+        for segment in path.split('/').filter(|s| !s.is_empty() && *s != ".") {
+            if segment == ".." || segment.contains('\\') {
+                return None;
+            }
+            resolved.push(segment);
+        }
+        Some(resolved)
+    }
+
+    fn error(&self, code: HttpCode) -> Response {
+        let status = match code {
+            HttpCode::Forbidden => 403,
+            HttpCode::NotFound => 404,
+            _ => 0,
+        };
+        let Some(page) = self.error_pages.get(&status) else {
+            return Response {
+                code,
+                content: None,
+                headers: None,
+            };
+        };
+        let headers = HeaderMap::from([
+            (String::from("Content-Length"), page.len().to_string()),
+            (String::from("Content-Type"), String::from("text/html")),
+        ]);
+        Response {
+            code,
+            content: Some(page.clone().into()),
+            headers: Some(headers),
+        }
+    }
+}
+
+/// Splits a `Host` value into the name and port, keeping bracketed IPv6
+/// literals intact.
+pub(crate) fn split_port(host: &str) -> (&str, Option<&str>) {
+    match host.rsplit_once(':') {
+        Some((name, port)) if !port.contains(']') => (name, Some(port)),
+        _ => (host, None),
+    }
+}
+
+pub(crate) fn content_type(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or("")
+        .to_ascii_lowercase();
+    match extension.as_str() {
+        "html" | "htm" => "text/html",
+        "css" => "text/css",
+        "js" => "text/javascript",
+        "json" => "application/json",
+        "txt" => "text/plain",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "ico" => "image/x-icon",
+        _ => "application/octet-stream",
+    }
+}
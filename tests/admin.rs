@@ -0,0 +1,77 @@
+//! The admin listener's shutdown endpoint and the drain it triggers.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use http_server_starter_rust::{admin, config::Config, control::Control, handlers, server};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+async fn exchange(address: &str, request: &[u8]) -> String {
+    let mut stream = TcpStream::connect(address).await.unwrap();
+    stream.write_all(request).await.unwrap();
+    stream.shutdown().await.unwrap();
+    let mut response = Vec::new();
+    let _ = tokio::time::timeout(Duration::from_secs(2), stream.read_to_end(&mut response)).await;
+    String::from_utf8_lossy(&response).into_owned()
+}
+
+#[tokio::test]
+async fn shutdown_requires_token_and_drains_in_flight_requests() {
+    let config = Arc::new(Config {
+        admin_token: Some(String::from("s3cret")),
+        log_requests: false,
+        ..Config::default()
+    });
+    let control = Arc::new(Control::new(&config));
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let routes = Arc::new(handlers::routes(&config, &control).unwrap());
+    let server = tokio::spawn({
+        let (config, control) = (config.clone(), control.clone());
+        async move { server::serve_until(listener, config, routes, control.shutdown_requested()).await }
+    });
+    let admin_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let admin_address = admin_listener.local_addr().unwrap().to_string();
+    let admin_routes = Arc::new(admin::routes(&config, &control));
+    tokio::spawn(server::serve(admin_listener, config.clone(), admin_routes));
+
+    // Start a request that is still being sent when shutdown begins.
+    let mut in_flight = TcpStream::connect(&address).await.unwrap();
+    in_flight
+        .write_all(b"GET /echo/late HTTP/1.1\r\n")
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let denied = exchange(
+        &admin_address,
+        b"POST /admin/shutdown HTTP/1.1\r\nHost: localhost\r\nAuthorization: Bearer wrong\r\n\r\n",
+    )
+    .await;
+    assert!(denied.starts_with("HTTP/1.1 401 Unauthorized\r\n"));
+    assert!(!server.is_finished());
+
+    let accepted = exchange(
+        &admin_address,
+        b"POST /admin/shutdown HTTP/1.1\r\nHost: localhost\r\nAuthorization: Bearer s3cret\r\n\r\n",
+    )
+    .await;
+    assert!(accepted.starts_with("HTTP/1.1 202 Accepted\r\n"));
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(TcpStream::connect(&address).await.is_err());
+    assert!(!server.is_finished());
+
+    in_flight
+        .write_all(b"Host: localhost\r\n\r\n")
+        .await
+        .unwrap();
+    let mut response = Vec::new();
+    in_flight.read_to_end(&mut response).await.unwrap();
+    assert!(String::from_utf8_lossy(&response).ends_with("\r\n\r\nlate"));
+    tokio::time::timeout(Duration::from_secs(2), server)
+        .await
+        .unwrap()
+        .unwrap();
+}
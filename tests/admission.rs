@@ -0,0 +1,143 @@
+//! Concurrency limiting with a bounded wait queue and 503 load shedding.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use http_server_starter_rust::admission::Admission;
+use http_server_starter_rust::config::Config;
+use http_server_starter_rust::response::{HttpCode, Response};
+use http_server_starter_rust::routes::{CompareType, Route, Routes};
+use http_server_starter_rust::server;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+
+/// Serves `/slow`, which takes 300ms, so one request holds the only slot
+/// while others arrive, and `/fast`.
+fn start(queue_depth: usize, queue_timeout: Duration) -> String {
+    serve(Config {
+        max_concurrency: 1,
+        queue_depth,
+        queue_timeout,
+        log_requests: false,
+        ..Config::default()
+    })
+}
+
+fn serve(config: Config) -> String {
+    let mut routes = Routes::new(&config);
+    for (prefix, limit) in config.route_limits.iter() {
+        routes.limit(
+            prefix,
+            Admission::new(*limit, config.queue_depth, config.queue_timeout),
+        );
+    }
+    routes.add(Route::new(
+        "GET",
+        "/slow",
+        CompareType::Exact,
+        Box::new(|_, _| {
+            std::thread::sleep(Duration::from_millis(300));
+            Response {
+                code: HttpCode::OK,
+                content: None,
+                headers: None,
+            }
+        }),
+    ));
+    routes.add(Route::new(
+        "GET",
+        "/fast",
+        CompareType::Exact,
+        Box::new(|_, _| Response {
+            code: HttpCode::OK,
+            content: None,
+            headers: None,
+        }),
+    ));
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.set_nonblocking(true).unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let listener = TcpListener::from_std(listener).unwrap();
+    tokio::spawn(server::serve(listener, Arc::new(config), Arc::new(routes)));
+    address
+}
+
+fn request(address: &str) -> JoinHandle<String> {
+    get(address, "/slow")
+}
+
+fn get(address: &str, path: &str) -> JoinHandle<String> {
+    let address = address.to_owned();
+    let request = format!(
+        "GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+        path
+    );
+    tokio::spawn(async move {
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = Vec::new();
+        let _ =
+            tokio::time::timeout(Duration::from_secs(3), stream.read_to_end(&mut response)).await;
+        String::from_utf8_lossy(&response).into_owned()
+    })
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn requests_beyond_the_queue_are_shed() {
+    let address = start(1, Duration::from_secs(2));
+    let mut pending = Vec::new();
+    for _ in 0..3 {
+        pending.push(request(&address));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    let mut statuses = Vec::new();
+    for response in pending {
+        let response = response.await.unwrap();
+        statuses.push(response.lines().next().unwrap_or("").to_owned());
+        if response.starts_with("HTTP/1.1 503") {
+            assert!(response.contains("Retry-After: 120\r\n"));
+        }
+    }
+    assert_eq!(
+        statuses,
+        [
+            "HTTP/1.1 200 OK",
+            "HTTP/1.1 200 OK",
+            "HTTP/1.1 503 Service Unavailable"
+        ]
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn queued_requests_give_up_after_the_timeout() {
+    let address = start(8, Duration::from_millis(100));
+    let first = request(&address);
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let second = request(&address);
+    assert!(second
+        .await
+        .unwrap()
+        .starts_with("HTTP/1.1 503 Service Unavailable\r\n"));
+    assert!(first.await.unwrap().starts_with("HTTP/1.1 200 OK\r\n"));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn route_limits_only_affect_their_prefix() {
+    let address = serve(Config {
+        route_limits: vec![(String::from("/slow"), 1)],
+        queue_depth: 0,
+        log_requests: false,
+        ..Config::default()
+    });
+    let first = request(&address);
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let second = request(&address);
+    let fast = get(&address, "/fast");
+    assert!(second
+        .await
+        .unwrap()
+        .starts_with("HTTP/1.1 503 Service Unavailable\r\n"));
+    assert!(fast.await.unwrap().starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(first.await.unwrap().starts_with("HTTP/1.1 200 OK\r\n"));
+}
@@ -0,0 +1,89 @@
+//! Files compiled into the binary, served like static files.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use http_server_starter_rust::assets::{self, EmbeddedAssets};
+use http_server_starter_rust::{config::Config, control::Control, handlers, server};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+const FILES: &[(&str, &[u8])] = &[
+    ("/index.html", b"<h1>ui</h1>"),
+    ("/js/app.js", b"console.log(1)"),
+];
+
+async fn get(address: &str, path: &str, extra: &str) -> String {
+    let mut stream = TcpStream::connect(address).await.unwrap();
+    let request = format!("GET {} HTTP/1.1\r\nHost: localhost\r\n{}\r\n", path, extra);
+    stream.write_all(request.as_bytes()).await.unwrap();
+    stream.shutdown().await.unwrap();
+    let mut response = Vec::new();
+    let _ = tokio::time::timeout(Duration::from_secs(2), stream.read_to_end(&mut response)).await;
+    String::from_utf8_lossy(&response).into_owned()
+}
+
+#[tokio::test]
+async fn etags_are_opt_in() {
+    let config =
+        Config::from_args(["server", "--log-requests", "false"].map(String::from)).unwrap();
+    assert!(!config.etag);
+    let control = Arc::new(Control::new(&config));
+    let routes = handlers::routes(&config, &control).unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    tokio::spawn(server::serve(listener, Arc::new(config), Arc::new(routes)));
+
+    let response = get(&address, "/echo/abc", "").await;
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(!response.contains("ETag"));
+}
+
+#[tokio::test]
+async fn embedded_files_get_types_and_etags() {
+    let config = Config::from_args(
+        ["server", "--log-requests", "false", "--etag", "true"].map(String::from),
+    )
+    .unwrap();
+    let control = Arc::new(Control::new(&config));
+    let mut routes = handlers::routes(&config, &control).unwrap();
+    routes.middleware("/ui", Arc::new(EmbeddedAssets::new("/ui/", FILES)));
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    tokio::spawn(server::serve(listener, Arc::new(config), Arc::new(routes)));
+
+    let index = get(&address, "/ui/", "").await;
+    assert!(index.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(index.contains("Content-Type: text/html\r\n"));
+    assert!(index.ends_with("<h1>ui</h1>"));
+
+    let script = get(&address, "/ui/js/app.js?v=2", "").await;
+    assert!(script.contains("Content-Type: text/javascript\r\n"));
+    let etag = script
+        .lines()
+        .find_map(|line| line.strip_prefix("ETag: "))
+        .unwrap()
+        .to_owned();
+    let cached = get(
+        &address,
+        "/ui/js/app.js",
+        &format!("If-None-Match: {}\r\n", etag),
+    )
+    .await;
+    assert!(cached.starts_with("HTTP/1.1 304 Not Modified\r\n"));
+
+    assert!(get(&address, "/ui/missing.js", "")
+        .await
+        .starts_with("HTTP/1.1 404"));
+}
+
+#[test]
+fn embedded_assets_require_a_build_with_assets() {
+    if !assets::EMBEDDED.is_empty() {
+        return;
+    }
+    let config =
+        Config::from_args(["server", "--embedded-assets", "/ui"].map(String::from)).unwrap();
+    let control = Arc::new(Control::new(&config));
+    assert!(handlers::routes(&config, &control).is_err());
+}
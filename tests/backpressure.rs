@@ -0,0 +1,83 @@
+//! Streamed bodies are read no further ahead of a slow client than
+//! `max_write_buffer` allows.
+
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use http_server_starter_rust::config::Config;
+use http_server_starter_rust::request::Request;
+use http_server_starter_rust::response::{Body, HttpCode, Response};
+use http_server_starter_rust::routes::{CompareType, Route, Routes};
+use tokio::io::{AsyncRead, AsyncReadExt, ReadBuf};
+
+const SIZE: usize = 1024 * 1024;
+
+/// Produces `left` bytes of `x`, counting how many were read.
+struct Counting {
+    read: Arc<AtomicUsize>,
+    left: usize,
+}
+
+impl AsyncRead for Counting {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let len = buf.remaining().min(self.left);
+        buf.put_slice(&vec![b'x'; len]);
+        self.left -= len;
+        self.read.fetch_add(len, Ordering::Relaxed);
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[tokio::test]
+async fn reads_pause_while_the_client_is_not_reading() {
+    let config = Config {
+        log_requests: false,
+        file_chunk_size: 1024,
+        max_write_buffer: 4096,
+        ..Config::default()
+    };
+    let read = Arc::new(AtomicUsize::new(0));
+    let counted = read.clone();
+    let mut routes = Routes::new(&config);
+    routes.add(Route::new(
+        "GET",
+        "/large",
+        CompareType::Exact,
+        Box::new(move |_, _| {
+            let reader = Counting {
+                read: counted.clone(),
+                left: SIZE,
+            };
+            Response::builder()
+                .status(HttpCode::OK)
+                .header("Content-Length", SIZE.to_string())
+                .body(Body::Reader(Box::new(reader)))
+        }),
+    ));
+
+    let (mut client, mut server) = tokio::io::duplex(4096);
+    let req = Request::parse(b"GET /large HTTP/1.1\r\nHost: a\r\n\r\n").unwrap();
+    let respond = tokio::spawn(async move {
+        routes.execute(&mut server, req, &[]).await;
+    });
+
+    // Nothing is read off the client end, so only the socket's buffer, the
+    // queue and the chunks in hand can be filled.
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let ahead = read.load(Ordering::Relaxed);
+    assert!(ahead < 16 * 1024, "read {} bytes ahead", ahead);
+
+    let mut received = Vec::new();
+    client.read_to_end(&mut received).await.unwrap();
+    respond.await.unwrap();
+    assert_eq!(read.load(Ordering::Relaxed), SIZE);
+    let body = received.windows(4).position(|w| w == b"\r\n\r\n").unwrap() + 4;
+    assert_eq!(received[body..], vec![b'x'; SIZE]);
+}
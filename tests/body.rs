@@ -0,0 +1,23 @@
+//! Taking the buffered request body, once.
+
+use http_server_starter_rust::body::BodyError;
+use http_server_starter_rust::request::Request;
+
+#[test]
+fn body_is_handed_over_once() {
+    let mut req =
+        Request::parse(b"POST / HTTP/1.1\r\nHost: localhost\r\nContent-Length: 5\r\n\r\nhello")
+            .unwrap();
+    assert_eq!(req.content_length(), Some(5));
+    assert_eq!(req.body_text().unwrap(), "hello");
+    assert!(matches!(req.body_bytes(), Err(BodyError::Consumed)));
+    assert!(matches!(req.body_text(), Err(BodyError::Consumed)));
+
+    let mut req = Request::parse(
+        b"POST / HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\n\r\n\xff\xfe",
+    )
+    .unwrap();
+    assert_eq!(req.content_length(), None);
+    assert!(matches!(req.body_text(), Err(BodyError::NotUtf8(_))));
+    assert!(matches!(req.body_bytes(), Err(BodyError::Consumed)));
+}
@@ -0,0 +1,98 @@
+//! Request bodies over the server-wide or per-route limit get a 413.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use http_server_starter_rust::config::Config;
+use http_server_starter_rust::response::{HttpCode, Response};
+use http_server_starter_rust::routes::{CompareType, Route, Routes};
+use http_server_starter_rust::server;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// Accepts 8 byte bodies, except on `/large` which takes up to 64.
+async fn start() -> String {
+    let config = Config {
+        max_body_size: 8,
+        log_requests: false,
+        ..Config::default()
+    };
+    let mut routes = Routes::new(&config);
+    for path in ["/small", "/large"] {
+        let route = Route::new(
+            "POST",
+            path,
+            CompareType::Exact,
+            Box::new(|req, _| Response {
+                code: HttpCode::OK,
+                content: Some(req.body.into()),
+                headers: None,
+            }),
+        );
+        routes.add(match path {
+            "/large" => route.max_body_size(64),
+            _ => route,
+        });
+    }
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    tokio::spawn(server::serve(listener, Arc::new(config), Arc::new(routes)));
+    address
+}
+
+async fn post(address: &str, path: &str, framing: &str, body: &str) -> String {
+    let mut stream = TcpStream::connect(address).await.unwrap();
+    let request = format!(
+        "POST {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n{}\r\n\r\n{}",
+        path, framing, body
+    );
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = Vec::new();
+    let _ = tokio::time::timeout(Duration::from_secs(2), stream.read_to_end(&mut response)).await;
+    String::from_utf8_lossy(&response).into_owned()
+}
+
+#[tokio::test]
+async fn declared_length_over_the_limit_is_refused() {
+    let address = start().await;
+    let body = "x".repeat(20);
+    let length = format!("Content-Length: {}", body.len());
+
+    let response = post(&address, "/small", &length, &body).await;
+    assert!(response.starts_with("HTTP/1.1 413 Content Too Large\r\n"));
+    assert!(response.contains("Connection: close\r\n"));
+
+    let response = post(&address, "/large", &length, &body).await;
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(response.ends_with(&body));
+}
+
+#[tokio::test]
+async fn chunked_body_over_the_limit_is_refused() {
+    let address = start().await;
+    let chunked = "Transfer-Encoding: chunked";
+    let response = post(
+        &address,
+        "/small",
+        chunked,
+        "5\r\nhello\r\n5\r\nworld\r\n0\r\n\r\n",
+    )
+    .await;
+    assert!(response.starts_with("HTTP/1.1 413 Content Too Large\r\n"));
+
+    let response = post(&address, "/small", chunked, "5\r\nhello\r\n0\r\n\r\n").await;
+    assert!(response.ends_with("hello"));
+}
+
+#[tokio::test]
+async fn chunk_sizes_that_overflow_the_total_are_refused() {
+    let address = start().await;
+    let response = post(
+        &address,
+        "/small",
+        "Transfer-Encoding: chunked",
+        "1\r\nx\r\nffffffffffffffff\r\n",
+    )
+    .await;
+    assert!(response.starts_with("HTTP/1.1 413 Content Too Large\r\n"));
+}
@@ -0,0 +1,121 @@
+//! Request bodies streamed to handlers while the response is written.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use http_server_starter_rust::config::Config;
+use http_server_starter_rust::response::{Body, HttpCode, Response};
+use http_server_starter_rust::routes::{CompareType, Route, Routes};
+use http_server_starter_rust::server;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+
+/// Serves `POST /shout`, which echoes each chunk of the body uppercased as
+/// soon as it arrives, ending with `!` or, if the body fails, `?`.
+async fn start() -> String {
+    let config = Config {
+        log_requests: false,
+        ..Config::default()
+    };
+    let mut routes = Routes::new(&config);
+    routes.add(
+        Route::new(
+            "POST",
+            "/shout",
+            CompareType::Exact,
+            Box::new(|mut req, _| {
+                let mut body = req.body_stream();
+                let (tx, rx) = mpsc::channel(1);
+                tokio::spawn(async move {
+                    while let Some(chunk) = body.chunk().await {
+                        let reply = match chunk {
+                            Ok(chunk) => chunk.to_ascii_uppercase(),
+                            Err(_) => b"?".to_vec(),
+                        };
+                        if tx.send(reply).await.is_err() {
+                            return;
+                        }
+                    }
+                    let _ = tx.send(b"!".to_vec()).await;
+                });
+                Response {
+                    code: HttpCode::OK,
+                    content: Some(Body::Stream(rx)),
+                    headers: None,
+                }
+            }),
+        )
+        .max_body_size(16)
+        .stream_body(),
+    );
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    tokio::spawn(server::serve(listener, Arc::new(config), Arc::new(routes)));
+    address
+}
+
+/// Reads from `stream` until what was received ends with `suffix`.
+async fn read_until(stream: &mut TcpStream, received: &mut Vec<u8>, suffix: &[u8]) {
+    while !received.ends_with(suffix) {
+        let mut chunk = [0; 256];
+        let len = tokio::time::timeout(Duration::from_secs(2), stream.read(&mut chunk))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(
+            len > 0,
+            "closed after {:?}",
+            String::from_utf8_lossy(received)
+        );
+        received.extend_from_slice(&chunk[..len]);
+    }
+}
+
+#[tokio::test]
+async fn handler_reads_the_body_as_it_arrives() {
+    let address = start().await;
+    let mut stream = TcpStream::connect(&address).await.unwrap();
+    stream
+        .write_all(b"POST /shout HTTP/1.1\r\nHost: localhost\r\nContent-Length: 10\r\n\r\nhello")
+        .await
+        .unwrap();
+    // The first half comes back before the second is sent.
+    let mut received = Vec::new();
+    read_until(&mut stream, &mut received, b"5\r\nHELLO\r\n").await;
+    stream.write_all(b"world").await.unwrap();
+    read_until(&mut stream, &mut received, b"1\r\n!\r\n0\r\n\r\n").await;
+    let response = String::from_utf8(received).unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(response.contains("5\r\nWORLD\r\n"));
+
+    // The connection moves on to the next request.
+    stream
+        .write_all(
+            b"POST /shout HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n0\r\n\r\n",
+        )
+        .await
+        .unwrap();
+    let mut received = Vec::new();
+    read_until(&mut stream, &mut received, b"1\r\n!\r\n0\r\n\r\n").await;
+    assert!(String::from_utf8(received)
+        .unwrap()
+        .contains("3\r\nABC\r\n"));
+}
+
+#[tokio::test]
+async fn body_over_the_limit_fails_the_stream() {
+    let address = start().await;
+    let mut stream = TcpStream::connect(&address).await.unwrap();
+    stream
+        .write_all(
+            b"POST /shout HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\n\r\n8\r\n12345678\r\n10\r\n0123456789abcdef\r\n0\r\n\r\n",
+        )
+        .await
+        .unwrap();
+    let mut received = Vec::new();
+    stream.read_to_end(&mut received).await.unwrap();
+    let response = String::from_utf8(received).unwrap();
+    assert!(response.contains("8\r\n12345678\r\n1\r\n?\r\n"));
+    assert!(!response.contains("0123456789ABCDEF"));
+}
@@ -0,0 +1,81 @@
+//! The request buffer starts small and grows up to the configured limits.
+
+use std::sync::Arc;
+
+use http_server_starter_rust::{config::Config, control::Control, handlers, server};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// Starts a server whose buffer starts at 64 bytes, with heads limited to
+/// 16KB, serving files from the temp directory.
+async fn start() -> String {
+    let config = Config {
+        log_requests: false,
+        directory: std::env::temp_dir().to_string_lossy().into_owned(),
+        initial_buffer_size: 64,
+        max_header_size: 16 * 1024,
+        max_header_line: 16 * 1024,
+        ..Config::default()
+    };
+    let control = Arc::new(Control::new(&config));
+    let routes = handlers::routes(&config, &control).unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    tokio::spawn(server::serve(listener, Arc::new(config), Arc::new(routes)));
+    address
+}
+
+/// Sends `request` and reads until the server closes. A refused request
+/// may be closed on before it is read whole, resetting the connection, so
+/// what arrived until then is the response.
+async fn send(address: &str, request: &[u8]) -> String {
+    let mut stream = TcpStream::connect(address).await.unwrap();
+    let _ = stream.write_all(request).await;
+    let mut response = Vec::new();
+    let _ = stream.read_to_end(&mut response).await;
+    String::from_utf8(response).unwrap()
+}
+
+#[tokio::test]
+async fn heads_larger_than_the_initial_buffer_are_read_whole() {
+    let address = start().await;
+    let cookie = "a".repeat(10_000);
+    let request = format!(
+        "GET /user-agent HTTP/1.1\r\nHost: a\r\nCookie: session={}\r\nUser-Agent: grower/1.0\r\nConnection: close\r\n\r\n",
+        cookie
+    );
+    let response = send(&address, request.as_bytes()).await;
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(response.ends_with("\r\n\r\ngrower/1.0"));
+}
+
+#[tokio::test]
+async fn bodies_larger_than_the_initial_buffer_are_read_whole() {
+    let address = start().await;
+    let name = format!("buffer-growth-{}", std::process::id());
+    let body = "b".repeat(100_000);
+    let request = format!(
+        "POST /files/{} HTTP/1.1\r\nHost: a\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        name,
+        body.len(),
+        body
+    );
+    let response = send(&address, request.as_bytes()).await;
+    assert!(response.starts_with("HTTP/1.1 201 Created\r\n"));
+    let path = std::env::temp_dir().join(name);
+    let written = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(written, body);
+}
+
+#[tokio::test]
+async fn heads_past_the_limit_are_refused() {
+    let address = start().await;
+    let cookie = "a".repeat(20_000);
+    let request = format!(
+        "GET /user-agent HTTP/1.1\r\nHost: a\r\nCookie: session={}\r\n\r\n",
+        cookie
+    );
+    let response = send(&address, request.as_bytes()).await;
+    assert!(response.starts_with("HTTP/1.1 431 Request Header Fields Too Large\r\n"));
+}
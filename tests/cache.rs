@@ -0,0 +1,106 @@
+//! Content-hash ETags, `304 Not Modified` and Cache-Control policies for
+//! buffered responses.
+
+use std::sync::Arc;
+
+use http_server_starter_rust::cache::CacheMiddleware;
+use http_server_starter_rust::config::Config;
+use http_server_starter_rust::request::Request;
+use http_server_starter_rust::response::{HttpCode, Response};
+use http_server_starter_rust::routes::{CompareType, Route, Routes};
+
+/// Serves `page` on every GET and POST, with `max-age=60` for `/static/*`.
+fn routes() -> Routes {
+    let config = Config {
+        log_requests: false,
+        ..Config::default()
+    };
+    let mut routes = Routes::new(&config);
+    let policies = vec![(String::from("/static/*"), String::from("max-age=60"))];
+    routes.middleware("/", Arc::new(CacheMiddleware::new(true, policies)));
+    for method in ["GET", "POST"] {
+        routes.add(Route::new(
+            method,
+            "/",
+            CompareType::Prefix,
+            Box::new(|_, _| {
+                Response::builder()
+                    .status(HttpCode::OK)
+                    .header("Content-Type", "text/plain")
+                    .header("Vary", "Accept-Encoding")
+                    .header("Expires", "Thu, 01 Jan 2099 00:00:00 GMT")
+                    .header("Content-Location", "/page.txt")
+                    .header("X-Other", "dropped")
+                    .body("page")
+            }),
+        ));
+    }
+    routes
+}
+
+async fn send(routes: &Routes, method: &str, path: &str, fields: &str) -> String {
+    let head = format!("{} {} HTTP/1.1\r\nHost: a\r\n{}\r\n", method, path, fields);
+    let req = Request::parse(head.as_bytes()).unwrap();
+    let mut written = Vec::new();
+    routes.execute(&mut written, req, &[]).await;
+    String::from_utf8(written).unwrap()
+}
+
+fn header<'a>(response: &'a str, name: &str) -> Option<&'a str> {
+    let prefix = format!("{}: ", name);
+    response
+        .split("\r\n")
+        .find_map(|line| line.strip_prefix(prefix.as_str()))
+}
+
+#[tokio::test]
+async fn matching_if_none_match_gets_a_304_with_the_cache_fields() {
+    let routes = routes();
+    let response = send(&routes, "GET", "/static/page", "").await;
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    let etag = header(&response, "ETag").unwrap().to_owned();
+    assert!(etag.starts_with('"') && etag.ends_with('"'));
+
+    let fields = format!("If-None-Match: W/{}\r\n", etag);
+    let response = send(&routes, "GET", "/static/page", &fields).await;
+    assert!(response.starts_with("HTTP/1.1 304 Not Modified\r\n"));
+    assert_eq!(header(&response, "ETag"), Some(etag.as_str()));
+    assert_eq!(header(&response, "Cache-Control"), Some("max-age=60"));
+    assert_eq!(header(&response, "Vary"), Some("Accept-Encoding"));
+    assert_eq!(
+        header(&response, "Expires"),
+        Some("Thu, 01 Jan 2099 00:00:00 GMT")
+    );
+    assert_eq!(header(&response, "Content-Location"), Some("/page.txt"));
+    assert!(header(&response, "Date").is_some());
+    assert_eq!(header(&response, "Content-Type"), None);
+    assert_eq!(header(&response, "X-Other"), None);
+    assert!(response.ends_with("\r\n\r\n"));
+
+    let response = send(
+        &routes,
+        "GET",
+        "/static/page",
+        "If-None-Match: \"other\"\r\n",
+    )
+    .await;
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+}
+
+#[tokio::test]
+async fn policies_apply_by_path_pattern() {
+    let routes = routes();
+    let response = send(&routes, "GET", "/static/app.js", "").await;
+    assert_eq!(header(&response, "Cache-Control"), Some("max-age=60"));
+    let response = send(&routes, "GET", "/api/items", "").await;
+    assert_eq!(header(&response, "Cache-Control"), None);
+    assert!(header(&response, "ETag").is_some());
+}
+
+#[tokio::test]
+async fn only_gets_are_tagged() {
+    let routes = routes();
+    let response = send(&routes, "POST", "/static/page", "If-None-Match: *\r\n").await;
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert_eq!(header(&response, "ETag"), None);
+}
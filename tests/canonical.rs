@@ -0,0 +1,48 @@
+//! Redirects to the canonical host name.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use http_server_starter_rust::{config::Config, control::Control, handlers, server};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+async fn get(address: &str, host: &str, path: &str) -> String {
+    let mut stream = TcpStream::connect(address).await.unwrap();
+    let request = format!("GET {} HTTP/1.1\r\nHost: {}\r\n\r\n", path, host);
+    stream.write_all(request.as_bytes()).await.unwrap();
+    stream.shutdown().await.unwrap();
+    let mut response = Vec::new();
+    let _ = tokio::time::timeout(Duration::from_secs(2), stream.read_to_end(&mut response)).await;
+    String::from_utf8_lossy(&response).into_owned()
+}
+
+#[tokio::test]
+async fn other_hosts_are_redirected_permanently() {
+    let config = Config::from_args(
+        [
+            "server",
+            "--log-requests",
+            "false",
+            "--canonical-host",
+            "example.com",
+        ]
+        .map(String::from),
+    )
+    .unwrap();
+    let control = Arc::new(Control::new(&config));
+    let routes = handlers::routes(&config, &control).unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    tokio::spawn(server::serve(listener, Arc::new(config), Arc::new(routes)));
+
+    let www = get(&address, "www.example.com", "/echo/a?b=1").await;
+    assert!(www.starts_with("HTTP/1.1 301 Moved Permanently\r\n"));
+    assert!(www.contains("Location: http://example.com/echo/a?b=1\r\n"));
+
+    let upper = get(&address, "EXAMPLE.com:8080", "/").await;
+    assert!(upper.contains("Location: http://example.com:8080/\r\n"));
+
+    let canonical = get(&address, "example.com:8080", "/echo/ok").await;
+    assert!(canonical.starts_with("HTTP/1.1 200 OK\r\n"));
+}
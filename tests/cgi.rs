@@ -0,0 +1,154 @@
+//! CGI scripts run for a mounted path prefix.
+
+use std::os::unix::fs::PermissionsExt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use http_server_starter_rust::{config::Config, control::Control, handlers, server};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+const SCRIPT: &str = r#"#!/bin/sh
+if [ "$PATH_INFO" = "/missing" ]; then
+    printf 'Status: 404 Not Found\r\nContent-Type: text/plain\r\n\r\nnothing here'
+    exit 0
+fi
+if [ "$PATH_INFO" = "/unchanged" ]; then
+    printf 'Status: 304 Not Modified\r\nETag: "a"\r\n\r\nnot sent'
+    exit 0
+fi
+if [ "$PATH_INFO" = "/split" ]; then
+    printf 'Content-Type: text/plain\r\nX-Note: a\rSet-Cookie: admin=1\r\n\r\nbody'
+    exit 0
+fi
+if [ "$PATH_INFO" = "/bloated" ]; then
+    printf 'X-Padding: '
+    head -c 20000 /dev/zero | tr '\0' 'a'
+    printf '\r\n\r\nbody'
+    exit 0
+fi
+printf 'Content-Type: text/plain\r\nX-Script: env\r\n\r\n'
+printf '%s|%s|%s|%s|%s|%s\n' "$REQUEST_METHOD" "$SCRIPT_NAME" "$PATH_INFO" \
+    "$QUERY_STRING" "$HTTP_X_TOKEN" "$CONTENT_LENGTH"
+cat
+"#;
+
+async fn start() -> String {
+    let directory = std::env::temp_dir().join(format!("cgi-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    let script = directory.join("env.sh");
+    std::fs::write(&script, SCRIPT).unwrap();
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let config = Config::from_args(
+        [
+            "server",
+            "--log-requests",
+            "false",
+            "--max-header-size",
+            "16384",
+            "--cgi",
+            &format!("/cgi-bin={}", directory.display()),
+        ]
+        .map(String::from),
+    )
+    .unwrap();
+    let control = Arc::new(Control::new(&config));
+    let routes = handlers::routes(&config, &control).unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    tokio::spawn(server::serve(listener, Arc::new(config), Arc::new(routes)));
+    address
+}
+
+async fn send(address: &str, request: &str) -> String {
+    let mut stream = TcpStream::connect(address).await.unwrap();
+    stream.write_all(request.as_bytes()).await.unwrap();
+    stream.shutdown().await.unwrap();
+    let mut response = Vec::new();
+    let _ = tokio::time::timeout(Duration::from_secs(5), stream.read_to_end(&mut response)).await;
+    String::from_utf8_lossy(&response).into_owned()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn scripts_see_request_metadata_and_body() {
+    let address = start().await;
+
+    let get = send(
+        &address,
+        "GET /cgi-bin/env.sh/a/b?x=1 HTTP/1.1\r\nHost: localhost\r\nX-Token: abc\r\n\r\n",
+    )
+    .await;
+    assert!(get.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(get.contains("X-Script: env\r\n"));
+    assert!(get.contains("Transfer-Encoding: chunked\r\n"));
+    assert!(get.contains("GET|/cgi-bin/env.sh|/a/b|x=1|abc|\n"));
+
+    let post = send(
+        &address,
+        "POST /cgi-bin/env.sh HTTP/1.1\r\nHost: localhost\r\nContent-Length: 5\r\n\r\nhello",
+    )
+    .await;
+    assert!(post.contains("POST|/cgi-bin/env.sh||||5\n"));
+    assert!(post.contains("hello"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn status_header_and_missing_scripts() {
+    let address = start().await;
+
+    let missing = send(
+        &address,
+        "GET /cgi-bin/env.sh/missing HTTP/1.1\r\nHost: localhost\r\n\r\n",
+    )
+    .await;
+    assert!(missing.starts_with("HTTP/1.1 404 Not Found\r\n"));
+    assert!(missing.contains("nothing here"));
+
+    let unknown = send(
+        &address,
+        "GET /cgi-bin/nope.sh HTTP/1.1\r\nHost: localhost\r\n\r\n",
+    )
+    .await;
+    assert!(unknown.starts_with("HTTP/1.1 404 Not Found\r\n"));
+    let escape = send(
+        &address,
+        "GET /cgi-bin/../cgi.sh HTTP/1.1\r\nHost: localhost\r\n\r\n",
+    )
+    .await;
+    assert!(escape.starts_with("HTTP/1.1 404 Not Found\r\n"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn bodyless_statuses_drop_the_script_body() {
+    let address = start().await;
+    let response = send(
+        &address,
+        "GET /cgi-bin/env.sh/unchanged HTTP/1.1\r\nHost: localhost\r\n\r\n",
+    )
+    .await;
+    assert!(response.starts_with("HTTP/1.1 304 Not Modified\r\n"));
+    assert!(response.contains("ETag: \"a\"\r\n"));
+    assert!(!response.contains("Transfer-Encoding"));
+    assert!(response.ends_with("\r\n\r\n"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn malformed_script_headers_are_a_bad_gateway() {
+    let address = start().await;
+    for path in ["/split", "/bloated"] {
+        let response = send(
+            &address,
+            &format!(
+                "GET /cgi-bin/env.sh{} HTTP/1.1\r\nHost: localhost\r\n\r\n",
+                path
+            ),
+        )
+        .await;
+        assert!(
+            response.starts_with("HTTP/1.1 502 Bad Gateway\r\n"),
+            "{}",
+            path
+        );
+        assert!(!response.contains("admin=1"), "{}", path);
+    }
+}
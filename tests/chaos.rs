@@ -0,0 +1,58 @@
+//! Fault injection with rates pinned to 0 or 1, so every outcome is certain.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use http_server_starter_rust::{config::Config, control::Control, handlers, server};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+async fn send(config: Config, request: &[u8]) -> String {
+    let control = Arc::new(Control::new(&config));
+    let routes = handlers::routes(&config, &control).unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(server::serve(listener, Arc::new(config), Arc::new(routes)));
+
+    let mut stream = TcpStream::connect(address).await.unwrap();
+    stream.write_all(request).await.unwrap();
+    stream.shutdown().await.unwrap();
+    let mut response = Vec::new();
+    let _ = tokio::time::timeout(Duration::from_secs(2), stream.read_to_end(&mut response)).await;
+    String::from_utf8_lossy(&response).into_owned()
+}
+
+const REQUEST: &[u8] = b"GET /echo/abcdefgh HTTP/1.1\r\nHost: localhost\r\n\r\n";
+
+#[tokio::test]
+async fn error_rate_answers_with_500() {
+    let config = Config {
+        chaos_error_rate: 1.0,
+        log_requests: false,
+        ..Config::default()
+    };
+    let response = send(config, REQUEST).await;
+    assert!(response.starts_with("HTTP/1.1 500 Internal Server Error\r\n"));
+}
+
+#[tokio::test]
+async fn drop_rate_closes_without_response() {
+    let config = Config {
+        chaos_drop_rate: 1.0,
+        log_requests: false,
+        ..Config::default()
+    };
+    assert_eq!(send(config, REQUEST).await, "");
+}
+
+#[tokio::test]
+async fn truncate_rate_cuts_the_response() {
+    let config = Config {
+        chaos_truncate_rate: 1.0,
+        log_requests: false,
+        ..Config::default()
+    };
+    let response = send(config, REQUEST).await;
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(!response.ends_with("abcdefgh"));
+}
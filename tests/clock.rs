@@ -0,0 +1,120 @@
+//! Time-dependent behavior driven by a `ManualClock`, so expiry is checked
+//! deterministically instead of by sleeping.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use http_server_starter_rust::auth::DigestAuth;
+use http_server_starter_rust::clock::{self, Clock, Elapsed, ManualClock};
+use http_server_starter_rust::hash::{hex, md5};
+use http_server_starter_rust::request::Request;
+use http_server_starter_rust::response::{HttpCode, Response};
+use http_server_starter_rust::routes::Middleware;
+
+#[tokio::test]
+async fn sleep_resolves_only_after_advance() {
+    let clock = ManualClock::new();
+    let mut sleep = clock.sleep(Duration::from_secs(30));
+    assert!(!is_done(&mut sleep).await);
+    clock.advance(Duration::from_secs(29));
+    assert!(!is_done(&mut sleep).await);
+    clock.advance(Duration::from_secs(1));
+    sleep.await;
+}
+
+#[tokio::test]
+async fn timeout_elapses_on_advance() {
+    let clock = Arc::new(ManualClock::new());
+    let waiting = tokio::spawn({
+        let clock = clock.clone();
+        async move {
+            clock::timeout(
+                &*clock,
+                Duration::from_secs(5),
+                std::future::pending::<()>(),
+            )
+            .await
+        }
+    });
+    tokio::task::yield_now().await;
+    clock.advance(Duration::from_secs(5));
+    assert_eq!(waiting.await.unwrap(), Err(Elapsed));
+}
+
+#[tokio::test]
+async fn timeout_returns_completed_output() {
+    let clock = ManualClock::new();
+    let output = clock::timeout(&clock, Duration::from_secs(5), async { 7 }).await;
+    assert_eq!(output, Ok(7));
+}
+
+#[test]
+fn digest_nonce_goes_stale_after_lifetime() {
+    let clock = Arc::new(ManualClock::new());
+    let auth = DigestAuth::new("test")
+        .user("alice", "secret")
+        .nonce_lifetime(Duration::from_secs(60))
+        .clock(clock.clone());
+    let ok = |_| Response {
+        code: HttpCode::OK,
+        content: None,
+        headers: None,
+    };
+
+    let challenge = auth.handle(get(None), &ok);
+    assert_eq!(challenge.code, HttpCode::Unauthorized);
+    let header = &challenge.headers.unwrap()["WWW-Authenticate"];
+    let md5_challenge = header
+        .split("Digest ")
+        .find(|c| c.contains("algorithm=MD5,"))
+        .unwrap();
+    let nonce = param(md5_challenge, "nonce");
+    let opaque = param(md5_challenge, "opaque");
+
+    let authorization = |nc: &str| {
+        let ha1 = hex(&md5(b"alice:test:secret"));
+        let ha2 = hex(&md5(b"GET:/private"));
+        let response = hex(&md5(
+            format!("{}:{}:{}:abc:auth:{}", ha1, nonce, nc, ha2).as_bytes()
+        ));
+        format!(
+            "Digest username=\"alice\", realm=\"test\", nonce=\"{}\", uri=\"/private\", qop=auth, nc={}, cnonce=\"abc\", response=\"{}\", opaque=\"{}\", algorithm=MD5",
+            nonce, nc, response, opaque
+        )
+    };
+
+    clock.advance(Duration::from_secs(60));
+    assert_eq!(
+        auth.handle(get(Some(&authorization("00000001"))), &ok).code,
+        HttpCode::OK
+    );
+
+    clock.advance(Duration::from_secs(1));
+    let stale = auth.handle(get(Some(&authorization("00000002"))), &ok);
+    assert_eq!(stale.code, HttpCode::Unauthorized);
+    assert!(stale.headers.unwrap()["WWW-Authenticate"].contains("stale=true"));
+}
+
+fn get(authorization: Option<&str>) -> Request {
+    let mut head = String::from("GET /private HTTP/1.1\r\nHost: localhost\r\n");
+    if let Some(value) = authorization {
+        head.push_str(&format!("Authorization: {}\r\n", value));
+    }
+    head.push_str("\r\n");
+    Request::parse(head.as_bytes()).unwrap()
+}
+
+fn param(challenge: &str, name: &str) -> String {
+    let start = challenge.find(&format!("{}=\"", name)).unwrap() + name.len() + 2;
+    let len = challenge[start..].find('"').unwrap();
+    challenge[start..start + len].to_owned()
+}
+
+/// Polls `sleep` once, reporting whether it has already finished.
+async fn is_done(sleep: &mut clock::Sleep) -> bool {
+    tokio::select! {
+        biased;
+        _ = sleep => true,
+        _ = std::future::ready(()) => false,
+    }
+}
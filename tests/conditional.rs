@@ -0,0 +1,70 @@
+//! Entity tags and the conditional request fields built on them.
+
+use http_server_starter_rust::conditional::{ETagMatch, EntityTag};
+use http_server_starter_rust::date::{http_date, parse_http_date};
+use http_server_starter_rust::request::Request;
+use std::time::{Duration, UNIX_EPOCH};
+
+fn get(fields: &str) -> Request {
+    let head = format!("GET / HTTP/1.1\r\nHost: localhost\r\n{}\r\n", fields);
+    Request::parse(head.as_bytes()).unwrap()
+}
+
+fn tag(weak: bool, tag: &str) -> EntityTag {
+    let tag = tag.to_string();
+    EntityTag { weak, tag }
+}
+
+#[test]
+fn etag_lists_are_compared_strongly_or_weakly() {
+    let req = get("If-None-Match: W/\"a,b\", \"c\"\r\nIf-None-Match: \"d\"\r\nIf-Match: *\r\n");
+    let if_none_match = req.if_none_match().unwrap();
+    assert_eq!(
+        if_none_match,
+        ETagMatch::Tags(vec![tag(true, "a,b"), tag(false, "c"), tag(false, "d")])
+    );
+    assert!(if_none_match.matches_weak(Some(&tag(false, "a,b"))));
+    assert!(!if_none_match.matches_weak(Some(&tag(false, "e"))));
+    assert!(!if_none_match.matches_strong(Some(&tag(false, "a,b"))));
+    assert!(if_none_match.matches_strong(Some(&tag(false, "c"))));
+
+    let if_match = req.if_match().unwrap();
+    assert!(if_match.matches_strong(Some(&tag(true, "x"))));
+    assert!(!if_match.matches_strong(None));
+
+    assert_eq!(EntityTag::parse("W/\"x\"").unwrap().to_string(), "W/\"x\"");
+    assert!(get("If-None-Match: abc\r\n").if_none_match().is_none());
+    assert!(get("If-None-Match: \"a\" \"b\"\r\n")
+        .if_none_match()
+        .is_none());
+    assert!(get("If-Match: *, \"a\"\r\n").if_match().is_none());
+}
+
+#[test]
+fn http_dates_are_parsed_in_all_three_formats() {
+    let expected = UNIX_EPOCH + Duration::from_secs(784111777);
+    for value in [
+        "Sun, 06 Nov 1994 08:49:37 GMT",
+        "Sunday, 06-Nov-94 08:49:37 GMT",
+        "Sun Nov  6 08:49:37 1994",
+    ] {
+        assert_eq!(parse_http_date(value), Some(expected), "{}", value);
+    }
+    let now = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+    assert_eq!(parse_http_date(&http_date(now)), Some(now));
+    for value in [
+        "",
+        "yesterday",
+        "Sun, 06 Nov 1994 25:49:37 GMT",
+        "Sun, 06 Foo 1994 08:49:37 GMT",
+    ] {
+        assert_eq!(parse_http_date(value), None, "{}", value);
+    }
+
+    let req =
+        get("If-Modified-Since: Sun, 06 Nov 1994 08:49:37 GMT\r\nIf-Unmodified-Since: soon\r\n");
+    assert_eq!(req.if_modified_since(), Some(expected));
+    assert_eq!(req.if_unmodified_since(), None);
+    let req = get("If-None-Match: \"a\"\r\nIf-Modified-Since: Sun, 06 Nov 1994 08:49:37 GMT\r\n");
+    assert_eq!(req.if_modified_since(), None);
+}
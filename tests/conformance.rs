@@ -0,0 +1,690 @@
+//! Raw-byte request/response cases derived from the message syntax rules of
+//! RFC 7230 and RFC 7231, run against the live connection handler.
+//!
+//! Cases the server does not satisfy yet are `#[ignore]`d with the missing
+//! behavior as the reason, so they can be switched on as the parser evolves.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use http_server_starter_rust::hash::{base64_encode, sha256};
+use http_server_starter_rust::{config::Config, control::Control, handlers, server};
+use pretty_assertions::assert_eq;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Starts a server on an ephemeral port serving files from a fresh
+/// temporary directory.
+async fn start() -> (String, PathBuf) {
+    let directory = std::env::temp_dir().join(format!(
+        "conformance-{}-{}",
+        std::process::id(),
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos()
+    ));
+    std::fs::create_dir_all(&directory).unwrap();
+    let config = Config {
+        directory: directory.to_string_lossy().into_owned(),
+        log_requests: false,
+        ..Config::default()
+    };
+    let control = Arc::new(Control::new(&config));
+    let routes = handlers::routes(&config, &control).unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    tokio::spawn(server::serve(listener, Arc::new(config), Arc::new(routes)));
+    (address, directory)
+}
+
+/// Sends `request` on a fresh connection, half-closes it and returns
+/// everything the server wrote back before closing or timing out.
+async fn exchange(address: &str, request: &[u8]) -> String {
+    let mut stream = TcpStream::connect(address).await.unwrap();
+    stream.write_all(request).await.unwrap();
+    stream.shutdown().await.unwrap();
+    let mut response = Vec::new();
+    let _ = tokio::time::timeout(RESPONSE_TIMEOUT, stream.read_to_end(&mut response)).await;
+    String::from_utf8_lossy(&response).into_owned()
+}
+
+async fn send(request: &[u8]) -> String {
+    let (address, _) = start().await;
+    exchange(&address, request).await
+}
+
+fn status_line(response: &str) -> &str {
+    response.split("\r\n").next().unwrap_or("")
+}
+
+fn body(response: &str) -> &str {
+    response.split_once("\r\n\r\n").map_or("", |(_, body)| body)
+}
+
+// RFC 7230 §3.1.1: request-line = method SP request-target SP HTTP-version CRLF
+
+#[tokio::test]
+async fn origin_form_get() {
+    let response = send(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").await;
+    assert_eq!(status_line(&response), "HTTP/1.1 200 OK");
+}
+
+// RFC 7231 §7.1.1.2: an origin server MUST send a Date header field in
+// 2xx, 3xx and 4xx responses.
+
+#[tokio::test]
+async fn responses_carry_a_date() {
+    for request in [
+        &b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n"[..],
+        &b"GET /missing HTTP/1.1\r\nHost: localhost\r\n\r\n"[..],
+        &b"BREW / HTTP/1.1\r\nHost: localhost\r\n\r\n"[..],
+    ] {
+        let response = send(request).await;
+        let date = response
+            .split("\r\n")
+            .find_map(|line| line.strip_prefix("Date: "))
+            .unwrap_or_else(|| panic!("no Date in {:?}", response));
+        assert_eq!(date.len(), "Sun, 06 Nov 1994 08:49:37 GMT".len());
+        assert!(date.ends_with(" GMT"));
+    }
+}
+
+#[tokio::test]
+async fn unknown_target_is_not_found() {
+    let response = send(b"GET /missing HTTP/1.1\r\nHost: localhost\r\n\r\n").await;
+    assert_eq!(status_line(&response), "HTTP/1.1 404 Not Found");
+}
+
+#[tokio::test]
+async fn request_line_without_target_is_bad_request() {
+    let response = send(b"GET\r\nHost: localhost\r\n\r\n").await;
+    assert_eq!(status_line(&response), "HTTP/1.1 400 Bad Request");
+}
+
+#[tokio::test]
+async fn malformed_request_lines_are_bad_requests() {
+    for line in [
+        "GET  / HTTP/1.1",
+        " GET / HTTP/1.1",
+        "GET / HTTP/1.1 ",
+        "GET\t/ HTTP/1.1",
+        "GET /a b HTTP/1.1",
+        "GET /",
+        "GET / HTTP/1.1x",
+        "GET / FTP/1.1",
+    ] {
+        let request = format!("{}\r\nHost: localhost\r\n\r\n", line);
+        let response = send(request.as_bytes()).await;
+        assert_eq!(
+            status_line(&response),
+            "HTTP/1.1 400 Bad Request",
+            "{:?}",
+            line
+        );
+    }
+}
+
+#[tokio::test]
+async fn tls_and_binary_garbage_are_refused_early() {
+    // The start of a ClientHello, with no line ending in sight.
+    let response = send(&[
+        0x16, 0x03, 0x01, 0x02, 0x00, 0x01, 0x00, 0x01, 0xfc, 0x03, 0x03,
+    ])
+    .await;
+    assert_eq!(status_line(&response), "HTTP/1.1 400 Bad Request");
+    assert!(body(&response).contains("plain HTTP"));
+
+    for garbage in [&b"\x00\x01\x02\xff"[..], &[b'A'; 40]] {
+        let response = send(garbage).await;
+        assert_eq!(
+            status_line(&response),
+            "HTTP/1.1 400 Bad Request",
+            "{:?}",
+            garbage
+        );
+    }
+}
+
+#[tokio::test]
+async fn unknown_method_is_not_implemented() {
+    let response = send(b"BREW /pot HTTP/1.1\r\nHost: localhost\r\n\r\n").await;
+    assert_eq!(status_line(&response), "HTTP/1.1 501 Not Implemented");
+}
+
+#[tokio::test]
+async fn unsupported_major_version() {
+    let response = send(b"GET / HTTP/2.0\r\nHost: localhost\r\n\r\n").await;
+    assert_eq!(
+        status_line(&response),
+        "HTTP/1.1 505 HTTP Version Not Supported"
+    );
+}
+
+// RFC 7230 §3.1.1: a server that receives a request-target longer than
+// any URI it wishes to parse MUST respond with 414.
+
+#[tokio::test]
+async fn overlong_target_is_uri_too_long() {
+    let request = format!(
+        "GET /echo/{} HTTP/1.1\r\nHost: localhost\r\n\r\n",
+        "a".repeat(10 * 1024)
+    );
+    let response = send(request.as_bytes()).await;
+    assert_eq!(status_line(&response), "HTTP/1.1 414 URI Too Long");
+}
+
+// RFC 9110 §15.5.7: 406 when no representation matches the proactive
+// negotiation header fields.
+
+#[tokio::test]
+async fn unacceptable_representation_is_not_acceptable() {
+    let response =
+        send(b"GET /echo/x HTTP/1.1\r\nHost: localhost\r\nAccept: image/png\r\n\r\n").await;
+    assert_eq!(status_line(&response), "HTTP/1.1 406 Not Acceptable");
+    assert!(response.contains("Vary: Accept\r\n"));
+    let response = send(
+        b"GET /echo/x HTTP/1.1\r\nHost: localhost\r\nAccept: text/plain;q=0.2, application/json\r\n\r\n",
+    )
+    .await;
+    assert!(response.contains("Vary: Accept\r\n"));
+    assert_eq!(body(&response), "{\"message\":\"x\"}");
+    let response = send(b"GET /echo/x HTTP/1.1\r\nHost: localhost\r\n\r\n").await;
+    assert!(response.contains("Vary: Accept\r\n"));
+    assert_eq!(body(&response), "x");
+}
+
+// RFC 6585 §5: 431 when the header fields, alone or together, are too
+// large.
+
+#[tokio::test]
+async fn oversized_header_fields_are_refused() {
+    let many = (0..101)
+        .map(|i| format!("X-Field-{}: {}\r\n", i, i))
+        .collect::<String>();
+    let long = format!("X-Long: {}\r\n", "a".repeat(9 * 1024));
+    let huge = (0..9)
+        .map(|i| format!("X-Big-{}: {}\r\n", i, "b".repeat(8000)))
+        .collect::<String>();
+    for fields in [many, long, huge] {
+        let request = format!("GET / HTTP/1.1\r\nHost: localhost\r\n{}\r\n", fields);
+        let response = send(request.as_bytes()).await;
+        assert_eq!(
+            status_line(&response),
+            "HTTP/1.1 431 Request Header Fields Too Large"
+        );
+    }
+}
+
+// RFC 3986 §5.2.4: dot segments are removed before the path is used.
+
+#[tokio::test]
+async fn dot_segments_and_empty_segments_are_normalized() {
+    let response = send(b"GET /echo//x HTTP/1.1\r\nHost: localhost\r\n\r\n").await;
+    assert_eq!(body(&response), "x");
+    let response =
+        send(b"GET /files/../echo/./y?a=/../b HTTP/1.1\r\nHost: localhost\r\n\r\n").await;
+    assert_eq!(body(&response), "y");
+    let response = send(b"GET /files/../../etc/passwd HTTP/1.1\r\nHost: localhost\r\n\r\n").await;
+    assert_eq!(status_line(&response), "HTTP/1.1 404 Not Found");
+}
+
+// RFC 3986 §2.1: percent-encoded octets are decoded before the path is used,
+// and decoded dot segments are removed like literal ones.
+
+#[tokio::test]
+async fn escaped_paths_are_decoded() {
+    let response = send(b"GET /echo/hello%20world HTTP/1.1\r\nHost: localhost\r\n\r\n").await;
+    assert_eq!(body(&response), "hello world");
+    let response = send(b"GET /files/%2e%2E/echo/z HTTP/1.1\r\nHost: localhost\r\n\r\n").await;
+    assert_eq!(body(&response), "z");
+    for target in ["/echo/%zz", "/echo/%4", "/echo/%00", "/echo/%ff"] {
+        let request = format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", target);
+        let response = send(request.as_bytes()).await;
+        assert_eq!(
+            status_line(&response),
+            "HTTP/1.1 400 Bad Request",
+            "{}",
+            target
+        );
+    }
+}
+
+// RFC 7230 §3.5: a server SHOULD ignore at least one empty line received
+// before the request-line, and MAY accept a bare LF as a line terminator.
+
+#[tokio::test]
+async fn leading_empty_line_is_ignored() {
+    let response = send(b"\r\nGET / HTTP/1.1\r\nHost: localhost\r\n\r\n").await;
+    assert_eq!(status_line(&response), "HTTP/1.1 200 OK");
+}
+
+#[tokio::test]
+async fn bare_lf_line_endings() {
+    let response = send(b"GET / HTTP/1.1\nHost: localhost\n\n").await;
+    assert_eq!(status_line(&response), "HTTP/1.1 200 OK");
+}
+
+// RFC 7230 §3.2: header-field = field-name ":" OWS field-value OWS
+
+#[tokio::test]
+async fn header_value_is_passed_to_handler() {
+    let response =
+        send(b"GET /user-agent HTTP/1.1\r\nHost: localhost\r\nUser-Agent: conformance/1.0\r\n\r\n")
+            .await;
+    assert_eq!(status_line(&response), "HTTP/1.1 200 OK");
+    assert_eq!(body(&response), "conformance/1.0");
+}
+
+#[tokio::test]
+async fn header_names_are_case_insensitive() {
+    let response =
+        send(b"GET /user-agent HTTP/1.1\r\nhost: localhost\r\nuser-agent: lower/1.0\r\n\r\n").await;
+    assert_eq!(body(&response), "lower/1.0");
+}
+
+#[tokio::test]
+async fn optional_whitespace_is_trimmed() {
+    let response =
+        send(b"GET /user-agent HTTP/1.1\r\nHost: localhost\r\nUser-Agent:\t ows/1.0 \t\r\n\r\n")
+            .await;
+    assert_eq!(body(&response), "ows/1.0");
+}
+
+#[tokio::test]
+async fn whitespace_before_colon_is_bad_request() {
+    let response = send(b"GET / HTTP/1.1\r\nHost : localhost\r\n\r\n").await;
+    assert_eq!(status_line(&response), "HTTP/1.1 400 Bad Request");
+    let response =
+        send(b"POST /echo HTTP/1.1\r\nHost: localhost\r\nContent-Length : 5\r\n\r\nhello").await;
+    assert_eq!(status_line(&response), "HTTP/1.1 400 Bad Request");
+}
+
+#[tokio::test]
+async fn malformed_field_lines_are_bad_request() {
+    for line in ["No-Colon-Here", ": no name", "Bad Name: x", "Bad\"Name: x"] {
+        let request = format!("GET / HTTP/1.1\r\nHost: localhost\r\n{}\r\n\r\n", line);
+        let response = send(request.as_bytes()).await;
+        assert_eq!(
+            status_line(&response),
+            "HTTP/1.1 400 Bad Request",
+            "{:?}",
+            line
+        );
+    }
+}
+
+#[tokio::test]
+async fn obsolete_line_folding_is_bad_request() {
+    let response = send(
+        b"GET /user-agent HTTP/1.1\r\nHost: localhost\r\nUser-Agent: folded\r\n value\r\n\r\n",
+    )
+    .await;
+    assert_eq!(status_line(&response), "HTTP/1.1 400 Bad Request");
+}
+
+// RFC 7230 §5.4: a server MUST respond with 400 to an HTTP/1.1 request
+// that lacks a Host header field.
+
+#[tokio::test]
+async fn missing_host_is_bad_request() {
+    let response = send(b"GET / HTTP/1.1\r\n\r\n").await;
+    assert_eq!(status_line(&response), "HTTP/1.1 400 Bad Request");
+}
+
+// RFC 9112 §3.2: a server MUST respond with 400 to a request with more
+// than one Host field line, even when the target carries the authority.
+
+#[tokio::test]
+async fn repeated_host_is_bad_request() {
+    let response = send(b"GET / HTTP/1.1\r\nHost: localhost\r\nHost: localhost\r\n\r\n").await;
+    assert_eq!(status_line(&response), "HTTP/1.1 400 Bad Request");
+    let response =
+        send(b"GET http://localhost/ HTTP/1.1\r\nHost: localhost\r\nHost: other\r\n\r\n").await;
+    assert_eq!(status_line(&response), "HTTP/1.1 400 Bad Request");
+}
+
+#[tokio::test]
+async fn host_with_userinfo_is_bad_request() {
+    let response = send(b"GET / HTTP/1.1\r\nHost: user@localhost\r\n\r\n").await;
+    assert_eq!(status_line(&response), "HTTP/1.1 400 Bad Request");
+}
+
+// RFC 7230 §5.3.2: a server MUST accept the absolute-form in requests.
+
+#[tokio::test]
+async fn absolute_form_target() {
+    let response = send(b"GET http://localhost/echo/abs HTTP/1.1\r\nHost: localhost\r\n\r\n").await;
+    assert_eq!(body(&response), "abs");
+}
+
+#[tokio::test]
+async fn authority_form_outside_connect_is_bad_request() {
+    let response = send(b"GET localhost:4221 HTTP/1.1\r\nHost: localhost\r\n\r\n").await;
+    assert_eq!(status_line(&response), "HTTP/1.1 400 Bad Request");
+}
+
+// RFC 7230 §3.3: message body framing.
+
+#[tokio::test]
+async fn content_length_body_is_read() {
+    let (address, directory) = start().await;
+    let response = exchange(
+        &address,
+        b"POST /files/upload.txt HTTP/1.1\r\nHost: localhost\r\nContent-Length: 11\r\n\r\nhello world",
+    )
+    .await;
+    assert_eq!(status_line(&response), "HTTP/1.1 201 Created");
+    assert_eq!(
+        std::fs::read_to_string(directory.join("upload.txt")).unwrap(),
+        "hello world"
+    );
+}
+
+#[tokio::test]
+async fn head_split_across_segments_is_read() {
+    let (address, _) = start().await;
+    let mut stream = TcpStream::connect(&address).await.unwrap();
+    for piece in [
+        &b"GET /echo/pie"[..],
+        b"ces HTTP/1.1\r\nHost: local",
+        b"host\r\nConnection: close\r\n\r",
+        b"\n",
+    ] {
+        stream.write_all(piece).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    let mut response = Vec::new();
+    let _ = tokio::time::timeout(RESPONSE_TIMEOUT, stream.read_to_end(&mut response)).await;
+    let response = String::from_utf8_lossy(&response);
+    assert_eq!(status_line(&response), "HTTP/1.1 200 OK");
+    assert_eq!(body(&response), "pieces");
+}
+
+#[tokio::test]
+async fn body_split_across_segments_is_read() {
+    let (address, directory) = start().await;
+    let mut stream = TcpStream::connect(&address).await.unwrap();
+    stream
+        .write_all(
+            b"POST /files/split.txt HTTP/1.1\r\nHost: localhost\r\nContent-Length: 10\r\nConnection: close\r\n\r\nhello",
+        )
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    stream.write_all(b"world").await.unwrap();
+    let mut response = Vec::new();
+    let _ = tokio::time::timeout(RESPONSE_TIMEOUT, stream.read_to_end(&mut response)).await;
+    assert_eq!(
+        status_line(&String::from_utf8_lossy(&response)),
+        "HTTP/1.1 201 Created"
+    );
+    assert_eq!(
+        std::fs::read_to_string(directory.join("split.txt")).unwrap(),
+        "helloworld"
+    );
+}
+
+#[tokio::test]
+async fn binary_body_is_kept_verbatim() {
+    let (address, directory) = start().await;
+    let mut request =
+        b"POST /files/blob.bin HTTP/1.1\r\nHost: localhost\r\nContent-Length: 4\r\n\r\n".to_vec();
+    request.extend_from_slice(&[0xff, 0x00, 0xc3, 0x28]);
+    let response = exchange(&address, &request).await;
+    assert_eq!(status_line(&response), "HTTP/1.1 201 Created");
+    assert_eq!(
+        std::fs::read(directory.join("blob.bin")).unwrap(),
+        [0xff, 0x00, 0xc3, 0x28]
+    );
+}
+
+#[tokio::test]
+async fn invalid_content_length_is_bad_request() {
+    let response =
+        send(b"POST /files/x HTTP/1.1\r\nHost: localhost\r\nContent-Length: abc\r\n\r\n").await;
+    assert_eq!(status_line(&response), "HTTP/1.1 400 Bad Request");
+}
+
+#[tokio::test]
+async fn conflicting_content_lengths_are_bad_request() {
+    let response = send(
+        b"POST /files/x HTTP/1.1\r\nHost: localhost\r\nContent-Length: 3\r\nContent-Length: 4\r\n\r\nabcd",
+    )
+    .await;
+    assert_eq!(status_line(&response), "HTTP/1.1 400 Bad Request");
+}
+
+#[tokio::test]
+async fn chunked_with_content_length_closes_the_connection() {
+    let response = send(
+        b"POST /echo HTTP/1.1\r\nHost: localhost\r\nContent-Length: 3\r\nTransfer-Encoding: chunked\r\n\r\n2\r\nab\r\n0\r\n\r\nGET /echo/smuggled HTTP/1.1\r\nHost: localhost\r\n\r\n",
+    )
+    .await;
+    assert_eq!(status_line(&response), "HTTP/1.1 200 OK");
+    assert!(response.contains("Connection: close\r\n"));
+    assert!(!response.contains("smuggled"));
+}
+
+#[tokio::test]
+async fn transfer_codings_must_end_in_a_single_chunked() {
+    for (codings, status) in [
+        (
+            "Transfer-Encoding: xchunked",
+            "HTTP/1.1 501 Not Implemented",
+        ),
+        (
+            "Transfer-Encoding: gzip, chunked",
+            "HTTP/1.1 501 Not Implemented",
+        ),
+        (
+            "Transfer-Encoding: chunked, chunked",
+            "HTTP/1.1 400 Bad Request",
+        ),
+        ("Transfer-Encoding: chunked, ", "HTTP/1.1 400 Bad Request"),
+        (
+            "Transfer-Encoding: chunked\r\nTransfer-Encoding: chunked",
+            "HTTP/1.1 400 Bad Request",
+        ),
+    ] {
+        let request = format!(
+            "POST /echo HTTP/1.1\r\nHost: localhost\r\n{}\r\n\r\n2\r\nab\r\n0\r\n\r\n",
+            codings
+        );
+        let response = send(request.as_bytes()).await;
+        assert_eq!(status_line(&response), status, "{}", codings);
+    }
+    let response = send(
+        b"POST /echo HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: Chunked\r\n\r\n2\r\nab\r\n0\r\n\r\n",
+    )
+    .await;
+    assert_eq!(status_line(&response), "HTTP/1.1 200 OK");
+}
+
+#[tokio::test]
+async fn chunked_body_is_decoded() {
+    let (address, directory) = start().await;
+    let response = exchange(
+        &address,
+        b"POST /files/chunked.txt HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n6\r\n world\r\n0\r\n\r\n",
+    )
+    .await;
+    assert_eq!(status_line(&response), "HTTP/1.1 201 Created");
+    assert_eq!(
+        std::fs::read_to_string(directory.join("chunked.txt")).unwrap(),
+        "hello world"
+    );
+}
+
+#[tokio::test]
+async fn chunk_sizes_must_be_hex_digits() {
+    let (address, _) = start().await;
+    for size in ["+5", "-5", "0x5", " 5", ""] {
+        let request = format!(
+            "GET /user-agent HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\n\r\n{}\r\nhello\r\n0\r\n\r\n",
+            size
+        );
+        let response = exchange(&address, request.as_bytes()).await;
+        assert_eq!(
+            status_line(&response),
+            "HTTP/1.1 400 Bad Request",
+            "{:?}",
+            size
+        );
+    }
+}
+
+#[tokio::test]
+async fn chunk_extensions_and_trailers_are_accepted() {
+    let (address, directory) = start().await;
+    let response = exchange(
+        &address,
+        b"POST /files/ext.txt HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\n\r\n3;name=value\r\nabc\r\nA\r\n0123456789\r\n0\r\nX-Trailer: yes\r\n\r\n",
+    )
+    .await;
+    assert_eq!(status_line(&response), "HTTP/1.1 201 Created");
+    assert_eq!(
+        std::fs::read_to_string(directory.join("ext.txt")).unwrap(),
+        "abc0123456789"
+    );
+}
+
+#[tokio::test]
+async fn trailer_digest_is_checked() {
+    let (address, directory) = start().await;
+    let response = exchange(
+        &address,
+        b"POST /files/trailer.txt HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\nTrailer: Content-MD5\r\n\r\n5\r\nhello\r\n0\r\nContent-MD5: AAAAAAAAAAAAAAAAAAAAAA==\r\n\r\n",
+    )
+    .await;
+    assert_eq!(status_line(&response), "HTTP/1.1 422 Unprocessable Content");
+    assert!(!directory.join("trailer.txt").exists());
+}
+
+#[tokio::test]
+async fn oversized_trailers_are_refused() {
+    let many = (0..101)
+        .map(|i| format!("X-Trailer-{}: {}\r\n", i, i))
+        .collect::<String>();
+    let long = format!("X-Long: {}\r\n", "a".repeat(9 * 1024));
+    for fields in [many, long] {
+        let (address, directory) = start().await;
+        let request = format!(
+            "POST /files/trailers.txt HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n0\r\n{}\r\n",
+            fields
+        );
+        let response = exchange(&address, request.as_bytes()).await;
+        assert_eq!(
+            status_line(&response),
+            "HTTP/1.1 431 Request Header Fields Too Large"
+        );
+        assert!(!directory.join("trailers.txt").exists());
+    }
+}
+
+// RFC 7230 §4.1: chunked transfer coding of response bodies.
+
+#[tokio::test]
+async fn streamed_echo_is_chunked() {
+    let response = send(
+        b"POST /echo HTTP/1.1\r\nHost: localhost\r\nContent-Type: text/plain\r\nContent-Length: 11\r\n\r\nhello world",
+    )
+    .await;
+    assert_eq!(status_line(&response), "HTTP/1.1 200 OK");
+    assert!(response.contains("Transfer-Encoding: chunked\r\n"));
+    assert!(!response.contains("Content-Length"));
+    assert_eq!(body(&response), "b\r\nhello world\r\n0\r\n\r\n");
+}
+
+#[tokio::test]
+async fn streamed_echo_answers_before_the_body_ends() {
+    let (address, _) = start().await;
+    let mut stream = TcpStream::connect(&address).await.unwrap();
+    stream
+        .write_all(b"POST /echo HTTP/1.1\r\nHost: localhost\r\nContent-Length: 11\r\n\r\nhello")
+        .await
+        .unwrap();
+    let mut received = Vec::new();
+    while !received.ends_with(b"5\r\nhello\r\n") {
+        let mut chunk = [0; 256];
+        let len = tokio::time::timeout(RESPONSE_TIMEOUT, stream.read(&mut chunk))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(len > 0);
+        received.extend_from_slice(&chunk[..len]);
+    }
+    stream.write_all(b" world").await.unwrap();
+    stream.shutdown().await.unwrap();
+    let _ = tokio::time::timeout(RESPONSE_TIMEOUT, stream.read_to_end(&mut received)).await;
+    let response = String::from_utf8(received).unwrap();
+    assert_eq!(body(&response), "5\r\nhello\r\n6\r\n world\r\n0\r\n\r\n");
+}
+
+#[tokio::test]
+async fn streamed_echo_sends_digest_trailer() {
+    let response = send(
+        b"POST /echo HTTP/1.1\r\nHost: localhost\r\nTE: trailers\r\nContent-Length: 11\r\n\r\nhello world",
+    )
+    .await;
+    assert!(response.contains("Trailer: Content-Digest\r\n"));
+    let trailer = format!(
+        "0\r\nContent-Digest: sha-256=:{}:\r\n\r\n",
+        base64_encode(&sha256(b"hello world"))
+    );
+    assert_eq!(body(&response), format!("b\r\nhello world\r\n{}", trailer));
+}
+
+#[tokio::test]
+async fn streamed_echo_to_http10_is_close_delimited() {
+    let response = send(
+        b"POST /echo HTTP/1.0\r\nConnection: keep-alive\r\nContent-Type: text/plain\r\nContent-Length: 11\r\n\r\nhello world",
+    )
+    .await;
+    assert_eq!(status_line(&response), "HTTP/1.1 200 OK");
+    assert!(!response.contains("Transfer-Encoding"));
+    assert!(response.contains("Connection: close\r\n"));
+    assert_eq!(body(&response), "hello world");
+}
+
+// RFC 7231 §4.3: method semantics.
+
+#[tokio::test]
+async fn head_has_no_body() {
+    let response = send(b"HEAD /echo/abc HTTP/1.1\r\nHost: localhost\r\n\r\n").await;
+    assert_eq!(status_line(&response), "HTTP/1.1 200 OK");
+    assert!(response.contains("Content-Length: 3\r\n"));
+    assert_eq!(body(&response), "");
+}
+
+#[tokio::test]
+async fn trace_is_not_allowed_by_default() {
+    let response = send(b"TRACE /echo/x HTTP/1.1\r\nHost: localhost\r\n\r\n").await;
+    assert_eq!(status_line(&response), "HTTP/1.1 405 Method Not Allowed");
+    assert!(response.contains("Allow: GET, HEAD, OPTIONS\r\n"));
+
+    let response = send(b"TRACE /nowhere HTTP/1.1\r\nHost: localhost\r\n\r\n").await;
+    assert_eq!(status_line(&response), "HTTP/1.1 404 Not Found");
+}
+
+#[tokio::test]
+async fn options_asterisk_lists_server_methods() {
+    let response = send(b"OPTIONS * HTTP/1.1\r\nHost: localhost\r\n\r\n").await;
+    assert_eq!(status_line(&response), "HTTP/1.1 200 OK");
+    assert!(response.contains("Allow: GET, HEAD, POST, PROPFIND, MKCOL, OPTIONS\r\n"));
+    assert_eq!(body(&response), "");
+}
+
+#[tokio::test]
+async fn options_on_path_lists_route_methods() {
+    let response = send(b"OPTIONS /files/a.txt HTTP/1.1\r\nHost: localhost\r\n\r\n").await;
+    assert_eq!(status_line(&response), "HTTP/1.1 204 No Content");
+    assert!(response.contains("Allow: GET, HEAD, POST, PROPFIND, MKCOL, OPTIONS\r\n"));
+    assert!(!response.contains("Content-Length"));
+
+    let response = send(b"OPTIONS /user-agent HTTP/1.1\r\nHost: localhost\r\n\r\n").await;
+    assert!(response.contains("Allow: GET, HEAD, OPTIONS\r\n"));
+}
@@ -0,0 +1,45 @@
+//! Cookies sent by clients and cookies set on responses.
+
+use std::time::{Duration, SystemTime};
+
+use http_server_starter_rust::cookie::{Cookie, SameSite};
+use http_server_starter_rust::request::Request;
+
+#[test]
+fn cookies_are_read_from_every_cookie_field() {
+    let req = Request::parse(
+        b"GET / HTTP/1.1\r\nHost: localhost\r\nCookie: session=abc; theme=\"dark mode\"; junk\r\ncookie: lang=en;session=shadowed\r\n\r\n",
+    )
+    .unwrap();
+    let jar = req.cookies();
+    assert_eq!(jar.len(), 4);
+    assert_eq!(jar.get("session").unwrap().value, "abc");
+    assert_eq!(jar.get("theme").unwrap().value, "dark mode");
+    assert_eq!(jar.get("lang").unwrap().value, "en");
+    assert!(jar.get("Session").is_none());
+    assert!(jar.get("junk").is_none());
+}
+
+#[test]
+fn set_cookie_lists_the_attributes() {
+    let cookie = Cookie::new("session", "abc")
+        .path("/")
+        .max_age(Duration::from_secs(3600))
+        .secure()
+        .http_only()
+        .same_site(SameSite::Lax);
+    assert_eq!(
+        cookie.to_string(),
+        "session=abc; Path=/; Max-Age=3600; Secure; HttpOnly; SameSite=Lax"
+    );
+    assert_eq!(
+        Cookie::new("a", "1")
+            .expires(SystemTime::UNIX_EPOCH + Duration::from_secs(784111777))
+            .to_string(),
+        "a=1; Expires=Sun, 06 Nov 1994 08:49:37 GMT"
+    );
+    assert_eq!(
+        Cookie::removal("session").to_string(),
+        "session=; Max-Age=0; Expires=Thu, 01 Jan 1970 00:00:00 GMT"
+    );
+}
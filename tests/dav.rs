@@ -0,0 +1,83 @@
+//! WebDAV browsing and directory creation under /files.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use http_server_starter_rust::{config::Config, control::Control, handlers, server};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+async fn send(address: &str, request: &str) -> String {
+    let mut stream = TcpStream::connect(address).await.unwrap();
+    stream.write_all(request.as_bytes()).await.unwrap();
+    stream.shutdown().await.unwrap();
+    let mut response = Vec::new();
+    let _ = tokio::time::timeout(Duration::from_secs(2), stream.read_to_end(&mut response)).await;
+    String::from_utf8_lossy(&response).into_owned()
+}
+
+#[tokio::test]
+async fn propfind_lists_and_mkcol_creates() {
+    let directory = std::env::temp_dir().join(format!("dav-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&directory);
+    std::fs::create_dir_all(directory.join("docs")).unwrap();
+    std::fs::write(directory.join("a b.txt"), "hello").unwrap();
+    let config = Config {
+        directory: directory.to_string_lossy().into_owned(),
+        log_requests: false,
+        ..Config::default()
+    };
+    let control = Arc::new(Control::new(&config));
+    let routes = handlers::routes(&config, &control).unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    tokio::spawn(server::serve(listener, Arc::new(config), Arc::new(routes)));
+
+    let listing = send(
+        &address,
+        "PROPFIND /files/ HTTP/1.1\r\nHost: localhost\r\nDepth: 1\r\n\r\n",
+    )
+    .await;
+    assert!(listing.starts_with("HTTP/1.1 207 Multi-Status\r\n"));
+    assert!(listing.contains("<D:href>/files/</D:href>"));
+    assert!(listing.contains("<D:href>/files/a%20b.txt</D:href>"));
+    assert!(listing.contains("<D:getcontentlength>5</D:getcontentlength>"));
+    assert!(listing.contains(
+        "<D:href>/files/docs/</D:href><D:propstat><D:prop><D:displayname>docs</D:displayname>\
+         <D:resourcetype><D:collection/></D:resourcetype>"
+    ));
+
+    let file = send(
+        &address,
+        "PROPFIND /files/a%20b.txt HTTP/1.1\r\nHost: localhost\r\nDepth: 0\r\n\r\n",
+    )
+    .await;
+    assert!(file.contains("<D:displayname>a b.txt</D:displayname><D:resourcetype/>"));
+    assert!(file.contains(" GMT</D:getlastmodified>"));
+
+    let created = send(
+        &address,
+        "MKCOL /files/docs/new HTTP/1.1\r\nHost: localhost\r\n\r\n",
+    )
+    .await;
+    assert!(created.starts_with("HTTP/1.1 201 Created\r\n"));
+    assert!(directory.join("docs/new").is_dir());
+    let again = send(
+        &address,
+        "MKCOL /files/docs/new HTTP/1.1\r\nHost: localhost\r\n\r\n",
+    )
+    .await;
+    assert!(again.starts_with("HTTP/1.1 405 Method Not Allowed\r\n"));
+    let orphan = send(
+        &address,
+        "MKCOL /files/x/y HTTP/1.1\r\nHost: localhost\r\n\r\n",
+    )
+    .await;
+    assert!(orphan.starts_with("HTTP/1.1 409 Conflict\r\n"));
+    let escape = send(
+        &address,
+        "PROPFIND /files/..%5Cescape HTTP/1.1\r\nHost: localhost\r\n\r\n",
+    )
+    .await;
+    assert!(escape.starts_with("HTTP/1.1 403 Forbidden\r\n"));
+}
@@ -0,0 +1,116 @@
+//! Uploads carrying Content-Digest or Content-MD5 are verified before they
+//! are written.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use http_server_starter_rust::hash::{base64_encode, md5, sha256};
+use http_server_starter_rust::{config::Config, control::Control, handlers, server};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+async fn upload(address: &str, name: &str, header: &str, body: &str) -> String {
+    let mut stream = TcpStream::connect(address).await.unwrap();
+    let request = format!(
+        "POST /files/{} HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n{}\r\n\r\n{}",
+        name,
+        body.len(),
+        header,
+        body
+    );
+    stream.write_all(request.as_bytes()).await.unwrap();
+    stream.shutdown().await.unwrap();
+    let mut response = Vec::new();
+    let _ = tokio::time::timeout(Duration::from_secs(2), stream.read_to_end(&mut response)).await;
+    String::from_utf8_lossy(&response).into_owned()
+}
+
+#[tokio::test]
+async fn mismatched_digests_are_rejected() {
+    let directory = std::env::temp_dir().join(format!("digest-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    let config = Config {
+        directory: directory.to_string_lossy().into_owned(),
+        log_requests: false,
+        ..Config::default()
+    };
+    let control = Arc::new(Control::new(&config));
+    let routes = handlers::routes(&config, &control).unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    tokio::spawn(server::serve(listener, Arc::new(config), Arc::new(routes)));
+
+    let sha = format!(
+        "Content-Digest: sha-512=:AAAA:, sha-256=:{}:",
+        base64_encode(&sha256(b"hello"))
+    );
+    let ok = upload(&address, "good.txt", &sha, "hello").await;
+    assert!(ok.starts_with("HTTP/1.1 201 Created\r\n"));
+    assert_eq!(std::fs::read(directory.join("good.txt")).unwrap(), b"hello");
+
+    let bad = upload(&address, "bad.txt", &sha, "hellO").await;
+    assert!(bad.starts_with("HTTP/1.1 422 Unprocessable Content\r\n"));
+    assert!(!directory.join("bad.txt").exists());
+
+    let md5 = format!("Content-MD5: {}", base64_encode(&md5(b"hello")));
+    assert!(upload(&address, "md5.txt", &md5, "hello")
+        .await
+        .starts_with("HTTP/1.1 201"));
+    assert!(upload(&address, "md5-bad.txt", &md5, "jello")
+        .await
+        .starts_with("HTTP/1.1 422"));
+}
+
+#[tokio::test]
+async fn streamed_uploads_only_replace_the_file_once_verified() {
+    let directory = std::env::temp_dir().join(format!("digest-stream-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    std::fs::write(directory.join("keep.txt"), b"old").unwrap();
+    let config = Config {
+        directory: directory.to_string_lossy().into_owned(),
+        log_requests: false,
+        ..Config::default()
+    };
+    let control = Arc::new(Control::new(&config));
+    let routes = handlers::routes(&config, &control).unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    tokio::spawn(server::serve(listener, Arc::new(config), Arc::new(routes)));
+
+    let chunked = |trailer: &str| {
+        format!(
+            "POST /files/keep.txt HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\nTrailer: Content-Digest\r\n\r\n3\r\nnew\r\n4\r\n tex\r\n1\r\nt\r\n0\r\nContent-Digest: sha-256=:{}:\r\n\r\n",
+            trailer
+        )
+    };
+    let send = |request: String| {
+        let address = address.clone();
+        async move {
+            let mut stream = TcpStream::connect(&address).await.unwrap();
+            stream.write_all(request.as_bytes()).await.unwrap();
+            stream.shutdown().await.unwrap();
+            let mut response = Vec::new();
+            let _ = tokio::time::timeout(Duration::from_secs(2), stream.read_to_end(&mut response))
+                .await;
+            String::from_utf8_lossy(&response).into_owned()
+        }
+    };
+
+    let bad = send(chunked(&base64_encode(&sha256(b"other")))).await;
+    assert!(bad.starts_with("HTTP/1.1 422 Unprocessable Content\r\n"));
+    assert_eq!(std::fs::read(directory.join("keep.txt")).unwrap(), b"old");
+
+    let good = send(chunked(&base64_encode(&sha256(b"new text")))).await;
+    assert!(good.starts_with("HTTP/1.1 201 Created\r\n"));
+    assert_eq!(
+        std::fs::read(directory.join("keep.txt")).unwrap(),
+        b"new text"
+    );
+
+    let leftovers = std::fs::read_dir(&directory)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .filter(|name| name.ends_with(".part"))
+        .collect::<Vec<String>>();
+    assert!(leftovers.is_empty(), "{:?}", leftovers);
+}
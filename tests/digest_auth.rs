@@ -0,0 +1,85 @@
+//! Digest authentication through the route table.
+
+use std::sync::Arc;
+
+use http_server_starter_rust::auth::DigestAuth;
+use http_server_starter_rust::config::Config;
+use http_server_starter_rust::hash::{hex, md5};
+use http_server_starter_rust::request::Request;
+use http_server_starter_rust::response::{HttpCode, Response};
+use http_server_starter_rust::routes::{CompareType, Route, Routes};
+
+fn routes() -> Routes {
+    let config = Config {
+        log_requests: false,
+        ..Config::default()
+    };
+    let mut routes = Routes::new(&config);
+    let auth = DigestAuth::new("test").user("alice", "secret");
+    routes.middleware("/", Arc::new(auth));
+    routes.add(Route::new(
+        "GET",
+        "/private",
+        CompareType::Exact,
+        Box::new(|_, _| Response::text(HttpCode::OK, "hidden")),
+    ));
+    routes
+}
+
+async fn send(routes: &Routes, method: &str, authorization: Option<&str>) -> String {
+    let mut head = format!("{} /private HTTP/1.1\r\nHost: a\r\n", method);
+    if let Some(value) = authorization {
+        head.push_str(&format!("Authorization: {}\r\n", value));
+    }
+    head.push_str("\r\n");
+    let req = Request::parse(head.as_bytes()).unwrap();
+    let mut written = Vec::new();
+    routes.execute(&mut written, req, &[]).await;
+    String::from_utf8(written).unwrap()
+}
+
+fn param(challenge: &str, name: &str) -> String {
+    let start = challenge.find(&format!("{}=\"", name)).unwrap() + name.len() + 2;
+    let len = challenge[start..].find('"').unwrap();
+    challenge[start..start + len].to_owned()
+}
+
+/// Credentials for `method` answering the MD5 challenge in `response`.
+fn authorization(scheme: &str, method: &str, response: &str) -> String {
+    let challenge = response
+        .split("Digest ")
+        .find(|c| c.contains("algorithm=MD5,"))
+        .unwrap();
+    let (nonce, opaque) = (param(challenge, "nonce"), param(challenge, "opaque"));
+    let ha1 = hex(&md5(b"alice:test:secret"));
+    let ha2 = hex(&md5(format!("{}:/private", method).as_bytes()));
+    let digest = hex(&md5(
+        format!("{}:{}:00000001:abc:auth:{}", ha1, nonce, ha2).as_bytes()
+    ));
+    format!(
+        "{} username=\"alice\", realm=\"test\", nonce=\"{}\", uri=\"/private\", qop=auth, nc=00000001, cnonce=\"abc\", response=\"{}\", opaque=\"{}\", algorithm=MD5",
+        scheme, nonce, digest, opaque
+    )
+}
+
+#[tokio::test]
+async fn head_is_checked_against_the_method_sent() {
+    let routes = routes();
+    let challenge = send(&routes, "HEAD", None).await;
+    assert!(challenge.starts_with("HTTP/1.1 401 Unauthorized\r\n"));
+
+    let credentials = authorization("Digest", "HEAD", &challenge);
+    let response = send(&routes, "HEAD", Some(&credentials)).await;
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(response.ends_with("\r\n\r\n"));
+}
+
+#[tokio::test]
+async fn the_scheme_is_case_insensitive() {
+    let routes = routes();
+    let challenge = send(&routes, "GET", None).await;
+    let credentials = authorization("digest", "GET", &challenge);
+    let response = send(&routes, "GET", Some(&credentials)).await;
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(response.ends_with("hidden"));
+}
@@ -0,0 +1,59 @@
+//! `103 Early Hints` ahead of responses that middleware lets through.
+
+use std::sync::Arc;
+
+use http_server_starter_rust::auth::BasicAuth;
+use http_server_starter_rust::config::Config;
+use http_server_starter_rust::hash::base64_encode;
+use http_server_starter_rust::request::Request;
+use http_server_starter_rust::response::{HttpCode, Response};
+use http_server_starter_rust::routes::{CompareType, Route, Routes};
+
+/// Serves `/app*`, hinting at a stylesheet, with Basic auth for
+/// `user:secret` on `/app/private`.
+fn routes() -> Routes {
+    let config = Config {
+        log_requests: false,
+        ..Config::default()
+    };
+    let mut routes = Routes::new(&config);
+    routes.early_hint("/app", "</app.css>; rel=preload; as=style");
+    let verifier = Box::new(|user: &str, pass: &str| user == "user" && pass == "secret");
+    routes.middleware("/app/private", Arc::new(BasicAuth::new("app", verifier)));
+    routes.add(Route::new(
+        "GET",
+        "/app",
+        CompareType::Prefix,
+        Box::new(|_, _| Response::text(HttpCode::OK, "page")),
+    ));
+    routes
+}
+
+async fn get(routes: &Routes, path: &str, fields: &str) -> String {
+    let head = format!("GET {} HTTP/1.1\r\nHost: a\r\n{}\r\n", path, fields);
+    let req = Request::parse(head.as_bytes()).unwrap();
+    let mut written = Vec::new();
+    routes.execute(&mut written, req, &[]).await;
+    String::from_utf8(written).unwrap()
+}
+
+#[tokio::test]
+async fn hints_precede_the_response() {
+    let response = get(&routes(), "/app/page", "").await;
+    assert!(response
+        .starts_with("HTTP/1.1 103 Early Hints\r\nLink: </app.css>; rel=preload; as=style\r\n"));
+    assert!(response.contains("\r\n\r\nHTTP/1.1 200 OK\r\n"));
+}
+
+#[tokio::test]
+async fn refused_requests_get_no_hints() {
+    let routes = routes();
+    let response = get(&routes, "/app/private", "").await;
+    assert!(response.starts_with("HTTP/1.1 401 Unauthorized\r\n"));
+    assert!(!response.contains("103"));
+
+    let authorization = format!("Authorization: Basic {}\r\n", base64_encode(b"user:secret"));
+    let response = get(&routes, "/app/private", &authorization).await;
+    assert!(response.starts_with("HTTP/1.1 103 Early Hints\r\n"));
+    assert!(response.ends_with("page"));
+}
@@ -0,0 +1,90 @@
+//! `Expect: 100-continue` handshakes for uploads.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use http_server_starter_rust::{config::Config, control::Control, handlers, server};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// Serves the default routes with `/files` behind Basic auth for
+/// `user:secret`.
+async fn start() -> String {
+    let directory = std::env::temp_dir().join(format!("expect-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    let htpasswd = directory.join("htpasswd");
+    std::fs::write(&htpasswd, "user:secret\n").unwrap();
+    let config = Config {
+        directory: directory.to_string_lossy().into_owned(),
+        basic_auth: vec![(
+            String::from("/files"),
+            htpasswd.to_string_lossy().into_owned(),
+        )],
+        log_requests: false,
+        ..Config::default()
+    };
+    let control = Arc::new(Control::new(&config));
+    let routes = handlers::routes(&config, &control).unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    tokio::spawn(server::serve(listener, Arc::new(config), Arc::new(routes)));
+    address
+}
+
+async fn read_some(stream: &mut TcpStream) -> String {
+    let mut buf = vec![0; 1024];
+    let len = tokio::time::timeout(Duration::from_secs(2), stream.read(&mut buf))
+        .await
+        .unwrap()
+        .unwrap();
+    String::from_utf8_lossy(&buf[..len]).into_owned()
+}
+
+#[tokio::test]
+async fn body_is_sent_after_100_continue() {
+    let address = start().await;
+    let mut stream = TcpStream::connect(&address).await.unwrap();
+    stream
+        .write_all(
+            b"POST /echo HTTP/1.1\r\nHost: localhost\r\nExpect: 100-continue\r\nContent-Length: 5\r\nConnection: close\r\n\r\n",
+        )
+        .await
+        .unwrap();
+    // The echo streams, so its head may follow the 100 before the body is
+    // sent.
+    let mut response = read_some(&mut stream).await;
+    assert!(response.starts_with("HTTP/1.1 100 Continue\r\n\r\n"));
+
+    stream.write_all(b"hello").await.unwrap();
+    stream.read_to_string(&mut response).await.unwrap();
+    assert!(response.contains("\r\n\r\nHTTP/1.1 200 OK\r\n"));
+    assert!(response.ends_with("5\r\nhello\r\n0\r\n\r\n"));
+}
+
+#[tokio::test]
+async fn unauthorized_upload_is_refused_before_the_body() {
+    let address = start().await;
+    let mut stream = TcpStream::connect(&address).await.unwrap();
+    stream
+        .write_all(
+            b"POST /files/upload.txt HTTP/1.1\r\nHost: localhost\r\nExpect: 100-continue\r\nContent-Length: 5\r\n\r\n",
+        )
+        .await
+        .unwrap();
+    let response = read_some(&mut stream).await;
+    assert!(response.starts_with("HTTP/1.1 401 Unauthorized\r\n"));
+    assert!(response.contains("Connection: close\r\n"));
+}
+
+#[tokio::test]
+async fn unknown_expectation_fails() {
+    let address = start().await;
+    let mut stream = TcpStream::connect(&address).await.unwrap();
+    stream
+        .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nExpect: teapot\r\n\r\n")
+        .await
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    assert!(response.starts_with("HTTP/1.1 417 Expectation Failed\r\n"));
+}
@@ -0,0 +1,72 @@
+//! The client address, taken from forwarding fields only when the peer is
+//! a trusted proxy.
+
+use http_server_starter_rust::config::Config;
+use http_server_starter_rust::request::Request;
+use std::net::{IpAddr, SocketAddr};
+
+fn client(peer: &str, fields: &str, trusted: &[&str]) -> IpAddr {
+    let head = format!("GET / HTTP/1.1\r\nHost: localhost\r\n{}\r\n", fields);
+    let mut req = Request::parse(head.as_bytes()).unwrap();
+    assert_eq!(req.remote_addr, None);
+    let peer = peer.parse::<SocketAddr>().unwrap();
+    let trusted = trusted
+        .iter()
+        .map(|ip| ip.parse().unwrap())
+        .collect::<Vec<_>>();
+    req.set_remote(peer, &trusted);
+    assert_eq!(req.remote_addr, Some(peer));
+    req.client_ip.unwrap()
+}
+
+fn ip(ip: &str) -> IpAddr {
+    ip.parse().unwrap()
+}
+
+#[test]
+fn forwarding_fields_are_believed_from_trusted_proxies_only() {
+    let xff = "X-Forwarded-For: 203.0.113.9, 198.51.100.1, 10.0.0.2\r\n";
+    assert_eq!(client("10.0.0.1:5000", xff, &[]), ip("10.0.0.1"));
+    assert_eq!(client("10.0.0.1:5000", xff, &["10.0.0.1"]), ip("10.0.0.2"));
+    assert_eq!(
+        client("10.0.0.1:5000", xff, &["10.0.0.1", "10.0.0.2"]),
+        ip("198.51.100.1")
+    );
+    assert_eq!(
+        client(
+            "10.0.0.1:5000",
+            "X-Forwarded-For: unknown\r\n",
+            &["10.0.0.1"]
+        ),
+        ip("10.0.0.1")
+    );
+
+    let forwarded = "Forwarded: for=\"[2001:db8::1]:4711\";proto=https, for=10.0.0.2\r\n";
+    assert_eq!(
+        client(
+            "10.0.0.1:5000",
+            &format!("{}{}", forwarded, xff),
+            &["10.0.0.1", "10.0.0.2"]
+        ),
+        ip("2001:db8::1")
+    );
+    assert_eq!(
+        client("10.0.0.1:5000", "Forwarded: for=_hidden\r\n", &["10.0.0.1"]),
+        ip("10.0.0.1")
+    );
+}
+
+#[test]
+fn trusted_proxies_are_configured_by_address() {
+    let args = [
+        "server",
+        "--trusted-proxy",
+        "10.0.0.1",
+        "--trusted-proxy",
+        "::1",
+    ];
+    let config = Config::from_args(args.map(String::from)).unwrap();
+    assert_eq!(config.trusted_proxies, [ip("10.0.0.1"), ip("::1")]);
+    let args = ["server", "--trusted-proxy", "10.0.0.0/8"];
+    assert!(Config::from_args(args.map(String::from)).is_err());
+}
@@ -0,0 +1,372 @@
+//! HTTP/2 with prior knowledge, served from the same routes as HTTP/1.1.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use http_server_starter_rust::hpack::{self, Decoder};
+use http_server_starter_rust::response::{Body, HttpCode, Response};
+use http_server_starter_rust::routes::{CompareType, Route, Routes};
+use http_server_starter_rust::{config::Config, control::Control, h2, handlers, server};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+async fn start() -> String {
+    let config = Config {
+        log_requests: false,
+        ..Config::default()
+    };
+    let control = Arc::new(Control::new(&config));
+    let routes = handlers::routes(&config, &control).unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    tokio::spawn(server::serve(listener, Arc::new(config), Arc::new(routes)));
+    address
+}
+
+/// Reads one frame: its type, flags, stream id and payload.
+async fn read_frame(stream: &mut TcpStream) -> (u8, u8, u32, Vec<u8>) {
+    let mut head = [0; 9];
+    tokio::time::timeout(Duration::from_secs(2), stream.read_exact(&mut head))
+        .await
+        .unwrap()
+        .unwrap();
+    let mut payload = vec![0; u32::from_be_bytes([0, head[0], head[1], head[2]]) as usize];
+    stream.read_exact(&mut payload).await.unwrap();
+    let id = u32::from_be_bytes([head[5], head[6], head[7], head[8]]);
+    (head[3], head[4], id, payload)
+}
+
+/// A request to send: its stream id, header fields and body.
+type Outgoing<'a> = (u32, Vec<(&'a str, &'a str)>, &'a [u8]);
+
+/// A response as received: its stream id, header fields and body.
+type Received = (u32, Vec<(String, String)>, Vec<u8>);
+
+async fn write_frame(stream: &mut TcpStream, kind: u8, flags: u8, id: u32, payload: &[u8]) {
+    let mut frame = (payload.len() as u32).to_be_bytes()[1..].to_vec();
+    frame.extend([kind, flags]);
+    frame.extend(id.to_be_bytes());
+    frame.extend(payload);
+    stream.write_all(&frame).await.unwrap();
+}
+
+/// Opens a connection and sends the preface, an empty SETTINGS frame and
+/// one request per `(id, fields, body)` entry.
+async fn send(address: &str, requests: &[Outgoing<'_>]) -> TcpStream {
+    let mut stream = TcpStream::connect(address).await.unwrap();
+    stream.write_all(h2::PREFACE).await.unwrap();
+    write_frame(&mut stream, 0x4, 0, 0, &[]).await;
+    for (id, fields, body) in requests {
+        let block = hpack::encode(fields.iter().copied());
+        let flags = if body.is_empty() { 0x5 } else { 0x4 };
+        write_frame(&mut stream, 0x1, flags, *id, &block).await;
+        // Split the body so it arrives over several DATA frames.
+        let chunks = body.chunks(4).collect::<Vec<&[u8]>>();
+        for (i, chunk) in chunks.iter().enumerate() {
+            let flags = if i + 1 == chunks.len() { 0x1 } else { 0 };
+            write_frame(&mut stream, 0x0, flags, *id, chunk).await;
+        }
+    }
+    stream
+}
+
+/// Reads frames until `count` streams have ended, returning each stream's
+/// decoded headers and body by id.
+async fn responses(stream: &mut TcpStream, count: usize) -> Vec<Received> {
+    let mut decoder = Decoder::new(4096, usize::MAX);
+    let mut responses: Vec<Received> = Vec::new();
+    let mut ended = 0;
+    while ended < count {
+        let mut head = [0; 9];
+        tokio::time::timeout(Duration::from_secs(2), stream.read_exact(&mut head))
+            .await
+            .unwrap()
+            .unwrap();
+        let len = u32::from_be_bytes([0, head[0], head[1], head[2]]) as usize;
+        let id = u32::from_be_bytes([head[5], head[6], head[7], head[8]]);
+        let mut payload = vec![0; len];
+        stream.read_exact(&mut payload).await.unwrap();
+        let index = match responses.iter().position(|(known, ..)| *known == id) {
+            Some(index) => index,
+            None => {
+                responses.push((id, Vec::new(), Vec::new()));
+                responses.len() - 1
+            }
+        };
+        match head[3] {
+            0x0 => responses[index].2.extend(payload),
+            0x1 => responses[index].1.extend(decoder.decode(&payload).unwrap()),
+            _ => continue,
+        }
+        if head[4] & 0x1 != 0 {
+            ended += 1;
+        }
+    }
+    responses.retain(|(id, ..)| *id != 0);
+    responses.sort_by_key(|(id, ..)| *id);
+    responses
+}
+
+fn field<'a>(fields: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    fields
+        .iter()
+        .find(|(known, _)| known == name)
+        .map(|(_, value)| value.as_str())
+}
+
+#[test]
+fn hpack_decodes_huffman_strings_and_the_dynamic_table() {
+    // RFC 7541 C.4.1 and C.4.2.
+    let mut decoder = Decoder::new(4096, usize::MAX);
+    let first = decoder
+        .decode(b"\x82\x86\x84\x41\x8c\xf1\xe3\xc2\xe5\xf2\x3a\x6b\xa0\xab\x90\xf4\xff")
+        .unwrap();
+    assert_eq!(first[3], (":authority".into(), "www.example.com".into()));
+    let second = decoder
+        .decode(b"\x82\x86\x84\xbe\x58\x86\xa8\xeb\x10\x64\x9c\xbf")
+        .unwrap();
+    assert_eq!(second[3], (":authority".into(), "www.example.com".into()));
+    assert_eq!(second[4], ("cache-control".into(), "no-cache".into()));
+
+    let block = hpack::encode([(":status", "200"), ("x-custom", "value")]);
+    assert_eq!(
+        Decoder::new(4096, usize::MAX).decode(&block).unwrap(),
+        [
+            (String::from(":status"), String::from("200")),
+            (String::from("x-custom"), String::from("value")),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn streams_are_answered_by_the_routes() {
+    let address = start().await;
+    let get = |path| {
+        vec![
+            (":method", "GET"),
+            (":scheme", "http"),
+            (":authority", "localhost"),
+            (":path", path),
+        ]
+    };
+    let mut stream = send(
+        &address,
+        &[
+            (1, get("/echo/one"), b""),
+            (3, get("/missing"), b""),
+            (
+                5,
+                vec![
+                    (":method", "POST"),
+                    (":scheme", "http"),
+                    (":authority", "localhost"),
+                    (":path", "/echo"),
+                    ("content-length", "11"),
+                ],
+                b"hello world",
+            ),
+        ],
+    )
+    .await;
+    let responses = responses(&mut stream, 3).await;
+
+    let (_, fields, body) = &responses[0];
+    assert_eq!(field(fields, ":status"), Some("200"));
+    assert!(field(fields, "date").is_some());
+    assert_eq!(body, b"one");
+
+    assert_eq!(field(&responses[1].1, ":status"), Some("404"));
+
+    let (_, fields, body) = &responses[2];
+    assert_eq!(field(fields, ":status"), Some("200"));
+    assert_eq!(field(fields, "transfer-encoding"), None);
+    assert_eq!(body, b"hello world");
+}
+
+#[tokio::test]
+async fn malformed_requests_reset_their_stream() {
+    let address = start().await;
+    let mut stream = send(
+        &address,
+        &[(
+            1,
+            vec![(":method", "GET"), (":path", "/"), ("connection", "close")],
+            b"",
+        )],
+    )
+    .await;
+    loop {
+        let mut head = [0; 9];
+        stream.read_exact(&mut head).await.unwrap();
+        let mut payload = vec![0; u32::from_be_bytes([0, head[0], head[1], head[2]]) as usize];
+        stream.read_exact(&mut payload).await.unwrap();
+        if head[3] == 0x3 {
+            assert_eq!(head[8], 1);
+            assert_eq!(payload, 1_u32.to_be_bytes());
+            break;
+        }
+    }
+}
+
+#[tokio::test]
+async fn host_may_repeat_the_authority() {
+    let address = start().await;
+    let fields = |host| {
+        vec![
+            (":method", "GET"),
+            (":scheme", "http"),
+            (":authority", "localhost"),
+            (":path", "/echo/hosted"),
+            ("host", host),
+        ]
+    };
+    let mut stream = send(&address, &[(1, fields("localhost"), b"")]).await;
+    let responses = responses(&mut stream, 1).await;
+    assert_eq!(field(&responses[0].1, ":status"), Some("200"));
+    assert_eq!(responses[0].2, b"hosted");
+
+    let mut stream = send(&address, &[(1, fields("elsewhere"), b"")]).await;
+    loop {
+        let (kind, _, id, payload) = read_frame(&mut stream).await;
+        if kind == 0x3 {
+            assert_eq!(id, 1);
+            assert_eq!(payload, 1_u32.to_be_bytes());
+            break;
+        }
+    }
+}
+
+#[tokio::test]
+async fn upgrade_answers_the_request_on_stream_one() {
+    let address = start().await;
+    let mut stream = TcpStream::connect(&address).await.unwrap();
+    stream
+        .write_all(
+            b"GET /echo/upgraded HTTP/1.1\r\nHost: localhost\r\nConnection: Upgrade, HTTP2-Settings\r\nUpgrade: h2c\r\nHTTP2-Settings: AAMAAABkAAQCAAAAAAIAAAAA\r\n\r\n",
+        )
+        .await
+        .unwrap();
+    let mut head = Vec::new();
+    while !head.ends_with(b"\r\n\r\n") {
+        let mut byte = [0; 1];
+        stream.read_exact(&mut byte).await.unwrap();
+        head.push(byte[0]);
+    }
+    let head = String::from_utf8(head).unwrap();
+    assert!(head.starts_with("HTTP/1.1 101 Switching Protocols\r\n"));
+    assert!(head.contains("Upgrade: h2c\r\n"));
+    assert!(!head.contains("Content-Length"));
+
+    stream.write_all(h2::PREFACE).await.unwrap();
+    write_frame(&mut stream, 0x4, 0, 0, &[]).await;
+    let responses = responses(&mut stream, 1).await;
+    let (id, fields, body) = &responses[0];
+    assert_eq!(*id, 1);
+    assert_eq!(field(fields, ":status"), Some("200"));
+    assert_eq!(body, b"upgraded");
+}
+
+#[tokio::test]
+async fn header_lists_past_the_advertised_size_end_the_connection() {
+    let address = start().await;
+    let mut stream = TcpStream::connect(&address).await.unwrap();
+    stream.write_all(h2::PREFACE).await.unwrap();
+    write_frame(&mut stream, 0x4, 0, 0, &[]).await;
+    // A 4000-byte field added to the table, then indexed 30 times: a block
+    // of about 4KB that decodes to over 120KB.
+    let mut block = vec![0x40, 5];
+    block.extend(b"x-big");
+    block.extend([0x7f, 0xa1, 0x1e]);
+    block.extend([b'a'; 4000]);
+    block.extend([0xbe; 30]);
+    write_frame(&mut stream, 0x1, 0x5, 1, &block).await;
+    loop {
+        let (kind, _, _, payload) = read_frame(&mut stream).await;
+        if kind == 0x7 {
+            assert_eq!(payload[4..8], 0xb_u32.to_be_bytes());
+            break;
+        }
+    }
+}
+
+#[tokio::test]
+async fn too_many_fields_are_refused() {
+    let address = start().await;
+    let names = (0..101)
+        .map(|i| format!("x-field-{}", i))
+        .collect::<Vec<_>>();
+    let mut fields = vec![
+        (":method", "GET"),
+        (":scheme", "http"),
+        (":authority", "localhost"),
+        (":path", "/echo/one"),
+    ];
+    fields.extend(names.iter().map(|name| (name.as_str(), "1")));
+    let mut stream = send(&address, &[(1, fields, b"")]).await;
+    let responses = responses(&mut stream, 1).await;
+    assert_eq!(field(&responses[0].1, ":status"), Some("431"));
+}
+
+#[tokio::test]
+async fn streams_hold_a_server_wide_slot_until_reset() {
+    let config = Config {
+        log_requests: false,
+        max_concurrency: 1,
+        queue_depth: 1,
+        queue_timeout: Duration::from_millis(200),
+        ..Config::default()
+    };
+    // `/hold` starts a response whose body never ends.
+    let senders = Arc::new(Mutex::new(Vec::new()));
+    let held = senders.clone();
+    let mut routes = Routes::new(&config);
+    routes.add(Route::new(
+        "GET",
+        "/hold",
+        CompareType::Exact,
+        Box::new(move |_, _| {
+            let (tx, rx) = tokio::sync::mpsc::channel(1);
+            held.lock().unwrap().push(tx);
+            Response::builder()
+                .status(HttpCode::OK)
+                .body(Body::Stream(rx))
+        }),
+    ));
+    routes.add(Route::new(
+        "GET",
+        "/ok",
+        CompareType::Exact,
+        Box::new(|_, _| Response::text(HttpCode::OK, "ok")),
+    ));
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    tokio::spawn(server::serve(listener, Arc::new(config), Arc::new(routes)));
+    let get = |path| {
+        vec![
+            (":method", "GET"),
+            (":scheme", "http"),
+            (":authority", "localhost"),
+            (":path", path),
+        ]
+    };
+
+    let mut stream = send(&address, &[(1, get("/hold"), b"")]).await;
+    while read_frame(&mut stream).await.0 != 0x1 {}
+
+    let mut other = TcpStream::connect(&address).await.unwrap();
+    other
+        .write_all(b"GET /ok HTTP/1.1\r\nHost: a\r\n\r\n")
+        .await
+        .unwrap();
+    let mut head = [0; 34];
+    other.read_exact(&mut head).await.unwrap();
+    assert!(head.starts_with(b"HTTP/1.1 503 Service Unavailable\r\n"));
+
+    // CANCEL stops the stream's task, giving its slot back.
+    write_frame(&mut stream, 0x3, 0, 1, &0x8_u32.to_be_bytes()).await;
+    let block = hpack::encode(get("/ok").into_iter());
+    write_frame(&mut stream, 0x1, 0x5, 3, &block).await;
+    let responses = responses(&mut stream, 1).await;
+    assert_eq!(field(&responses[0].1, ":status"), Some("200"));
+    assert_eq!(responses[0].2, b"ok");
+}
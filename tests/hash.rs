@@ -0,0 +1,110 @@
+//! Known-answer tests for the digests and base64, from RFC 1321, FIPS
+//! 180-4 and RFC 4648. Other tests compute expected values with these
+//! functions, so they are pinned here.
+
+use http_server_starter_rust::hash::{
+    base64_decode, base64_encode, hex, md5, sha1, sha256, Md5, Sha256,
+};
+
+/// A million `a`s, the long FIPS 180 message.
+fn million_a() -> Vec<u8> {
+    vec![b'a'; 1_000_000]
+}
+
+#[test]
+fn md5_matches_rfc_1321() {
+    let cases: [(&[u8], &str); 7] = [
+        (b"", "d41d8cd98f00b204e9800998ecf8427e"),
+        (b"a", "0cc175b9c0f1b6a831c399e269772661"),
+        (b"abc", "900150983cd24fb0d6963f7d28e17f72"),
+        (b"message digest", "f96b697d7cb7938d525a2f31aaf161d0"),
+        (
+            b"abcdefghijklmnopqrstuvwxyz",
+            "c3fcd3d76192e4007dfb496cca67e13b",
+        ),
+        (
+            b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789",
+            "d174ab98d277d9f5a5611c2c9f419d9f",
+        ),
+        (
+            b"12345678901234567890123456789012345678901234567890123456789012345678901234567890",
+            "57edf4a22be3c955ac49da2e2107b67a",
+        ),
+    ];
+    for (input, digest) in cases {
+        assert_eq!(hex(&md5(input)), digest, "md5 of {:?}", input);
+    }
+}
+
+#[test]
+fn sha1_matches_fips_180() {
+    assert_eq!(hex(&sha1(b"")), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+    assert_eq!(
+        hex(&sha1(b"abc")),
+        "a9993e364706816aba3e25717850c26c9cd0d89d"
+    );
+    assert_eq!(
+        hex(&sha1(
+            b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"
+        )),
+        "84983e441c3bd26ebaae4aa1f95129e5e54670f1"
+    );
+    assert_eq!(
+        hex(&sha1(&million_a())),
+        "34aa973cd4c4daa4f61eeb2bdbad27316534016f"
+    );
+}
+
+#[test]
+fn sha256_matches_fips_180() {
+    assert_eq!(
+        hex(&sha256(b"")),
+        "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+    );
+    assert_eq!(
+        hex(&sha256(b"abc")),
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    );
+    assert_eq!(
+        hex(&sha256(
+            b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"
+        )),
+        "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+    );
+    assert_eq!(
+        hex(&sha256(&million_a())),
+        "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0"
+    );
+}
+
+#[test]
+fn incremental_digests_match_one_shot() {
+    let data = million_a();
+    let mut md5_hasher = Md5::new();
+    let mut sha256_hasher = Sha256::new();
+    for piece in data.chunks(1000).chain(data[..37].chunks(7)) {
+        md5_hasher.update(piece);
+        sha256_hasher.update(piece);
+    }
+    let whole = [&data[..], &data[..37]].concat();
+    assert_eq!(md5_hasher.finish(), md5(&whole));
+    assert_eq!(sha256_hasher.finish(), sha256(&whole));
+}
+
+#[test]
+fn base64_matches_rfc_4648() {
+    let cases = [
+        ("", ""),
+        ("f", "Zg=="),
+        ("fo", "Zm8="),
+        ("foo", "Zm9v"),
+        ("foob", "Zm9vYg=="),
+        ("fooba", "Zm9vYmE="),
+        ("foobar", "Zm9vYmFy"),
+    ];
+    for (input, encoded) in cases {
+        assert_eq!(base64_encode(input.as_bytes()), encoded);
+        assert_eq!(base64_decode(encoded).unwrap(), input.as_bytes());
+    }
+    assert_eq!(base64_decode("Zm9v!"), None);
+}
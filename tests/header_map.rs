@@ -0,0 +1,30 @@
+//! Case-insensitive, multi-valued header fields.
+
+use http_server_starter_rust::header_map::HeaderMap;
+use http_server_starter_rust::request::Request;
+
+#[test]
+fn names_match_any_case_and_repeats_are_kept() {
+    let req =
+        Request::parse(b"GET / HTTP/1.1\r\nhost: localhost\r\nCookie: a=1\r\ncookie:b=2 \r\n\r\n")
+            .unwrap();
+    assert_eq!(req.host(), Some("localhost"));
+    assert_eq!(req.headers.get("COOKIE").unwrap(), "a=1");
+    assert_eq!(
+        req.headers.get_all("Cookie").collect::<Vec<&String>>(),
+        ["a=1", "b=2"]
+    );
+
+    let mut headers = HeaderMap::from([
+        (String::from("Vary"), String::from("Accept")),
+        (String::from("vary"), String::from("Cookie")),
+    ]);
+    assert_eq!(
+        headers.insert(String::from("VARY"), String::from("*")),
+        Some(String::from("Accept"))
+    );
+    assert_eq!(headers.len(), 1);
+    assert_eq!(headers["vary"], "*");
+    assert_eq!(headers.remove("Vary"), Some(String::from("*")));
+    assert!(headers.is_empty());
+}
@@ -0,0 +1,107 @@
+//! Headers injected from configuration by path pattern.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use http_server_starter_rust::{config::Config, control::Control, handlers, server};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+async fn get(address: &str, path: &str) -> String {
+    let mut stream = TcpStream::connect(address).await.unwrap();
+    let request = format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path);
+    stream.write_all(request.as_bytes()).await.unwrap();
+    stream.shutdown().await.unwrap();
+    let mut response = Vec::new();
+    let _ = tokio::time::timeout(Duration::from_secs(2), stream.read_to_end(&mut response)).await;
+    String::from_utf8_lossy(&response).into_owned()
+}
+
+#[tokio::test]
+async fn headers_are_added_by_path_pattern() {
+    let config = Config::from_args(
+        [
+            "server",
+            "--log-requests",
+            "false",
+            "--add-header",
+            "*=Cross-Origin-Resource-Policy: same-origin",
+            "--add-header",
+            "/echo/private*=X-Robots-Tag: noindex",
+            "--add-header",
+            "*=Content-Type: application/x-ignored",
+        ]
+        .map(String::from),
+    )
+    .unwrap();
+    let control = Arc::new(Control::new(&config));
+    let routes = handlers::routes(&config, &control).unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    tokio::spawn(server::serve(listener, Arc::new(config), Arc::new(routes)));
+
+    let public = get(&address, "/echo/public").await;
+    assert!(public.contains("Cross-Origin-Resource-Policy: same-origin\r\n"));
+    assert!(!public.contains("X-Robots-Tag"));
+    // Handler-set headers win over configured ones.
+    assert!(public.contains("Content-Type: text/plain; charset=utf-8\r\n"));
+
+    let private = get(&address, "/echo/private?x=1").await;
+    assert!(private.contains("X-Robots-Tag: noindex\r\n"));
+
+    let missing = get(&address, "/nope").await;
+    assert!(missing.starts_with("HTTP/1.1 404 Not Found\r\n"));
+    assert!(missing.contains("Cross-Origin-Resource-Policy: same-origin\r\n"));
+}
+
+#[tokio::test]
+async fn alt_svc_is_advertised_everywhere() {
+    assert!(Config::from_args(["server", "--alt-svc", "h2=8443"].map(String::from)).is_err());
+    let config = Config::from_args(
+        [
+            "server",
+            "--log-requests",
+            "false",
+            "--alt-svc",
+            "h2=\":8443\"; ma=3600",
+        ]
+        .map(String::from),
+    )
+    .unwrap();
+    let control = Arc::new(Control::new(&config));
+    let routes = handlers::routes(&config, &control).unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    tokio::spawn(server::serve(listener, Arc::new(config), Arc::new(routes)));
+
+    for path in ["/", "/echo/a", "/nope"] {
+        let response = get(&address, path).await;
+        assert!(response.contains("Alt-Svc: h2=\":8443\"; ma=3600\r\n"));
+    }
+}
+
+#[tokio::test]
+async fn server_header_can_be_overridden_or_suppressed() {
+    for (value, expected) in [
+        (None, Some("Server: codecrafters-http/")),
+        (Some("edge"), Some("Server: edge\r\n")),
+        (Some("off"), None),
+    ] {
+        let mut args = vec!["server", "--log-requests", "false"];
+        if let Some(value) = value {
+            args.extend(["--server-header", value]);
+        }
+        let config = Config::from_args(args.into_iter().map(String::from)).unwrap();
+        let control = Arc::new(Control::new(&config));
+        let routes = handlers::routes(&config, &control).unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        tokio::spawn(server::serve(listener, Arc::new(config), Arc::new(routes)));
+
+        let response = get(&address, "/").await;
+        match expected {
+            Some(header) => assert!(response.contains(header), "{}", response),
+            None => assert!(!response.contains("Server:"), "{}", response),
+        }
+    }
+}
@@ -0,0 +1,76 @@
+//! Basic auth against an htpasswd file.
+
+use std::path::PathBuf;
+
+use http_server_starter_rust::auth::BasicAuth;
+use http_server_starter_rust::hash::{base64_encode, constant_time_eq};
+use http_server_starter_rust::request::Request;
+use http_server_starter_rust::routes::Middleware;
+
+/// Writes `contents` to a file of its own under the temp directory.
+fn htpasswd(name: &str, contents: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("htpasswd-{}-{}", std::process::id(), name));
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+fn allowed(auth: &BasicAuth, user: &str, pass: &str) -> bool {
+    let credentials = base64_encode(format!("{}:{}", user, pass).as_bytes());
+    let head = format!(
+        "GET / HTTP/1.1\r\nHost: a\r\nAuthorization: Basic {}\r\n\r\n",
+        credentials
+    );
+    auth.expect(&Request::parse(head.as_bytes()).unwrap())
+        .is_none()
+}
+
+#[test]
+fn sha_apr1_and_plain_entries_are_checked() {
+    let path = htpasswd(
+        "formats",
+        "# users\n\
+         sha:{SHA}5en6G6MezRroT3XKqkdPOmY/BfQ=\n\
+         apr:$apr1$abcdefgh$h9FWgUz3n9YxylKLlR5SQ/\n\
+         plain:secret\n",
+    );
+    let auth = BasicAuth::htpasswd("files", path.to_str().unwrap()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    for user in ["sha", "apr", "plain"] {
+        assert!(allowed(&auth, user, "secret"), "{}", user);
+        assert!(!allowed(&auth, user, "Secret"), "{}", user);
+        assert!(!allowed(&auth, user, "secret "), "{}", user);
+    }
+    assert!(!allowed(&auth, "nobody", "secret"));
+}
+
+#[test]
+fn unsupported_schemes_are_refused_at_startup() {
+    for (name, entry) in [
+        (
+            "bcrypt",
+            "user:$2y$05$c4WoMPo3SXsafkva.HHa6uXQZWr7oboPiC2bT/r7q1BB8I2s0BRqC\n",
+        ),
+        ("crypt", "user:abJnggxhB/yWI\n"),
+        (
+            "sha512-crypt",
+            "user:$6$saltsalt$qFmFH.bQmmtXzyBY0s9v7Oicd2z4XSIecDzlB5KiA2/jctKu9YterLp8wwnSq.qc.eoxqOmSuNp2xS0ktL3nh/\n",
+        ),
+        ("md5-crypt", "user:$1$saltsalt$FSYmvnuDuMEb2dGvgzkal1\n"),
+        ("ssha", "user:{SSHA}Y3J5cHRvZ3JhcGh5c2FsdHNhbHQ=\n"),
+    ] {
+        let path = htpasswd(name, entry);
+        let loaded = BasicAuth::htpasswd("files", path.to_str().unwrap());
+        std::fs::remove_file(&path).unwrap();
+        let err = loaded.err().expect(name).to_string();
+        assert!(err.contains("unsupported hash for user"), "{}", err);
+    }
+}
+
+#[test]
+fn constant_time_eq_compares_whole_strings() {
+    assert!(constant_time_eq(b"secret", b"secret"));
+    assert!(!constant_time_eq(b"secret", b"secreT"));
+    assert!(!constant_time_eq(b"secret", b"secrets"));
+    assert!(constant_time_eq(b"", b""));
+}
@@ -0,0 +1,96 @@
+//! Reading JSON request bodies into typed values, and writing values as
+//! JSON responses.
+
+use http_server_starter_rust::json::{FromJson, JsonError, Value};
+use http_server_starter_rust::request::Request;
+use http_server_starter_rust::response::{Body, HttpCode, Response};
+use std::collections::HashMap;
+
+#[derive(Debug, PartialEq)]
+struct Upload {
+    name: String,
+    size: u64,
+    tags: Vec<String>,
+    note: Option<String>,
+}
+
+impl FromJson for Upload {
+    fn from_json(mut value: Value) -> Result<Self, JsonError> {
+        Ok(Upload {
+            name: value.field("name")?,
+            size: value.field("size")?,
+            tags: value.field("tags")?,
+            note: value.field("note")?,
+        })
+    }
+}
+
+fn post(content_type: &str, body: &str) -> Request {
+    let head = format!(
+        "POST /files HTTP/1.1\r\nHost: localhost\r\nContent-Type: {}\r\n\r\n{}",
+        content_type, body
+    );
+    Request::parse(head.as_bytes()).unwrap()
+}
+
+#[test]
+fn body_is_read_into_a_typed_value() {
+    let req = post(
+        "application/json; charset=utf-8",
+        r#" {"name": "caf\u00e9 \ud83d\ude00", "size": 12, "tags": ["a", "b"], "extra": {"x": [1.5e2, null]}} "#,
+    );
+    assert_eq!(
+        req.json::<Upload>().unwrap(),
+        Upload {
+            name: String::from("café 😀"),
+            size: 12,
+            tags: vec![String::from("a"), String::from("b")],
+            note: None,
+        }
+    );
+    let value = req.json::<Value>().unwrap();
+    assert_eq!(
+        value.get("extra").unwrap().to_string(),
+        r#"{"x":[150,null]}"#
+    );
+}
+
+#[test]
+fn errors_map_to_415_and_400() {
+    let code = |err: JsonError| Response::from(err).code;
+    let err = post("text/plain", "{}").json::<Value>().unwrap_err();
+    assert_eq!(code(err), HttpCode::UnsupportedMediaType);
+    for body in ["{\"name\": }", "[1, 2", "01", "{} {}", "\"\\x\""] {
+        let err = post("application/json", body).json::<Value>().unwrap_err();
+        assert!(matches!(err, JsonError::Syntax { .. }), "{}", body);
+        assert_eq!(code(err), HttpCode::BadRequest);
+    }
+    let err = post(
+        "application/vnd.api+json",
+        r#"{"name": "a", "size": -1, "tags": []}"#,
+    )
+    .json::<Upload>()
+    .unwrap_err();
+    assert_eq!(err.to_string(), "size: expected a non-negative integer");
+    assert_eq!(code(err), HttpCode::BadRequest);
+}
+
+#[test]
+fn values_are_written_into_json_responses() {
+    let mut scores = HashMap::new();
+    scores.insert(String::from("b"), vec![Some(1.5), None]);
+    scores.insert(String::from("a\""), vec![Some(-2.0)]);
+    let res = Response::json(&scores);
+    assert_eq!(res.code, HttpCode::OK);
+    let headers = res.headers.as_ref().unwrap();
+    assert_eq!(headers.get("Content-Type").unwrap(), "application/json");
+    let Some(Body::Bytes(body)) = res.content else {
+        panic!("no body");
+    };
+    assert_eq!(body, br#"{"a\"":[-2],"b":[1.5,null]}"#);
+
+    assert_eq!(Response::json("hi").code, HttpCode::OK);
+    let res = Response::json(&vec![1.0, f64::NAN]);
+    assert_eq!(res.code, HttpCode::InternalServerError);
+    assert!(res.content.is_none());
+}
@@ -0,0 +1,121 @@
+//! Persistent connections: several requests per connection, closed on
+//! request, after the per-connection cap, when idle and on shutdown.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use http_server_starter_rust::{config::Config, control::Control, handlers, server};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::oneshot;
+
+async fn start(args: &[&str]) -> (String, oneshot::Sender<()>) {
+    let mut all = vec!["server", "--log-requests", "false"];
+    all.extend_from_slice(args);
+    let config = Config::from_args(all.into_iter().map(String::from)).unwrap();
+    let control = Arc::new(Control::new(&config));
+    let routes = handlers::routes(&config, &control).unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let (stop, stopped) = oneshot::channel();
+    tokio::spawn(server::serve_until(
+        listener,
+        Arc::new(config),
+        Arc::new(routes),
+        async move {
+            let _ = stopped.await;
+        },
+    ));
+    (address, stop)
+}
+
+/// Writes `requests` in one go and reads until the server closes the
+/// connection, returning the responses and how long that took.
+async fn exchange(address: &str, requests: &str) -> (String, Duration) {
+    let started = Instant::now();
+    let mut stream = TcpStream::connect(address).await.unwrap();
+    stream.write_all(requests.as_bytes()).await.unwrap();
+    let mut response = Vec::new();
+    let _ = tokio::time::timeout(Duration::from_secs(5), stream.read_to_end(&mut response)).await;
+    (
+        String::from_utf8_lossy(&response).into_owned(),
+        started.elapsed(),
+    )
+}
+
+#[tokio::test]
+async fn pipelined_requests_share_a_connection() {
+    let (address, _stop) = start(&[]).await;
+    let (response, elapsed) = exchange(
+        &address,
+        "GET /echo/one HTTP/1.1\r\nHost: localhost\r\n\r\n\
+         GET /echo/two HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    let responses = response.split("HTTP/1.1 200 OK\r\n").collect::<Vec<&str>>();
+    assert_eq!(responses.len(), 3);
+    assert!(responses[1].contains("Connection: keep-alive\r\n"));
+    assert!(responses[1].contains("Keep-Alive: timeout=5, max=99\r\n"));
+    assert!(responses[1].ends_with("one"));
+    assert!(responses[2].contains("Connection: close\r\n"));
+    assert!(responses[2].ends_with("two"));
+    assert!(elapsed < Duration::from_secs(1));
+}
+
+#[tokio::test]
+async fn chunked_bodies_are_delimited_on_a_persistent_connection() {
+    let (address, _stop) = start(&[]).await;
+    let (response, _) = exchange(
+        &address,
+        "POST /echo HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\n\r\n\
+         4\r\nping\r\n0\r\n\r\n\
+         GET /echo/after HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    assert_eq!(response.matches("HTTP/1.1 200 OK").count(), 2);
+    assert!(response.contains("4\r\nping\r\n0\r\n\r\n"));
+    assert!(response.ends_with("after"));
+}
+
+#[tokio::test]
+async fn http10_closes_unless_asked_and_the_cap_is_enforced() {
+    let (address, _stop) = start(&["--keep-alive-max", "2"]).await;
+    let (response, elapsed) = exchange(&address, "GET /echo/old HTTP/1.0\r\n\r\n").await;
+    assert!(response.contains("Connection: close\r\n"));
+    assert!(elapsed < Duration::from_secs(1));
+
+    let request = "GET /echo/x HTTP/1.0\r\nConnection: keep-alive\r\n\r\n";
+    let (response, _) = exchange(&address, &request.repeat(3)).await;
+    assert_eq!(response.matches("HTTP/1.1 200 OK").count(), 2);
+    assert!(response.contains("Keep-Alive: timeout=5, max=1\r\n"));
+    assert!(response.contains("Connection: close\r\n"));
+}
+
+#[tokio::test]
+async fn idle_connections_time_out_and_close_on_shutdown() {
+    let (address, _stop) = start(&["--keep-alive-timeout", "1"]).await;
+    let (response, elapsed) = exchange(
+        &address,
+        "GET /echo/idle HTTP/1.1\r\nHost: localhost\r\n\r\n",
+    )
+    .await;
+    assert!(response.ends_with("idle"));
+    assert!(elapsed >= Duration::from_millis(900) && elapsed < Duration::from_secs(3));
+
+    let (address, stop) = start(&[]).await;
+    let mut stream = TcpStream::connect(&address).await.unwrap();
+    stream
+        .write_all(b"GET /echo/a HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .await
+        .unwrap();
+    let mut buf = [0; 1024];
+    assert!(stream.read(&mut buf).await.unwrap() > 0);
+    let started = Instant::now();
+    stop.send(()).unwrap();
+    let mut rest = Vec::new();
+    tokio::time::timeout(Duration::from_secs(2), stream.read_to_end(&mut rest))
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(started.elapsed() < Duration::from_secs(1));
+}
@@ -0,0 +1,62 @@
+//! Maintenance mode: 503 with `Retry-After` for everything but exempt
+//! paths, toggled at runtime.
+
+use std::sync::Arc;
+
+use http_server_starter_rust::config::Config;
+use http_server_starter_rust::control::Control;
+use http_server_starter_rust::handlers;
+use http_server_starter_rust::request::Request;
+use http_server_starter_rust::routes::Routes;
+
+async fn get(routes: &Routes, path: &str) -> String {
+    let head = format!("GET {} HTTP/1.1\r\nHost: a\r\n\r\n", path);
+    let req = Request::parse(head.as_bytes()).unwrap();
+    let mut written = Vec::new();
+    routes.execute(&mut written, req, &[]).await;
+    String::from_utf8(written).unwrap()
+}
+
+#[tokio::test]
+async fn maintenance_refuses_all_but_exempt_paths_until_turned_off() {
+    let config = Config {
+        log_requests: false,
+        retry_after: 120,
+        ..Config::default()
+    };
+    let control = Arc::new(Control::new(&config));
+    let routes = handlers::routes(&config, &control).unwrap();
+    assert!(get(&routes, "/echo/up").await.ends_with("up"));
+
+    control.set_maintenance(true);
+    let response = get(&routes, "/echo/up").await;
+    assert!(response.starts_with("HTTP/1.1 503 Service Unavailable\r\n"));
+    assert!(response.contains("Retry-After: 120\r\n"));
+    let health = get(&routes, "/healthz").await;
+    assert!(health.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(health.ends_with("maintenance"));
+
+    control.set_maintenance(false);
+    assert!(get(&routes, "/echo/up").await.ends_with("up"));
+    assert!(get(&routes, "/healthz").await.ends_with("ok"));
+}
+
+#[tokio::test]
+async fn maintenance_serves_the_configured_page() {
+    let page = std::env::temp_dir().join(format!("maintenance-{}.html", std::process::id()));
+    std::fs::write(&page, "<h1>Back soon</h1>").unwrap();
+    let config = Config {
+        log_requests: false,
+        maintenance: true,
+        maintenance_page: Some(page.to_str().unwrap().to_owned()),
+        ..Config::default()
+    };
+    let control = Arc::new(Control::new(&config));
+    let routes = handlers::routes(&config, &control).unwrap();
+    std::fs::remove_file(&page).unwrap();
+
+    let response = get(&routes, "/").await;
+    assert!(response.starts_with("HTTP/1.1 503 Service Unavailable\r\n"));
+    assert!(response.contains("Content-Type: text/html\r\n"));
+    assert!(response.ends_with("\r\n\r\n<h1>Back soon</h1>"));
+}
@@ -0,0 +1,37 @@
+//! `Content-Type` values parsed into type, subtype and parameters.
+
+use http_server_starter_rust::media_type::MediaType;
+
+#[test]
+fn parameters_are_parsed_and_unquoted() {
+    let media: MediaType = "Multipart/Form-Data; Boundary=\"a b\\\"c\" ; charset=UTF-8;"
+        .parse()
+        .unwrap();
+    assert!(media.is("multipart", "form-data"));
+    assert_eq!(media.essence(), "multipart/form-data");
+    assert_eq!(media.boundary(), Some("a b\"c"));
+    assert_eq!(media.charset(), Some("UTF-8"));
+    assert_eq!(
+        media.to_string(),
+        "multipart/form-data; boundary=\"a b\\\"c\"; charset=UTF-8"
+    );
+
+    let media: MediaType = "application/problem+json".parse().unwrap();
+    assert_eq!(media.suffix(), Some("json"));
+    assert_eq!(media.charset(), None);
+}
+
+#[test]
+fn malformed_values_are_refused() {
+    for value in [
+        "",
+        "text",
+        "text/",
+        "text/plain; charset",
+        "text/plain; charset=\"utf-8",
+        "text/plain; charset=a b",
+        "text/plain extra",
+    ] {
+        assert!(value.parse::<MediaType>().is_err(), "{:?}", value);
+    }
+}
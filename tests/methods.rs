@@ -0,0 +1,106 @@
+//! Routing on the full set of request methods.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use http_server_starter_rust::config::Config;
+use http_server_starter_rust::response::{HttpCode, Response};
+use http_server_starter_rust::routes::{CompareType, Route, Routes};
+use http_server_starter_rust::server;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// Serves `/items*`, answering each registered method with its name.
+async fn start() -> String {
+    serve(Config {
+        log_requests: false,
+        ..Config::default()
+    })
+    .await
+}
+
+async fn serve(config: Config) -> String {
+    let mut routes = Routes::new(&config);
+    for method in ["GET", "PUT", "DELETE", "PATCH"] {
+        routes.add(Route::new(
+            method,
+            "/items",
+            CompareType::Prefix,
+            Box::new(move |req, _| Response {
+                code: HttpCode::OK,
+                content: Some(format!("{} {}", method, req.body.len()).into_bytes().into()),
+                headers: None,
+            }),
+        ));
+    }
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    tokio::spawn(server::serve(listener, Arc::new(config), Arc::new(routes)));
+    address
+}
+
+async fn exchange(address: &str, request: &str) -> String {
+    let mut stream = TcpStream::connect(address).await.unwrap();
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = Vec::new();
+    let _ = tokio::time::timeout(Duration::from_secs(2), stream.read_to_end(&mut response)).await;
+    String::from_utf8_lossy(&response).into_owned()
+}
+
+#[tokio::test]
+async fn put_delete_and_patch_reach_their_routes() {
+    let address = start().await;
+    for (method, body) in [("PUT", "abc"), ("DELETE", ""), ("PATCH", "de")] {
+        let response = exchange(
+            &address,
+            &format!(
+                "{} /items/1 HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nContent-Length: {}\r\n\r\n{}",
+                method,
+                body.len(),
+                body
+            ),
+        )
+        .await;
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+        assert!(response.ends_with(&format!("{} {}", method, body.len())));
+    }
+}
+
+#[tokio::test]
+async fn other_methods_on_a_routed_path_are_not_allowed() {
+    let address = start().await;
+    let response = exchange(
+        &address,
+        "POST /items/1 HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nContent-Length: 0\r\n\r\n",
+    )
+    .await;
+    assert!(response.starts_with("HTTP/1.1 405 Method Not Allowed\r\n"));
+    assert!(response.contains("Allow: GET, HEAD, PUT, PATCH, DELETE, OPTIONS\r\n"));
+
+    let response = exchange(
+        &address,
+        "POST /other HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nContent-Length: 0\r\n\r\n",
+    )
+    .await;
+    assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+}
+
+#[tokio::test]
+async fn trace_echoes_the_head_without_credentials() {
+    let address = serve(Config {
+        trace: true,
+        log_requests: false,
+        ..Config::default()
+    })
+    .await;
+    let response = exchange(
+        &address,
+        "TRACE /items HTTP/1.1\r\nHost: localhost\r\nAuthorization: Basic YTpi\r\nCookie: id=1\r\nproxy-authorization: x\r\nMax-Forwards: 0\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(response.contains("Content-Type: message/http\r\n"));
+    assert!(response.ends_with(
+        "\r\n\r\nTRACE /items HTTP/1.1\r\nHost: localhost\r\nMax-Forwards: 0\r\nConnection: close\r\n\r\n"
+    ));
+}
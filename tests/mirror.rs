@@ -0,0 +1,69 @@
+//! Copies of incoming requests sent to a shadow upstream.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use http_server_starter_rust::{config::Config, control::Control, handlers, server};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+async fn start(mirror: &str, rate: &str) -> String {
+    let config = Config::from_args(
+        [
+            "server",
+            "--log-requests",
+            "false",
+            "--mirror",
+            mirror,
+            "--mirror-rate",
+            rate,
+        ]
+        .map(String::from),
+    )
+    .unwrap();
+    let control = Arc::new(Control::new(&config));
+    let routes = handlers::routes(&config, &control).unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    tokio::spawn(server::serve(listener, Arc::new(config), Arc::new(routes)));
+    address
+}
+
+async fn get(address: &str, path: &str) -> String {
+    let mut stream = TcpStream::connect(address).await.unwrap();
+    let request = format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path);
+    stream.write_all(request.as_bytes()).await.unwrap();
+    stream.shutdown().await.unwrap();
+    let mut response = Vec::new();
+    let _ = tokio::time::timeout(Duration::from_secs(2), stream.read_to_end(&mut response)).await;
+    String::from_utf8_lossy(&response).into_owned()
+}
+
+#[tokio::test]
+async fn requests_are_copied_to_the_mirror() {
+    let shadow = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = start(&shadow.local_addr().unwrap().to_string(), "1").await;
+
+    // The client is answered even though the mirror never responds.
+    assert!(get(&address, "/echo/shadow").await.ends_with("shadow"));
+
+    let (mut copy, _) = tokio::time::timeout(Duration::from_secs(2), shadow.accept())
+        .await
+        .unwrap()
+        .unwrap();
+    let mut request = Vec::new();
+    copy.read_to_end(&mut request).await.unwrap();
+    assert!(String::from_utf8_lossy(&request).starts_with("GET /echo/shadow HTTP/1.1\r\n"));
+}
+
+#[tokio::test]
+async fn a_zero_rate_mirrors_nothing() {
+    let shadow = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = start(&shadow.local_addr().unwrap().to_string(), "0").await;
+    assert!(get(&address, "/echo/quiet").await.ends_with("quiet"));
+    assert!(
+        tokio::time::timeout(Duration::from_millis(200), shadow.accept())
+            .await
+            .is_err()
+    );
+}
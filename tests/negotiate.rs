@@ -0,0 +1,89 @@
+//! `Accept` media ranges, `Accept-Encoding` codings, `Accept-Language`
+//! ranges and picking a representation from them.
+
+use http_server_starter_rust::negotiate::{parse_accept, parse_weighted};
+use http_server_starter_rust::request::Request;
+
+fn get(accept: &str) -> Request {
+    let head = format!("GET / HTTP/1.1\r\nHost: localhost\r\n{}\r\n", accept);
+    Request::parse(head.as_bytes()).unwrap()
+}
+
+#[test]
+fn ranges_are_ordered_by_quality_then_specificity() {
+    let ranges = parse_accept(["*/*;q=0.1, text/*;q=0.8, application/json;q=0.8, text/html, bad"]);
+    let order = ranges
+        .iter()
+        .map(|range| (range.kind, range.subtype, range.quality))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        order,
+        [
+            ("text", "html", 1.0),
+            ("application", "json", 0.8),
+            ("text", "*", 0.8),
+            ("*", "*", 0.1),
+        ]
+    );
+}
+
+#[test]
+fn offers_are_picked_across_accept_fields() {
+    let offers = ["text/plain", "application/json"];
+    assert_eq!(get("").negotiate(&offers), Some("text/plain"));
+    let req = get("Accept: text/plain;q=0.5\r\nAccept: application/*\r\n");
+    assert_eq!(req.accept().len(), 2);
+    assert_eq!(req.negotiate(&offers), Some("application/json"));
+    assert_eq!(
+        get("Accept: text/plain;q=0, image/*\r\n").negotiate(&offers),
+        None
+    );
+}
+
+#[test]
+fn encodings_are_ordered_by_quality() {
+    let codings = parse_weighted(["gzip;q=0.5, br, identity;q=0, ;q=1, deflate;q=x"]);
+    let order = codings
+        .iter()
+        .map(|coding| (coding.value, coding.quality))
+        .collect::<Vec<_>>();
+    assert_eq!(order, [("br", 1.0), ("gzip", 0.5), ("identity", 0.0)]);
+}
+
+#[test]
+fn encodings_are_accepted_by_name_wildcard_or_default() {
+    let req = get("");
+    assert!(req.accepts_encoding("identity"));
+    assert!(!req.accepts_encoding("gzip"));
+
+    let req = get("Accept-Encoding: GZIP, identity;q=0\r\n");
+    assert!(req.accepts_encoding("gzip"));
+    assert!(!req.accepts_encoding("identity"));
+    assert!(!req.accepts_encoding("br"));
+
+    let req = get("Accept-Encoding: br;q=0.2\r\nAccept-Encoding: *;q=0.5, gzip;q=0\r\n");
+    assert!(req.accepts_encoding("deflate"));
+    assert!(!req.accepts_encoding("gzip"));
+    assert_eq!(
+        req.negotiate_encoding(&["gzip", "br", "identity"]),
+        Some("identity")
+    );
+    assert_eq!(
+        get("Accept-Encoding: *;q=0\r\n").negotiate_encoding(&["gzip", "identity"]),
+        None
+    );
+}
+
+#[test]
+fn languages_are_picked_by_longest_matching_range() {
+    let offers = ["en", "de-AT", "fr"];
+    assert_eq!(get("").pick_language(&offers), Some("en"));
+    let req = get("Accept-Language: de;q=0.9, EN-us, en;q=0.5, *;q=0.1\r\n");
+    assert_eq!(req.pick_language(&offers), Some("de-AT"));
+    assert_eq!(req.pick_language(&["en-US", "de"]), Some("en-US"));
+    assert_eq!(req.pick_language(&["fr", "it"]), Some("fr"));
+    assert_eq!(
+        get("Accept-Language: de\r\n").pick_language(&["deu", "en"]),
+        None
+    );
+}
@@ -0,0 +1,58 @@
+//! Request heads parsed from a buffer that fills a little at a time.
+
+use http_server_starter_rust::parser::{parse_head, Parsed};
+use http_server_starter_rust::request::{HttpVersion, Request};
+use std::time::SystemTime;
+
+#[test]
+fn head_is_incomplete_until_its_empty_line_arrives() {
+    let data = b"\r\nPOST /a?b=c HTTP/1.1\r\nHost: localhost\nContent-Length: 2\r\n\r\nhi";
+    let end = data.len() - 2;
+    for len in 0..end {
+        assert!(
+            matches!(parse_head(&data[..len]).unwrap(), Parsed::Incomplete),
+            "{}",
+            len
+        );
+    }
+    let Parsed::Complete(head, len) = parse_head(data).unwrap() else {
+        panic!("head not found");
+    };
+    assert_eq!(len, end);
+    assert_eq!(
+        (head.method, head.target, head.version),
+        ("POST", "/a?b=c", "HTTP/1.1")
+    );
+    assert_eq!(
+        head.fields,
+        [("Host", "localhost"), ("Content-Length", "2")]
+    );
+    assert_eq!(
+        head.text,
+        "POST /a?b=c HTTP/1.1\r\nHost: localhost\nContent-Length: 2"
+    );
+
+    let Parsed::Complete(req, len) = Request::parse_prefix(data).unwrap() else {
+        panic!("head not found");
+    };
+    assert_eq!((req.path.as_str(), len), ("/a", end));
+    assert!(req.body.is_empty());
+}
+
+#[test]
+fn stray_carriage_returns_and_folding_are_refused() {
+    assert!(parse_head(b"GET / HTTP/1.1\r\nX: a\rb\r\n\r\n").is_err());
+    assert!(parse_head(b"GET / HTTP/1.1\r\nX: a\r\n b\r\n\r\n").is_err());
+    assert!(Request::parse(b"GET\r\nHost: localhost\r\n\r\n").is_err());
+}
+
+#[test]
+fn raw_request_line_and_arrival_are_kept() {
+    let before = SystemTime::now();
+    let head = b"GET /a%20b/../c?x=%41 HTTP/1.0\nHost: localhost\n\n";
+    let req = Request::parse(head).unwrap();
+    assert_eq!(req.request_line(), "GET /a%20b/../c?x=%41 HTTP/1.0");
+    assert_eq!(req.path, "/c");
+    assert_eq!(req.version, HttpVersion::Http10);
+    assert!(req.received_at >= before && req.received_at <= SystemTime::now());
+}
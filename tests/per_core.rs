@@ -0,0 +1,67 @@
+//! The thread-per-core runtime: one shard per core, each with its own
+//! route table, all accepting on the same address.
+
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use http_server_starter_rust::config::{Config, RuntimeMode};
+use http_server_starter_rust::control::Control;
+use http_server_starter_rust::handlers;
+use http_server_starter_rust::routes::Routes;
+use http_server_starter_rust::server;
+
+static BUILT: AtomicUsize = AtomicUsize::new(0);
+
+fn counted_routes(config: &Config, control: &Arc<Control>) -> Result<Routes> {
+    BUILT.fetch_add(1, Ordering::SeqCst);
+    handlers::routes(config, control)
+}
+
+fn get(address: &str, path: &str) -> std::io::Result<String> {
+    let mut stream = TcpStream::connect(address)?;
+    let request = format!(
+        "GET {} HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n",
+        path
+    );
+    stream.write_all(request.as_bytes())?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+    Ok(response)
+}
+
+#[test]
+fn every_shard_builds_its_routes_and_serves_the_address() {
+    let address = {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.local_addr().unwrap().to_string()
+    };
+    let config = Config {
+        address: address.clone(),
+        runtime: RuntimeMode::PerCore,
+        log_requests: false,
+        ..Config::default()
+    };
+    thread::spawn(move || server::run(config, counted_routes));
+
+    let cores = thread::available_parallelism().map_or(1, |n| n.get());
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while BUILT.load(Ordering::SeqCst) < cores {
+        assert!(Instant::now() < deadline, "shards never started");
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert_eq!(BUILT.load(Ordering::SeqCst), cores);
+
+    // Shards bind before building their routes, so all of them listen now.
+    let response = get(&address, "/echo/sharded").unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(response.ends_with("sharded"));
+    for _ in 0..4 * cores {
+        let response = get(&address, "/echo/again").unwrap();
+        assert!(response.ends_with("again"));
+    }
+}
@@ -0,0 +1,31 @@
+//! The shared buffer pool files are streamed through.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use http_server_starter_rust::pool::BufferPool;
+
+#[tokio::test]
+async fn buffers_are_reused_once_returned() {
+    let pool = Arc::new(BufferPool::new(1024, 2));
+    let buf = pool.get().await;
+    assert_eq!(buf.len(), 1024);
+    let address = buf.as_ptr();
+    drop(buf);
+
+    let buf = pool.get().await;
+    assert_eq!(buf.as_ptr(), address);
+}
+
+#[tokio::test]
+async fn checkouts_wait_once_every_buffer_is_out() {
+    let pool = Arc::new(BufferPool::new(16, 2));
+    let first = pool.get().await;
+    let _second = pool.get().await;
+    let third = tokio::time::timeout(Duration::from_millis(50), pool.get()).await;
+    assert!(third.is_err());
+
+    drop(first);
+    let third = tokio::time::timeout(Duration::from_millis(50), pool.get()).await;
+    assert!(third.is_ok());
+}
@@ -0,0 +1,39 @@
+//! The query string and fragment, split off the path of the request target.
+
+use http_server_starter_rust::request::Request;
+
+fn parse(target: &str) -> Request {
+    let head = format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", target);
+    Request::parse(head.as_bytes()).unwrap()
+}
+
+#[test]
+fn query_is_split_off_the_path() {
+    let req = parse("/echo/foo?x=1&y=a+b&z=%2Fc&x=2&flag");
+    assert_eq!(req.path, "/echo/foo");
+    assert_eq!(req.query_raw(), Some("x=1&y=a+b&z=%2Fc&x=2&flag"));
+    let query = req.query();
+    assert_eq!(query["x"], "1");
+    assert_eq!(query["y"], "a b");
+    assert_eq!(query["z"], "/c");
+    assert_eq!(query["flag"], "");
+    assert_eq!(req.target(), "/echo/foo?x=1&y=a+b&z=%2Fc&x=2&flag");
+
+    let req = parse("http://example.com?q=%zz");
+    assert_eq!(req.path, "/");
+    assert_eq!(req.query()["q"], "%zz");
+
+    let req = parse("/echo/%23x?q=1#frag?ment");
+    assert_eq!(req.path, "/echo/#x");
+    assert_eq!(req.query_raw(), Some("q=1"));
+    assert_eq!(req.fragment(), Some("frag?ment"));
+    assert_eq!(req.target(), "/echo/%23x?q=1");
+
+    let req = parse("http://example.com#top");
+    assert_eq!((req.path.as_str(), req.fragment()), ("/", Some("top")));
+
+    let req = parse("/a/../b");
+    assert_eq!(req.path, "/b");
+    assert_eq!(req.query_raw(), None);
+    assert!(req.query().is_empty());
+}
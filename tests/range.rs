@@ -0,0 +1,49 @@
+//! `Range` byte range sets, parsed on demand.
+
+use http_server_starter_rust::range::{parse_range, ByteRange};
+use http_server_starter_rust::request::Request;
+
+#[test]
+fn range_sets_are_parsed_and_resolved() {
+    let head = "GET /files/a HTTP/1.1\r\nHost: localhost\r\nRange: bytes=0-99, 200-,-50\r\n\r\n";
+    let req = Request::parse(head.as_bytes()).unwrap();
+    let ranges = req.range().unwrap().unwrap();
+    assert_eq!(
+        ranges,
+        [
+            ByteRange::Bounded { first: 0, last: 99 },
+            ByteRange::From(200),
+            ByteRange::Suffix(50),
+        ]
+    );
+    let resolved = ranges
+        .iter()
+        .map(|range| range.resolve(150))
+        .collect::<Vec<_>>();
+    assert_eq!(resolved, [Some((0, 99)), None, Some((100, 149))]);
+    assert_eq!(ByteRange::Suffix(500).resolve(10), Some((0, 9)));
+    assert_eq!(ByteRange::From(0).resolve(0), None);
+
+    let head = "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n";
+    assert!(Request::parse(head.as_bytes()).unwrap().range().is_none());
+}
+
+#[test]
+fn malformed_or_overlapping_ranges_are_refused() {
+    for value in [
+        "bytes",
+        "items=0-1",
+        "bytes=",
+        "bytes=1",
+        "bytes=a-b",
+        "bytes=+1-2",
+        "bytes=-",
+        "bytes=5-2",
+        "bytes=0-99999999999999999999",
+        "bytes=0-10,5-20",
+        "bytes=100-,0-200",
+    ] {
+        assert!(parse_range(value).is_err(), "{} was accepted", value);
+    }
+    assert!(parse_range("bytes=0-4,5-9,-100").is_ok());
+}
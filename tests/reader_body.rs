@@ -0,0 +1,76 @@
+//! Response bodies read from an `AsyncRead` a chunk at a time.
+
+use std::io::Cursor;
+
+use http_server_starter_rust::config::Config;
+use http_server_starter_rust::request::Request;
+use http_server_starter_rust::response::{Body, Response};
+use http_server_starter_rust::routes::{CompareType, Route, Routes};
+
+/// Serves `GET /sized`, which announces its length, and `GET /unsized`,
+/// which doesn't, both reading `hello world` 4 bytes at a time.
+fn routes() -> Routes {
+    let config = Config {
+        log_requests: false,
+        file_chunk_size: 4,
+        ..Config::default()
+    };
+    let mut routes = Routes::new(&config);
+    for (path, sized) in [("/sized", true), ("/unsized", false)] {
+        let handler = move |_, _: &String| {
+            let builder = Response::builder().header("Content-Type", "text/plain");
+            let builder = if sized {
+                builder.header("Content-Length", "11")
+            } else {
+                builder
+            };
+            builder.body(Body::reader(Cursor::new(b"hello world".to_vec())))
+        };
+        routes.add(Route::new(
+            "GET",
+            path,
+            CompareType::Exact,
+            Box::new(handler),
+        ));
+    }
+    routes
+}
+
+async fn get(routes: &Routes, head: &str) -> (String, bool) {
+    let req = Request::parse(head.as_bytes()).unwrap();
+    let mut written = Vec::new();
+    let reusable = routes.execute(&mut written, req, &[]).await;
+    (String::from_utf8(written).unwrap(), reusable)
+}
+
+#[tokio::test]
+async fn readers_are_sent_as_is_or_chunked() {
+    let routes = routes();
+    let (response, reusable) = get(&routes, "GET /sized HTTP/1.1\r\nHost: a\r\n\r\n").await;
+    assert!(response.contains("Content-Length: 11\r\n"));
+    assert!(!response.contains("Transfer-Encoding"));
+    assert!(response.ends_with("\r\n\r\nhello world"));
+    assert!(reusable);
+
+    let (response, reusable) = get(&routes, "GET /unsized HTTP/1.1\r\nHost: a\r\n\r\n").await;
+    assert!(response.contains("Transfer-Encoding: chunked\r\n"));
+    assert!(response.ends_with("\r\n\r\n4\r\nhell\r\n4\r\no wo\r\n3\r\nrld\r\n0\r\n\r\n"));
+    assert!(reusable);
+
+    // HTTP/1.0 has no chunked coding: the body ends with the connection.
+    let (response, reusable) = get(&routes, "GET /unsized HTTP/1.0\r\n\r\n").await;
+    assert!(!response.contains("Transfer-Encoding"));
+    assert!(response.ends_with("\r\n\r\nhello world"));
+    assert!(!reusable);
+
+    let (response, _) = get(&routes, "HEAD /unsized HTTP/1.1\r\nHost: a\r\n\r\n").await;
+    assert!(response.contains("Transfer-Encoding: chunked\r\n"));
+    assert!(response.ends_with("\r\n\r\n"));
+
+    let (response, reusable) = get(&routes, "HEAD /unsized HTTP/1.0\r\n\r\n").await;
+    assert!(!response.contains("Transfer-Encoding"));
+    assert!(!response.contains("Content-Length"));
+    assert!(response.contains("Connection: close\r\n"));
+    assert!(response.ends_with("\r\n\r\n"));
+    assert!(!reusable);
+}
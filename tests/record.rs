@@ -0,0 +1,47 @@
+//! Recording traffic and replaying it against the same server.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use http_server_starter_rust::{config::Config, control::Control, handlers, record, server};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+#[tokio::test(flavor = "multi_thread")]
+async fn recorded_traffic_replays_unchanged() {
+    let dir = std::env::temp_dir().join(format!("record-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let config = Config {
+        record: Some(dir.to_string_lossy().into_owned()),
+        log_requests: false,
+        ..Config::default()
+    };
+    let control = Arc::new(Control::new(&config));
+    let routes = handlers::routes(&config, &control).unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    tokio::spawn(server::serve(listener, Arc::new(config), Arc::new(routes)));
+
+    for request in [
+        "GET /echo/replay HTTP/1.1\r\nHost: localhost\r\n\r\n",
+        "GET /missing HTTP/1.1\r\nHost: localhost\r\n\r\n",
+    ] {
+        let mut stream = TcpStream::connect(&address).await.unwrap();
+        stream.write_all(request.as_bytes()).await.unwrap();
+        stream.shutdown().await.unwrap();
+        let mut response = Vec::new();
+        let _ =
+            tokio::time::timeout(Duration::from_secs(2), stream.read_to_end(&mut response)).await;
+    }
+    // Recordings are written after the response, so give them a moment.
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let recorded = std::fs::read_dir(&dir).unwrap().count();
+    assert_eq!(recorded, 4);
+
+    let replay_dir = dir.to_string_lossy().into_owned();
+    let changed = tokio::task::spawn_blocking(move || record::replay(&replay_dir, &address))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(changed, 0);
+}
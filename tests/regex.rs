@@ -0,0 +1,57 @@
+//! The configuration regex engine used by rewrite rules.
+
+use http_server_starter_rust::regex::Regex;
+use pretty_assertions::assert_eq;
+
+fn captures(pattern: &str, text: &str) -> Option<Vec<Option<String>>> {
+    Regex::new(pattern).unwrap().captures(text)
+}
+
+fn strings(values: &[Option<&str>]) -> Option<Vec<Option<String>>> {
+    Some(values.iter().map(|v| v.map(String::from)).collect())
+}
+
+#[test]
+fn literals_classes_and_anchors() {
+    assert!(Regex::new("^/blog/\\d+$").unwrap().is_match("/blog/42"));
+    assert!(!Regex::new("^/blog/\\d+$").unwrap().is_match("/blog/42/x"));
+    assert!(Regex::new("a.c").unwrap().is_match("xxabcxx"));
+    assert!(Regex::new("^[a-c_]+$").unwrap().is_match("ab_c"));
+    assert!(!Regex::new("^[^/]+$").unwrap().is_match("a/b"));
+    assert!(Regex::new("^\\w\\s\\W$").unwrap().is_match("a !"));
+    assert!(Regex::new("^(cat|dog)s?$").unwrap().is_match("dogs"));
+}
+
+#[test]
+fn groups_capture_submatches() {
+    assert_eq!(
+        captures("^/posts/(\\d{4})/([^/]+)\\.html$", "/posts/2023/hello.html"),
+        strings(&[Some("/posts/2023/hello.html"), Some("2023"), Some("hello")])
+    );
+    assert_eq!(
+        captures("^/a(?:/(x))?(/.*)?$", "/a/y"),
+        strings(&[Some("/a/y"), None, Some("/y")])
+    );
+}
+
+#[test]
+fn greedy_and_lazy_quantifiers() {
+    assert_eq!(
+        captures("^(.*)/(.*)$", "/a/b/c"),
+        strings(&[Some("/a/b/c"), Some("/a/b"), Some("c")])
+    );
+    assert_eq!(
+        captures("^(.*?)/(.*)$", "/a/b/c"),
+        strings(&[Some("/a/b/c"), Some(""), Some("a/b/c")])
+    );
+    assert!(Regex::new("^a{2,3}$").unwrap().is_match("aaa"));
+    assert!(!Regex::new("^a{2,3}$").unwrap().is_match("aaaa"));
+    assert!(Regex::new("^(a*)*b$").unwrap().is_match("aab"));
+}
+
+#[test]
+fn invalid_patterns_are_rejected() {
+    for pattern in ["(a", "a)", "[a-", "*a", "a{3,1}", "\\"] {
+        assert!(Regex::new(pattern).is_err(), "{}", pattern);
+    }
+}
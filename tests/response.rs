@@ -0,0 +1,80 @@
+//! Building responses and serializing their heads.
+
+use http_server_starter_rust::response::{Body, HttpCode, Response};
+
+#[test]
+fn builder_sets_status_headers_and_body() {
+    let res = Response::builder()
+        .status(HttpCode::Created)
+        .header("Location", "/files/a")
+        .header("Set-Cookie", "a=1")
+        .header(String::from("Set-Cookie"), String::from("b=2"))
+        .body("stored");
+    assert_eq!(res.code, HttpCode::Created);
+    let headers = res.headers.as_ref().unwrap();
+    assert_eq!(headers.get("location").unwrap(), "/files/a");
+    assert_eq!(
+        headers.get_all("Set-Cookie").collect::<Vec<_>>(),
+        ["a=1", "b=2"]
+    );
+    assert!(matches!(&res.content, Some(Body::Bytes(body)) if body == b"stored"));
+
+    let (head, _) = res.into_parts();
+    let head = String::from_utf8(head).unwrap();
+    assert!(head.starts_with("HTTP/1.1 201 Created\r\n"));
+    assert!(head.contains("Content-Length: 6\r\n"));
+
+    let res = Response::builder().build();
+    assert_eq!(res.code, HttpCode::OK);
+    assert!(res.content.is_none() && res.headers.is_none());
+    let res = Response {
+        code: HttpCode::NoContent,
+        ..Default::default()
+    };
+    assert!(res
+        .into_parts()
+        .0
+        .starts_with(b"HTTP/1.1 204 No Content\r\n"));
+}
+
+#[test]
+fn content_length_follows_the_body() {
+    let head = |res: Response| String::from_utf8(res.into_parts().0).unwrap();
+    let res = Response::builder()
+        .header("Content-Length", "99")
+        .body("four");
+    assert!(head(res).contains("Content-Length: 4\r\n"));
+
+    // Without a body, as for HEAD, a length set by the handler stands.
+    let res = Response::builder().header("Content-Length", "99").build();
+    assert!(head(res).contains("Content-Length: 99\r\n"));
+    assert!(head(Response::default()).contains("Content-Length: 0\r\n"));
+
+    let res = Response::builder()
+        .header("Transfer-Encoding", "chunked")
+        .body("four");
+    assert!(!head(res).contains("Content-Length"));
+
+    let path = std::env::temp_dir().join("response-content-length.txt");
+    std::fs::write(&path, "seven b").unwrap();
+    let res = Response::builder().body(Body::File(std::fs::File::open(&path).unwrap()));
+    assert!(head(res).contains("Content-Length: 7\r\n"));
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn text_and_html_carry_their_charset() {
+    let res = Response::text(HttpCode::NotFound, "gone");
+    assert_eq!(res.code, HttpCode::NotFound);
+    let (head, body) = res.into_parts();
+    let head = String::from_utf8(head).unwrap();
+    assert!(head.contains("Content-Type: text/plain; charset=utf-8\r\n"));
+    assert!(head.contains("Content-Length: 4\r\n"));
+    assert!(matches!(body, Some(Body::Bytes(body)) if body == b"gone"));
+
+    let res = Response::html(HttpCode::OK, String::from("<p>é</p>"));
+    let (head, _) = res.into_parts();
+    let head = String::from_utf8(head).unwrap();
+    assert!(head.contains("Content-Type: text/html; charset=utf-8\r\n"));
+    assert!(head.contains("Content-Length: 9\r\n"));
+}
@@ -0,0 +1,96 @@
+//! Rewrite rules applied ahead of routing.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use http_server_starter_rust::{config::Config, control::Control, handlers, server};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+async fn start(rules: &[&str]) -> String {
+    let mut args = vec!["server", "--log-requests", "false"];
+    for rule in rules {
+        args.extend(["--rewrite", rule]);
+    }
+    let config = Config::from_args(args.into_iter().map(String::from)).unwrap();
+    let control = Arc::new(Control::new(&config));
+    let routes = handlers::routes(&config, &control).unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    tokio::spawn(server::serve(listener, Arc::new(config), Arc::new(routes)));
+    address
+}
+
+async fn get(address: &str, path: &str) -> String {
+    let mut stream = TcpStream::connect(address).await.unwrap();
+    let request = format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path);
+    stream.write_all(request.as_bytes()).await.unwrap();
+    stream.shutdown().await.unwrap();
+    let mut response = Vec::new();
+    let _ = tokio::time::timeout(Duration::from_secs(2), stream.read_to_end(&mut response)).await;
+    String::from_utf8_lossy(&response).into_owned()
+}
+
+#[tokio::test]
+async fn internal_rules_chain_and_last_stops() {
+    let address = start(&[
+        "^/say/(\\w+)$ /shout/$1",
+        "^/shout/(\\w+)$ /echo/$1! internal",
+        "^/old/(.*)$ /echo/$1 last",
+        "^/echo/.*$ /missing",
+    ])
+    .await;
+    // The fourth rule would send everything to /missing, so only requests
+    // that stopped at `last` still reach /echo.
+    assert!(get(&address, "/say/hi").await.starts_with("HTTP/1.1 404"));
+    assert!(get(&address, "/old/page").await.ends_with("\r\n\r\npage"));
+}
+
+#[tokio::test]
+async fn redirect_keeps_the_query_string() {
+    let address = start(&["^/blog/(\\d{4})/(.+)$ /posts/$2-$1 redirect"]).await;
+    let response = get(&address, "/blog/2023/hello?ref=feed").await;
+    assert!(response.starts_with("HTTP/1.1 302 Found\r\n"));
+    assert!(response.contains("Location: /posts/hello-2023?ref=feed\r\n"));
+}
+
+#[test]
+fn invalid_rules_are_rejected() {
+    let config = Config::from_args(["server", "--rewrite", "^/(a /b"].map(String::from)).unwrap();
+    let control = Arc::new(Control::new(&config));
+    assert!(handlers::routes(&config, &control).is_err());
+    assert!(
+        Config::from_args(["server", "--rewrite", "^/a /b sideways"].map(String::from)).is_err()
+    );
+}
+
+#[tokio::test]
+async fn rewritten_paths_are_normalized() {
+    let address = start(&["^/up/(.*)$ /echo/../echo/$1"]).await;
+    let response = get(&address, "/up/page").await;
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(response.ends_with("\r\n\r\npage"));
+}
+
+#[tokio::test]
+async fn body_handling_follows_the_rewritten_route() {
+    // `POST /echo` streams its body back; it only answers before the body
+    // is complete if the rewritten path picked that route.
+    let address = start(&["^/talk$ /echo last"]).await;
+    let mut stream = TcpStream::connect(&address).await.unwrap();
+    stream
+        .write_all(b"POST /talk HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n")
+        .await
+        .unwrap();
+    let mut response = vec![0; 1024];
+    let mut read = 0;
+    while !String::from_utf8_lossy(&response[..read]).contains("hello") {
+        let len = tokio::time::timeout(Duration::from_secs(2), stream.read(&mut response[read..]))
+            .await
+            .expect("the echo should arrive before the body ends")
+            .unwrap();
+        assert!(len > 0);
+        read += len;
+    }
+    assert!(response.starts_with(b"HTTP/1.1 200 OK\r\n"));
+}
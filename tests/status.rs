@@ -0,0 +1,51 @@
+//! Status codes, their numbers and reason phrases.
+
+use http_server_starter_rust::response::{HttpCode, Response};
+
+#[test]
+fn codes_convert_to_and_from_numbers() {
+    for number in [100, 200, 204, 308, 404, 429, 451, 504] {
+        let code = HttpCode::try_from(number).unwrap();
+        assert_eq!(code.as_u16(), number);
+    }
+    assert_eq!(HttpCode::try_from(418_u16).unwrap_err().0, 418);
+    assert_eq!(HttpCode::TooManyRequests.reason(), "Too Many Requests");
+    assert_eq!(HttpCode::GatewayTimeout.to_string(), "504 Gateway Timeout");
+
+    let (head, _) = Response {
+        code: HttpCode::try_from(303).unwrap(),
+        content: None,
+        headers: None,
+    }
+    .into_parts();
+    assert!(head.starts_with(b"HTTP/1.1 303 See Other\r\n"));
+}
+
+#[test]
+fn codes_are_classified_by_class() {
+    assert!(HttpCode::EarlyHints.is_informational());
+    assert!(HttpCode::NoContent.is_success());
+    assert!(HttpCode::PermanentRedirect.is_redirection());
+    assert!(HttpCode::Gone.is_client_error());
+    assert!(!HttpCode::Gone.is_server_error());
+    assert!(HttpCode::BadGateway.is_server_error());
+    assert!(!HttpCode::OK.is_client_error());
+}
+
+#[test]
+fn custom_codes_keep_their_reason_phrase() {
+    let code = HttpCode::custom(299, Some("Custom")).unwrap();
+    assert_eq!(code, HttpCode::Custom(299, Some(String::from("Custom"))));
+    let (head, _) = Response {
+        code,
+        content: None,
+        headers: None,
+    }
+    .into_parts();
+    assert!(head.starts_with(b"HTTP/1.1 299 Custom\r\n"));
+
+    assert_eq!(HttpCode::custom(404, None).unwrap(), HttpCode::NotFound);
+    assert_eq!(HttpCode::custom(599, None).unwrap().to_string(), "599 ");
+    assert!(HttpCode::custom(1000, None).is_err());
+    assert!(HttpCode::custom(299, Some("Split\r\nX-Injected: 1")).is_err());
+}
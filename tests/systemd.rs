@@ -0,0 +1,54 @@
+//! Readiness, stopping and watchdog notifications to systemd.
+
+use std::os::unix::net::UnixDatagram;
+use std::sync::Arc;
+use std::time::Duration;
+
+use http_server_starter_rust::clock::ManualClock;
+use http_server_starter_rust::{config::Config, control::Control, systemd};
+
+fn receive(socket: &UnixDatagram) -> String {
+    let mut buf = [0; 64];
+    let len = socket.recv(&mut buf).unwrap();
+    String::from_utf8_lossy(&buf[..len]).into_owned()
+}
+
+// A single test, since the notification socket comes from the process
+// environment.
+#[tokio::test]
+async fn states_are_sent_to_the_notify_socket() {
+    let path = std::env::temp_dir().join(format!("notify-{}.sock", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let socket = UnixDatagram::bind(&path).unwrap();
+    socket
+        .set_read_timeout(Some(Duration::from_secs(2)))
+        .unwrap();
+    std::env::set_var("NOTIFY_SOCKET", &path);
+    std::env::set_var("WATCHDOG_USEC", "2000000");
+
+    systemd::notify("READY=1");
+    assert_eq!(receive(&socket), "READY=1");
+
+    let clock = Arc::new(ManualClock::new());
+    let control = Arc::new(Control::with_clock(&Config::default(), clock.clone()));
+    tokio::spawn(systemd::watchdog(control.clone()));
+    // The watchdog may not have started sleeping yet, so keep nudging the
+    // clock until the first ping arrives.
+    socket.set_nonblocking(true).unwrap();
+    let mut buf = [0; 64];
+    let ping = loop {
+        clock.advance(Duration::from_secs(1));
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        if let Ok(len) = socket.recv(&mut buf) {
+            break String::from_utf8_lossy(&buf[..len]).into_owned();
+        }
+    };
+    assert_eq!(ping, "WATCHDOG=1");
+    socket.set_nonblocking(false).unwrap();
+
+    control.shutdown();
+    control.shutdown();
+    assert_eq!(receive(&socket), "STOPPING=1");
+    socket.set_nonblocking(true).unwrap();
+    assert!(socket.recv(&mut [0; 64]).is_err());
+}
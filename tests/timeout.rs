@@ -0,0 +1,69 @@
+//! Deadlines for receiving the request head and body.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use http_server_starter_rust::{config::Config, control::Control, handlers, server};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::oneshot;
+
+async fn start(args: &[&str]) -> (String, oneshot::Sender<()>) {
+    let mut all = vec!["server", "--log-requests", "false"];
+    all.extend_from_slice(args);
+    let config = Config::from_args(all.into_iter().map(String::from)).unwrap();
+    let control = Arc::new(Control::new(&config));
+    let routes = handlers::routes(&config, &control).unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let (stop, stopped) = oneshot::channel();
+    tokio::spawn(server::serve_until(
+        listener,
+        Arc::new(config),
+        Arc::new(routes),
+        async move {
+            let _ = stopped.await;
+        },
+    ));
+    (address, stop)
+}
+
+/// Sends `partial` and never the rest, returning what the server answered
+/// before closing the connection and how long that took.
+async fn stall(address: &str, partial: &str) -> (String, Duration) {
+    let started = Instant::now();
+    let mut stream = TcpStream::connect(address).await.unwrap();
+    stream.write_all(partial.as_bytes()).await.unwrap();
+    let mut response = Vec::new();
+    let _ = tokio::time::timeout(Duration::from_secs(5), stream.read_to_end(&mut response)).await;
+    (
+        String::from_utf8_lossy(&response).into_owned(),
+        started.elapsed(),
+    )
+}
+
+#[tokio::test]
+async fn slow_heads_time_out() {
+    let (address, _stop) = start(&["--header-timeout", "1"]).await;
+    let (response, elapsed) = stall(&address, "GET /echo/slow HTT").await;
+    assert!(response.starts_with("HTTP/1.1 408 Request Timeout\r\n"));
+    assert!(response.contains("Connection: close\r\n"));
+    assert!(elapsed >= Duration::from_secs(1) && elapsed < Duration::from_secs(3));
+
+    // A connection that never sends anything is closed without an answer.
+    let (response, elapsed) = stall(&address, "").await;
+    assert_eq!(response, "");
+    assert!(elapsed < Duration::from_secs(3));
+}
+
+#[tokio::test]
+async fn slow_bodies_time_out() {
+    let (address, _stop) = start(&["--body-timeout", "1"]).await;
+    let (response, elapsed) = stall(
+        &address,
+        "POST /echo/slow HTTP/1.1\r\nHost: localhost\r\nContent-Length: 10\r\n\r\nab",
+    )
+    .await;
+    assert!(response.starts_with("HTTP/1.1 408 Request Timeout\r\n"));
+    assert!(elapsed >= Duration::from_secs(1) && elapsed < Duration::from_secs(3));
+}
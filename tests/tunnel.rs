@@ -0,0 +1,150 @@
+//! CONNECT tunnels to allow-listed upstreams.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use http_server_starter_rust::auth::BasicAuth;
+use http_server_starter_rust::config::Config;
+use http_server_starter_rust::hash::base64_encode;
+use http_server_starter_rust::routes::Routes;
+use http_server_starter_rust::server;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// Starts an upstream that echoes back whatever it receives.
+async fn echo_upstream() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                let (mut reader, mut writer) = stream.split();
+                let _ = tokio::io::copy(&mut reader, &mut writer).await;
+            });
+        }
+    });
+    address
+}
+
+/// Starts a server with no routes, with Basic auth over the whole server
+/// when `auth` is set.
+async fn start(config: Config, auth: bool) -> String {
+    let mut routes = Routes::new(&config);
+    if auth {
+        let verifier = Box::new(|user: &str, pass: &str| user == "alice" && pass == "secret");
+        routes.middleware("/", Arc::new(BasicAuth::new("proxy", verifier)));
+    }
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    tokio::spawn(server::serve(listener, Arc::new(config), Arc::new(routes)));
+    address
+}
+
+fn config(allow: &str) -> Config {
+    Config {
+        connect_allow: vec![allow.to_owned()],
+        log_requests: false,
+        ..Config::default()
+    }
+}
+
+/// Reads until the end of a response head.
+async fn read_head(stream: &mut TcpStream) -> String {
+    let mut head = Vec::new();
+    while !head.ends_with(b"\r\n\r\n") {
+        let mut byte = [0];
+        if stream.read(&mut byte).await.unwrap() == 0 {
+            break;
+        }
+        head.push(byte[0]);
+    }
+    String::from_utf8(head).unwrap()
+}
+
+#[tokio::test]
+async fn tunnel_relays_bytes_sent_with_and_after_the_request() {
+    let upstream = echo_upstream().await;
+    let address = start(config(&upstream), false).await;
+    let mut stream = TcpStream::connect(&address).await.unwrap();
+    let request = format!("CONNECT {0} HTTP/1.1\r\nHost: {0}\r\n\r\nearly ", upstream);
+    stream.write_all(request.as_bytes()).await.unwrap();
+
+    let head = read_head(&mut stream).await;
+    assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(!head.contains("Content-Length"));
+    assert!(!head.contains("Transfer-Encoding"));
+
+    stream.write_all(b"late").await.unwrap();
+    let mut echoed = [0; 10];
+    stream.read_exact(&mut echoed).await.unwrap();
+    assert_eq!(&echoed, b"early late");
+}
+
+#[tokio::test]
+async fn tunnels_are_refused_off_the_allow_list_or_without_credentials() {
+    let upstream = echo_upstream().await;
+    let address = start(config("example.com:443"), false).await;
+    let mut stream = TcpStream::connect(&address).await.unwrap();
+    let request = format!("CONNECT {0} HTTP/1.1\r\nHost: {0}\r\n\r\n", upstream);
+    stream.write_all(request.as_bytes()).await.unwrap();
+    assert!(read_head(&mut stream)
+        .await
+        .starts_with("HTTP/1.1 403 Forbidden\r\n"));
+
+    let address = start(config(&upstream), true).await;
+    let mut stream = TcpStream::connect(&address).await.unwrap();
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let head = read_head(&mut stream).await;
+    assert!(head.starts_with("HTTP/1.1 407 Proxy Authentication Required\r\n"));
+    assert!(head.contains("Proxy-Authenticate: Basic realm=\"proxy\""));
+
+    // Credentials meant for an origin don't open a tunnel.
+    let mut stream = TcpStream::connect(&address).await.unwrap();
+    let request = format!(
+        "CONNECT {0} HTTP/1.1\r\nHost: {0}\r\nAuthorization: Basic {1}\r\n\r\n",
+        upstream,
+        base64_encode(b"alice:secret")
+    );
+    stream.write_all(request.as_bytes()).await.unwrap();
+    assert!(read_head(&mut stream)
+        .await
+        .starts_with("HTTP/1.1 407 Proxy Authentication Required\r\n"));
+
+    let mut stream = TcpStream::connect(&address).await.unwrap();
+    let request = format!(
+        "CONNECT {0} HTTP/1.1\r\nHost: {0}\r\nProxy-Authorization: Basic {1}\r\n\r\n",
+        upstream,
+        base64_encode(b"alice:secret")
+    );
+    stream.write_all(request.as_bytes()).await.unwrap();
+    assert!(read_head(&mut stream)
+        .await
+        .starts_with("HTTP/1.1 200 OK\r\n"));
+}
+
+#[tokio::test]
+async fn open_tunnels_count_against_the_concurrency_limit() {
+    let upstream = echo_upstream().await;
+    let address = start(
+        Config {
+            max_concurrency: 1,
+            queue_depth: 0,
+            queue_timeout: Duration::from_millis(100),
+            ..config(&upstream)
+        },
+        false,
+    )
+    .await;
+    let request = format!("CONNECT {0} HTTP/1.1\r\nHost: {0}\r\n\r\n", upstream);
+    let mut first = TcpStream::connect(&address).await.unwrap();
+    first.write_all(request.as_bytes()).await.unwrap();
+    assert!(read_head(&mut first)
+        .await
+        .starts_with("HTTP/1.1 200 OK\r\n"));
+
+    let mut second = TcpStream::connect(&address).await.unwrap();
+    second.write_all(request.as_bytes()).await.unwrap();
+    assert!(read_head(&mut second)
+        .await
+        .starts_with("HTTP/1.1 503 Service Unavailable\r\n"));
+}
@@ -0,0 +1,59 @@
+//! Header fields read and written through `TypedHeader`.
+
+use http_server_starter_rust::header_map::HeaderMap;
+use http_server_starter_rust::request::Request;
+use http_server_starter_rust::typed_header::{ContentLength, ContentType, Host, UserAgent};
+
+fn post(fields: &str) -> Request {
+    let head = format!("POST / HTTP/1.1\r\n{}\r\n", fields);
+    Request::parse(head.as_bytes()).unwrap()
+}
+
+#[test]
+fn common_fields_are_parsed() {
+    let req = post(
+        "Host: [::1]:8080\r\nUser-Agent: curl/8.0\r\nContent-Type: text/plain; charset=utf-8\r\n\
+         Content-Length: 0, 0\r\nContent-Length: 0\r\n",
+    );
+    let host = req.typed_header::<Host>().unwrap().unwrap();
+    assert_eq!((host.host.as_str(), host.port), ("[::1]", Some(8080)));
+    let agent = req.typed_header::<UserAgent>().unwrap().unwrap();
+    assert_eq!(agent, UserAgent(String::from("curl/8.0")));
+    let ContentType(media) = req.typed_header::<ContentType>().unwrap().unwrap();
+    assert_eq!(media.charset(), Some("utf-8"));
+    assert_eq!(
+        req.typed_header::<ContentLength>().unwrap().unwrap(),
+        ContentLength(0)
+    );
+
+    let req = post("Host: example.com\r\nContent-Length: 1, 2\r\nContent-Type: text\r\n");
+    let host = req.typed_header::<Host>().unwrap().unwrap();
+    assert_eq!((host.host.as_str(), host.port), ("example.com", None));
+    assert!(req.typed_header::<ContentLength>().unwrap().is_err());
+    assert!(req.typed_header::<ContentType>().unwrap().is_err());
+    assert!(req.typed_header::<UserAgent>().is_none());
+    assert!(post("Host: ::1\r\n")
+        .typed_header::<Host>()
+        .unwrap()
+        .is_err());
+    assert!(post("Host: a:http\r\n")
+        .typed_header::<Host>()
+        .unwrap()
+        .is_err());
+}
+
+#[test]
+fn typed_fields_are_written_back() {
+    let mut headers = HeaderMap::new();
+    headers.insert(String::from("content-length"), String::from("1"));
+    headers.insert_typed(&ContentLength(42));
+    let host = Host {
+        host: String::from("localhost"),
+        port: Some(4221),
+    };
+    headers.insert_typed(&host);
+    assert_eq!(headers.len(), 2);
+    assert_eq!(headers.get("Content-Length").unwrap(), "42");
+    assert_eq!(headers.get("Host").unwrap(), "localhost:4221");
+    assert_eq!(headers.typed::<Host>().unwrap().unwrap(), host);
+}
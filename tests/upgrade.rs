@@ -0,0 +1,104 @@
+//! Handing connections over to other protocols with `Upgrade`.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use http_server_starter_rust::config::Config;
+use http_server_starter_rust::header_map::HeaderMap;
+use http_server_starter_rust::request::Request;
+use http_server_starter_rust::response::{HttpCode, Response};
+use http_server_starter_rust::routes::{CompareType, Route, Routes};
+use http_server_starter_rust::server;
+use http_server_starter_rust::upgrade::{Upgrade, Upgraded};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// Shouts back whatever the client sends, starting with any bytes that
+/// arrived along with the request. Only accepts requests to `/shout`.
+struct Shout;
+
+impl Upgrade for Shout {
+    fn accept(&self, req: &Request) -> Option<HeaderMap> {
+        (req.path == "/shout")
+            .then(|| HeaderMap::from([(String::from("X-Volume"), String::from("loud"))]))
+    }
+
+    fn run(&self, upgraded: Upgraded) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+        Box::pin(async move {
+            let Upgraded {
+                mut stream, buf, ..
+            } = upgraded;
+            let _ = stream.write_all(&buf.to_ascii_uppercase()).await;
+            let mut chunk = [0; 64];
+            while let Ok(len) = stream.read(&mut chunk).await {
+                if len == 0
+                    || stream
+                        .write_all(&chunk[..len].to_ascii_uppercase())
+                        .await
+                        .is_err()
+                {
+                    break;
+                }
+            }
+        })
+    }
+}
+
+async fn start() -> String {
+    let config = Config {
+        log_requests: false,
+        ..Config::default()
+    };
+    let mut routes = Routes::new(&config);
+    routes.upgrade("shout", Arc::new(Shout));
+    routes.add(Route::new(
+        "GET",
+        "/",
+        CompareType::Prefix,
+        Box::new(|_, _| Response {
+            code: HttpCode::OK,
+            content: Some(b"plain".to_vec().into()),
+            headers: None,
+        }),
+    ));
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    tokio::spawn(server::serve(listener, Arc::new(config), Arc::new(routes)));
+    address
+}
+
+#[tokio::test]
+async fn accepted_upgrades_take_over_the_connection() {
+    let address = start().await;
+    let mut stream = TcpStream::connect(&address).await.unwrap();
+    stream
+        .write_all(b"GET /shout HTTP/1.1\r\nHost: localhost\r\nConnection: upgrade\r\nUpgrade: websocket, SHOUT\r\n\r\nhello")
+        .await
+        .unwrap();
+    stream.write_all(b" there").await.unwrap();
+    stream.shutdown().await.unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    assert!(response.starts_with("HTTP/1.1 101 Switching Protocols\r\n"));
+    assert!(response.contains("Upgrade: shout\r\n"));
+    assert!(response.contains("X-Volume: loud\r\n"));
+    assert!(response.ends_with("\r\n\r\nHELLO THERE"));
+}
+
+#[tokio::test]
+async fn declined_or_unknown_upgrades_reach_the_routes() {
+    let address = start().await;
+    for request in [
+        "GET /other HTTP/1.1\r\nHost: localhost\r\nConnection: upgrade, close\r\nUpgrade: shout\r\n\r\n",
+        "GET /shout HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nUpgrade: shout\r\n\r\n",
+        "GET /shout HTTP/1.1\r\nHost: localhost\r\nConnection: upgrade, close\r\nUpgrade: whisper\r\n\r\n",
+    ] {
+        let mut stream = TcpStream::connect(&address).await.unwrap();
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+        assert!(response.ends_with("plain"));
+    }
+}
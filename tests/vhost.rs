@@ -0,0 +1,95 @@
+//! Host-based selection of document roots, index files and error pages.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use http_server_starter_rust::{config::Config, control::Control, handlers, server};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+fn site(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("vhost-{}-{}", std::process::id(), name));
+    for (path, contents) in files {
+        let path = root.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, contents).unwrap();
+    }
+    root
+}
+
+async fn start() -> String {
+    let blog = site(
+        "blog",
+        &[("index.html", "blog home"), ("posts/a.txt", "post a")],
+    );
+    let shop = site(
+        "shop",
+        &[("home.html", "shop home"), ("missing.html", "gone")],
+    );
+    let config = Config::from_args(
+        [
+            "server",
+            "--log-requests",
+            "false",
+            "--vhost",
+            &format!("blog.test={}", blog.display()),
+            "--vhost",
+            &format!("*.shop.test={}", shop.display()),
+            "--vhost-index",
+            "*.shop.test=home.html",
+            "--vhost-error-page",
+            &format!("*.shop.test=404:{}", shop.join("missing.html").display()),
+        ]
+        .map(String::from),
+    )
+    .unwrap();
+    let control = Arc::new(Control::new(&config));
+    let routes = handlers::routes(&config, &control).unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    tokio::spawn(server::serve(listener, Arc::new(config), Arc::new(routes)));
+    address
+}
+
+async fn get(address: &str, host: &str, path: &str) -> String {
+    let mut stream = TcpStream::connect(address).await.unwrap();
+    let request = format!("GET {} HTTP/1.1\r\nHost: {}\r\n\r\n", path, host);
+    stream.write_all(request.as_bytes()).await.unwrap();
+    stream.shutdown().await.unwrap();
+    let mut response = Vec::new();
+    let _ = tokio::time::timeout(Duration::from_secs(2), stream.read_to_end(&mut response)).await;
+    String::from_utf8_lossy(&response).into_owned()
+}
+
+#[tokio::test]
+async fn hosts_are_served_from_their_own_roots() {
+    let address = start().await;
+
+    let response = get(&address, "blog.test:4221", "/").await;
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(response.contains("Content-Type: text/html\r\n"));
+    assert!(response.ends_with("blog home"));
+    assert!(get(&address, "BLOG.test", "/posts/a.txt")
+        .await
+        .ends_with("post a"));
+
+    assert!(get(&address, "www.shop.test", "/")
+        .await
+        .ends_with("shop home"));
+    let missing = get(&address, "www.shop.test", "/nope").await;
+    assert!(missing.starts_with("HTTP/1.1 404 Not Found\r\n"));
+    assert!(missing.ends_with("gone"));
+
+    // Dot segments are resolved before routing, so `/../` never climbs
+    // out of the root; backslashes still have to be refused here.
+    let climbed = get(&address, "blog.test", "/../posts/a.txt").await;
+    assert!(climbed.ends_with("post a"));
+    let escape = get(&address, "blog.test", "/..\\vhost-shop/home.html").await;
+    assert!(escape.starts_with("HTTP/1.1 403 Forbidden\r\n"));
+
+    // Unknown hosts fall through to the regular routes.
+    assert!(get(&address, "other.test", "/echo/routes")
+        .await
+        .ends_with("routes"));
+}